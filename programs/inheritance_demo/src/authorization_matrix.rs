@@ -0,0 +1,506 @@
+//! Who may call which instruction, in one auditable place.
+//!
+//! `INSTRUCTION_AUTH` lists the role required by every instruction. Handlers call
+//! `authorize` before anything else for roles recorded on the vault, and
+//! `authorize_holder` for roles recorded elsewhere (a committee, a guardian set, an
+//! escrow, the protocol config). `ProtocolAuthority` instructions without a vault rely
+//! on their Anchor constraints (e.g. `has_one = authority` on the protocol config),
+//! and the table documents the role those constraints enforce. `Anyone` instructions
+//! take no check at all: `authorize` rejects that role.
+
+use anchor_lang::prelude::*;
+
+use crate::{instruction, ErrorCode, ProtocolConfig, Vault};
+
+/// Anchor's 8-byte instruction discriminator
+pub type InstructionDiscriminant = &'static [u8];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Role {
+    Testator,
+    Beneficiary,
+    /// Whoever inherits the assets (`Vault::heir`). Only held for executions and claims.
+    Heir,
+    Verifier,
+    /// The testator, or a delegate pinging on their behalf: the liveness scheduler, the
+    /// ping delegate, or the custody agent. Not to be confused with the members of a
+    /// `GuardianSet`, who can never ping.
+    Pinger,
+    /// The vault's health oracle
+    HealthOracle,
+    /// The vault's tax authority oracle, or the tax authority of its `TaxEscrow`
+    TaxOracle,
+    /// The oracle of the testator's `ValuationOracle`
+    ValuationOracle,
+    /// The protocol config's authority (or recovery authority), checked by the
+    /// instruction's config constraint
+    ProtocolAuthority,
+    /// The buyer of a vault's `ExecutionRight`
+    Executor,
+    /// A member of a multi-signer set (a verifier committee or a guardian set)
+    CoSigner,
+    /// The party named by a pending proposal (e.g. a proposed new beneficiary)
+    Nominee,
+    /// The vault's fallback beneficiary, before being promoted to beneficiary
+    FallbackBeneficiary,
+    /// The testator, or the delegate they allowed to extend the deadline
+    DeadlineExtender,
+    /// No check: listed in the table only. `authorize` never grants it.
+    Anyone,
+}
+
+pub const INSTRUCTION_AUTH: &[(InstructionDiscriminant, Role)] = &[
+    (instruction::InitInheritance::DISCRIMINATOR, Role::Testator),
+    (instruction::CreateCompressedLiveness::DISCRIMINATOR, Role::Testator),
+    (instruction::RetryCreateCompressedLiveness::DISCRIMINATOR, Role::Testator),
+    (instruction::RecordCompressionFailure::DISCRIMINATOR, Role::Testator),
+    (instruction::UpdateLiveness::DISCRIMINATOR, Role::Pinger),
+    (instruction::UpdateLivenessWithNote::DISCRIMINATOR, Role::Pinger),
+    (instruction::ExecuteInheritance::DISCRIMINATOR, Role::Heir),
+    (instruction::InitLightRegistry::DISCRIMINATOR, Role::Anyone),
+    (instruction::VerifyBeneficiaryIdentity::DISCRIMINATOR, Role::Beneficiary),
+    (instruction::CancelWill::DISCRIMINATOR, Role::Testator),
+    (instruction::InitVaultFundingAddress::DISCRIMINATOR, Role::Anyone),
+    (instruction::CollectVaultDonations::DISCRIMINATOR, Role::Anyone),
+    (instruction::AddEmergencyContact::DISCRIMINATOR, Role::Testator),
+    (instruction::ReadEmergencyContacts::DISCRIMINATOR, Role::Verifier),
+    (instruction::RemoveEmergencyContact::DISCRIMINATOR, Role::Testator),
+    (instruction::SealTimeCapsule::DISCRIMINATOR, Role::Testator),
+    (instruction::UpdateCapsule::DISCRIMINATOR, Role::Testator),
+    (instruction::CloseCapsule::DISCRIMINATOR, Role::Testator),
+    (instruction::InitProtocolConfig::DISCRIMINATOR, Role::ProtocolAuthority),
+    (instruction::RecordLightProgramHash::DISCRIMINATOR, Role::ProtocolAuthority),
+    (instruction::VerifyLightSdkCompatibility::DISCRIMINATOR, Role::Anyone),
+    (instruction::UpdateMaxVaultsPerTestator::DISCRIMINATOR, Role::ProtocolAuthority),
+    (instruction::UpdateMaxTotalProtocolVaults::DISCRIMINATOR, Role::ProtocolAuthority),
+    (instruction::ConfigureInheritanceTax::DISCRIMINATOR, Role::Testator),
+    (instruction::MarkTaxPaid::DISCRIMINATOR, Role::TaxOracle),
+    (instruction::InitStateHistory::DISCRIMINATOR, Role::Anyone),
+    (instruction::ReadStateHistory::DISCRIMINATOR, Role::Anyone),
+    (instruction::PruneStateHistory::DISCRIMINATOR, Role::Testator),
+    (instruction::CreateGovernanceProposal::DISCRIMINATOR, Role::Testator),
+    (instruction::VoteOnProposal::DISCRIMINATOR, Role::Testator),
+    (instruction::ExecuteProposal::DISCRIMINATOR, Role::Anyone),
+    (instruction::SetUpgradeProxyAuthority::DISCRIMINATOR, Role::ProtocolAuthority),
+    (instruction::GrantCpiAccess::DISCRIMINATOR, Role::ProtocolAuthority),
+    (instruction::MigrateVault::DISCRIMINATOR, Role::Testator),
+    (instruction::SetCompressedLivenessV2Enabled::DISCRIMINATOR, Role::ProtocolAuthority),
+    (instruction::CreateCompressedLivenessV2::DISCRIMINATOR, Role::Testator),
+    (instruction::UpdateLivenessV2::DISCRIMINATOR, Role::Pinger),
+    (instruction::MigrateCompressedLivenessToV2::DISCRIMINATOR, Role::Testator),
+    (instruction::GrantCustody::DISCRIMINATOR, Role::Testator),
+    (instruction::RevokeCustody::DISCRIMINATOR, Role::Testator),
+    (instruction::SetBeneficiaryInstructions::DISCRIMINATOR, Role::Testator),
+    (instruction::ProposeVerifierMigration::DISCRIMINATOR, Role::Verifier),
+    (instruction::ApproveVerifierMigration::DISCRIMINATOR, Role::ProtocolAuthority),
+    (instruction::ApplyVerifierMigration::DISCRIMINATOR, Role::Anyone),
+    (instruction::VerifyLightCpiSigner::DISCRIMINATOR, Role::Anyone),
+    (instruction::SetBenchmarksEnabled::DISCRIMINATOR, Role::ProtocolAuthority),
+    (instruction::RunBenchmark::DISCRIMINATOR, Role::Anyone),
+    (instruction::SetKeyFragmentMetadata::DISCRIMINATOR, Role::Testator),
+    (instruction::SetTreasury::DISCRIMINATOR, Role::ProtocolAuthority),
+    (instruction::AtomicBiometricUpdate::DISCRIMINATOR, Role::Testator),
+    (instruction::DepositAndUpdateLiveness::DISCRIMINATOR, Role::Pinger),
+    (instruction::VerifyAndExecute::DISCRIMINATOR, Role::Heir),
+    (instruction::LookupVaultAddress::DISCRIMINATOR, Role::Anyone),
+    (instruction::PauseAllExecutions::DISCRIMINATOR, Role::ProtocolAuthority),
+    (instruction::ResumeAllExecutions::DISCRIMINATOR, Role::ProtocolAuthority),
+    (instruction::InitVerifierCommittee::DISCRIMINATOR, Role::Testator),
+    (instruction::SubmitCommitteeApproval::DISCRIMINATOR, Role::CoSigner),
+    (instruction::FinalizeCommitteeExecution::DISCRIMINATOR, Role::Heir),
+    (instruction::RevokePendingExecution::DISCRIMINATOR, Role::Testator),
+    (instruction::SetMaxVerificationAge::DISCRIMINATOR, Role::Testator),
+    (instruction::RevalidateIdentity::DISCRIMINATOR, Role::Beneficiary),
+    (instruction::SetExpectedPingNote::DISCRIMINATOR, Role::Testator),
+    (instruction::ClearExpectedPingNote::DISCRIMINATOR, Role::Testator),
+    (instruction::AdminPingLiveness::DISCRIMINATOR, Role::ProtocolAuthority),
+    (instruction::ExportVaultState::DISCRIMINATOR, Role::Anyone),
+    (instruction::InitCompressedLivenessIndex::DISCRIMINATOR, Role::Anyone),
+    (instruction::InsertCompressedLivenessIndex::DISCRIMINATOR, Role::Testator),
+    (instruction::LookupCompressedLiveness::DISCRIMINATOR, Role::Anyone),
+    (instruction::SetHealthOracle::DISCRIMINATOR, Role::Testator),
+    (instruction::SubmitHealthSignal::DISCRIMINATOR, Role::HealthOracle),
+    (instruction::SetLotteryMode::DISCRIMINATOR, Role::Testator),
+    (instruction::RegisterLotteryParticipant::DISCRIMINATOR, Role::Testator),
+    (instruction::ClaimLotteryPrize::DISCRIMINATOR, Role::Heir),
+    (instruction::SetRecoveryAuthority::DISCRIMINATOR, Role::ProtocolAuthority),
+    (instruction::EmergencyKeyReveal::DISCRIMINATOR, Role::ProtocolAuthority),
+    (instruction::SetRecoveryEncryptionKey::DISCRIMINATOR, Role::ProtocolAuthority),
+    (instruction::SetRecoveryEscrow::DISCRIMINATOR, Role::Testator),
+    (instruction::LookupVaultById::DISCRIMINATOR, Role::Anyone),
+    (instruction::SetDryRunsEnabled::DISCRIMINATOR, Role::ProtocolAuthority),
+    (instruction::DryRunCompressedLivenessUpdate::DISCRIMINATOR, Role::Anyone),
+    #[cfg(feature = "deed_nft")]
+    (instruction::MintVaultDeed::DISCRIMINATOR, Role::Testator),
+    #[cfg(feature = "deed_nft")]
+    (instruction::BurnVaultDeed::DISCRIMINATOR, Role::Anyone),
+    (instruction::RegisterLivenessScheduler::DISCRIMINATOR, Role::Testator),
+    (instruction::UnregisterLivenessScheduler::DISCRIMINATOR, Role::Testator),
+    (instruction::VerifyContentHash::DISCRIMINATOR, Role::Anyone),
+    (instruction::DepositNft::DISCRIMINATOR, Role::Testator),
+    (instruction::WithdrawNft::DISCRIMINATOR, Role::Testator),
+    (instruction::ComputeActivityScore::DISCRIMINATOR, Role::Anyone),
+    (instruction::SetVaultExpiry::DISCRIMINATOR, Role::Testator),
+    (instruction::CloseExpiredVault::DISCRIMINATOR, Role::Anyone),
+    (instruction::InitNullifierRegistry::DISCRIMINATOR, Role::ProtocolAuthority),
+    (instruction::GrowNullifierRegistry::DISCRIMINATOR, Role::Anyone),
+    (instruction::PruneNullifierRegistry::DISCRIMINATOR, Role::Anyone),
+    (instruction::SetExtraNullifierCheck::DISCRIMINATOR, Role::ProtocolAuthority),
+    (instruction::RegisterValuationOracle::DISCRIMINATOR, Role::Testator),
+    (instruction::SubmitEstateValuation::DISCRIMINATOR, Role::ValuationOracle),
+    (instruction::ReadEstateValuation::DISCRIMINATOR, Role::Anyone),
+    (instruction::SetSecretQuestion::DISCRIMINATOR, Role::Testator),
+    (instruction::VerifySecretAnswer::DISCRIMINATOR, Role::Beneficiary),
+    (instruction::SetVaultCreationFee::DISCRIMINATOR, Role::ProtocolAuthority),
+    (instruction::GrantFeeWaiver::DISCRIMINATOR, Role::ProtocolAuthority),
+    (instruction::ListExecutionRight::DISCRIMINATOR, Role::Testator),
+    (instruction::PurchaseExecutionRight::DISCRIMINATOR, Role::Anyone),
+    (instruction::ExecuteInheritanceAsExecutor::DISCRIMINATOR, Role::Executor),
+    (instruction::CommitLegalDocument::DISCRIMINATOR, Role::Testator),
+    (instruction::RemoveLegalDocument::DISCRIMINATOR, Role::Testator),
+    (instruction::IssueContactChallenge::DISCRIMINATOR, Role::Testator),
+    (instruction::VerifyContactChallenge::DISCRIMINATOR, Role::Beneficiary),
+    (instruction::BatchReadCompressedLiveness::DISCRIMINATOR, Role::Anyone),
+    (instruction::ExecuteInheritanceWithMemo::DISCRIMINATOR, Role::Heir),
+    (instruction::ArchiveVault::DISCRIMINATOR, Role::Beneficiary),
+    (instruction::SetBeneficiarySplits::DISCRIMINATOR, Role::Testator),
+    (instruction::DepositToken::DISCRIMINATOR, Role::Testator),
+    (instruction::WithdrawToken::DISCRIMINATOR, Role::Testator),
+    (instruction::ExecuteTokenInheritance::DISCRIMINATOR, Role::Heir),
+    (instruction::ExecuteNftInheritance::DISCRIMINATOR, Role::Heir),
+    #[cfg(feature = "pnft")]
+    (instruction::DepositPnft::DISCRIMINATOR, Role::Testator),
+    #[cfg(feature = "pnft")]
+    (instruction::WithdrawPnft::DISCRIMINATOR, Role::Testator),
+    #[cfg(feature = "pnft")]
+    (instruction::ExecutePnftInheritance::DISCRIMINATOR, Role::Heir),
+    (instruction::DepositCnft::DISCRIMINATOR, Role::Testator),
+    (instruction::WithdrawCnft::DISCRIMINATOR, Role::Testator),
+    (instruction::ExecuteCnftInheritance::DISCRIMINATOR, Role::Heir),
+    (instruction::UpdateVaultConfig::DISCRIMINATOR, Role::Testator),
+    (instruction::ProposeBeneficiaryChange::DISCRIMINATOR, Role::Testator),
+    (instruction::AcceptBeneficiaryChange::DISCRIMINATOR, Role::Nominee),
+    (instruction::DepositSol::DISCRIMINATOR, Role::Anyone),
+    (instruction::WithdrawSol::DISCRIMINATOR, Role::Testator),
+    (instruction::SetFallbackBeneficiary::DISCRIMINATOR, Role::Testator),
+    (instruction::ClaimAsFallbackBeneficiary::DISCRIMINATOR, Role::FallbackBeneficiary),
+    (instruction::InitGuardianSet::DISCRIMINATOR, Role::Testator),
+    (instruction::ProposeTestatorRotation::DISCRIMINATOR, Role::CoSigner),
+    (instruction::ApproveTestatorRotation::DISCRIMINATOR, Role::CoSigner),
+    (instruction::ApplyTestatorRotation::DISCRIMINATOR, Role::Anyone),
+    (instruction::CancelTestatorRotation::DISCRIMINATOR, Role::Testator),
+    (instruction::MigrateLegacyKeyWrap::DISCRIMINATOR, Role::Testator),
+    (instruction::MigratePlaintextKey::DISCRIMINATOR, Role::Testator),
+    (instruction::SetRequireLivenessProof::DISCRIMINATOR, Role::Testator),
+    (instruction::ExecuteInheritanceWithLivenessProof::DISCRIMINATOR, Role::Heir),
+    (instruction::CancelWillWithCompressedLiveness::DISCRIMINATOR, Role::Testator),
+    (instruction::SetCrankBounty::DISCRIMINATOR, Role::Testator),
+    (instruction::MarkWarning::DISCRIMINATOR, Role::Anyone),
+    (instruction::MarkClaimable::DISCRIMINATOR, Role::Anyone),
+    (instruction::SetDeadlineExtensionPolicy::DISCRIMINATOR, Role::Testator),
+    (instruction::ExtendDeadline::DISCRIMINATOR, Role::DeadlineExtender),
+    (instruction::RegisterPingDelegate::DISCRIMINATOR, Role::Testator),
+    (instruction::RevokePingDelegate::DISCRIMINATOR, Role::Testator),
+    (instruction::UpdateLivenessRelayed::DISCRIMINATOR, Role::Anyone),
+    (instruction::SetRequireVerifierAttestation::DISCRIMINATOR, Role::Testator),
+    (instruction::ExecuteInheritanceWithAttestation::DISCRIMINATOR, Role::Heir),
+    (instruction::InitVerifierRegistry::DISCRIMINATOR, Role::ProtocolAuthority),
+    (instruction::AddRegisteredVerifier::DISCRIMINATOR, Role::ProtocolAuthority),
+    (instruction::RemoveRegisteredVerifier::DISCRIMINATOR, Role::ProtocolAuthority),
+    (instruction::SetRequireRegisteredVerifier::DISCRIMINATOR, Role::ProtocolAuthority),
+    (instruction::RotateVerifier::DISCRIMINATOR, Role::Testator),
+    (instruction::CancelVerifierRotation::DISCRIMINATOR, Role::Testator),
+    (instruction::CancelVerifierCommittee::DISCRIMINATOR, Role::Testator),
+    (instruction::ApplyVerifierRotation::DISCRIMINATOR, Role::Anyone),
+    (instruction::SetVerifierRotationDelay::DISCRIMINATOR, Role::Testator),
+    (instruction::DrawLotteryWinner::DISCRIMINATOR, Role::Anyone),
+];
+
+/// Role required by the instruction with `discriminator`, if it is in the table.
+pub const fn required_role(discriminator: &[u8]) -> Option<Role> {
+    let mut i = 0;
+    while i < INSTRUCTION_AUTH.len() {
+        if bytes_eq(INSTRUCTION_AUTH[i].0, discriminator) {
+            return Some(INSTRUCTION_AUTH[i].1);
+        }
+        i += 1;
+    }
+    None
+}
+
+/// Fails with `ErrorCode::RoleNotAuthorized` unless `signer` holds `required_role`
+/// on `vault`. Roles the vault doesn't record always fail; see `authorize_holder`.
+pub fn authorize(signer: &AccountInfo, required_role: Role, vault: &Vault) -> Result<()> {
+    require!(signer.is_signer, ErrorCode::RoleNotAuthorized);
+    let key = signer.key;
+    let authorized = match required_role {
+        Role::Testator => *key == vault.testator,
+        Role::Beneficiary => *key == vault.beneficiary,
+        Role::Heir => vault.heir() == Some(*key),
+        Role::Verifier => *key == vault.verifier,
+        Role::Pinger => {
+            *key == vault.testator
+                || vault.custody_agent == Some(*key)
+                || vault.scheduler_pubkey == Some(*key)
+                || vault.ping_delegate == Some(*key)
+        }
+        Role::HealthOracle => vault.health_oracle == Some(*key),
+        Role::TaxOracle => vault.tax_authority_oracle == Some(*key),
+        Role::FallbackBeneficiary => vault
+            .fallback_beneficiary
+            .as_ref()
+            .is_some_and(|fallback| fallback.beneficiary == *key),
+        Role::DeadlineExtender => *key == vault.testator || vault.deadline_delegate == Some(*key),
+        Role::ValuationOracle
+        | Role::ProtocolAuthority
+        | Role::Executor
+        | Role::CoSigner
+        | Role::Nominee
+        | Role::Anyone => false,
+    };
+    require!(authorized, ErrorCode::RoleNotAuthorized);
+    Ok(())
+}
+
+/// `authorize` for roles recorded outside the vault: `signer` must sign and be
+/// `holder`, the key the instruction's own accounts name for `required_role` (`None`
+/// when nobody holds it, e.g. an unsold execution right).
+pub fn authorize_holder(signer: &AccountInfo, required_role: Role, holder: Option<Pubkey>) -> Result<()> {
+    require!(required_role != Role::Anyone, ErrorCode::RoleNotAuthorized);
+    require!(
+        signer.is_signer && holder == Some(*signer.key),
+        ErrorCode::RoleNotAuthorized
+    );
+    Ok(())
+}
+
+/// `authorize` for protocol-level instructions: only `ProtocolAuthority` (the config's
+/// authority) can be held there.
+pub fn authorize_protocol(signer: &AccountInfo, required_role: Role, config: &ProtocolConfig) -> Result<()> {
+    require!(
+        required_role == Role::ProtocolAuthority && signer.is_signer && *signer.key == config.authority,
+        ErrorCode::RoleNotAuthorized
+    );
+    Ok(())
+}
+
+const fn bytes_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < a.len() {
+        if a[i] != b[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+const fn has_duplicate_discriminators() -> bool {
+    let mut i = 0;
+    while i < INSTRUCTION_AUTH.len() {
+        let mut j = i + 1;
+        while j < INSTRUCTION_AUTH.len() {
+            if bytes_eq(INSTRUCTION_AUTH[i].0, INSTRUCTION_AUTH[j].0) {
+                return true;
+            }
+            j += 1;
+        }
+        i += 1;
+    }
+    false
+}
+
+// Table check: no instruction listed twice
+const _: () = assert!(!has_duplicate_discriminators());
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A vault with a distinct key for every party recorded on it
+    struct Parties {
+        vault: Vault,
+        testator: Pubkey,
+        beneficiary: Pubkey,
+        verifier: Pubkey,
+        custody_agent: Pubkey,
+        scheduler: Pubkey,
+        ping_delegate: Pubkey,
+        health_oracle: Pubkey,
+        tax_oracle: Pubkey,
+        fallback_beneficiary: Pubkey,
+        deadline_delegate: Pubkey,
+    }
+
+    impl Parties {
+        fn new() -> Self {
+            let [
+                testator,
+                beneficiary,
+                verifier,
+                custody_agent,
+                scheduler,
+                ping_delegate,
+                health_oracle,
+                tax_oracle,
+                fallback_beneficiary,
+                deadline_delegate,
+            ] = [(); 10].map(|_| Pubkey::new_unique());
+            let vault = Vault {
+                testator,
+                beneficiary,
+                verifier,
+                custody_agent: Some(custody_agent),
+                scheduler_pubkey: Some(scheduler),
+                ping_delegate: Some(ping_delegate),
+                health_oracle: Some(health_oracle),
+                tax_authority_oracle: Some(tax_oracle),
+                fallback_beneficiary: Some(crate::FallbackBeneficiary {
+                    beneficiary: fallback_beneficiary,
+                    identity_hash: [0; 32],
+                    email_hash: [0; 32],
+                    document_id_hash: [0; 32],
+                    encrypted_password: vec![],
+                    claim_window_secs: 0,
+                }),
+                deadline_delegate: Some(deadline_delegate),
+                ..Default::default()
+            };
+            Self {
+                vault,
+                testator,
+                beneficiary,
+                verifier,
+                custody_agent,
+                scheduler,
+                ping_delegate,
+                health_oracle,
+                tax_oracle,
+                fallback_beneficiary,
+                deadline_delegate,
+            }
+        }
+
+        fn all(&self) -> Vec<Pubkey> {
+            vec![
+                self.testator,
+                self.beneficiary,
+                self.verifier,
+                self.custody_agent,
+                self.scheduler,
+                self.ping_delegate,
+                self.health_oracle,
+                self.tax_oracle,
+                self.fallback_beneficiary,
+                self.deadline_delegate,
+                Pubkey::new_unique(),
+            ]
+        }
+    }
+
+    fn authorized(key: Pubkey, is_signer: bool, role: Role, vault: &Vault) -> bool {
+        let (mut lamports, mut data) = (0, Vec::<u8>::new());
+        let signer = AccountInfo::new(&key, is_signer, false, &mut lamports, &mut data, &key, false, 0);
+        authorize(&signer, role, vault).is_ok()
+    }
+
+    /// Asserts that exactly `holders` hold `role` on `vault`, and only when signing
+    fn assert_held_by(role: Role, vault: &Vault, candidates: &[Pubkey], holders: &[Pubkey]) {
+        for key in candidates {
+            assert_eq!(authorized(*key, true, role, vault), holders.contains(key), "{role:?} {key}");
+            assert!(!authorized(*key, false, role, vault), "{role:?} {key} without signing");
+        }
+    }
+
+    #[test]
+    fn vault_roles_admit_only_their_holders() {
+        let p = Parties::new();
+        let all = p.all();
+        assert_held_by(Role::Testator, &p.vault, &all, &[p.testator]);
+        assert_held_by(Role::Beneficiary, &p.vault, &all, &[p.beneficiary]);
+        assert_held_by(Role::Heir, &p.vault, &all, &[p.beneficiary]);
+        assert_held_by(Role::Verifier, &p.vault, &all, &[p.verifier]);
+        assert_held_by(
+            Role::Pinger,
+            &p.vault,
+            &all,
+            &[p.testator, p.custody_agent, p.scheduler, p.ping_delegate],
+        );
+        assert_held_by(Role::HealthOracle, &p.vault, &all, &[p.health_oracle]);
+        assert_held_by(Role::TaxOracle, &p.vault, &all, &[p.tax_oracle]);
+        assert_held_by(Role::FallbackBeneficiary, &p.vault, &all, &[p.fallback_beneficiary]);
+        assert_held_by(Role::DeadlineExtender, &p.vault, &all, &[p.testator, p.deadline_delegate]);
+    }
+
+    #[test]
+    fn lottery_winner_is_heir_but_not_beneficiary() {
+        let mut p = Parties::new();
+        let winner = Pubkey::new_unique();
+        p.vault.lottery_winner = Some(winner);
+        let mut all = p.all();
+        all.push(winner);
+        assert_held_by(Role::Heir, &p.vault, &all, &[winner]);
+        assert_held_by(Role::Beneficiary, &p.vault, &all, &[p.beneficiary]);
+    }
+
+    #[test]
+    fn nobody_is_heir_of_an_executed_lottery_before_the_draw() {
+        let mut p = Parties::new();
+        p.vault.lottery_mode = true;
+        assert_held_by(Role::Heir, &p.vault, &p.all(), &[p.beneficiary]);
+        p.vault.executed = true;
+        assert_held_by(Role::Heir, &p.vault, &p.all(), &[]);
+    }
+
+    #[test]
+    fn roles_held_outside_the_vault_fail_closed() {
+        let p = Parties::new();
+        for role in [
+            Role::ValuationOracle,
+            Role::ProtocolAuthority,
+            Role::Executor,
+            Role::CoSigner,
+            Role::Nominee,
+            Role::Anyone,
+        ] {
+            assert_held_by(role, &p.vault, &p.all(), &[]);
+        }
+    }
+
+    #[test]
+    fn holder_roles_admit_only_the_signing_holder() {
+        let holder = Pubkey::new_unique();
+        let other = Pubkey::new_unique();
+        let check = |key: Pubkey, is_signer: bool, role: Role, expected: Option<Pubkey>| {
+            let (mut lamports, mut data) = (0, Vec::<u8>::new());
+            let signer = AccountInfo::new(&key, is_signer, false, &mut lamports, &mut data, &key, false, 0);
+            authorize_holder(&signer, role, expected).is_ok()
+        };
+        for role in [Role::ValuationOracle, Role::Executor, Role::CoSigner, Role::Nominee] {
+            assert!(check(holder, true, role, Some(holder)), "{role:?}");
+            assert!(!check(holder, false, role, Some(holder)), "{role:?} without signing");
+            assert!(!check(other, true, role, Some(holder)), "{role:?} wrong signer");
+            assert!(!check(holder, true, role, None), "{role:?} unheld");
+        }
+        assert!(!check(holder, true, Role::Anyone, Some(holder)));
+    }
+
+    #[test]
+    fn protocol_authority_is_the_signing_config_authority() {
+        let authority = Pubkey::new_unique();
+        let config = ProtocolConfig { authority, ..Default::default() };
+        let check = |key: Pubkey, is_signer: bool, role: Role| {
+            let (mut lamports, mut data) = (0, Vec::<u8>::new());
+            let signer = AccountInfo::new(&key, is_signer, false, &mut lamports, &mut data, &key, false, 0);
+            authorize_protocol(&signer, role, &config).is_ok()
+        };
+        assert!(check(authority, true, Role::ProtocolAuthority));
+        assert!(!check(authority, false, Role::ProtocolAuthority));
+        assert!(!check(Pubkey::new_unique(), true, Role::ProtocolAuthority));
+        assert!(!check(authority, true, Role::Anyone));
+        assert!(!check(authority, true, Role::Testator));
+    }
+}
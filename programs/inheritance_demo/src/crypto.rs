@@ -0,0 +1,83 @@
+//! Key derivation over the SHA-256 syscall: HMAC-SHA256 (RFC 2104) and HKDF-SHA256
+//! (RFC 5869), next to the constant-time comparisons. Uses only `core`, no allocation.
+
+pub use constant_time_eq::{constant_time_eq, constant_time_eq_32};
+
+/// HKDF `info` label for K_light, the key wrapping the vault key K
+pub const K_LIGHT_LABEL: &[u8] = b"zelf-k-light";
+
+/// Most `data` parts `hmac_sha256` accepts
+pub const MAX_HMAC_PARTS: usize = 7;
+
+/// HMAC-SHA256 of the concatenation of `data` (at most `MAX_HMAC_PARTS` parts).
+pub fn hmac_sha256(key: &[u8; 32], data: &[&[u8]]) -> [u8; 32] {
+    assert!(data.len() <= MAX_HMAC_PARTS, "too many HMAC input parts");
+    let mut ipad = [0x36u8; 64];
+    let mut opad = [0x5cu8; 64];
+    for ((i, o), k) in ipad.iter_mut().zip(opad.iter_mut()).zip(key) {
+        *i ^= k;
+        *o ^= k;
+    }
+
+    let mut inner_input: [&[u8]; MAX_HMAC_PARTS + 1] = [&[]; MAX_HMAC_PARTS + 1];
+    inner_input[0] = &ipad;
+    inner_input[1..=data.len()].copy_from_slice(data);
+    let inner = solana_sha256_hasher::hashv(&inner_input[..=data.len()]).to_bytes();
+    solana_sha256_hasher::hashv(&[opad.as_slice(), inner.as_slice()]).to_bytes()
+}
+
+/// HKDF-Extract: the pseudorandom key for `ikm` under `salt`.
+pub fn hkdf_extract(salt: &[u8; 32], ikm: &[u8]) -> [u8; 32] {
+    hmac_sha256(salt, &[ikm])
+}
+
+/// HKDF-Expand to a single 32-byte block: `T(1) = HMAC(prk, info || 0x01)`. `info` is
+/// the concatenation of its parts, at most `MAX_HMAC_PARTS - 1` of them.
+pub fn hkdf_expand_32(prk: &[u8; 32], info: &[&[u8]]) -> [u8; 32] {
+    assert!(info.len() < MAX_HMAC_PARTS, "too many HKDF info parts");
+    let mut parts: [&[u8]; MAX_HMAC_PARTS] = [&[]; MAX_HMAC_PARTS];
+    parts[..info.len()].copy_from_slice(info);
+    parts[info.len()] = &[1u8];
+    hmac_sha256(prk, &parts[..=info.len()])
+}
+
+/// HKDF-SHA256 with a 32-byte output. `info` should start with a domain separation
+/// label such as `K_LIGHT_LABEL`.
+pub fn hkdf_sha256(salt: &[u8; 32], ikm: &[u8], info: &[&[u8]]) -> [u8; 32] {
+    hkdf_expand_32(&hkdf_extract(salt, ikm), info)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hmac_sha256_rfc_4231_case_2() {
+        // Keys shorter than the block are zero-padded, so "Jefe" and its 32-byte padding agree
+        let mut key = [0u8; 32];
+        key[..4].copy_from_slice(b"Jefe");
+        let expected = [
+            0x5b, 0xdc, 0xc1, 0x46, 0xbf, 0x60, 0x75, 0x4e, 0x6a, 0x04, 0x24, 0x26, 0x08, 0x95, 0x75, 0xc7,
+            0x5a, 0x00, 0x3f, 0x08, 0x9d, 0x27, 0x39, 0x83, 0x9d, 0xec, 0x58, 0xb9, 0x64, 0xec, 0x38, 0x43,
+        ];
+        assert_eq!(hmac_sha256(&key, &[b"what do ya want for nothing?".as_slice()]), expected);
+        assert_eq!(hmac_sha256(&key, &[b"what do ya ".as_slice(), b"want for nothing?"]), expected);
+    }
+
+    #[test]
+    fn hkdf_sha256_rfc_5869_case_3() {
+        // An empty salt is HashLen zero bytes; the expected output is the first block of the OKM
+        let ikm = [0x0bu8; 22];
+        let prk = [
+            0x19, 0xef, 0x24, 0xa3, 0x2c, 0x71, 0x7b, 0x16, 0x7f, 0x33, 0xa9, 0x1d, 0x6f, 0x64, 0x8b, 0xdf,
+            0x96, 0x59, 0x67, 0x76, 0xaf, 0xdb, 0x63, 0x77, 0xac, 0x43, 0x4c, 0x1c, 0x29, 0x3c, 0xcb, 0x04,
+        ];
+        let okm = [
+            0x8d, 0xa4, 0xe7, 0x75, 0xa5, 0x63, 0xc1, 0x8f, 0x71, 0x5f, 0x80, 0x2a, 0x06, 0x3c, 0x5a, 0x31,
+            0xb8, 0xa1, 0x1f, 0x5c, 0x5e, 0xe1, 0x87, 0x9e, 0xc3, 0x45, 0x4e, 0x5f, 0x3c, 0x73, 0x8d, 0x2d,
+        ];
+        assert_eq!(hkdf_extract(&[0; 32], &ikm), prk);
+        assert_eq!(hkdf_expand_32(&prk, &[]), okm);
+        assert_eq!(hkdf_sha256(&[0; 32], &ikm, &[]), okm);
+    }
+}
@@ -0,0 +1,139 @@
+use anchor_lang::prelude::*;
+
+use crate::{ErrorCode, Vault};
+
+/// Common checks on raw account infos, shared across instructions.
+pub trait AccountInfoExtensions {
+    /// Fails with `error` unless the account's key equals `expected`.
+    fn assert_key_matches(&self, expected: &Pubkey, error: ErrorCode) -> Result<()>;
+    /// Fails with `ErrorCode::Unauthorized` unless the account signed the transaction.
+    fn assert_is_signer(&self) -> Result<()>;
+    /// Fails with `ErrorCode::WrongAccountOwner` unless the account is owned by `program_id`.
+    fn assert_owned_by(&self, program_id: &Pubkey) -> Result<()>;
+    /// Current lamport balance, or 0 if the lamports are already borrowed.
+    fn safe_lamports(&self) -> u64;
+}
+
+impl AccountInfoExtensions for AccountInfo<'_> {
+    fn assert_key_matches(&self, expected: &Pubkey, error: ErrorCode) -> Result<()> {
+        require!(self.key == expected, error);
+        Ok(())
+    }
+
+    fn assert_is_signer(&self) -> Result<()> {
+        require!(self.is_signer, ErrorCode::Unauthorized);
+        Ok(())
+    }
+
+    fn assert_owned_by(&self, program_id: &Pubkey) -> Result<()> {
+        require!(self.owner == program_id, ErrorCode::WrongAccountOwner);
+        Ok(())
+    }
+
+    fn safe_lamports(&self) -> u64 {
+        self.try_lamports().unwrap_or(0)
+    }
+}
+
+/// Common checks on a deserialized `Vault` account.
+pub trait VaultExtensions {
+    /// Fails with `ErrorCode::AlreadyExecuted` if the inheritance was executed.
+    fn assert_not_executed(&self) -> Result<()>;
+    /// Fails with `ErrorCode::Unauthorized` unless `signer` is the vault's testator.
+    fn assert_testator(&self, signer: &Pubkey) -> Result<()>;
+    /// Fails with `ErrorCode::Unauthorized` unless `signer` inherits the vault's assets
+    /// (`Vault::heir`).
+    fn assert_heir(&self, signer: &Pubkey) -> Result<()>;
+}
+
+impl VaultExtensions for Account<'_, Vault> {
+    fn assert_not_executed(&self) -> Result<()> {
+        require!(!self.executed, ErrorCode::AlreadyExecuted);
+        Ok(())
+    }
+
+    fn assert_testator(&self, signer: &Pubkey) -> Result<()> {
+        require!(self.testator == *signer, ErrorCode::Unauthorized);
+        Ok(())
+    }
+
+    fn assert_heir(&self, signer: &Pubkey) -> Result<()> {
+        require!(self.heir() == Some(*signer), ErrorCode::Unauthorized);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(code: ErrorCode) -> anchor_lang::error::Error {
+        code.into()
+    }
+
+    #[test]
+    fn account_info_checks() {
+        let key = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let (mut lamports, mut data) = (42, vec![]);
+        let info = AccountInfo::new(&key, true, false, &mut lamports, &mut data, &owner, false, 0);
+
+        assert!(info.assert_key_matches(&key, ErrorCode::InvalidVerifier).is_ok());
+        assert_eq!(
+            info.assert_key_matches(&owner, ErrorCode::InvalidVerifier).unwrap_err(),
+            error(ErrorCode::InvalidVerifier)
+        );
+        assert!(info.assert_is_signer().is_ok());
+        assert!(info.assert_owned_by(&owner).is_ok());
+        assert_eq!(info.assert_owned_by(&key).unwrap_err(), error(ErrorCode::WrongAccountOwner));
+        assert_eq!(info.safe_lamports(), 42);
+
+        let other = Pubkey::new_unique();
+        let (mut lamports, mut data) = (0, vec![]);
+        let unsigned = AccountInfo::new(&other, false, false, &mut lamports, &mut data, &owner, false, 0);
+        assert_eq!(unsigned.assert_is_signer().unwrap_err(), error(ErrorCode::Unauthorized));
+    }
+
+    #[test]
+    fn safe_lamports_tolerates_a_borrow() {
+        let key = Pubkey::new_unique();
+        let (mut lamports, mut data) = (42, vec![]);
+        let info = AccountInfo::new(&key, false, true, &mut lamports, &mut data, &key, false, 0);
+
+        let borrowed = info.lamports.borrow_mut();
+        assert_eq!(info.safe_lamports(), 0);
+        drop(borrowed);
+        assert_eq!(info.safe_lamports(), 42);
+    }
+
+    #[test]
+    fn vault_checks() {
+        let testator = Pubkey::new_unique();
+        let beneficiary = Pubkey::new_unique();
+        let winner = Pubkey::new_unique();
+        let vault = Vault { testator, beneficiary, ..Default::default() };
+
+        let key = Pubkey::new_unique();
+        let mut lamports = 0;
+        let mut data = vec![];
+        vault.try_serialize(&mut data).unwrap();
+        let info = AccountInfo::new(&key, false, true, &mut lamports, &mut data, &crate::ID, false, 0);
+        let mut account = Account::<Vault>::try_from(&info).unwrap();
+
+        assert!(account.assert_not_executed().is_ok());
+        assert!(account.assert_testator(&testator).is_ok());
+        assert_eq!(account.assert_testator(&beneficiary).unwrap_err(), error(ErrorCode::Unauthorized));
+        assert!(account.assert_heir(&beneficiary).is_ok());
+        assert_eq!(account.assert_heir(&testator).unwrap_err(), error(ErrorCode::Unauthorized));
+
+        account.executed = true;
+        assert_eq!(account.assert_not_executed().unwrap_err(), error(ErrorCode::AlreadyExecuted));
+
+        // An executed lottery has no heir until the winner is drawn
+        account.lottery_mode = true;
+        assert_eq!(account.assert_heir(&beneficiary).unwrap_err(), error(ErrorCode::Unauthorized));
+        account.lottery_winner = Some(winner);
+        assert!(account.assert_heir(&winner).is_ok());
+        assert_eq!(account.assert_heir(&beneficiary).unwrap_err(), error(ErrorCode::Unauthorized));
+    }
+}
@@ -1,3 +1,5 @@
+#![deny(deprecated)]
+
 use anchor_lang::prelude::*;
 use light_sdk::{
     derive_light_cpi_signer,
@@ -9,20 +11,124 @@ use light_sdk::{
 };
 use light_sdk::instruction::ValidityProof as LightValidityProof;
 use borsh::{BorshSerialize, BorshDeserialize};
+use aes_gcm::{aead::{AeadInPlace, KeyInit}, Aes256Gcm, Nonce, Tag};
+
+pub mod crypto;
+use crypto::{constant_time_eq, constant_time_eq_32};
+pub mod extensions;
+pub use extensions::{AccountInfoExtensions, VaultExtensions};
+pub mod authorization_matrix;
+pub use authorization_matrix::{authorize, authorize_holder, authorize_protocol, Role};
+#[cfg(not(target_os = "solana"))]
+pub mod ui_state;
+#[cfg(not(target_os = "solana"))]
+pub use ui_state::{PauseReason, UIVaultState};
 
 declare_id!("PQ6EV39W9BQECUnf4v7MPbPCxJwgmwvUwrLY67u13QE");
 
+/// Light System Program ID (the program our compressed-account CPIs target)
+pub const LIGHT_SYSTEM_PROGRAM_ID: Pubkey = pubkey!("SySTEM1eSU2p4BGQfQpimFEWWSC1XDFeun3Nqzz3rT7");
+
+/// SPL Memo program, which records `execute_inheritance_with_memo` statements
+pub const SPL_MEMO_PROGRAM_ID: Pubkey = pubkey!("MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr");
+
+/// Maximum length in bytes of the statement passed to `execute_inheritance_with_memo`
+pub const MAX_LEGAL_MEMO_LEN: usize = 128;
+
+/// Metaplex Bubblegum program, which owns compressed NFTs escrowed by vaults
+pub const BUBBLEGUM_PROGRAM_ID: Pubkey = pubkey!("BGUMAp9Gq7iTEuizy4pqaxsTyUCBK68MDfK752saRPUY");
+pub const SPL_ACCOUNT_COMPRESSION_PROGRAM_ID: Pubkey = pubkey!("cmtDvXumGCrqC1Age74AVPhSRVXJMd8PJS91L8KbNCK");
+pub const SPL_NOOP_PROGRAM_ID: Pubkey = pubkey!("noopb9bkMVfRPU8AsbpTUg8AQkHtKwMYZiFUjNRtMmV");
+
+/// Light account compression program, which owns the state Merkle trees
+pub const LIGHT_ACCOUNT_COMPRESSION_PROGRAM_ID: Pubkey =
+    pubkey!("compr6CUsB5m2jS4Y3831ztGSTnDpnKJTKS95d64XVq");
+
+/// Anchor discriminator of Bubblegum's `transfer` instruction
+const BUBBLEGUM_TRANSFER_DISCRIMINATOR: [u8; 8] = [163, 52, 200, 231, 140, 3, 69, 186];
+
 /// Light Protocol CPI Signer - derived from program ID
 pub const LIGHT_CPI_SIGNER: CpiSigner = 
     derive_light_cpi_signer!("PQ6EV39W9BQECUnf4v7MPbPCxJwgmwvUwrLY67u13QE");
 
+/// Seed of the Light CPI signer PDA
+const LIGHT_CPI_AUTHORITY_SEED: &[u8] = b"cpi_authority";
+
+/// First 8 bytes of the expected CPI signer, `find_program_address([b"cpi_authority"], ID)`
+/// (3E5RPy9j2imvp6Q4KZ9Jbx69k8VnM6giDF8HKd2Sw2yh, bump 254)
+pub const EXPECTED_CPI_SIGNER_PREFIX: [u8; 8] = [33, 16, 236, 59, 120, 194, 250, 44];
+
+const fn starts_with(bytes: &[u8; 32], prefix: &[u8]) -> bool {
+    let mut i = 0;
+    while i < prefix.len() {
+        if bytes[i] != prefix[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+// The build fails if the program ID changes without LIGHT_CPI_SIGNER (and
+// EXPECTED_CPI_SIGNER_PREFIX) being updated with it; see also light_cpi_signer_constant_test
+const _: () = assert!(starts_with(&LIGHT_CPI_SIGNER.program_id, &ID.to_bytes()));
+const _: () = assert!(starts_with(&LIGHT_CPI_SIGNER.cpi_signer, &EXPECTED_CPI_SIGNER_PREFIX));
+
+/// Whether `signer` is the Light CPI signer of `program_id`: it names `program_id`,
+/// and the `[LIGHT_CPI_AUTHORITY_SEED]` PDA of `program_id` and its bump.
+fn is_light_cpi_signer_of(signer: &CpiSigner, program_id: &Pubkey) -> bool {
+    let (cpi_signer, bump) = Pubkey::find_program_address(&[LIGHT_CPI_AUTHORITY_SEED], program_id);
+    signer.program_id == program_id.to_bytes()
+        && signer.cpi_signer == cpi_signer.to_bytes()
+        && signer.bump == bump
+}
+
+/// Wire format version of `ValidityProofData`
+pub const LIGHT_PROOF_VERSION: u32 = 1;
+
 /// Anchor-compatible wrapper for Light Protocol ValidityProof
-/// Serialized as raw bytes to avoid Anchor IDL compatibility issues
-#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+/// Serialized as raw bytes to avoid Anchor IDL compatibility issues.
+/// The bytes are prefixed with a version tag and a size hint (the length of `data`)
+/// so malformed proofs can be diagnosed from the transaction logs.
+#[derive(AnchorSerialize, Clone, Debug)]
 pub struct ValidityProofData {
+    pub version: u32,
+    pub size_hint: u32,
     pub data: Vec<u8>,
 }
 
+impl AnchorDeserialize for ValidityProofData {
+    fn deserialize_reader<R: std::io::Read>(reader: &mut R) -> std::io::Result<Self> {
+        let version = u32::deserialize_reader(reader)?;
+        msg!("Light proof version: {}", version);
+        if version != LIGHT_PROOF_VERSION {
+            return Err(invalid_proof_data(format!(
+                "unsupported Light proof version {} (expected {})",
+                version, LIGHT_PROOF_VERSION
+            )));
+        }
+
+        let size_hint = u32::deserialize_reader(reader)?;
+        let data = Vec::<u8>::deserialize_reader(reader).map_err(|_| {
+            invalid_proof_data(format!("Light proof truncated: expected {} bytes", size_hint))
+        })?;
+        if data.len() != size_hint as usize {
+            return Err(invalid_proof_data(format!(
+                "Light proof size mismatch: hint {} bytes, got {}",
+                size_hint,
+                data.len()
+            )));
+        }
+
+        Ok(Self { version, size_hint, data })
+    }
+}
+
+fn invalid_proof_data(message: String) -> std::io::Error {
+    msg!("{}", message);
+    std::io::Error::new(std::io::ErrorKind::InvalidData, message)
+}
+
 /// Anchor-compatible wrapper for Light Protocol PackedAddressTreeInfo
 #[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
 pub struct AddressTreeInfoData {
@@ -30,22 +136,111 @@ pub struct AddressTreeInfoData {
     pub address_queue_pubkey_index: u8,
 }
 
+/// Canonical layout of the wrapped key K: AES-256-GCM ciphertext under K_light,
+/// with the one-time nonce and authentication tag stored alongside so off-chain
+/// SDKs can decrypt it directly. The vault pubkey is used as associated data.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EncryptedKeyV2 {
+    pub nonce: [u8; 12],
+    pub ciphertext: [u8; 32],
+    pub tag: [u8; 16],
+}
+
+impl EncryptedKeyV2 {
+    pub const SIZE: usize = 12 + 32 + 16;
+}
+
+const _: () = assert!(core::mem::size_of::<EncryptedKeyV2>() == EncryptedKeyV2::SIZE);
+
+/// Escrow copy of the vault's key K for `emergency_key_reveal`. The client picks an
+/// ephemeral X25519 key pair and wraps K (AES-256-GCM, the vault pubkey as associated
+/// data) under `HKDF-SHA256(X25519(ephemeral secret, recovery_encryption_key), salt =
+/// vault, info = RECOVERY_ESCROW_LABEL)`. Only the holder of the recovery private key
+/// can recompute that unwrap key, and it opens this vault's escrow only.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RecoveryEscrow {
+    pub ephemeral_pubkey: [u8; 32],
+    pub encrypted_key: EncryptedKeyV2,
+}
+
+impl RecoveryEscrow {
+    pub const SIZE: usize = 32 + EncryptedKeyV2::SIZE;
+}
+
+/// HKDF `info` of the recovery escrow unwrap key
+pub const RECOVERY_ESCROW_LABEL: &[u8] = b"zelf-recovery-escrow";
+
 /// Compressed Liveness Account - stored in Light Protocol's state tree
 /// This is a ZK-compressed account that tracks testator liveness at ~200x lower cost
 #[derive(Clone, Debug, Default, LightDiscriminator, BorshSerialize, BorshDeserialize)]
 pub struct CompressedLiveness {
+    /// The testator the vault was created for (`Vault::seed_testator`), kept across
+    /// testator rotations like the vault address
+    pub testator: Pubkey,
+    pub last_ping: i64,
+    pub vault_address: Pubkey,
+}
+
+impl CompressedLiveness {
+    /// Canonical address derivation for a vault's compressed liveness account.
+    /// Every instruction must derive the address through this method so the seeds
+    /// can never drift apart. `testator` is `Vault::seed_testator`, so the address
+    /// survives a testator rotation, and `vault` keeps the accounts of a testator's
+    /// vaults apart. Returns `(address, address_seed)`.
+    pub fn derive_address(
+        testator: &Pubkey,
+        vault: &Pubkey,
+        tree_pubkey: &Pubkey,
+        program_id: &Pubkey,
+    ) -> ([u8; 32], [u8; 32]) {
+        derive_address(&[b"liveness", testator.as_ref(), vault.as_ref()], tree_pubkey, program_id)
+    }
+}
+
+/// Compressed Liveness Account, version 2 - adds ping statistics for richer ZK state.
+/// Lives at a separate address (`[b"liveness_v2", testator, vault]`) from the V1 account.
+#[derive(Clone, Debug, Default, LightDiscriminator, BorshSerialize, BorshDeserialize)]
+pub struct CompressedLivenessV2 {
+    /// `Vault::seed_testator`, as in `CompressedLiveness`
     pub testator: Pubkey,
     pub last_ping: i64,
     pub vault_address: Pubkey,
+    pub ping_count: u64,
+    pub last_guardian_ping: i64,
+    pub compression_created_at: i64,
+}
+
+impl CompressedLivenessV2 {
+    /// Canonical address derivation for a vault's V2 compressed liveness account,
+    /// with the seeds of `CompressedLiveness::derive_address`. Returns
+    /// `(address, address_seed)`.
+    pub fn derive_address(
+        testator: &Pubkey,
+        vault: &Pubkey,
+        tree_pubkey: &Pubkey,
+        program_id: &Pubkey,
+    ) -> ([u8; 32], [u8; 32]) {
+        derive_address(&[b"liveness_v2", testator.as_ref(), vault.as_ref()], tree_pubkey, program_id)
+    }
+}
+
+/// Which compressed liveness account layout a vault uses
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum CompressedLivenessVersion {
+    #[default]
+    V1,
+    V2,
 }
 
-/// Event emitted when an inheritance is successfully executed.
+/// Event emitted when an inheritance is successfully executed, once per beneficiary.
 /// Contains the encrypted password (the "reward") that the beneficiary can use
 /// to decrypt and recover the testator's mnemonic/ZelfProof.
 #[event]
 pub struct InheritanceExecuted {
     pub vault: Pubkey,
     pub beneficiary: Pubkey,
+    /// Lamports paid to `beneficiary` (0 without `transfer_funds` and on lottery vaults)
+    pub amount: u64,
     pub testator: Pubkey,
     /// The encrypted password - this is the key to unlock the ZelfProof
     pub encrypted_password: Vec<u8>,
@@ -59,6 +254,69 @@ pub struct InheritanceExecuted {
     pub beneficiary_email_hash: [u8; 32],
     /// SHA-256 hash of beneficiary's document ID for lookup
     pub beneficiary_document_id_hash: [u8; 32],
+    /// Step-by-step guidance encrypted to the beneficiary's key (empty if none was set)
+    pub beneficiary_instructions: Vec<u8>,
+    /// How the beneficiary's app should interpret `encrypted_password`
+    pub key_fragment_type: KeyFragmentType,
+    /// Which fragment of a split secret this vault held, out of `key_fragment_total`
+    pub key_fragment_index: u8,
+    pub key_fragment_total: u8,
+    pub vault_id: u64,
+    pub content_hash: [u8; 32],
+    /// Notarized legal documents anchored with `commit_legal_document`
+    pub legal_documents: Vec<LegalDocumentCommitment>,
+    /// Compute units consumed by the instruction (0 unless built with `compute_metrics`)
+    pub compute_units_used: u64,
+}
+
+/// Event emitted on every successful liveness update (proof of life).
+#[event]
+pub struct PingReceived {
+    pub vault: Pubkey,
+    pub testator: Pubkey,
+    pub timestamp: i64,
+    /// Set when the ping came from `update_liveness_with_note`
+    pub note: Option<[u8; 32]>,
+    /// Whether the ping also updated the compressed liveness account
+    pub via_compressed: bool,
+    pub content_hash: [u8; 32],
+    /// Compute units consumed by the instruction (0 unless built with `compute_metrics`)
+    pub compute_units_used: u64,
+}
+
+/// Event emitted when a vault key is wrapped (again) on-chain by a key migration
+#[event]
+pub struct KeyWrapCompleted {
+    pub vault: Pubkey,
+    pub wrapped_at: i64,
+}
+
+/// Event emitted when the testator records a failed compressed liveness creation
+#[event]
+pub struct CompressedLivenessFailureRecorded {
+    pub vault: Pubkey,
+    pub failures: u8,
+    pub failed_attempt_slot: u64,
+}
+
+/// Event emitted when `retry_create_compressed_liveness` makes another attempt.
+#[event]
+pub struct CompressedLivenessRetryAttempted {
+    pub vault: Pubkey,
+    /// Overall attempt number (the initial `create_compressed_liveness` call is #1)
+    pub attempt_number: u8,
+    pub timestamp: i64,
+}
+
+/// Event emitted when the compressed liveness account is created in Light Protocol's state tree.
+#[event]
+pub struct CompressedLivenessCreated {
+    pub vault: Pubkey,
+    pub testator: Pubkey,
+    /// The derived address of the compressed account
+    pub address: [u8; 32],
+    /// Compute units consumed by the instruction (0 unless built with `compute_metrics`)
+    pub compute_units_used: u64,
 }
 
 /// Event emitted when a beneficiary successfully verifies their identity.
@@ -78,598 +336,11794 @@ pub struct BeneficiaryVerified {
     pub executed: bool,
 }
 
-#[program]
-pub mod inheritance_demo {
-    use super::*;
+/// Event emitted when donations sent to a vault's funding address are swept into the vault.
+#[event]
+pub struct DonationCollected {
+    pub vault: Pubkey,
+    /// Donors send plain system transfers to the funding address, so they are not known here
+    pub donor: Option<Pubkey>,
+    pub amount: u64,
+    pub new_vault_balance: u64,
+    pub content_hash: [u8; 32],
+}
 
-    #[allow(clippy::too_many_arguments)]
-    pub fn init_inheritance(
-        ctx: Context<InitInheritance>,
-        beneficiary: Pubkey,
-        verifier: Pubkey,
-        beneficiary_identity_hash: [u8; 32],
-        beneficiary_email_hash: [u8; 32],
-        beneficiary_document_id_hash: [u8; 32],
-        cid: [u8; 32],
-        cid_validator: [u8; 32],
-        warning_timeout_secs: i64,
-        timeout_secs: i64,
-        lamports: u64,
-        encrypted_password: Vec<u8>,
-        unwrapped_key: [u8; 32],
-        is_debug: bool,
-    ) -> Result<()> {
-        // Validate encrypted password
-        require!(!encrypted_password.is_empty(), ErrorCode::EmptyEncryptedPassword);
-        require!(
-            encrypted_password.len() <= Vault::MAX_ENCRYPTED_PASSWORD_SIZE,
-            ErrorCode::EncryptedPasswordTooLarge
-        );
-        require!(warning_timeout_secs < timeout_secs, ErrorCode::InvalidWarningTimeout);
+/// Event emitted when the verifier reads the vault's emergency contacts.
+/// Contacts are encrypted to the verifier's key and decrypted off-chain.
+#[event]
+pub struct EmergencyContactsRead {
+    pub vault: Pubkey,
+    pub contacts: Vec<EncryptedContact>,
+}
 
-        let vault = &mut ctx.accounts.vault;
-        vault.testator = ctx.accounts.testator.key();
-        vault.beneficiary = beneficiary;
-        vault.verifier = verifier; // Set the trusted identity verifier
-        vault.beneficiary_identity_hash = beneficiary_identity_hash;
-        vault.beneficiary_email_hash = beneficiary_email_hash;
-        vault.beneficiary_document_id_hash = beneficiary_document_id_hash;
-        vault.cid = cid;
-        vault.cid_validator = cid_validator;
-        
-        let now = Clock::get()?.unix_timestamp;
-        vault.last_ping = now;
-        vault.created_at = now;
-        vault.warning_timeout_secs = warning_timeout_secs;
-        vault.timeout_secs = timeout_secs;
-        vault.executed = false;
-        vault.lamports = lamports;
-        vault.encrypted_password = encrypted_password;
-        vault.encrypted_key = None;
-        vault.unwrapped_key = Some(unwrapped_key);
-        vault.light_root = None;
-        vault.is_debug = is_debug;
-        vault.has_compressed_liveness = false;
-        vault.bump = ctx.bumps.vault;
+/// Event emitted during `execute_inheritance` when the vault has a time capsule.
+/// The message is encrypted to the beneficiary's key and decrypted off-chain.
+#[event]
+pub struct TimeCapsuleRevealed {
+    pub vault: Pubkey,
+    pub beneficiary: Pubkey,
+    pub encrypted_message: Vec<u8>,
+}
 
-        // Transfer initial deposit from PAYER (not testator) to vault
-        anchor_lang::system_program::transfer(
-            CpiContext::new(
-                ctx.accounts.system_program.to_account_info(),
-                anchor_lang::system_program::Transfer {
-                    from: ctx.accounts.payer.to_account_info(),
-                    to: ctx.accounts.vault.to_account_info(),
-                },
-            ),
-            lamports,
-        )?;
+/// Event emitted when the deployed Light System Program matches the recorded hash.
+#[event]
+pub struct LightSdkCompatibilityVerified {
+    pub program_hash: [u8; 8],
+    pub verified_at: i64,
+}
 
-        Ok(())
-    }
+/// Event emitted when `migrate_plaintext_key` drops a lingering plaintext copy of an
+/// already wrapped key.
+#[event]
+pub struct UnwrappedKeyErased {
+    pub vault: Pubkey,
+    pub erased_at: i64,
+}
 
-    /// Create a compressed liveness account in Light Protocol's state tree.
-    /// This uses ZK Compression to store liveness data at ~200x lower cost.
-    /// 
-    /// Note: This instruction requires Light Protocol system accounts to be passed
-    /// via remaining_accounts. Use the Light SDK client to prepare these accounts.
-    pub fn create_compressed_liveness<'info>(
-        ctx: Context<'_, '_, '_, 'info, CreateCompressedLiveness<'info>>,
-        proof_data: ValidityProofData,
-        address_tree_info: AddressTreeInfoData,
-        output_tree_index: u8,
-    ) -> Result<()> {
-        // Deserialize the validity proof from raw bytes
-        let proof = LightValidityProof::try_from_slice(&proof_data.data)
-            .map_err(|_| ErrorCode::InvalidLightProof)?;
-        
-        // Create Light CPI accounts from remaining accounts
-        let light_cpi_accounts = CpiAccounts::new(
-            ctx.accounts.fee_payer.as_ref(),
-            ctx.remaining_accounts,
-            crate::LIGHT_CPI_SIGNER,
-        );
+/// Event emitted when `migrate_plaintext_key` moves a legacy vault to the current layout.
+#[event]
+pub struct LegacyVaultMigrated {
+    pub legacy_vault: Pubkey,
+    pub vault: Pubkey,
+    pub migrated_at: i64,
+}
 
-        // Get the address tree pubkey from remaining accounts
-        let address_tree_pubkey = ctx.remaining_accounts
-            .get(address_tree_info.address_merkle_tree_pubkey_index as usize)
-            .ok_or(ErrorCode::InvalidLightRoot)?
-            .key();
+/// Event emitted when a testator is within 2 vaults of `max_vaults_per_testator`.
+#[event]
+pub struct VaultLimitApproaching {
+    pub testator: Pubkey,
+    pub current_count: u8,
+    pub max_count: u8,
+}
 
-        // Derive unique address for this testator's liveness account
-        let (address, address_seed) = derive_address(
-            &[b"liveness", ctx.accounts.testator.key().as_ref()],
-            &address_tree_pubkey,
-            &crate::ID,
-        );
-        
-        // Create packed address params manually
-        let new_address_params = light_sdk::address::PackedNewAddressParams {
-            seed: address_seed.into(),
-            address_merkle_tree_account_index: address_tree_info.address_merkle_tree_pubkey_index,
-            address_queue_account_index: address_tree_info.address_queue_pubkey_index,
-            address_merkle_tree_root_index: 0, // Will be filled by Light Protocol
-        };
+/// Event emitted when `execute_inheritance` holds back the inheritance tax in escrow.
+#[event]
+pub struct TaxEscrowCreated {
+    pub vault: Pubkey,
+    pub tax_amount: u64,
+    pub due_by: i64,
+}
 
-        // Create the compressed liveness account
-        let mut liveness_account = LightAccount::<CompressedLiveness>::new_init(
-            &crate::ID,
-            Some(address),
-            output_tree_index,
-        );
+/// Event emitted when the tax authority oracle releases the tax escrow to itself.
+#[event]
+pub struct TaxPaid {
+    pub vault: Pubkey,
+    pub tax_authority: Pubkey,
+    pub amount: u64,
+}
 
-        liveness_account.testator = ctx.accounts.testator.key();
-        liveness_account.last_ping = Clock::get()?.unix_timestamp;
-        liveness_account.vault_address = ctx.accounts.vault.key();
+/// Event emitted by `read_state_history` with the recorded transitions, oldest first.
+#[event]
+pub struct StateHistoryRead {
+    pub vault: Pubkey,
+    pub entries: Vec<StateTransitionEntry>,
+    pub total_transitions: u64,
+}
 
-        // CPI to Light System Program to create the compressed account
-        LightSystemProgramCpi::new_cpi(crate::LIGHT_CPI_SIGNER, proof)
-            .with_light_account(liveness_account)
-            .map_err(|_| ErrorCode::InvalidLightProof)?
-            .with_new_addresses(&[new_address_params])
-            .invoke(light_cpi_accounts)
-            .map_err(|_| ErrorCode::InvalidLightProof)?;
+/// Event emitted when a long-term testator opens a governance proposal.
+#[event]
+pub struct ProposalCreated {
+    pub proposal_id: u64,
+    pub proposer: Pubkey,
+    pub parameter: GovernanceParameter,
+    pub proposed_value: i64,
+    pub voting_ends_at: i64,
+}
 
-        // Mark that the vault now has a compressed liveness account
-        let vault = &mut ctx.accounts.vault;
-        vault.has_compressed_liveness = true;
+/// Event emitted for every vote cast on a governance proposal.
+#[event]
+pub struct VoteCast {
+    pub proposal_id: u64,
+    pub vault: Pubkey,
+    pub vote: bool,
+    pub weight: u64,
+}
 
-        Ok(())
-    }
+/// Event emitted when a passed proposal is applied to the protocol config.
+#[event]
+pub struct ProposalExecuted {
+    pub proposal_id: u64,
+    pub parameter: GovernanceParameter,
+    pub new_value: i64,
+}
 
-    /// Update liveness using Light Protocol ZK Compression.
-    /// This function updates the compressed liveness account in the state tree
-    /// and updates the vault's last_ping timestamp.
-    pub fn update_liveness<'info>(
-        ctx: Context<'_, '_, '_, 'info, UpdateLiveness<'info>>,
-        proof_data: ValidityProofData,
-        output_tree_index: u8,
-    ) -> Result<()> {
-        let vault = &mut ctx.accounts.vault;
-        let now = Clock::get()?.unix_timestamp;
+/// Event emitted when a vault is handed over to a newer program version.
+#[event]
+pub struct VaultMigrated {
+    pub vault: Pubkey,
+    pub caller_program_id: Pubkey,
+    pub via_cpi: bool,
+    pub migrated_at: i64,
+}
 
-        // --- Light Protocol CPI Update ---
-        if vault.has_compressed_liveness && !vault.is_debug {
-            // Deserialize the validity proof from raw bytes
-            let proof = LightValidityProof::try_from_slice(&proof_data.data)
-                .map_err(|_| ErrorCode::InvalidLightProof)?;
-            
-            // Create Light CPI accounts from remaining accounts
-            let light_cpi_accounts = CpiAccounts::new(
-                ctx.accounts.fee_payer.as_ref(),
-                ctx.remaining_accounts,
-                crate::LIGHT_CPI_SIGNER,
-            );
+/// Event emitted when a V1 compressed liveness account is replaced by a V2 account.
+#[event]
+pub struct CompressedLivenessMigrated {
+    pub vault: Pubkey,
+    pub v1_address: [u8; 32],
+    pub v2_address: [u8; 32],
+}
 
-            // Derive the address for this testator's liveness account
-            // Must match the address used in create_compressed_liveness
-            let address_tree_pubkey = ctx.remaining_accounts
-                .get(0)
-                .ok_or(ErrorCode::InvalidLightRoot)?
-                .key();
+/// Event emitted when the testator hands ping custody to a trusted agent.
+#[event]
+pub struct CustodyGranted {
+    pub vault: Pubkey,
+    pub agent: Pubkey,
+    pub expires_at: i64,
+}
 
-            let (address, _) = derive_address(
-                &[b"liveness", ctx.accounts.testator.key().as_ref()],
-                &address_tree_pubkey,
-                &crate::ID,
-            );
+/// Event emitted when a custody agent pings on the testator's behalf.
+#[event]
+pub struct CustodialPingReceived {
+    pub vault: Pubkey,
+    pub agent: Pubkey,
+}
 
-            // Update the compressed liveness account with new timestamp
-            let mut liveness_account = LightAccount::<CompressedLiveness>::new_update(
-                &crate::ID,
-                Some(address),
-                output_tree_index,
-            );
+/// Event emitted when a vault's move to the rotated verifier key is scheduled.
+#[event]
+pub struct VerifierMigrationScheduled {
+    pub vault: Pubkey,
+    pub old_verifier: Pubkey,
+    pub new_verifier: Pubkey,
+    pub effective_at: i64,
+}
 
-            liveness_account.testator = ctx.accounts.testator.key();
-            liveness_account.last_ping = now;
-            liveness_account.vault_address = vault.key();
+/// Event emitted by `run_benchmark` with the measured cost of an operation.
+#[event]
+pub struct BenchmarkResult {
+    pub operation: BenchmarkOperation,
+    pub iterations: u8,
+    /// 0 unless built with `compute_metrics`
+    pub compute_units_per_iteration: u64,
+    /// Wall-clock time is not observable on-chain; reserved for off-chain harnesses
+    pub total_time_ns: Option<u64>,
+}
 
-            // CPI to Light System Program to update the compressed account
-            LightSystemProgramCpi::new_cpi(crate::LIGHT_CPI_SIGNER, proof)
-                .with_light_account(liveness_account)
-                .map_err(|_| ErrorCode::InvalidLightProof)?
-                .invoke(light_cpi_accounts)
-                .map_err(|_| ErrorCode::InvalidLightProof)?;
+/// Event emitted when a testator cancels after the beneficiary verified their identity
+/// and forfeits the destruction bond.
+#[event]
+pub struct DestructionBondForfeited {
+    pub vault: Pubkey,
+    pub testator: Pubkey,
+    pub beneficiary: Pubkey,
+    pub amount: u64,
+}
 
-            msg!("✅ Compressed liveness updated via Light Protocol");
-        } else if vault.is_debug {
-            msg!("⚠️ Debug mode: Skipping Light Protocol verification");
-        } else {
-            msg!("ℹ️ No compressed liveness account, using standard update");
+/// Event emitted after an atomic biometric re-enrollment.
+/// `updated_fields_mask` uses the `Vault::BIOMETRIC_*` bits.
+#[event]
+pub struct BiometricDataUpdated {
+    pub vault: Pubkey,
+    pub testator: Pubkey,
+    pub update_sequence: u64,
+    pub updated_fields_mask: u8,
+    pub content_hash: [u8; 32],
+}
+
+/// Event emitted when SOL is deposited into a vault outside of init_inheritance
+#[event]
+pub struct SolDeposited {
+    pub vault: Pubkey,
+    pub depositor: Pubkey,
+    pub amount: u64,
+    pub new_vault_balance: u64,
+    pub content_hash: [u8; 32],
+}
+
+/// Event emitted by lookup_vault_address when the vault exists
+#[event]
+pub struct VaultAddressLookup {
+    pub testator: Pubkey,
+    pub beneficiary: Pubkey,
+    pub vault_pda: Pubkey,
+    pub bump: u8,
+}
+
+/// Event emitted by lookup_vault_address when no vault exists for the pair
+#[event]
+pub struct VaultAddressNotFound {
+    pub testator: Pubkey,
+    pub beneficiary: Pubkey,
+    pub would_be_pda: Pubkey,
+}
+
+/// Event emitted when the protocol authority pauses all inheritance executions
+#[event]
+pub struct ExecutionsPaused {
+    pub authority: Pubkey,
+    pub paused_at: i64,
+    pub expires_at: i64,
+}
+
+/// Event emitted when the protocol authority lifts an execution pause
+#[event]
+pub struct ExecutionsResumed {
+    pub authority: Pubkey,
+    pub resumed_at: i64,
+}
+
+/// Event emitted when a committee member approves a vault's execution
+#[event]
+pub struct CommitteeApprovalSubmitted {
+    pub vault: Pubkey,
+    pub verifier: Pubkey,
+    pub verifier_index: u8,
+    pub live_approvals: u8,
+    pub threshold: u8,
+}
+
+/// Event emitted when a beneficiary executes a committee-verified vault
+#[event]
+pub struct CommitteeExecutionFinalised {
+    pub vault: Pubkey,
+    pub beneficiary: Pubkey,
+    pub live_approvals: u8,
+    pub threshold: u8,
+}
+
+/// Event emitted when the testator aborts a pending execution during the grace period
+#[event]
+pub struct PendingExecutionRevoked {
+    pub vault: Pubkey,
+    pub testator: Pubkey,
+    pub revoked_at: i64,
+    pub execution_count_before_revoke: u8,
+    pub content_hash: [u8; 32],
+}
+
+/// Event emitted for each vault pinged by the protocol authority
+#[event]
+pub struct AdminLivenessPing {
+    pub vault: Pubkey,
+    pub authority: Pubkey,
+    pub evidence_cid: [u8; 32],
+    pub timestamp: i64,
+    pub content_hash: [u8; 32],
+}
+
+/// Event carrying a point-in-time archive of a vault, see `export_vault_state`
+#[event]
+pub struct VaultStateExported {
+    pub vault: Pubkey,
+    pub data: Vec<u8>,
+}
+
+/// Event emitted by lookup_compressed_liveness
+#[event]
+pub struct CompressedLivenessLookup {
+    pub testator: Pubkey,
+    pub found: bool,
+    pub page_id: u8,
+    pub vault: Pubkey,
+    pub address: [u8; 32],
+}
+
+/// Event emitted when a vault's health oracle reports a signal
+#[event]
+pub struct HealthSignalReceived {
+    pub vault: Pubkey,
+    pub oracle: Pubkey,
+    pub timestamp: i64,
+    pub health_hash: [u8; 32],
+}
+
+/// Event emitted when a lottery vault commits to the slot whose hash draws its winner
+#[event]
+pub struct LotteryDrawScheduled {
+    pub vault: Pubkey,
+    pub draw_slot: u64,
+}
+
+/// Event emitted when `draw_lottery_winner` draws a lottery vault's winner
+#[event]
+pub struct LotteryWinner {
+    pub vault: Pubkey,
+    pub winner: Pubkey,
+    pub participants: u8,
+    /// The first slot at or after `Vault::lottery_draw_slot`, and its hash
+    pub draw_slot: u64,
+    pub slot_hash: [u8; 32],
+}
+
+/// Event emitted when the lottery winner claims the prize
+#[event]
+pub struct LotteryPrizeClaimed {
+    pub vault: Pubkey,
+    pub winner: Pubkey,
+    pub amount: u64,
+    pub content_hash: [u8; 32],
+}
+
+/// Event emitted when the recovery authority reveals a vault's key. Public and
+/// irreversible: anyone can read `decrypted_key` from the transaction logs.
+#[event]
+pub struct EmergencyKeyRevealed {
+    pub vault: Pubkey,
+    pub testator: Pubkey,
+    pub beneficiary: Pubkey,
+    pub decrypted_key: [u8; 32],
+}
+
+/// Event emitted when a vault is created, so notification and beneficiary discovery
+/// services can subscribe instead of polling `getProgramAccounts`
+#[event]
+pub struct VaultCreated {
+    pub vault: Pubkey,
+    pub testator: Pubkey,
+    pub beneficiary: Pubkey,
+    pub vault_nonce: u8,
+    /// Sequential id for support and legal references (0 if created without the config)
+    pub vault_id: u64,
+    pub beneficiary_email_hash: [u8; 32],
+    pub beneficiary_document_id_hash: [u8; 32],
+    pub cid: [u8; 32],
+    pub cid_validator: [u8; 32],
+    pub warning_timeout_secs: i64,
+    pub timeout_secs: i64,
+    pub content_hash: [u8; 32],
+}
+
+/// Event emitted when the testator cancels their will
+#[event]
+pub struct WillCancelled {
+    pub vault: Pubkey,
+    pub testator: Pubkey,
+    pub vault_id: u64,
+    /// Lamports returned to the testator when the vault is closed
+    pub refunded_lamports: u64,
+    pub content_hash: [u8; 32],
+}
+
+/// Event emitted by lookup_vault_by_id
+#[event]
+pub struct VaultIdLookupResult {
+    pub vault_id: u64,
+    pub vault_pda: Pubkey,
+}
+
+/// Event emitted by `dry_run_compressed_liveness_update`
+#[event]
+pub struct DryRunResult {
+    pub valid: bool,
+    /// Anchor error code of the first failed check
+    pub error_code: Option<u32>,
+    /// Benchmarked cost of the real update on this vault's path
+    pub estimated_compute_units: u64,
+}
+
+/// Event emitted when a vault's deed NFT is minted to the testator
+#[event]
+pub struct VaultDeedMinted {
+    pub vault: Pubkey,
+    pub deed_mint: Pubkey,
+    pub testator: Pubkey,
+}
+
+/// Event emitted when a vault's deed NFT is burned
+#[event]
+pub struct VaultDeedBurned {
+    pub vault: Pubkey,
+    pub deed_mint: Pubkey,
+}
+
+/// Event emitted when a registered scheduler pings on the testator's behalf
+#[event]
+pub struct ScheduledLivenessPing {
+    pub vault: Pubkey,
+    pub scheduler: Pubkey,
+    pub timestamp: i64,
+}
+
+/// Event emitted by `verify_content_hash`
+#[event]
+pub struct ContentHashVerified {
+    pub vault: Pubkey,
+    pub matches: bool,
+    pub stored_hash: [u8; 32],
+    pub computed_hash: [u8; 32],
+}
+
+/// Event emitted when an NFT is deposited into a vault
+#[event]
+pub struct NftDeposited {
+    pub vault: Pubkey,
+    pub mint: Pubkey,
+    pub depositor: Pubkey,
+}
+
+/// Event emitted when the testator withdraws an NFT from a vault
+#[event]
+pub struct NftWithdrawn {
+    pub vault: Pubkey,
+    pub mint: Pubkey,
+    pub withdrawer: Pubkey,
+}
+
+/// Event emitted by `compute_activity_score`
+#[event]
+pub struct ActivityScoreComputed {
+    pub vault: Pubkey,
+    pub testator: Pubkey,
+    pub score: u8,
+    pub components: ActivityScoreComponents,
+}
+
+/// Event emitted when an expired vault returns its assets and is closed
+#[event]
+pub struct VaultExpiredAndClosed {
+    pub vault: Pubkey,
+    pub testator: Pubkey,
+    /// NFTs and token deposits returned from vault token accounts
+    pub assets_returned: u8,
+    /// Lamports held by the vault account, including its rent
+    pub sol_returned: u64,
+    pub closed_at: i64,
+}
+
+/// Event emitted when the valuation oracle submits a new estate valuation
+#[event]
+pub struct EstateValuationUpdated {
+    pub testator: Pubkey,
+    pub total_usd_value_e6: u64,
+    pub vault_count: u16,
+    pub last_valued_at: i64,
+}
+
+/// Event emitted by `read_estate_valuation` with the full valuation
+#[event]
+pub struct EstateValuationRead {
+    pub testator: Pubkey,
+    pub oracle: Pubkey,
+    pub total_sol_lamports: u64,
+    pub token_valuations: Vec<TokenValuation>,
+    pub total_usd_value_e6: u64,
+    pub vault_count: u16,
+    pub last_valued_at: i64,
+}
+
+/// Event emitted when the beneficiary answers the vault's secret question
+#[event]
+pub struct SecretAnswerVerified {
+    pub vault: Pubkey,
+    pub beneficiary: Pubkey,
+    pub verified_at: i64,
+}
+
+/// Event emitted when a fee waiver covers a new vault's creation fee
+#[event]
+pub struct FeeWaiverApplied {
+    pub vault: Pubkey,
+    pub testator: Pubkey,
+    pub waiver_type: FeeWaiverType,
+    pub savings_lamports: u64,
+}
+
+/// Event emitted when a vault is created with an expired fee waiver; the fee is charged
+#[event]
+pub struct FeeWaiverExpired {
+    pub testator: Pubkey,
+    pub waiver_type: FeeWaiverType,
+}
+
+/// Event emitted when the testator lists the right to execute their vault
+#[event]
+pub struct ExecutionRightListed {
+    pub vault: Pubkey,
+    pub owner: Pubkey,
+    pub price_lamports: u64,
+}
+
+/// Event emitted when a professional executor buys a vault's execution right
+#[event]
+pub struct ExecutionRightPurchased {
+    pub vault: Pubkey,
+    pub buyer: Pubkey,
+    pub price_lamports: u64,
+    pub sold_at: i64,
+}
+
+/// Event emitted when a notarized legal document is anchored to a vault
+#[event]
+pub struct LegalDocumentCommitted {
+    pub vault: Pubkey,
+    pub document_type: LegalDocumentType,
+    pub hash: [u8; 32],
+    pub notary: Pubkey,
+}
+
+/// Event emitted when the beneficiary answers a contact verification challenge
+#[event]
+pub struct ContactVerified {
+    pub vault: Pubkey,
+    pub beneficiary: Pubkey,
+    pub verified_at: i64,
+}
+
+/// Event emitted by `batch_read_compressed_liveness` for each account read
+#[event]
+pub struct CompressedLivenessRead {
+    pub testator: Pubkey,
+    pub vault: Pubkey,
+    pub last_ping: i64,
+    pub address: [u8; 32],
+}
+
+/// Event emitted by `batch_read_compressed_liveness` for each entry that was skipped
+#[event]
+pub struct CompressedLivenessReadFailed {
+    pub address: [u8; 32],
+    /// Anchor error code of the failed check
+    pub error_code: u32,
+}
+
+/// Summary event emitted at the end of `batch_read_compressed_liveness`
+#[event]
+pub struct BatchCompressedLivenessRead {
+    pub count: u8,
+    /// Every account read was pinged within its vault's warning timeout
+    pub all_recent: bool,
+}
+
+/// Event emitted by `execute_inheritance_with_memo`, alongside `InheritanceExecuted`
+#[event]
+pub struct InheritanceExecutedWithMemo {
+    pub vault: Pubkey,
+    /// SHA-256 of the full memo written to the SPL Memo program
+    pub memo_hash: [u8; 32],
+}
+
+/// Event emitted when an executed vault is replaced by its `VaultArchive`
+#[event]
+pub struct VaultArchived {
+    pub vault: Pubkey,
+    pub archive_pubkey: Pubkey,
+    pub rent_returned: u64,
+}
+
+/// Event emitted when SPL or Token-2022 tokens are deposited into a vault
+#[event]
+pub struct TokenDeposited {
+    pub vault: Pubkey,
+    pub mint: Pubkey,
+    /// Tokens the vault actually received, net of any Token-2022 transfer fee
+    pub amount: u64,
+    pub transfer_fee: u64,
+    /// Total deposited for this mint after the deposit
+    pub total: u64,
+}
+
+/// Event emitted when the testator withdraws a mint's tokens from a vault
+#[event]
+pub struct TokenWithdrawn {
+    pub vault: Pubkey,
+    pub mint: Pubkey,
+    /// Tokens sent; the testator receives `amount - transfer_fee`
+    pub amount: u64,
+    pub transfer_fee: u64,
+}
+
+/// Event emitted when a mint's tokens are transferred to the heir of an executed vault
+#[event]
+pub struct TokenInheritanceExecuted {
+    pub vault: Pubkey,
+    pub beneficiary: Pubkey,
+    pub mint: Pubkey,
+    /// Tokens sent; the heir receives `amount - transfer_fee`
+    pub amount: u64,
+    pub transfer_fee: u64,
+}
+
+/// Event emitted when a vault's NFT or pNFT is transferred to its heir
+#[event]
+pub struct NftInherited {
+    pub vault: Pubkey,
+    pub mint: Pubkey,
+    pub beneficiary: Pubkey,
+}
+
+/// Event emitted when a compressed NFT moves into or out of a vault
+#[event]
+pub struct CnftTransferred {
+    pub vault: Pubkey,
+    pub asset_id: Pubkey,
+    pub from: Pubkey,
+    pub to: Pubkey,
+}
+
+/// Event emitted when the testator changes a vault's timeouts
+#[event]
+pub struct VaultConfigUpdated {
+    pub vault: Pubkey,
+    pub warning_timeout_secs: i64,
+    pub timeout_secs: i64,
+}
+
+/// Event emitted when the testator proposes a new beneficiary
+#[event]
+pub struct BeneficiaryChangeProposed {
+    pub vault: Pubkey,
+    pub current_beneficiary: Pubkey,
+    pub proposed_beneficiary: Pubkey,
+}
+
+/// Event emitted when a proposed beneficiary accepts and replaces the old one.
+/// The vault keeps its address, derived from `Vault::seed_beneficiary`.
+#[event]
+pub struct BeneficiaryChanged {
+    pub vault: Pubkey,
+    pub old_beneficiary: Pubkey,
+    pub new_beneficiary: Pubkey,
+}
+
+/// Event emitted by `deposit_sol`
+#[event]
+pub struct VaultFunded {
+    pub vault: Pubkey,
+    pub payer: Pubkey,
+    pub amount: u64,
+    pub new_vault_balance: u64,
+    pub content_hash: [u8; 32],
+}
+
+/// Event emitted by `withdraw_sol`
+#[event]
+pub struct SolWithdrawn {
+    pub vault: Pubkey,
+    pub testator: Pubkey,
+    pub amount: u64,
+    pub new_vault_balance: u64,
+    pub content_hash: [u8; 32],
+}
+
+/// Event emitted by `set_fallback_beneficiary`
+#[event]
+pub struct FallbackBeneficiarySet {
+    pub vault: Pubkey,
+    pub fallback_beneficiary: Option<Pubkey>,
+    pub claim_window_secs: i64,
+}
+
+/// Event emitted by `claim_as_fallback_beneficiary`
+#[event]
+pub struct FallbackBeneficiaryPromoted {
+    pub vault: Pubkey,
+    pub old_beneficiary: Pubkey,
+    pub new_beneficiary: Pubkey,
+}
+
+/// Event emitted when a guardian proposes moving the vault to a new testator key
+#[event]
+pub struct TestatorRotationProposed {
+    pub vault: Pubkey,
+    pub guardian: Pubkey,
+    pub current_testator: Pubkey,
+    pub proposed_testator: Pubkey,
+}
+
+/// Event emitted for each guardian approval of the pending testator rotation.
+/// `effective_at` is 0 until `threshold` guardians approved.
+#[event]
+pub struct TestatorRotationApproved {
+    pub vault: Pubkey,
+    pub guardian: Pubkey,
+    pub approvals: u8,
+    pub threshold: u8,
+    pub effective_at: i64,
+}
+
+/// Event emitted when the testator cancels a pending guardian rotation
+#[event]
+pub struct TestatorRotationCancelled {
+    pub vault: Pubkey,
+    pub cancelled_testator: Pubkey,
+}
+
+/// Event emitted when an approved testator rotation is applied after its delay.
+/// The vault keeps its address, derived from `Vault::seed_testator`.
+#[event]
+pub struct TestatorRotated {
+    pub vault: Pubkey,
+    pub old_testator: Pubkey,
+    pub new_testator: Pubkey,
+}
+
+/// Event emitted when a key wrapped under the `demo_hash` derivation is re-wrapped
+#[event]
+pub struct LegacyKeyWrapMigrated {
+    pub vault: Pubkey,
+    pub migrated_at: i64,
+}
+
+/// Event emitted when `cancel_will_with_compressed_liveness` closes the vault's
+/// compressed liveness account.
+#[event]
+pub struct CompressedLivenessClosed {
+    pub vault: Pubkey,
+    pub testator: Pubkey,
+    pub address: [u8; 32],
+}
+
+/// Event emitted by `mark_warning` when a vault has entered `VaultState::Warning`
+#[event]
+pub struct VaultWarning {
+    pub vault: Pubkey,
+    pub testator: Pubkey,
+    pub last_ping: i64,
+    pub cranker: Pubkey,
+    pub bounty_lamports: u64,
+}
+
+/// Event emitted by `mark_claimable` when a vault has become claimable
+#[event]
+pub struct VaultClaimable {
+    pub vault: Pubkey,
+    pub testator: Pubkey,
+    pub beneficiary: Pubkey,
+    pub last_ping: i64,
+    pub cranker: Pubkey,
+    pub bounty_lamports: u64,
+}
+
+/// Event emitted by `extend_deadline`
+#[event]
+pub struct DeadlineExtended {
+    pub vault: Pubkey,
+    pub extender: Pubkey,
+    pub extra_secs: i64,
+    /// Total extension since the last ping
+    pub total_extension_secs: i64,
+    /// When the vault becomes claimable without a ping
+    pub claimable_after: i64,
+}
+
+/// Event emitted when the testator registers a ping delegate (session key)
+#[event]
+pub struct PingDelegateRegistered {
+    pub vault: Pubkey,
+    pub delegate: Pubkey,
+    pub expires_at: i64,
+}
+
+/// Event emitted when the testator revokes their ping delegate before it expires
+#[event]
+pub struct PingDelegateRevoked {
+    pub vault: Pubkey,
+    pub delegate: Pubkey,
+}
+
+/// Event emitted when a relayer submits a ping signed off-chain by the testator
+#[event]
+pub struct RelayedLivenessPing {
+    pub vault: Pubkey,
+    pub relayer: Pubkey,
+    pub nonce: u64,
+    pub signed_at: i64,
+}
+
+/// Event emitted when the protocol authority adds or removes a registered verifier
+#[event]
+pub struct VerifierRegistryUpdated {
+    pub verifier: Pubkey,
+    pub registered: bool,
+}
+
+/// Event emitted when the testator schedules a verifier rotation
+#[event]
+pub struct VerifierRotationScheduled {
+    pub vault: Pubkey,
+    pub current_verifier: Pubkey,
+    pub new_verifier: Pubkey,
+    pub effective_at: i64,
+}
+
+/// Event emitted when a pending verifier rotation is cancelled or applied
+#[event]
+pub struct VerifierRotationSettled {
+    pub vault: Pubkey,
+    pub old_verifier: Pubkey,
+    pub new_verifier: Pubkey,
+    pub applied: bool,
+}
+
+/// Event emitted when the testator drops a verifier committee before it took effect
+#[event]
+pub struct VerifierCommitteeCancelled {
+    pub vault: Pubkey,
+    pub committee: Pubkey,
+    pub effective_at: i64,
+}
+
+#[program]
+pub mod inheritance_demo {
+    use super::*;
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn init_inheritance(
+        ctx: Context<InitInheritance>,
+        beneficiary: Pubkey,
+        vault_nonce: u8,
+        verifier: Pubkey,
+        beneficiary_identity_hash: [u8; 32],
+        beneficiary_email_hash: [u8; 32],
+        beneficiary_document_id_hash: [u8; 32],
+        cid: [u8; 32],
+        cid_validator: [u8; 32],
+        warning_timeout_secs: i64,
+        timeout_secs: i64,
+        min_ping_interval_secs: i64,
+        grace_period_secs: i64,
+        execution_delay_secs: i64,
+        lamports: u64,
+        destruction_bond_lamports: u64,
+        encrypted_password: Vec<u8>,
+        encrypted_key: EncryptedKeyV2,
+        light_root: [u8; 32],
+        is_debug: bool,
+        execution_revocable: bool,
+    ) -> Result<()> {
+        // Validate encrypted password
+        require!(!encrypted_password.is_empty(), ErrorCode::EmptyEncryptedPassword);
+        require!(
+            encrypted_password.len() <= Vault::MAX_ENCRYPTED_PASSWORD_SIZE,
+            ErrorCode::EncryptedPasswordTooLarge
+        );
+        Vault::validate_timeout_params(
+            warning_timeout_secs,
+            timeout_secs,
+            min_ping_interval_secs,
+            grace_period_secs,
+            execution_delay_secs,
+        )?;
+
+        // Enforce the protocol-wide and per-testator vault caps when the accounts are provided
+        let max_vaults_per_testator = ctx
+            .accounts
+            .config
+            .as_ref()
+            .map_or(ProtocolConfig::DEFAULT_MAX_VAULTS_PER_TESTATOR, |c| c.max_vaults_per_testator);
+        let mut vault_id = 0;
+        if let Some(config) = ctx.accounts.config.as_mut() {
+            require!(
+                config.total_vaults < config.max_total_protocol_vaults,
+                ErrorCode::ProtocolCapacityReached
+            );
+            if config.require_registered_verifier {
+                require!(
+                    ctx.accounts
+                        .verifier_registry
+                        .as_ref()
+                        .is_some_and(|registry| registry.contains(&verifier)),
+                    ErrorCode::VerifierNotRegistered
+                );
+            }
+            config.total_vaults += 1;
+            vault_id = config.next_vault_id;
+            config.next_vault_id += 1;
+        }
+        if let Some(registry) = ctx.accounts.vault_registry.as_mut() {
+            require!(
+                registry.count < max_vaults_per_testator,
+                ErrorCode::TestatorVaultLimitExceeded
+            );
+            registry.testator = ctx.accounts.testator.key();
+            registry.claim_vault_nonce(beneficiary, vault_nonce)?;
+            registry.count += 1;
+
+            if registry.count >= max_vaults_per_testator.saturating_sub(2) {
+                emit!(VaultLimitApproaching {
+                    testator: registry.testator,
+                    current_count: registry.count,
+                    max_count: max_vaults_per_testator,
+                });
+            }
+        }
+
+        let vault = &mut ctx.accounts.vault;
+        vault.testator = ctx.accounts.testator.key();
+        vault.beneficiary = beneficiary;
+        vault.verifier = verifier; // Set the trusted identity verifier
+        vault.beneficiary_identity_hash = beneficiary_identity_hash;
+        vault.beneficiary_email_hash = beneficiary_email_hash;
+        vault.beneficiary_document_id_hash = beneficiary_document_id_hash;
+        vault.cid = cid;
+        vault.cid_validator = cid_validator;
+        
+        let now = Clock::get()?.unix_timestamp;
+        vault.last_ping = now;
+        vault.created_at = now;
+        vault.warning_timeout_secs = warning_timeout_secs;
+        vault.timeout_secs = timeout_secs;
+        vault.min_ping_interval_secs = min_ping_interval_secs;
+        vault.grace_period_secs = grace_period_secs;
+        vault.execution_delay_secs = execution_delay_secs;
+        vault.executed = false;
+        vault.lamports = lamports;
+        vault.encrypted_password = encrypted_password;
+
+        // K arrives wrapped under the HKDF K_light of `light_root`, a root of the Light
+        // state tree passed as the first remaining account: check that it opens, then keep
+        // only the ciphertext
+        check_light_state_root(ctx.remaining_accounts, &light_root)?;
+        let k_light = derive_key_from_light_hkdf(&light_root, &vault.key(), &beneficiary);
+        decrypt_key_v2(&encrypted_key, &k_light, vault.key().as_ref())?;
+        vault.encrypted_key_v2 = Some(encrypted_key);
+        vault.light_root = Some(light_root);
+        vault.is_debug = is_debug;
+        vault.has_compressed_liveness = false;
+        vault.ping_count = 0;
+        vault.compression_retry_count = 0;
+        vault.last_compression_failure_slot = 0;
+        vault.tax_authority_oracle = None;
+        vault.tax_rate_bps = 0;
+        vault.migrated_to = None;
+        vault.execution_nonce = derive_execution_nonce(&ctx.accounts.recent_slothashes, &vault.key(), now)?;
+        vault.compressed_liveness_version = CompressedLivenessVersion::V1;
+        vault.compression_created_at = 0;
+        vault.custody_agent = None;
+        vault.custody_expires_at = 0;
+        vault.beneficiary_instructions = Vec::new();
+        vault.key_fragment_type = KeyFragmentType::EncryptedSeed;
+        vault.key_fragment_index = 0;
+        vault.key_fragment_total = 1;
+        vault.destruction_bond_lamports = destruction_bond_lamports;
+        vault.beneficiary_verified_at = None;
+        vault.update_sequence = 0;
+        vault.full_identity_verified = false;
+        vault.execution_revocable = execution_revocable;
+        vault.execution_revocation_count = 0;
+        vault.max_verification_age_secs = 0;
+        vault.last_identity_verified_at = 0;
+        vault.has_been_verified = false;
+        vault.last_ping_note = [0u8; 32];
+        vault.expected_ping_note = None;
+        vault.admin_pinged = false;
+        vault.admin_ping_count = 0;
+        vault.health_oracle = None;
+        vault.last_health_signal = 0;
+        vault.health_signal_weight_bps = 0;
+        vault.lottery_mode = false;
+        vault.lottery_participants = Vec::new();
+        vault.lottery_winner = None;
+        vault.emergency_revealed = false;
+        vault.vault_id = vault_id;
+        vault.deed_nft_mint = None;
+        vault.deed_burned = false;
+        vault.scheduler_pubkey = None;
+        vault.schedule_cron = [0u8; 32];
+        vault.scheduler_ping_count = 0;
+        vault.content_hash = [0u8; 32];
+        vault.registered_nfts = Vec::new();
+        vault.last_activity_score = 0;
+        vault.last_scored_at = 0;
+        vault.vault_expiry_secs = 0;
+        vault.expiry_refund_destination = None;
+        vault.key_wrapped_with_hkdf = true;
+        vault.secret_verified = false;
+        vault.legal_documents = Vec::new();
+        vault.contact_verified = false;
+        vault.vault_nonce = vault_nonce;
+        vault.executed_at = 0;
+        vault.lamports_transferred = 0;
+        vault.co_beneficiaries = Vec::new();
+        vault.token_deposits = Vec::new();
+        vault.registered_pnfts = Vec::new();
+        vault.registered_cnfts = Vec::new();
+        vault.seed_beneficiary = beneficiary;
+        vault.fallback_beneficiary = None;
+        vault.seed_testator = ctx.accounts.testator.key();
+        vault.require_liveness_proof = false;
+        vault.crank_bounty_lamports = 0;
+        vault.warning_cranked_for_ping = 0;
+        vault.claimable_cranked_for_ping = 0;
+        vault.deadline_delegate = None;
+        vault.max_deadline_extension_secs = 0;
+        vault.deadline_extension_secs = 0;
+        vault.deadline_extended_for_ping = 0;
+        vault.ping_delegate = None;
+        vault.ping_delegate_expires_at = 0;
+        vault.relayed_ping_nonce = 0;
+        vault.require_verifier_attestation = false;
+        vault.attestation_nonce = 0;
+        vault.pending_verifier = None;
+        vault.verifier_rotation_effective_at = 0;
+        vault.verifier_rotation_delay_secs = Vault::DEFAULT_VERIFIER_ROTATION_DELAY_SECS;
+        vault.recovery_escrow = None;
+        vault.verifier_committee = None;
+        vault.verifier_committee_effective_at = 0;
+        vault.lottery_draw_slot = 0;
+        vault.bump = ctx.bumps.vault;
+        authorize(&ctx.accounts.testator, Role::Testator, vault)?;
+
+        if let Some(index) = ctx.accounts.vault_id_index.as_mut() {
+            // Ids are only assigned when the protocol config is provided
+            require!(vault_id != 0, ErrorCode::VaultIdNotFound);
+            index.vault_id = vault_id;
+            index.vault = ctx.accounts.vault.key();
+            index.bump = ctx.bumps.vault_id_index.unwrap();
+        }
+
+        ctx.accounts.vault.refresh_content_hash();
+        emit!(VaultCreated {
+            vault: ctx.accounts.vault.key(),
+            testator: ctx.accounts.testator.key(),
+            beneficiary,
+            vault_nonce,
+            vault_id,
+            beneficiary_email_hash,
+            beneficiary_document_id_hash,
+            cid,
+            cid_validator,
+            warning_timeout_secs,
+            timeout_secs,
+            content_hash: ctx.accounts.vault.content_hash,
+        });
+
+        // Protocol vault creation fee, unless a valid fee waiver covers this vault
+        let (creation_fee, treasury_key) = ctx
+            .accounts
+            .config
+            .as_ref()
+            .map_or((0, Pubkey::default()), |c| (c.vault_creation_fee_lamports, c.treasury));
+        if creation_fee > 0 {
+            let mut waived = false;
+            if let Some(waiver) = ctx.accounts.fee_waiver.as_mut() {
+                if waiver.is_expired(now) {
+                    emit!(FeeWaiverExpired {
+                        testator: waiver.testator,
+                        waiver_type: waiver.waiver_type,
+                    });
+                } else if waiver.waivers_used < waiver.vaults_covered {
+                    waiver.waivers_used += 1;
+                    waived = true;
+                    emit!(FeeWaiverApplied {
+                        vault: ctx.accounts.vault.key(),
+                        testator: waiver.testator,
+                        waiver_type: waiver.waiver_type,
+                        savings_lamports: creation_fee,
+                    });
+                }
+            }
+
+            if !waived {
+                let treasury = ctx.accounts.treasury.as_ref().ok_or(ErrorCode::TreasuryAccountRequired)?;
+                require_keys_eq!(treasury.key(), treasury_key, ErrorCode::Unauthorized);
+                anchor_lang::system_program::transfer(
+                    CpiContext::new(
+                        ctx.accounts.system_program.to_account_info(),
+                        anchor_lang::system_program::Transfer {
+                            from: ctx.accounts.payer.to_account_info(),
+                            to: treasury.to_account_info(),
+                        },
+                    ),
+                    creation_fee,
+                )?;
+            }
+        }
+
+        // Transfer initial deposit and destruction bond from PAYER (not testator) to vault
+        anchor_lang::system_program::transfer(
+            CpiContext::new(
+                ctx.accounts.system_program.to_account_info(),
+                anchor_lang::system_program::Transfer {
+                    from: ctx.accounts.payer.to_account_info(),
+                    to: ctx.accounts.vault.to_account_info(),
+                },
+            ),
+            lamports
+                .checked_add(destruction_bond_lamports)
+                .ok_or(ErrorCode::ArithmeticOverflow)?,
+        )?;
+
+        Ok(())
+    }
+
+    /// Create a compressed liveness account in Light Protocol's state tree.
+    /// This uses ZK Compression to store liveness data at ~200x lower cost.
+    /// 
+    /// Note: This instruction requires Light Protocol system accounts to be passed
+    /// via remaining_accounts. Use the Light SDK client to prepare these accounts.
+    ///
+    /// `proof_nonce` must not have been used with another liveness proof for this
+    /// vault (see `UsedProofNonces`).
+    pub fn create_compressed_liveness<'info>(
+        mut ctx: Context<'_, '_, '_, 'info, CreateCompressedLiveness<'info>>,
+        proof_data: ValidityProofData,
+        address_tree_info: AddressTreeInfoData,
+        output_tree_index: u8,
+        proof_nonce: [u8; 8],
+    ) -> Result<()> {
+        authorize(&ctx.accounts.testator, Role::Testator, &ctx.accounts.vault)?;
+        let compute_units_start = compute_units_remaining();
+        process_create_compressed_liveness(
+            &mut ctx,
+            proof_data,
+            address_tree_info,
+            output_tree_index,
+            proof_nonce,
+            compute_units_start,
+        )
+    }
+
+    /// Record that a `create_compressed_liveness` (or retry) transaction sent at
+    /// `failed_attempt_slot` failed on the Light Protocol side (tree full, forester
+    /// busy). A failed CPI reverts its whole transaction, so failures are counted here,
+    /// at most `MAX_COMPRESSION_RETRIES` times; each one allows a
+    /// `retry_create_compressed_liveness`. Only callable by the testator.
+    pub fn record_compression_failure(
+        ctx: Context<TestatorUpdateVault>,
+        failed_attempt_slot: u64,
+    ) -> Result<()> {
+        authorize(&ctx.accounts.testator, Role::Testator, &ctx.accounts.vault)?;
+        let slot = Clock::get()?.slot;
+        let vault = &mut ctx.accounts.vault;
+        require!(
+            !vault.has_compressed_liveness,
+            ErrorCode::CompressedLivenessAlreadyExists
+        );
+        require!(
+            vault.compression_retry_count < Vault::MAX_COMPRESSION_RETRIES,
+            ErrorCode::MaxCompressionRetriesExceeded
+        );
+        require!(
+            failed_attempt_slot <= slot
+                && slot - failed_attempt_slot <= Vault::MAX_RETRY_SLOT_AGE
+                && failed_attempt_slot > vault.last_compression_failure_slot,
+            ErrorCode::InvalidPreviousAttemptSlot
+        );
+
+        vault.compression_retry_count += 1;
+        vault.last_compression_failure_slot = failed_attempt_slot;
+
+        emit!(CompressedLivenessFailureRecorded {
+            vault: vault.key(),
+            failures: vault.compression_retry_count,
+            failed_attempt_slot,
+        });
+
+        Ok(())
+    }
+
+    /// Retry creating the compressed liveness account after a transient Light Protocol
+    /// failure. Identical to `create_compressed_liveness` (proof nonce, nullifier and V2
+    /// checks included), but `previous_attempt_slot` must be the failure last recorded
+    /// with `record_compression_failure`, within the last `MAX_RETRY_SLOT_AGE` slots.
+    /// Once `MAX_COMPRESSION_RETRIES` failures are recorded, manual intervention is
+    /// required.
+    pub fn retry_create_compressed_liveness<'info>(
+        mut ctx: Context<'_, '_, '_, 'info, CreateCompressedLiveness<'info>>,
+        proof_data: ValidityProofData,
+        address_tree_info: AddressTreeInfoData,
+        output_tree_index: u8,
+        previous_attempt_slot: u64,
+        proof_nonce: [u8; 8],
+    ) -> Result<()> {
+        authorize(&ctx.accounts.testator, Role::Testator, &ctx.accounts.vault)?;
+        let compute_units_start = compute_units_remaining();
+        let clock = Clock::get()?;
+
+        let vault = &ctx.accounts.vault;
+        require!(
+            vault.compression_retry_count > 0
+                && previous_attempt_slot == vault.last_compression_failure_slot
+                && clock.slot - previous_attempt_slot <= Vault::MAX_RETRY_SLOT_AGE,
+            ErrorCode::InvalidPreviousAttemptSlot
+        );
+
+        // The original create_compressed_liveness call is attempt #1
+        emit!(CompressedLivenessRetryAttempted {
+            vault: vault.key(),
+            attempt_number: vault.compression_retry_count + 1,
+            timestamp: clock.unix_timestamp,
+        });
+
+        process_create_compressed_liveness(
+            &mut ctx,
+            proof_data,
+            address_tree_info,
+            output_tree_index,
+            proof_nonce,
+            compute_units_start,
+        )
+    }
+
+    /// Update liveness using Light Protocol ZK Compression.
+    /// This function updates the compressed liveness account in the state tree
+    /// and updates the vault's last_ping timestamp. `proof_nonce` must not have been
+    /// used with another liveness proof for this vault (see `UsedProofNonces`).
+    pub fn update_liveness<'info>(
+        mut ctx: Context<'_, '_, '_, 'info, UpdateLiveness<'info>>,
+        proof_data: ValidityProofData,
+        output_tree_index: u8,
+        proof_nonce: [u8; 8],
+    ) -> Result<()> {
+        authorize(&ctx.accounts.testator, Role::Pinger, &ctx.accounts.vault)?;
+        process_liveness_update(&mut ctx, proof_data, output_tree_index, proof_nonce, None)
+    }
+
+    /// Same as `update_liveness`, additionally recording a 32-byte `note` on the vault
+    /// and in `PingReceived`. If the testator set an `expected_ping_note`, the note
+    /// must match it.
+    pub fn update_liveness_with_note<'info>(
+        mut ctx: Context<'_, '_, '_, 'info, UpdateLiveness<'info>>,
+        note: [u8; 32],
+        proof_data: ValidityProofData,
+        output_tree_index: u8,
+        proof_nonce: [u8; 8],
+    ) -> Result<()> {
+        authorize(&ctx.accounts.testator, Role::Pinger, &ctx.accounts.vault)?;
+        process_liveness_update(&mut ctx, proof_data, output_tree_index, proof_nonce, Some(note))
+    }
+
+    /// Execute inheritance - transfers assets and reveals the encrypted password to the beneficiary.
+    /// 
+    /// # Arguments
+    /// * `transfer_funds` - If true, transfer SOL to beneficiary. If false, only mark as executed and emit password.
+    /// * `execution_nonce_provided` - The vault's current `execution_nonce`, read by the verifier before signing.
+    pub fn execute_inheritance<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ExecuteInheritance<'info>>,
+        transfer_funds: bool,
+        execution_nonce_provided: [u8; 8],
+    ) -> Result<()> {
+        authorize(&ctx.accounts.beneficiary, Role::Heir, &ctx.accounts.vault)?;
+        ctx.accounts.vault.assert_consistent()?;
+        let compute_units_start = compute_units_remaining();
+
+        // 0. Defense in depth: make sure the vault really is one of our Vault accounts
+        verify_vault_discriminator(&ctx.accounts.vault.to_account_info(), ctx.program_id)?;
+
+        // 1. Identity Verification (Beneficiary and Verifier must sign)
+        // This confirms the "Face Scan + ID Match" from your diagram happened off-chain.
+        ctx.accounts.vault.verify_execution_parties(
+            ctx.accounts.beneficiary.key,
+            ctx.accounts.verifier.key,
+            Clock::get()?.unix_timestamp,
+        )?;
+
+        settle_inheritance(
+            SettlementAccounts {
+                vault: &mut ctx.accounts.vault,
+                beneficiary: &ctx.accounts.beneficiary.to_account_info(),
+                config: &ctx.accounts.config,
+                time_capsule: &mut ctx.accounts.time_capsule,
+                tax_escrow: &mut ctx.accounts.tax_escrow,
+                state_history: &mut ctx.accounts.state_history,
+                co_beneficiaries: ctx.remaining_accounts,
+            },
+            Settlement {
+                transfer_funds,
+                execution_nonce: execution_nonce_provided,
+                proof: ExecutionProof::None,
+                compute_units_start,
+            },
+        )
+    }
+
+    pub fn init_light_registry(
+        ctx: Context<InitLightRegistry>,
+        initial_root: [u8; 32],
+    ) -> Result<()> {
+        let state = &mut ctx.accounts.light_state;
+        state.current_root = initial_root;
+        Ok(())
+    }
+
+    /// Verify if a given identity hash matches a vault's beneficiary_identity_hash.
+    /// This allows a user to prove they are the intended beneficiary.
+    /// 
+    /// Returns an event with vault details if the identity matches.
+    /// This is useful for beneficiaries to discover their inheritance claims.
+    pub fn verify_beneficiary_identity(
+        ctx: Context<VerifyBeneficiaryIdentity>,
+        identity_hash: [u8; 32],
+    ) -> Result<()> {
+        authorize(&ctx.accounts.beneficiary, Role::Beneficiary, &ctx.accounts.vault)?;
+        let vault = &mut ctx.accounts.vault;
+        
+        // Check if the provided identity hash matches
+        require!(
+            vault.beneficiary_identity_hash == identity_hash,
+            ErrorCode::IdentityHashMismatch
+        );
+
+        // From now on, cancelling the vault forfeits the testator's destruction bond
+        vault.record_identity_verification(Clock::get()?.unix_timestamp);
+        
+        // Emit an event with vault info for the beneficiary
+        emit!(BeneficiaryVerified {
+            vault: vault.key(),
+            beneficiary: vault.beneficiary,
+            testator: vault.testator,
+            cid: vault.cid,
+            cid_validator: vault.cid_validator,
+            is_claimable: vault.get_state(Clock::get()?.unix_timestamp).is_claimable(),
+            executed: vault.executed,
+        });
+        
+        Ok(())
+    }
+
+    /// Cancel a will/inheritance - closes the vault account and returns SOL to the testator.
+    /// This can only be called by the testator. Deposited tokens and NFTs must be
+    /// withdrawn first.
+    pub fn cancel_will(ctx: Context<CancelWill>) -> Result<()> {
+        authorize(&ctx.accounts.testator, Role::Testator, &ctx.accounts.vault)?;
+        let vault = &ctx.accounts.vault;
+        
+        // Safety check: Don't allow cancellation if already executed?
+        // Actually, Anchor's 'close' will handle the transfer.
+        // We just need to make sure the testator is the one signing (handled by accounts).
+        vault.assert_not_executed()?;
+        // The compressed liveness account would be orphaned by closing the vault
+        require!(!vault.has_compressed_liveness, ErrorCode::CompressedLivenessStillOpen);
+        // Token accounts owned by the vault PDA would be stranded by closing it
+        require!(
+            vault.token_deposits.is_empty()
+                && vault.registered_nfts.is_empty()
+                && vault.registered_pnfts.is_empty()
+                && vault.registered_cnfts.is_empty(),
+            ErrorCode::AssetsStillPresentInVault
+        );
+
+        // Once the beneficiary has verified, half of the bond goes to the beneficiary and
+        // half to the treasury. Otherwise the whole bond returns to the testator on close.
+        let bond = vault.destruction_bond_lamports;
+        if vault.beneficiary_verified_at.is_some() && bond > 0 {
+            let vault_info = vault.to_account_info();
+            let min_rent = minimum_rent_exempt_balance(vault_info.data_len())?;
+            require!(
+                vault_info.lamports() >= min_rent.saturating_add(bond),
+                ErrorCode::DestructionBondInsufficient
+            );
+
+            let (Some(beneficiary), Some(treasury), Some(config)) = (
+                ctx.accounts.beneficiary.as_ref(),
+                ctx.accounts.treasury.as_ref(),
+                ctx.accounts.config.as_ref(),
+            ) else {
+                return err!(ErrorCode::BondRecipientMissing);
+            };
+            require_keys_eq!(treasury.key(), config.treasury, ErrorCode::Unauthorized);
+
+            let beneficiary_share = bond / 2;
+            let treasury_share = bond - beneficiary_share;
+            **vault_info.try_borrow_mut_lamports()? -= bond;
+            **beneficiary.try_borrow_mut_lamports()? += beneficiary_share;
+            **treasury.try_borrow_mut_lamports()? += treasury_share;
+
+            emit!(DestructionBondForfeited {
+                vault: vault.key(),
+                testator: vault.testator,
+                beneficiary: vault.beneficiary,
+                amount: bond,
+            });
+        }
+
+        if let Some(config) = ctx.accounts.config.as_mut() {
+            config.total_vaults = config.total_vaults.saturating_sub(1);
+        }
+        if let Some(registry) = ctx.accounts.vault_registry.as_mut() {
+            registry.count = registry.count.saturating_sub(1);
+        }
+
+        emit!(WillCancelled {
+            vault: vault.key(),
+            testator: vault.testator,
+            vault_id: vault.vault_id,
+            // Everything left after the bond split goes to the testator on close
+            refunded_lamports: vault.to_account_info().lamports(),
+            content_hash: vault.content_hash,
+        });
+        
+        Ok(())
+    }
+
+    /// Materialize the vault's funding address (`[b"funding", vault]`) by funding it to
+    /// the rent-exempt minimum, so third parties (friends, family) can send SOL to it
+    /// without knowing the vault PDA or being the testator.
+    pub fn init_vault_funding_address(ctx: Context<InitVaultFundingAddress>) -> Result<()> {
+        ctx.accounts.vault.assert_not_executed()?;
+
+        let min_rent = minimum_rent_exempt_balance(0)?;
+        let top_up = min_rent.saturating_sub(ctx.accounts.funding_address.lamports());
+        if top_up > 0 {
+            anchor_lang::system_program::transfer(
+                CpiContext::new(
+                    ctx.accounts.system_program.to_account_info(),
+                    anchor_lang::system_program::Transfer {
+                        from: ctx.accounts.payer.to_account_info(),
+                        to: ctx.accounts.funding_address.to_account_info(),
+                    },
+                ),
+                top_up,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Permissionless: sweep everything above the rent-exempt minimum from the vault's
+    /// funding address into the vault and credit it to `vault.lamports`.
+    pub fn collect_vault_donations(ctx: Context<CollectVaultDonations>) -> Result<()> {
+        ctx.accounts.vault.assert_not_executed()?;
+
+        let min_rent = minimum_rent_exempt_balance(0)?;
+        let amount = ctx.accounts.funding_address.lamports().saturating_sub(min_rent);
+        require!(amount > 0, ErrorCode::NoDonationsToCollect);
+
+        let vault_key = ctx.accounts.vault.key();
+        let signer_seeds: &[&[u8]] = &[
+            b"funding",
+            vault_key.as_ref(),
+            &[ctx.bumps.funding_address],
+        ];
+
+        // The funding address is system-owned, so move lamports via the System Program
+        anchor_lang::system_program::transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.system_program.to_account_info(),
+                anchor_lang::system_program::Transfer {
+                    from: ctx.accounts.funding_address.to_account_info(),
+                    to: ctx.accounts.vault.to_account_info(),
+                },
+                &[signer_seeds],
+            ),
+            amount,
+        )?;
+
+        let vault = &mut ctx.accounts.vault;
+        vault.lamports = vault
+            .lamports
+            .checked_add(amount)
+            .ok_or(ErrorCode::ArithmeticOverflow)?;
+        check_rent_exempt(&vault.to_account_info())?;
+        vault.refresh_content_hash();
+
+        emit!(DonationCollected {
+            vault: vault.key(),
+            donor: None,
+            amount,
+            new_vault_balance: vault.lamports,
+            content_hash: vault.content_hash,
+        });
+
+        Ok(())
+    }
+
+    /// Append an emergency contact (encrypted to the verifier's key) to the vault's
+    /// contact registry. Requires both the testator and the verifier to sign.
+    pub fn add_emergency_contact(
+        ctx: Context<AddEmergencyContact>,
+        encrypted_data: [u8; 128],
+        contact_type: ContactType,
+    ) -> Result<()> {
+        authorize(&ctx.accounts.testator, Role::Testator, &ctx.accounts.vault)?;
+        ctx.accounts.vault.assert_not_executed()?;
+
+        let registry = &mut ctx.accounts.contact_registry;
+        require!(
+            registry.contacts.len() < EmergencyContactRegistry::MAX_CONTACTS,
+            ErrorCode::ContactRegistryFull
+        );
+
+        registry.vault = ctx.accounts.vault.key();
+        registry.bump = ctx.bumps.contact_registry;
+        registry.contacts.push(EncryptedContact {
+            encrypted_data,
+            contact_type,
+        });
+
+        Ok(())
+    }
+
+    /// Emit the vault's emergency contacts for the verifier to decrypt off-chain.
+    /// Only callable by the vault's verifier.
+    pub fn read_emergency_contacts(ctx: Context<ReadEmergencyContacts>) -> Result<()> {
+        authorize(&ctx.accounts.verifier, Role::Verifier, &ctx.accounts.vault)?;
+        emit!(EmergencyContactsRead {
+            vault: ctx.accounts.vault.key(),
+            contacts: ctx.accounts.contact_registry.contacts.clone(),
+        });
+
+        Ok(())
+    }
+
+    /// Remove the emergency contact at `index`. Only callable by the testator.
+    pub fn remove_emergency_contact(ctx: Context<RemoveEmergencyContact>, index: u8) -> Result<()> {
+        authorize(&ctx.accounts.testator, Role::Testator, &ctx.accounts.vault)?;
+        let registry = &mut ctx.accounts.contact_registry;
+        require!(
+            (index as usize) < registry.contacts.len(),
+            ErrorCode::ContactNotFound
+        );
+        registry.contacts.remove(index as usize);
+
+        Ok(())
+    }
+
+    /// Seal a personal message (encrypted to the beneficiary's key) that is revealed
+    /// atomically with the inheritance. Only callable by the testator.
+    pub fn seal_time_capsule(ctx: Context<SealTimeCapsule>, encrypted_message: Vec<u8>) -> Result<()> {
+        authorize(&ctx.accounts.testator, Role::Testator, &ctx.accounts.vault)?;
+        ctx.accounts.vault.assert_not_executed()?;
+        validate_capsule_message(&encrypted_message)?;
+
+        let capsule = &mut ctx.accounts.time_capsule;
+        require!(capsule.created_at == 0, ErrorCode::TimeCapsuleAlreadySealed);
+
+        capsule.vault = ctx.accounts.vault.key();
+        capsule.encrypted_message = encrypted_message;
+        capsule.created_at = Clock::get()?.unix_timestamp;
+        capsule.revealed = false;
+        capsule.bump = ctx.bumps.time_capsule;
+
+        Ok(())
+    }
+
+    /// Replace the sealed message with a newly encrypted one. Only callable by the testator.
+    pub fn update_capsule(ctx: Context<UpdateCapsule>, encrypted_message: Vec<u8>) -> Result<()> {
+        authorize(&ctx.accounts.testator, Role::Testator, &ctx.accounts.vault)?;
+        ctx.accounts.vault.assert_not_executed()?;
+        validate_capsule_message(&encrypted_message)?;
+
+        let capsule = &mut ctx.accounts.time_capsule;
+        capsule.encrypted_message = encrypted_message;
+        capsule.created_at = Clock::get()?.unix_timestamp;
+
+        Ok(())
+    }
+
+    /// Close the time capsule and return its rent to the testator. Only possible before execution.
+    pub fn close_capsule(ctx: Context<CloseCapsule>) -> Result<()> {
+        authorize(&ctx.accounts.testator, Role::Testator, &ctx.accounts.vault)?;
+        ctx.accounts.vault.assert_not_executed()?;
+        Ok(())
+    }
+
+    /// Create the program-wide `ProtocolConfig`. Called once at deployment;
+    /// the signer becomes the protocol authority.
+    pub fn init_protocol_config(ctx: Context<InitProtocolConfig>) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        config.authority = ctx.accounts.authority.key();
+        config.light_program_hash = [0u8; 8];
+        config.max_vaults_per_testator = ProtocolConfig::DEFAULT_MAX_VAULTS_PER_TESTATOR;
+        config.max_total_protocol_vaults = ProtocolConfig::DEFAULT_MAX_TOTAL_PROTOCOL_VAULTS;
+        config.total_vaults = 0;
+        config.proposal_count = 0;
+        config.upgrade_proxy_authority = None;
+        config.v2_enabled = false;
+        config.benchmarks_enabled = false;
+        config.treasury = ctx.accounts.authority.key();
+        config.executions_paused = false;
+        config.pause_expires_at = 0;
+        config.recovery_authority = None;
+        config.next_vault_id = 1;
+        config.dry_runs_enabled = false;
+        config.extra_nullifier_check = false;
+        config.vault_creation_fee_lamports = 0;
+        config.require_registered_verifier = false;
+        config.recovery_encryption_key = [0u8; 32];
+        config.encrypted_recovery_decryption_key = None;
+        config.bump = ctx.bumps.config;
+        Ok(())
+    }
+
+    /// Record the expected hash of the deployed Light System Program (see
+    /// `light_program_hash`). Called once at deployment by the protocol authority.
+    pub fn record_light_program_hash(
+        ctx: Context<UpdateProtocolConfig>,
+        program_hash: [u8; 8],
+    ) -> Result<()> {
+        ctx.accounts.config.light_program_hash = program_hash;
+        Ok(())
+    }
+
+    /// Check that the deployed Light System Program is the version this program was
+    /// built and tested against. Fails with `LightSdkVersionMismatch` if it was redeployed.
+    pub fn verify_light_sdk_compatibility(ctx: Context<VerifyLightSdkCompatibility>) -> Result<()> {
+        let program_hash = light_program_hash(&ctx.accounts.light_program_data)?;
+        require!(
+            constant_time_eq(&program_hash, &ctx.accounts.config.light_program_hash),
+            ErrorCode::LightSdkVersionMismatch
+        );
+
+        emit!(LightSdkCompatibilityVerified {
+            program_hash,
+            verified_at: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Update the per-testator vault cap. Only callable by the protocol authority.
+    pub fn update_max_vaults_per_testator(
+        ctx: Context<UpdateProtocolConfig>,
+        max_vaults_per_testator: u8,
+    ) -> Result<()> {
+        ctx.accounts.config.max_vaults_per_testator = max_vaults_per_testator;
+        Ok(())
+    }
+
+    /// Update the protocol-wide vault cap. Only callable by the protocol authority.
+    pub fn update_max_total_protocol_vaults(
+        ctx: Context<UpdateProtocolConfig>,
+        max_total_protocol_vaults: u64,
+    ) -> Result<()> {
+        ctx.accounts.config.max_total_protocol_vaults = max_total_protocol_vaults;
+        Ok(())
+    }
+
+    /// Configure the inheritance tax withheld at execution. Pass `None` to disable.
+    /// Only callable by the testator.
+    pub fn configure_inheritance_tax(
+        ctx: Context<TestatorUpdateVault>,
+        tax_authority_oracle: Option<Pubkey>,
+        tax_rate_bps: u16,
+    ) -> Result<()> {
+        authorize(&ctx.accounts.testator, Role::Testator, &ctx.accounts.vault)?;
+        require!(tax_rate_bps <= 10_000, ErrorCode::InvalidTaxRate);
+
+        let vault = &mut ctx.accounts.vault;
+        vault.assert_not_executed()?;
+        vault.tax_authority_oracle = tax_authority_oracle;
+        vault.tax_rate_bps = tax_rate_bps;
+
+        Ok(())
+    }
+
+    /// Called by the tax authority oracle once the tax is settled off-chain:
+    /// releases the escrowed tax to the oracle.
+    pub fn mark_tax_paid(ctx: Context<MarkTaxPaid>, evidence_cid: [u8; 32]) -> Result<()> {
+        authorize_holder(
+            &ctx.accounts.tax_authority,
+            Role::TaxOracle,
+            Some(ctx.accounts.tax_escrow.tax_authority),
+        )?;
+        let escrow = &mut ctx.accounts.tax_escrow;
+        require!(!escrow.paid, ErrorCode::TaxAlreadyPaid);
+
+        let amount = escrow.tax_amount;
+        escrow.paid = true;
+        escrow.evidence_cid = evidence_cid;
+
+        **escrow.to_account_info().try_borrow_mut_lamports()? -= amount;
+        **ctx.accounts.tax_authority.to_account_info().try_borrow_mut_lamports()? += amount;
+
+        emit!(TaxPaid {
+            vault: escrow.vault,
+            tax_authority: escrow.tax_authority,
+            amount,
+        });
+
+        Ok(())
+    }
+
+    /// Create the state history ring buffer for a vault, recording its current state.
+    pub fn init_state_history(ctx: Context<InitStateHistory>) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let state = ctx.accounts.vault.get_state(now);
+
+        let history = &mut ctx.accounts.state_history;
+        history.vault = ctx.accounts.vault.key();
+        history.bump = ctx.bumps.state_history;
+        history.record_transition(state, now, ctx.accounts.payer.key());
+
+        Ok(())
+    }
+
+    /// Emit the recorded state transitions of a vault, oldest first.
+    pub fn read_state_history(ctx: Context<ReadStateHistory>) -> Result<()> {
+        let history = &ctx.accounts.state_history;
+        emit!(StateHistoryRead {
+            vault: history.vault,
+            entries: history.chronological_entries(),
+            total_transitions: history.total_transitions,
+        });
+
+        Ok(())
+    }
+
+    /// Clear history entries older than `retention_days`. The most recent entry is
+    /// always kept so transition detection keeps working. Only callable by the testator.
+    pub fn prune_state_history(ctx: Context<PruneStateHistory>, retention_days: u16) -> Result<()> {
+        authorize(&ctx.accounts.testator, Role::Testator, &ctx.accounts.vault)?;
+        let now = Clock::get()?.unix_timestamp;
+        let cutoff = now - retention_days as i64 * 86400;
+        ctx.accounts.state_history.prune(cutoff);
+
+        Ok(())
+    }
+
+    /// Propose a protocol parameter change. Only testators whose vault has been
+    /// pinged at least `GovernanceProposal::MIN_PROPOSER_PING_COUNT` times may propose.
+    pub fn create_governance_proposal(
+        ctx: Context<CreateGovernanceProposal>,
+        parameter: GovernanceParameter,
+        proposed_value: i64,
+    ) -> Result<()> {
+        authorize(&ctx.accounts.testator, Role::Testator, &ctx.accounts.vault)?;
+        require!(
+            ctx.accounts.vault.ping_count >= GovernanceProposal::MIN_PROPOSER_PING_COUNT,
+            ErrorCode::InsufficientVotingPower
+        );
+        parameter.validate(proposed_value)?;
+
+        let now = Clock::get()?.unix_timestamp;
+        let config = &mut ctx.accounts.config;
+        let proposal = &mut ctx.accounts.proposal;
+        proposal.proposal_id = config.proposal_count;
+        proposal.proposer = ctx.accounts.testator.key();
+        proposal.parameter = parameter;
+        proposal.proposed_value = proposed_value;
+        proposal.votes_for = 0;
+        proposal.votes_against = 0;
+        proposal.voting_ends_at = now + GovernanceProposal::VOTING_PERIOD_SECS;
+        proposal.executed = false;
+        proposal.bump = ctx.bumps.proposal;
+
+        config.proposal_count = config
+            .proposal_count
+            .checked_add(1)
+            .ok_or(ErrorCode::ArithmeticOverflow)?;
+
+        emit!(ProposalCreated {
+            proposal_id: proposal.proposal_id,
+            proposer: proposal.proposer,
+            parameter,
+            proposed_value,
+            voting_ends_at: proposal.voting_ends_at,
+        });
+
+        Ok(())
+    }
+
+    /// Vote on a proposal, weighted by the vault's `ping_count`. Each vault votes once.
+    pub fn vote_on_proposal(
+        ctx: Context<VoteOnProposal>,
+        proposal_id: u64,
+        vote: bool,
+    ) -> Result<()> {
+        authorize(&ctx.accounts.testator, Role::Testator, &ctx.accounts.vault)?;
+        let vault = &ctx.accounts.vault;
+        vault.assert_not_executed()?;
+        let weight = vault.ping_count;
+        require!(weight > 0, ErrorCode::InsufficientVotingPower);
+
+        let proposal = &mut ctx.accounts.proposal;
+        require!(
+            Clock::get()?.unix_timestamp < proposal.voting_ends_at,
+            ErrorCode::VotingPeriodEnded
+        );
+
+        if vote {
+            proposal.votes_for = proposal.votes_for.saturating_add(weight);
+        } else {
+            proposal.votes_against = proposal.votes_against.saturating_add(weight);
+        }
+
+        let record = &mut ctx.accounts.vote_record;
+        record.proposal_id = proposal_id;
+        record.vault = ctx.accounts.vault.key();
+        record.vote = vote;
+        record.weight = weight;
+
+        emit!(VoteCast {
+            proposal_id,
+            vault: ctx.accounts.vault.key(),
+            vote,
+            weight,
+        });
+
+        Ok(())
+    }
+
+    /// Apply a passed proposal to the protocol config. Callable by anyone once
+    /// voting has ended.
+    pub fn execute_proposal(ctx: Context<ExecuteProposal>) -> Result<()> {
+        let proposal = &mut ctx.accounts.proposal;
+        require!(!proposal.executed, ErrorCode::ProposalAlreadyExecuted);
+        require!(
+            Clock::get()?.unix_timestamp >= proposal.voting_ends_at,
+            ErrorCode::VotingPeriodActive
+        );
+        require!(proposal.votes_for > proposal.votes_against, ErrorCode::ProposalRejected);
+
+        proposal.parameter.apply(&mut ctx.accounts.config, proposal.proposed_value)?;
+        proposal.executed = true;
+
+        emit!(ProposalExecuted {
+            proposal_id: proposal.proposal_id,
+            parameter: proposal.parameter,
+            new_value: proposal.proposed_value,
+        });
+
+        Ok(())
+    }
+
+    /// Set (or clear) the authority allowed to grant CPI access to future program
+    /// versions. Only callable by the protocol authority.
+    pub fn set_upgrade_proxy_authority(
+        ctx: Context<UpdateProtocolConfig>,
+        upgrade_proxy_authority: Option<Pubkey>,
+    ) -> Result<()> {
+        ctx.accounts.config.upgrade_proxy_authority = upgrade_proxy_authority;
+        Ok(())
+    }
+
+    /// Grant `caller_program_id` access to `migrate_vault` via CPI.
+    /// Only callable by the upgrade proxy authority.
+    pub fn grant_cpi_access(ctx: Context<GrantCpiAccess>, caller_program_id: Pubkey) -> Result<()> {
+        require!(
+            ctx.accounts.config.upgrade_proxy_authority == Some(ctx.accounts.upgrade_proxy_authority.key()),
+            ErrorCode::CpiAccessDenied
+        );
+
+        let token = &mut ctx.accounts.cpi_access;
+        token.caller_program_id = caller_program_id;
+        token.granted_by = ctx.accounts.upgrade_proxy_authority.key();
+        token.granted_at = Clock::get()?.unix_timestamp;
+        token.bump = ctx.bumps.cpi_access;
+
+        Ok(())
+    }
+
+    /// Hand a vault over to a newer program version. Called either directly by the
+    /// testator, or via CPI by a program holding a `CpiAccessToken`, which signs with
+    /// its `[b"upgrade_proxy"]` PDA.
+    pub fn migrate_vault(ctx: Context<MigrateVault>, caller_program_id: Pubkey) -> Result<()> {
+        let via_cpi = match ctx.accounts.upgrade_proxy.as_ref() {
+            Some(upgrade_proxy) => {
+                require!(
+                    ctx.accounts.config.upgrade_proxy_authority.is_some(),
+                    ErrorCode::CpiAccessDenied
+                );
+                require!(ctx.accounts.cpi_access.is_some(), ErrorCode::CpiAccessDenied);
+                let (expected_proxy, _) =
+                    Pubkey::find_program_address(&[UPGRADE_PROXY_SEED], &caller_program_id);
+                require_keys_eq!(upgrade_proxy.key(), expected_proxy, ErrorCode::CpiAccessDenied);
+                true
+            }
+            None => {
+                let testator = ctx.accounts.testator.as_ref().ok_or(ErrorCode::Unauthorized)?;
+                authorize(testator, Role::Testator, &ctx.accounts.vault)?;
+                false
+            }
+        };
+
+        let vault = &mut ctx.accounts.vault;
+        vault.assert_not_executed()?;
+        vault.migrated_to = Some(caller_program_id);
+
+        emit!(VaultMigrated {
+            vault: vault.key(),
+            caller_program_id,
+            via_cpi,
+            migrated_at: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Enable (or disable) V2 compressed liveness accounts for new vaults.
+    /// Only callable by the protocol authority.
+    pub fn set_compressed_liveness_v2_enabled(
+        ctx: Context<UpdateProtocolConfig>,
+        v2_enabled: bool,
+    ) -> Result<()> {
+        ctx.accounts.config.v2_enabled = v2_enabled;
+        Ok(())
+    }
+
+    /// Create a V2 compressed liveness account (see `CompressedLivenessV2`).
+    /// Requires `ProtocolConfig::v2_enabled`. `proof_nonce` must not have been used with
+    /// another liveness proof for this vault (see `UsedProofNonces`).
+    pub fn create_compressed_liveness_v2<'info>(
+        ctx: Context<'_, '_, '_, 'info, CreateCompressedLivenessV2<'info>>,
+        proof_data: ValidityProofData,
+        address_tree_info: AddressTreeInfoData,
+        output_tree_index: u8,
+        proof_nonce: [u8; 8],
+    ) -> Result<()> {
+        authorize(&ctx.accounts.testator, Role::Testator, &ctx.accounts.vault)?;
+        let compute_units_start = compute_units_remaining();
+        require!(ctx.accounts.config.v2_enabled, ErrorCode::CompressedLivenessV2Disabled);
+        require!(
+            !ctx.accounts.vault.has_compressed_liveness,
+            ErrorCode::CompressedLivenessAlreadyExists
+        );
+        let nullifier = liveness_nullifier(&proof_data, &ctx.accounts.vault.key());
+        let record_nullifier = check_liveness_nullifier(
+            Some(&*ctx.accounts.config),
+            ctx.accounts.nullifier_registry.as_ref(),
+            &nullifier,
+        )?;
+        let slot = Clock::get()?.slot;
+        check_proof_nonce(&ctx.accounts.used_nonces, &proof_nonce, slot)?;
+
+        let now = Clock::get()?.unix_timestamp;
+        let address = invoke_create_compressed_liveness_v2(
+            &ctx.accounts,
+            ctx.remaining_accounts,
+            &proof_data,
+            &address_tree_info,
+            output_tree_index,
+            now,
+        )?;
+
+        if record_nullifier {
+            record_liveness_nullifier(ctx.accounts.nullifier_registry.as_mut(), nullifier, now);
+        }
+        record_proof_nonce(
+            &mut ctx.accounts.used_nonces,
+            ctx.accounts.vault.key(),
+            ctx.bumps.used_nonces,
+            proof_nonce,
+            slot,
+        );
+
+        let vault = &mut ctx.accounts.vault;
+        vault.has_compressed_liveness = true;
+        vault.compressed_liveness_version = CompressedLivenessVersion::V2;
+        vault.compression_created_at = now;
+
+        emit!(CompressedLivenessCreated {
+            vault: vault.key(),
+            testator: vault.testator,
+            address,
+            compute_units_used: compute_units_start.saturating_sub(compute_units_remaining()),
+        });
+
+        Ok(())
+    }
+
+    /// Same as `update_liveness`, for vaults whose compressed liveness account is V2.
+    pub fn update_liveness_v2<'info>(
+        ctx: Context<'_, '_, '_, 'info, UpdateLiveness<'info>>,
+        proof_data: ValidityProofData,
+        output_tree_index: u8,
+        proof_nonce: [u8; 8],
+    ) -> Result<()> {
+        authorize(&ctx.accounts.testator, Role::Pinger, &ctx.accounts.vault)?;
+        require!(
+            ctx.accounts.vault.compressed_liveness_version == CompressedLivenessVersion::V2,
+            ErrorCode::CompressedLivenessVersionMismatch
+        );
+        update_liveness(ctx, proof_data, output_tree_index, proof_nonce)
+    }
+
+    /// Replace the vault's V1 compressed liveness account with a V2 account: the V1
+    /// account is closed and the V2 account created in a single Light System Program CPI.
+    /// The proof goes through the same nonce and nullifier checks as a creation.
+    pub fn migrate_compressed_liveness_to_v2<'info>(
+        ctx: Context<'_, '_, '_, 'info, CreateCompressedLivenessV2<'info>>,
+        proof_data: ValidityProofData,
+        address_tree_info: AddressTreeInfoData,
+        output_tree_index: u8,
+        proof_nonce: [u8; 8],
+    ) -> Result<()> {
+        authorize(&ctx.accounts.testator, Role::Testator, &ctx.accounts.vault)?;
+        require!(ctx.accounts.config.v2_enabled, ErrorCode::CompressedLivenessV2Disabled);
+        require!(
+            ctx.accounts.vault.has_compressed_liveness,
+            ErrorCode::CompressedLivenessNotFound
+        );
+        require!(
+            ctx.accounts.vault.compressed_liveness_version == CompressedLivenessVersion::V1,
+            ErrorCode::CompressedLivenessVersionMismatch
+        );
+        let nullifier = liveness_nullifier(&proof_data, &ctx.accounts.vault.key());
+        let record_nullifier = check_liveness_nullifier(
+            Some(&*ctx.accounts.config),
+            ctx.accounts.nullifier_registry.as_ref(),
+            &nullifier,
+        )?;
+        let slot = Clock::get()?.slot;
+        check_proof_nonce(&ctx.accounts.used_nonces, &proof_nonce, slot)?;
+
+        let now = Clock::get()?.unix_timestamp;
+        let (v1_address, v2_address) = invoke_migrate_compressed_liveness(
+            &ctx.accounts,
+            ctx.remaining_accounts,
+            &proof_data,
+            &address_tree_info,
+            output_tree_index,
+            now,
+        )?;
+
+        if record_nullifier {
+            record_liveness_nullifier(ctx.accounts.nullifier_registry.as_mut(), nullifier, now);
+        }
+        record_proof_nonce(
+            &mut ctx.accounts.used_nonces,
+            ctx.accounts.vault.key(),
+            ctx.bumps.used_nonces,
+            proof_nonce,
+            slot,
+        );
+
+        let vault = &mut ctx.accounts.vault;
+        vault.compressed_liveness_version = CompressedLivenessVersion::V2;
+        vault.compression_created_at = now;
+
+        emit!(CompressedLivenessMigrated {
+            vault: vault.key(),
+            v1_address,
+            v2_address,
+        });
+
+        Ok(())
+    }
+
+    /// Let a trusted agent (attorney, family member) ping on the testator's behalf for
+    /// `duration_secs`, e.g. while the testator travels. Only callable by the testator.
+    pub fn grant_custody(
+        ctx: Context<TestatorUpdateVault>,
+        agent: Pubkey,
+        duration_secs: i64,
+    ) -> Result<()> {
+        authorize(&ctx.accounts.testator, Role::Testator, &ctx.accounts.vault)?;
+        require!(duration_secs > 0, ErrorCode::InvalidCustodyDuration);
+
+        let vault = &mut ctx.accounts.vault;
+        vault.assert_not_executed()?;
+        let expires_at = Clock::get()?
+            .unix_timestamp
+            .checked_add(duration_secs)
+            .ok_or(ErrorCode::ArithmeticOverflow)?;
+        vault.custody_agent = Some(agent);
+        vault.custody_expires_at = expires_at;
+
+        emit!(CustodyGranted {
+            vault: vault.key(),
+            agent,
+            expires_at,
+        });
+
+        Ok(())
+    }
+
+    /// End the custody window early. Only callable by the testator.
+    pub fn revoke_custody(ctx: Context<TestatorUpdateVault>) -> Result<()> {
+        authorize(&ctx.accounts.testator, Role::Testator, &ctx.accounts.vault)?;
+        let vault = &mut ctx.accounts.vault;
+        vault.custody_agent = None;
+        vault.custody_expires_at = 0;
+        Ok(())
+    }
+
+    /// Attach step-by-step guidance for the beneficiary, encrypted to the beneficiary's
+    /// key. It is revealed in `InheritanceExecuted`. Only callable by the testator.
+    pub fn set_beneficiary_instructions(
+        ctx: Context<TestatorUpdateVault>,
+        encrypted_instructions: Vec<u8>,
+    ) -> Result<()> {
+        authorize(&ctx.accounts.testator, Role::Testator, &ctx.accounts.vault)?;
+        require!(
+            encrypted_instructions.len() <= Vault::MAX_BENEFICIARY_INSTRUCTIONS_SIZE,
+            ErrorCode::InstructionsTooLarge
+        );
+
+        let vault = &mut ctx.accounts.vault;
+        vault.assert_not_executed()?;
+        vault.beneficiary_instructions = encrypted_instructions;
+
+        Ok(())
+    }
+
+    /// Announce a verifier key rotation. Signed by the old verifier; takes effect on
+    /// vaults only after the protocol authority approves it.
+    pub fn propose_verifier_migration(
+        ctx: Context<ProposeVerifierMigration>,
+        new_verifier: Pubkey,
+    ) -> Result<()> {
+        let migration = &mut ctx.accounts.migration;
+        migration.old_verifier = ctx.accounts.old_verifier.key();
+        migration.new_verifier = new_verifier;
+        migration.signed_at = Clock::get()?.unix_timestamp;
+        migration.approved_by_authority = false;
+        migration.bump = ctx.bumps.migration;
+        Ok(())
+    }
+
+    /// Approve a proposed verifier migration. Only callable by the protocol authority.
+    pub fn approve_verifier_migration(ctx: Context<ApproveVerifierMigration>) -> Result<()> {
+        ctx.accounts.migration.approved_by_authority = true;
+        Ok(())
+    }
+
+    /// Schedule the move of a single vault from the old to the new verifier of an
+    /// approved migration, as a verifier rotation that `apply_verifier_rotation` makes
+    /// effective after the vault's `verifier_rotation_delay_secs`, and that the testator
+    /// may `cancel_verifier_rotation`. Permissionless, so the verifier service can crank
+    /// it over all of its vaults without testator co-signatures. It never replaces a
+    /// rotation that is already pending. Once moved, the vault no longer matches the
+    /// migration's old verifier, so it can't be applied twice; later migrations of the
+    /// new verifier still apply.
+    pub fn apply_verifier_migration(ctx: Context<ApplyVerifierMigration>) -> Result<()> {
+        let migration = &ctx.accounts.migration;
+        require!(migration.approved_by_authority, ErrorCode::VerifierMigrationNotApproved);
+
+        let vault = &mut ctx.accounts.vault;
+        vault.assert_not_executed()?;
+        require!(vault.verifier == migration.old_verifier, ErrorCode::InvalidVerifier);
+        require!(vault.pending_verifier.is_none(), ErrorCode::VerifierRotationPending);
+
+        let effective_at = Clock::get()?
+            .unix_timestamp
+            .saturating_add(vault.verifier_rotation_delay_secs);
+        vault.pending_verifier = Some(migration.new_verifier);
+        vault.verifier_rotation_effective_at = effective_at;
+
+        emit!(VerifierMigrationScheduled {
+            vault: vault.key(),
+            old_verifier: migration.old_verifier,
+            new_verifier: migration.new_verifier,
+            effective_at,
+        });
+
+        Ok(())
+    }
+
+    /// Recompute the Light CPI signer from `crate::ID` at runtime and check it against
+    /// `LIGHT_CPI_SIGNER`, so a stale constant is caught before any Light CPI fails.
+    pub fn verify_light_cpi_signer(_ctx: Context<VerifyLightCpiSigner>) -> Result<()> {
+        require!(
+            is_light_cpi_signer_of(&LIGHT_CPI_SIGNER, &crate::ID)
+                && LIGHT_CPI_SIGNER.cpi_signer[..8] == EXPECTED_CPI_SIGNER_PREFIX,
+            ErrorCode::LightCpiSignerMismatch
+        );
+
+        msg!(
+            "Light CPI signer: {} (bump {})",
+            Pubkey::new_from_array(LIGHT_CPI_SIGNER.cpi_signer),
+            LIGHT_CPI_SIGNER.bump
+        );
+        Ok(())
+    }
+
+    /// Enable (or disable) `run_benchmark`. Only callable by the protocol authority;
+    /// meant for devnet/testnet deployments.
+    pub fn set_benchmarks_enabled(
+        ctx: Context<UpdateProtocolConfig>,
+        benchmarks_enabled: bool,
+    ) -> Result<()> {
+        ctx.accounts.config.benchmarks_enabled = benchmarks_enabled;
+        Ok(())
+    }
+
+    /// Run a vault operation `iterations` times (max `MAX_BENCHMARK_ITERATIONS`) and
+    /// emit its compute cost, for load testing and capacity planning.
+    pub fn run_benchmark(
+        ctx: Context<RunBenchmark>,
+        iterations: u8,
+        operation: BenchmarkOperation,
+    ) -> Result<()> {
+        require!(ctx.accounts.config.benchmarks_enabled, ErrorCode::BenchmarksDisabled);
+        require!(
+            iterations > 0 && iterations <= MAX_BENCHMARK_ITERATIONS,
+            ErrorCode::InvalidBenchmarkIterations
+        );
+
+        let vault = &ctx.accounts.vault;
+        let now = Clock::get()?.unix_timestamp;
+        let compute_units_start = compute_units_remaining();
+
+        for _ in 0..iterations {
+            match operation {
+                BenchmarkOperation::GetState => {
+                    core::hint::black_box(vault.get_state(now));
+                }
+                BenchmarkOperation::ValidatePubkeys => {
+                    vault.verify_execution_parties(&vault.beneficiary, &vault.execution_verifier(now), now)?;
+                }
+                BenchmarkOperation::ComputeRentExempt => {
+                    core::hint::black_box(minimum_rent_exempt_balance(
+                        vault.to_account_info().data_len(),
+                    )?);
+                }
+                BenchmarkOperation::SerializeVault => {
+                    let mut buffer = Vec::with_capacity(8 + Vault::SIZE);
+                    vault.try_serialize(&mut buffer)?;
+                    core::hint::black_box(buffer);
+                }
+            }
+        }
+
+        let compute_units_used = compute_units_start.saturating_sub(compute_units_remaining());
+        emit!(BenchmarkResult {
+            operation,
+            iterations,
+            compute_units_per_iteration: compute_units_used / iterations as u64,
+            total_time_ns: None,
+        });
+
+        Ok(())
+    }
+
+    /// Describe what `encrypted_password` holds and, when the secret is split across
+    /// several vaults, which fragment this vault carries. Only callable by the testator.
+    pub fn set_key_fragment_metadata(
+        ctx: Context<TestatorUpdateVault>,
+        fragment_type: KeyFragmentType,
+        index: u8,
+        total: u8,
+    ) -> Result<()> {
+        authorize(&ctx.accounts.testator, Role::Testator, &ctx.accounts.vault)?;
+        require!(
+            index < total && total <= Vault::MAX_KEY_FRAGMENTS,
+            ErrorCode::InvalidKeyFragmentConfig
+        );
+
+        let vault = &mut ctx.accounts.vault;
+        vault.assert_not_executed()?;
+        vault.key_fragment_type = fragment_type;
+        vault.key_fragment_index = index;
+        vault.key_fragment_total = total;
+
+        Ok(())
+    }
+
+    /// Set the account that receives protocol fees. Only callable by the protocol authority.
+    pub fn set_treasury(ctx: Context<UpdateProtocolConfig>, treasury: Pubkey) -> Result<()> {
+        ctx.accounts.config.treasury = treasury;
+        Ok(())
+    }
+
+    /// Replace the ZelfProof artifacts after a biometric re-enrollment in one
+    /// instruction, so the vault never holds a mix of old and new CIDs. Requires
+    /// both the testator and the vault's verifier to sign.
+    pub fn atomic_biometric_update(
+        ctx: Context<AtomicBiometricUpdate>,
+        new_cid: [u8; 32],
+        new_cid_validator: [u8; 32],
+        new_encrypted_password: Vec<u8>,
+        new_identity_hash: Option<[u8; 32]>,
+    ) -> Result<()> {
+        authorize(&ctx.accounts.testator, Role::Testator, &ctx.accounts.vault)?;
+        // Validate every field before touching the vault
+        require!(new_cid != [0u8; 32], ErrorCode::AtomicUpdateFailed);
+        require!(new_cid_validator != [0u8; 32], ErrorCode::AtomicUpdateFailed);
+        require!(!new_encrypted_password.is_empty(), ErrorCode::EmptyEncryptedPassword);
+        require!(
+            new_encrypted_password.len() <= Vault::MAX_ENCRYPTED_PASSWORD_SIZE,
+            ErrorCode::EncryptedPasswordTooLarge
+        );
+        if let Some(hash) = new_identity_hash {
+            require!(hash != [0u8; 32], ErrorCode::AtomicUpdateFailed);
+        }
+
+        let vault = &mut ctx.accounts.vault;
+        vault.assert_not_executed()?;
+
+        let mut mask = 0u8;
+        if vault.cid != new_cid {
+            mask |= Vault::BIOMETRIC_CID;
+        }
+        if vault.cid_validator != new_cid_validator {
+            mask |= Vault::BIOMETRIC_CID_VALIDATOR;
+        }
+        if vault.encrypted_password != new_encrypted_password {
+            mask |= Vault::BIOMETRIC_ENCRYPTED_PASSWORD;
+        }
+        if new_identity_hash.is_some_and(|hash| hash != vault.beneficiary_identity_hash) {
+            mask |= Vault::BIOMETRIC_IDENTITY_HASH;
+        }
+
+        vault.cid = new_cid;
+        vault.cid_validator = new_cid_validator;
+        vault.encrypted_password = new_encrypted_password;
+        if let Some(hash) = new_identity_hash {
+            vault.beneficiary_identity_hash = hash;
+        }
+        vault.update_sequence = vault
+            .update_sequence
+            .checked_add(1)
+            .ok_or(ErrorCode::AtomicUpdateFailed)?;
+        vault.refresh_content_hash();
+
+        emit!(BiometricDataUpdated {
+            vault: vault.key(),
+            testator: vault.testator,
+            update_sequence: vault.update_sequence,
+            updated_fields_mask: mask,
+            content_hash: vault.content_hash,
+        });
+
+        Ok(())
+    }
+
+    /// Deposit `amount` from the fee payer into the vault and ping liveness in the same
+    /// instruction. A zero `amount` is a plain ping. The ping runs the full
+    /// `update_liveness` logic, including the Light Protocol path.
+    pub fn deposit_and_update_liveness<'info>(
+        ctx: Context<'_, '_, '_, 'info, UpdateLiveness<'info>>,
+        amount: u64,
+        proof_data: ValidityProofData,
+        output_tree_index: u8,
+        proof_nonce: [u8; 8],
+    ) -> Result<()> {
+        authorize(&ctx.accounts.testator, Role::Pinger, &ctx.accounts.vault)?;
+        if amount > 0 {
+            ctx.accounts.vault.assert_not_executed()?;
+
+            anchor_lang::system_program::transfer(
+                CpiContext::new(
+                    ctx.accounts.system_program.to_account_info(),
+                    anchor_lang::system_program::Transfer {
+                        from: ctx.accounts.fee_payer.to_account_info(),
+                        to: ctx.accounts.vault.to_account_info(),
+                    },
+                ),
+                amount,
+            )?;
+
+            let vault = &mut ctx.accounts.vault;
+            vault.lamports = vault
+                .lamports
+                .checked_add(amount)
+                .ok_or(ErrorCode::ArithmeticOverflow)?;
+            vault.refresh_content_hash();
+
+            emit!(SolDeposited {
+                vault: vault.key(),
+                depositor: ctx.accounts.fee_payer.key(),
+                amount,
+                new_vault_balance: vault.lamports,
+                content_hash: vault.content_hash,
+            });
+        }
+
+        update_liveness(ctx, proof_data, output_tree_index, proof_nonce)
+    }
+
+    /// Verify the beneficiary's identity hash and execute the inheritance in one
+    /// transaction. Emits `BeneficiaryVerified` followed by `InheritanceExecuted`;
+    /// everything rolls back if execution fails.
+    pub fn verify_and_execute<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ExecuteInheritance<'info>>,
+        identity_hash: [u8; 32],
+        transfer_funds: bool,
+        execution_nonce_provided: [u8; 8],
+    ) -> Result<()> {
+        authorize(&ctx.accounts.beneficiary, Role::Heir, &ctx.accounts.vault)?;
+        let now = Clock::get()?.unix_timestamp;
+        let vault = &mut ctx.accounts.vault;
+        require!(
+            constant_time_eq_32(&vault.beneficiary_identity_hash, &identity_hash),
+            ErrorCode::IdentityVerificationFailedInComposite
+        );
+
+        vault.record_identity_verification(now);
+
+        emit!(BeneficiaryVerified {
+            vault: vault.key(),
+            beneficiary: vault.beneficiary,
+            testator: vault.testator,
+            cid: vault.cid,
+            cid_validator: vault.cid_validator,
+            is_claimable: vault.get_state(now).is_claimable(),
+            executed: vault.executed,
+        });
+
+        execute_inheritance(ctx, transfer_funds, execution_nonce_provided)
+    }
+
+    /// Discovery primitive: report the vault PDA for a testator + beneficiary pair and
+    /// whether an account exists there. Reads no vault state and needs no signers.
+    pub fn lookup_vault_address(
+        ctx: Context<LookupVaultAddress>,
+        testator: Pubkey,
+        beneficiary: Pubkey,
+        vault_nonce: u8,
+    ) -> Result<()> {
+        let (vault_pda, bump) = compute_vault_pda(&testator, &beneficiary, vault_nonce);
+
+        if ctx.accounts.vault.data_is_empty() {
+            emit!(VaultAddressNotFound {
+                testator,
+                beneficiary,
+                would_be_pda: vault_pda,
+            });
+        } else {
+            emit!(VaultAddressLookup {
+                testator,
+                beneficiary,
+                vault_pda,
+                bump,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Emergency stop: block `execute_inheritance` on every non-debug vault for
+    /// `ProtocolConfig::EXECUTION_PAUSE_SECS`. Calling it again renews the pause.
+    pub fn pause_all_executions(ctx: Context<UpdateProtocolConfig>) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let config = &mut ctx.accounts.config;
+        config.executions_paused = true;
+        config.pause_expires_at = now + ProtocolConfig::EXECUTION_PAUSE_SECS;
+
+        emit!(ExecutionsPaused {
+            authority: config.authority,
+            paused_at: now,
+            expires_at: config.pause_expires_at,
+        });
+
+        Ok(())
+    }
+
+    /// Lift an execution pause before it expires on its own.
+    pub fn resume_all_executions(ctx: Context<UpdateProtocolConfig>) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        config.executions_paused = false;
+        config.pause_expires_at = 0;
+
+        emit!(ExecutionsResumed {
+            authority: config.authority,
+            resumed_at: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Put the vault under an M-of-N verifier committee. The committee PDA is recorded in
+    /// `vault.verifier_committee` and takes the verifier's place in executions only.
+    /// Like a verifier rotation, it takes effect after the vault's
+    /// `verifier_rotation_delay_secs`: until then the verifier still signs executions and
+    /// `cancel_verifier_committee` drops the committee; from then on no single verifier
+    /// can sign `execute_inheritance` and executions go through
+    /// `finalize_committee_execution` instead. `vault.verifier` keeps its other duties.
+    pub fn init_verifier_committee(
+        ctx: Context<InitVerifierCommittee>,
+        verifiers: Vec<Pubkey>,
+        threshold: u8,
+        approval_expires_in_secs: i64,
+    ) -> Result<()> {
+        authorize(&ctx.accounts.testator, Role::Testator, &ctx.accounts.vault)?;
+        require!(
+            !verifiers.is_empty() && verifiers.len() <= VerifierCommittee::MAX_VERIFIERS,
+            ErrorCode::InvalidCommitteeConfig
+        );
+        require!(
+            threshold > 0 && threshold as usize <= verifiers.len(),
+            ErrorCode::InvalidCommitteeConfig
+        );
+        require!(approval_expires_in_secs > 0, ErrorCode::InvalidCommitteeConfig);
+        for (i, verifier) in verifiers.iter().enumerate() {
+            require!(!verifiers[..i].contains(verifier), ErrorCode::InvalidCommitteeConfig);
+        }
+        ctx.accounts.vault.assert_not_executed()?;
+        require!(
+            ctx.accounts.vault.verifier_committee.is_none(),
+            ErrorCode::InvalidCommitteeConfig
+        );
+
+        let committee = &mut ctx.accounts.committee;
+        committee.vault = ctx.accounts.vault.key();
+        committee.approvals = vec![false; verifiers.len()];
+        committee.approval_timestamps = vec![0; verifiers.len()];
+        committee.verifiers = verifiers;
+        committee.threshold = threshold;
+        committee.approval_expires_in_secs = approval_expires_in_secs;
+        committee.bump = ctx.bumps.committee;
+
+        let vault = &mut ctx.accounts.vault;
+        let effective_at = Clock::get()?
+            .unix_timestamp
+            .saturating_add(vault.verifier_rotation_delay_secs);
+        vault.verifier_committee = Some(committee.key());
+        vault.verifier_committee_effective_at = effective_at;
+
+        emit!(VerifierRotationScheduled {
+            vault: vault.key(),
+            current_verifier: vault.verifier,
+            new_verifier: committee.key(),
+            effective_at,
+        });
+
+        Ok(())
+    }
+
+    /// Record the approval of committee member `verifier_index` (the signer).
+    /// An approval counts towards the threshold for `approval_expires_in_secs`.
+    pub fn submit_committee_approval(
+        ctx: Context<SubmitCommitteeApproval>,
+        verifier_index: u8,
+    ) -> Result<()> {
+        let index = verifier_index as usize;
+        authorize_holder(
+            &ctx.accounts.verifier,
+            Role::CoSigner,
+            ctx.accounts.committee.verifiers.get(index).copied(),
+        )?;
+        ctx.accounts.vault.assert_not_executed()?;
+
+        let now = Clock::get()?.unix_timestamp;
+        let committee = &mut ctx.accounts.committee;
+        require!(!committee.is_live_approval(index, now), ErrorCode::AlreadyApproved);
+
+        committee.approvals[index] = true;
+        committee.approval_timestamps[index] = now;
+
+        emit!(CommitteeApprovalSubmitted {
+            vault: committee.vault,
+            verifier: ctx.accounts.verifier.key(),
+            verifier_index,
+            live_approvals: committee.live_approvals(now),
+            threshold: committee.threshold,
+        });
+
+        Ok(())
+    }
+
+    /// Execute the inheritance of a committee-verified vault once `threshold` members
+    /// have live approvals. Signed by the beneficiary only; otherwise identical to
+    /// `execute_inheritance`.
+    pub fn finalize_committee_execution<'info>(
+        ctx: Context<'_, '_, 'info, 'info, FinalizeCommitteeExecution<'info>>,
+        transfer_funds: bool,
+        execution_nonce_provided: [u8; 8],
+    ) -> Result<()> {
+        authorize(&ctx.accounts.beneficiary, Role::Heir, &ctx.accounts.vault)?;
+        let compute_units_start = compute_units_remaining();
+        verify_vault_discriminator(&ctx.accounts.vault.to_account_info(), ctx.program_id)?;
+
+        let now = Clock::get()?.unix_timestamp;
+        require!(
+            now >= ctx.accounts.vault.verifier_committee_effective_at,
+            ErrorCode::VerifierRotationTimelocked
+        );
+        ctx.accounts
+            .vault
+            .verify_execution_parties(ctx.accounts.beneficiary.key, &ctx.accounts.committee.key(), now)?;
+        let committee = &ctx.accounts.committee;
+        let live_approvals = committee.live_approvals(now);
+        if live_approvals < committee.threshold {
+            let recorded = committee.approvals.iter().filter(|&&a| a).count();
+            if recorded >= committee.threshold as usize {
+                return err!(ErrorCode::CommitteeApprovalExpired);
+            }
+            return err!(ErrorCode::CommitteeThresholdNotMet);
+        }
+        let threshold = committee.threshold;
+
+        settle_inheritance(
+            SettlementAccounts {
+                vault: &mut ctx.accounts.vault,
+                beneficiary: &ctx.accounts.beneficiary.to_account_info(),
+                config: &ctx.accounts.config,
+                time_capsule: &mut ctx.accounts.time_capsule,
+                tax_escrow: &mut ctx.accounts.tax_escrow,
+                state_history: &mut ctx.accounts.state_history,
+                co_beneficiaries: ctx.remaining_accounts,
+            },
+            Settlement {
+                transfer_funds,
+                execution_nonce: execution_nonce_provided,
+                // Committee approvals are already individually signed and expire
+                proof: ExecutionProof::VerifierAttestation,
+                compute_units_start,
+            },
+        )?;
+
+        emit!(CommitteeExecutionFinalised {
+            vault: ctx.accounts.vault.key(),
+            beneficiary: ctx.accounts.beneficiary.key(),
+            live_approvals,
+            threshold,
+        });
+
+        Ok(())
+    }
+
+    /// Abort a pending execution: while the vault is Claimable and still within
+    /// `execution_delay_secs` of becoming so, the testator's signature proves they are
+    /// alive and returns the vault to Active. Allowed `MAX_EXECUTION_REVOCATIONS` times;
+    /// ordinary pings are rejected while an execution is pending.
+    pub fn revoke_pending_execution(ctx: Context<TestatorUpdateVault>) -> Result<()> {
+        authorize(&ctx.accounts.testator, Role::Testator, &ctx.accounts.vault)?;
+        let now = Clock::get()?.unix_timestamp;
+        let vault = &mut ctx.accounts.vault;
+        vault.assert_not_executed()?;
+        // Revocations switch `execution_revocable` off once the limit is reached
+        require!(
+            vault.execution_revocable
+                || vault.execution_revocation_count >= Vault::MAX_EXECUTION_REVOCATIONS,
+            ErrorCode::ExecutionNotRevocable
+        );
+        require!(
+            vault.execution_revocation_count < Vault::MAX_EXECUTION_REVOCATIONS,
+            ErrorCode::RevocationLimitReached
+        );
+        require!(vault.get_state(now).is_claimable(), ErrorCode::TransitionNotAllowed);
+
+        let claimable_for = now
+            .saturating_sub(vault.effective_last_ping())
+            .saturating_sub(vault.effective_timeout_secs());
+        require!(claimable_for <= vault.execution_delay_secs, ErrorCode::GracePeriodExpired);
+
+        let execution_count_before_revoke = vault.execution_revocation_count;
+        vault.execution_revocation_count += 1;
+        if vault.execution_revocation_count >= Vault::MAX_EXECUTION_REVOCATIONS {
+            vault.execution_revocable = false;
+        }
+        vault.last_ping = now;
+        vault.refresh_content_hash();
+
+        emit!(PendingExecutionRevoked {
+            vault: vault.key(),
+            testator: vault.testator,
+            revoked_at: now,
+            execution_count_before_revoke,
+            content_hash: vault.content_hash,
+        });
+
+        Ok(())
+    }
+
+    /// Require the beneficiary's identity verification to be at most `max_age_secs`
+    /// old at execution time (0 = no expiry, at most `Vault::MAX_VERIFICATION_AGE_SECS`).
+    /// Only callable by the testator.
+    pub fn set_max_verification_age(
+        ctx: Context<TestatorUpdateVault>,
+        max_age_secs: i64,
+    ) -> Result<()> {
+        authorize(&ctx.accounts.testator, Role::Testator, &ctx.accounts.vault)?;
+        let vault = &mut ctx.accounts.vault;
+        vault.assert_not_executed()?;
+        require!(
+            (0..=Vault::MAX_VERIFICATION_AGE_SECS).contains(&max_age_secs),
+            ErrorCode::InvalidVerificationAge
+        );
+        vault.max_verification_age_secs = max_age_secs;
+
+        Ok(())
+    }
+
+    /// Refresh the beneficiary's identity verification timestamp by re-proving the
+    /// identity hash. Unlike `verify_beneficiary_identity`, emits no event.
+    pub fn revalidate_identity(
+        ctx: Context<VerifyBeneficiaryIdentity>,
+        identity_hash: [u8; 32],
+    ) -> Result<()> {
+        authorize(&ctx.accounts.beneficiary, Role::Beneficiary, &ctx.accounts.vault)?;
+        let vault = &mut ctx.accounts.vault;
+        vault.assert_not_executed()?;
+        require!(
+            constant_time_eq_32(&vault.beneficiary_identity_hash, &identity_hash),
+            ErrorCode::IdentityHashMismatch
+        );
+        vault.record_identity_verification(Clock::get()?.unix_timestamp);
+
+        Ok(())
+    }
+
+    /// Require every liveness update to carry `note` (see `update_liveness_with_note`),
+    /// e.g. a challenge nonce issued by an offline backup system. Only callable by the testator.
+    pub fn set_expected_ping_note(
+        ctx: Context<TestatorUpdateVault>,
+        note: [u8; 32],
+    ) -> Result<()> {
+        authorize(&ctx.accounts.testator, Role::Testator, &ctx.accounts.vault)?;
+        let vault = &mut ctx.accounts.vault;
+        vault.assert_not_executed()?;
+        vault.expected_ping_note = Some(note);
+        Ok(())
+    }
+
+    /// Stop requiring a ping note. Only callable by the testator.
+    pub fn clear_expected_ping_note(ctx: Context<TestatorUpdateVault>) -> Result<()> {
+        authorize(&ctx.accounts.testator, Role::Testator, &ctx.accounts.vault)?;
+        ctx.accounts.vault.expected_ping_note = None;
+        Ok(())
+    }
+
+    /// Disaster recovery: the protocol authority pings up to `MAX_ADMIN_PING_VAULTS`
+    /// vaults at once on the strength of off-chain evidence (`evidence_cid`). The vaults
+    /// are passed as writable remaining accounts in the order of `vaults`.
+    ///
+    /// The transaction must carry, right before this instruction, an Ed25519 program
+    /// instruction in which the authority signs `evidence_cid || slot` (slot as u64 LE),
+    /// with `slot` at most `MAX_ADMIN_SIGNATURE_AGE_SLOTS` old.
+    pub fn admin_ping_liveness<'info>(
+        ctx: Context<'_, '_, 'info, 'info, AdminPingLiveness<'info>>,
+        vaults: Vec<Pubkey>,
+        evidence_cid: [u8; 32],
+        authority_signature: [u8; 64],
+    ) -> Result<()> {
+        authorize_protocol(&ctx.accounts.authority, Role::ProtocolAuthority, &ctx.accounts.config)?;
+        require!(vaults.len() <= MAX_ADMIN_PING_VAULTS, ErrorCode::AdminPingLimitExceeded);
+        require!(
+            ctx.remaining_accounts.len() == vaults.len(),
+            ErrorCode::AdminPingAccountsMismatch
+        );
+
+        let authority = ctx.accounts.authority.key();
+        verify_admin_ping_signature(
+            &ctx.accounts.instructions_sysvar,
+            &authority,
+            &evidence_cid,
+            &authority_signature,
+            Clock::get()?.slot,
+        )?;
+
+        let now = Clock::get()?.unix_timestamp;
+        for (expected, account_info) in vaults.iter().zip(ctx.remaining_accounts.iter()) {
+            account_info.assert_key_matches(expected, ErrorCode::Unauthorized)?;
+            let mut vault = Account::<Vault>::try_from(account_info)?;
+            vault.assert_not_executed()?;
+
+            vault.last_ping = now;
+            vault.admin_pinged = true;
+            vault.admin_ping_count = vault.admin_ping_count.saturating_add(1);
+            vault.refresh_content_hash();
+            vault.exit(ctx.program_id)?;
+
+            emit!(AdminLivenessPing {
+                vault: *expected,
+                authority,
+                evidence_cid,
+                timestamp: now,
+                content_hash: vault.content_hash,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Emit a verifiable archive of the vault for legal record keeping. `data` is
+    /// `schema_hash (8) || Borsh(Vault) || slot commitment (8)`, where the commitment is
+    /// the first 8 bytes of the most recent slot hash. Permissionless and read-only.
+    pub fn export_vault_state(ctx: Context<ExportVaultState>) -> Result<()> {
+        let vault = &ctx.accounts.vault;
+
+        let mut data = Vec::with_capacity(8 + Vault::SIZE + 8);
+        data.extend_from_slice(&vault_export_schema_hash());
+        AnchorSerialize::serialize(&**vault, &mut data)?;
+
+        let slot_hashes = ctx.accounts.recent_slothashes.try_borrow_data()?;
+        // SlotHashes layout: u64 entry count, then (slot: u64, hash: [u8; 32]), most recent first
+        data.extend_from_slice(slot_hashes.get(16..24).unwrap_or(&[0u8; 8]));
+
+        require!(data.len() <= MAX_VAULT_EXPORT_SIZE, ErrorCode::VaultExportTooLarge);
+
+        emit!(VaultStateExported {
+            vault: vault.key(),
+            data,
+        });
+
+        Ok(())
+    }
+
+    /// Create the compressed liveness index: the root (`[b"idx_root"]`) and its first,
+    /// empty page (`[b"idx_page", &[0]]`). Permissionless, called once per deployment.
+    pub fn init_compressed_liveness_index(ctx: Context<InitCompressedLivenessIndex>) -> Result<()> {
+        let page = &mut ctx.accounts.page;
+        page.page_id = 0;
+        page.bump = ctx.bumps.page;
+
+        let root = &mut ctx.accounts.root;
+        root.pages = vec![IndexPageRoot {
+            page_id: 0,
+            min_testator: Pubkey::default(),
+        }];
+        root.bump = ctx.bumps.root;
+
+        Ok(())
+    }
+
+    /// Add (or refresh) the signer's entry in the compressed liveness index. `page`
+    /// must be the page `root` routes the testator to. When that page is full, pass
+    /// `new_page` (`[b"idx_page", &[root.pages.len()]]`) and the upper half of the page
+    /// moves into it before inserting.
+    pub fn insert_compressed_liveness_index(
+        ctx: Context<InsertCompressedLivenessIndex>,
+        address: [u8; 32],
+    ) -> Result<()> {
+        authorize(&ctx.accounts.testator, Role::Testator, &ctx.accounts.vault)?;
+        let vault = &ctx.accounts.vault;
+        require!(
+            vault.has_compressed_liveness || vault.is_debug,
+            ErrorCode::CompressedLivenessNotFound
+        );
+        let entry = CompressedLivenessEntry {
+            testator: vault.testator,
+            vault: vault.key(),
+            address,
+        };
+
+        let root = &mut ctx.accounts.root;
+        let page = &mut ctx.accounts.page;
+        let route = root.route(&entry.testator);
+        require!(root.pages[route].page_id == page.page_id, ErrorCode::IndexPageMismatch);
+
+        let has_room = (page.count as usize) < CompressedLivenessIndexPage::CAPACITY;
+        if has_room || page.find(&entry.testator).is_ok() {
+            return page.upsert(entry);
+        }
+
+        // Page full: split it, then insert into whichever half the testator belongs to
+        let new_page = ctx
+            .accounts
+            .new_page
+            .as_mut()
+            .ok_or(ErrorCode::IndexPageFull)?;
+        require!(
+            root.pages.len() < CompressedLivenessIndexRoot::MAX_PAGES,
+            ErrorCode::IndexPageFull
+        );
+        new_page.page_id = root.pages.len() as u8;
+        new_page.bump = ctx.bumps.new_page.ok_or(ErrorCode::IndexPageFull)?;
+        page.split_into(new_page);
+        root.pages.insert(
+            route + 1,
+            IndexPageRoot {
+                page_id: new_page.page_id,
+                min_testator: new_page.entries[0].testator,
+            },
+        );
+
+        if entry.testator < new_page.entries[0].testator {
+            page.upsert(entry)
+        } else {
+            new_page.upsert(entry)
+        }
+    }
+
+    /// Look up a testator's compressed liveness entry: binary search over the root's
+    /// page ranges, then within `page`. Emits `CompressedLivenessLookup`.
+    pub fn lookup_compressed_liveness(
+        ctx: Context<LookupCompressedLiveness>,
+        testator: Pubkey,
+    ) -> Result<()> {
+        let root = &ctx.accounts.root;
+        let page = &ctx.accounts.page;
+        require!(
+            root.pages[root.route(&testator)].page_id == page.page_id,
+            ErrorCode::IndexPageMismatch
+        );
+
+        let entry = page.find(&testator).ok().map(|i| page.entries[i]);
+        emit!(CompressedLivenessLookup {
+            testator,
+            found: entry.is_some(),
+            page_id: page.page_id,
+            vault: entry.map_or(Pubkey::default(), |e| e.vault),
+            address: entry.map_or([0u8; 32], |e| e.address),
+        });
+
+        Ok(())
+    }
+
+    /// Let a wearable health data oracle contribute to the vault's liveness with
+    /// `weight_bps` of the blend (see `Vault::effective_last_ping`). Requires both the
+    /// testator and the vault's verifier to sign.
+    pub fn set_health_oracle(
+        ctx: Context<SetHealthOracle>,
+        oracle: Pubkey,
+        weight_bps: u16,
+    ) -> Result<()> {
+        authorize(&ctx.accounts.testator, Role::Testator, &ctx.accounts.vault)?;
+        require!(weight_bps <= 10_000, ErrorCode::InvalidHealthOracleWeight);
+
+        let vault = &mut ctx.accounts.vault;
+        vault.assert_not_executed()?;
+        vault.health_oracle = Some(oracle);
+        vault.health_signal_weight_bps = weight_bps;
+        vault.last_health_signal = 0;
+
+        Ok(())
+    }
+
+    /// Report a health signal observed at `timestamp`. Only callable by the vault's
+    /// health oracle; signals must be newer than the previous one and at most
+    /// `Vault::MAX_HEALTH_SIGNAL_AGE_SECS` old.
+    pub fn submit_health_signal(
+        ctx: Context<SubmitHealthSignal>,
+        timestamp: i64,
+        health_hash: [u8; 32],
+    ) -> Result<()> {
+        authorize(&ctx.accounts.oracle, Role::HealthOracle, &ctx.accounts.vault)?;
+        let now = Clock::get()?.unix_timestamp;
+        let vault = &mut ctx.accounts.vault;
+        vault.assert_not_executed()?;
+        require!(
+            vault.health_oracle == Some(ctx.accounts.oracle.key()),
+            ErrorCode::Unauthorized
+        );
+        require!(
+            timestamp <= now
+                && timestamp > vault.last_health_signal
+                && now - timestamp <= Vault::MAX_HEALTH_SIGNAL_AGE_SECS,
+            ErrorCode::HealthSignalTooOld
+        );
+
+        vault.last_health_signal = timestamp;
+
+        emit!(HealthSignalReceived {
+            vault: vault.key(),
+            oracle: ctx.accounts.oracle.key(),
+            timestamp,
+            health_hash,
+        });
+
+        Ok(())
+    }
+
+    /// Switch the vault into lottery mode: one registered participant inherits instead of
+    /// the beneficiary. Execution commits to a future slot, `draw_lottery_winner` draws
+    /// the winner from its hash, and the funds stay in the vault until the winner calls
+    /// `claim_lottery_prize`.
+    pub fn set_lottery_mode(ctx: Context<TestatorUpdateVault>, enabled: bool) -> Result<()> {
+        authorize(&ctx.accounts.testator, Role::Testator, &ctx.accounts.vault)?;
+        let vault = &mut ctx.accounts.vault;
+        vault.assert_not_executed()?;
+        require!(
+            !enabled || vault.co_beneficiaries.is_empty(),
+            ErrorCode::InvalidBeneficiarySplits
+        );
+        vault.lottery_mode = enabled;
+        Ok(())
+    }
+
+    /// Add a participant to the vault's lottery (at most `Vault::MAX_LOTTERY_PARTICIPANTS`).
+    pub fn register_lottery_participant(
+        ctx: Context<TestatorUpdateVault>,
+        participant: Pubkey,
+    ) -> Result<()> {
+        authorize(&ctx.accounts.testator, Role::Testator, &ctx.accounts.vault)?;
+        let vault = &mut ctx.accounts.vault;
+        vault.assert_not_executed()?;
+        if vault.lottery_participants.contains(&participant) {
+            return Ok(());
+        }
+        require!(
+            vault.lottery_participants.len() < Vault::MAX_LOTTERY_PARTICIPANTS,
+            ErrorCode::LotteryFull
+        );
+        vault.lottery_participants.push(participant);
+        Ok(())
+    }
+
+    /// Pay the prize held in an executed lottery vault to the drawn winner.
+    pub fn claim_lottery_prize(ctx: Context<ClaimLotteryPrize>) -> Result<()> {
+        authorize(&ctx.accounts.winner, Role::Heir, &ctx.accounts.vault)?;
+        let vault = &mut ctx.accounts.vault;
+        require!(vault.executed, ErrorCode::TransitionNotAllowed);
+        require!(
+            vault.lottery_winner == Some(ctx.accounts.winner.key()),
+            ErrorCode::Unauthorized
+        );
+
+        let amount = vault.lamports;
+        require!(amount > 0, ErrorCode::NoAssets);
+        vault.lamports = 0;
+        vault.lamports_transferred = amount;
+
+        let vault_account_info = vault.to_account_info();
+        **vault_account_info.try_borrow_mut_lamports()? -= amount;
+        **ctx.accounts.winner.to_account_info().try_borrow_mut_lamports()? += amount;
+        check_rent_exempt(&vault_account_info)?;
+        vault.refresh_content_hash();
+
+        emit!(LotteryPrizeClaimed {
+            vault: vault.key(),
+            winner: ctx.accounts.winner.key(),
+            amount,
+            content_hash: vault.content_hash,
+        });
+
+        Ok(())
+    }
+
+    /// Set (or clear) the recovery authority, typically a Squads multisig, allowed to
+    /// call `emergency_key_reveal`. Only callable by the protocol authority.
+    pub fn set_recovery_authority(
+        ctx: Context<UpdateProtocolConfig>,
+        recovery_authority: Option<Pubkey>,
+    ) -> Result<()> {
+        ctx.accounts.config.recovery_authority = recovery_authority;
+        Ok(())
+    }
+
+    /// Last resort when the Light Protocol state is permanently unavailable: open the
+    /// vault's `RecoveryEscrow` and publish K in `EmergencyKeyRevealed`. One-time only,
+    /// and only once the vault is claimable: K is public from then on, so revealing it
+    /// earlier would hand the testator's secret to anyone reading the logs.
+    ///
+    /// `recovery_unwrap_key` is the escrow's unwrap key, which the recovery authority
+    /// derives off-chain from the recovery private key and `escrow.ephemeral_pubkey`.
+    /// It is specific to this vault, so publishing it exposes no other escrow.
+    pub fn emergency_key_reveal(
+        ctx: Context<EmergencyKeyReveal>,
+        recovery_unwrap_key: [u8; 32],
+    ) -> Result<()> {
+        authorize_holder(
+            &ctx.accounts.recovery_authority,
+            Role::ProtocolAuthority,
+            ctx.accounts.config.recovery_authority,
+        )?;
+        let vault = &mut ctx.accounts.vault;
+        vault.assert_not_executed()?;
+        require!(!vault.emergency_revealed, ErrorCode::EmergencyAlreadyRevealed);
+        require!(
+            vault.get_state(Clock::get()?.unix_timestamp).is_claimable(),
+            ErrorCode::TransitionNotAllowed
+        );
+
+        let escrow = vault.recovery_escrow.ok_or(ErrorCode::RecoveryEscrowMissing)?;
+        let decrypted_key =
+            decrypt_key_v2(&escrow.encrypted_key, &recovery_unwrap_key, vault.key().as_ref())?;
+
+        vault.emergency_revealed = true;
+
+        emit!(EmergencyKeyRevealed {
+            vault: vault.key(),
+            testator: vault.testator,
+            beneficiary: vault.beneficiary,
+            decrypted_key,
+        });
+
+        Ok(())
+    }
+
+    /// Resolve a sequential `vault_id` to its vault PDA through the vault id index.
+    pub fn lookup_vault_by_id(ctx: Context<LookupVaultById>, vault_id: u64) -> Result<()> {
+        let index_info = &ctx.accounts.vault_id_index;
+        require!(
+            !index_info.data_is_empty() && index_info.owner == &crate::ID,
+            ErrorCode::VaultIdNotFound
+        );
+        let index = VaultIdIndex::try_deserialize(&mut &index_info.try_borrow_data()?[..])?;
+
+        emit!(VaultIdLookupResult {
+            vault_id,
+            vault_pda: index.vault,
+        });
+
+        Ok(())
+    }
+
+    /// Enable (or disable) `dry_run_compressed_liveness_update`. Only callable by the
+    /// protocol authority.
+    pub fn set_dry_runs_enabled(ctx: Context<UpdateProtocolConfig>, dry_runs_enabled: bool) -> Result<()> {
+        ctx.accounts.config.dry_runs_enabled = dry_runs_enabled;
+        Ok(())
+    }
+
+    /// Run the input checks of a compressed liveness update (proof decoding, tree
+    /// account bounds, address derivation) without the Light CPI or any state change,
+    /// so clients can validate a proof in a simulation before paying for it.
+    pub fn dry_run_compressed_liveness_update(
+        ctx: Context<DryRunCompressedLivenessUpdate>,
+        proof_data: ValidityProofData,
+        address_tree_info: AddressTreeInfoData,
+        output_tree_index: u8,
+    ) -> Result<()> {
+        require!(ctx.accounts.config.dry_runs_enabled, ErrorCode::DryRunNotEnabled);
+
+        let vault = &ctx.accounts.vault;
+        let result = validate_compressed_liveness_inputs(
+            &vault.seed_testator,
+            &vault.key(),
+            ctx.remaining_accounts,
+            &proof_data,
+            &address_tree_info,
+            output_tree_index,
+        );
+
+        let estimated_compute_units = if vault.is_debug {
+            DRY_RUN_DEBUG_UPDATE_CU
+        } else {
+            match vault.compressed_liveness_version {
+                CompressedLivenessVersion::V1 => DRY_RUN_COMPRESSED_V1_UPDATE_CU,
+                CompressedLivenessVersion::V2 => DRY_RUN_COMPRESSED_V2_UPDATE_CU,
+            }
+        };
+
+        emit!(DryRunResult {
+            valid: result.is_ok(),
+            error_code: result.err().map(u32::from),
+            estimated_compute_units,
+        });
+
+        Ok(())
+    }
+
+    /// Mint a Metaplex NFT deed for the vault to the testator. The vault PDA is the
+    /// mint and update authority; `uri` points to the deed's off-chain metadata.
+    #[cfg(feature = "deed_nft")]
+    pub fn mint_vault_deed(ctx: Context<MintVaultDeed>, uri: String) -> Result<()> {
+        authorize(&ctx.accounts.testator, Role::Testator, &ctx.accounts.vault)?;
+        let vault = &ctx.accounts.vault;
+        vault.assert_not_executed()?;
+        require!(vault.deed_nft_mint.is_none(), ErrorCode::DeedAlreadyMinted);
+
+        let signer_seeds: &[&[u8]] = &[
+            b"vault",
+            vault.seed_testator.as_ref(),
+            vault.seed_beneficiary.as_ref(),
+            &[vault.vault_nonce],
+            &[vault.bump],
+        ];
+
+        anchor_spl::token::mint_to(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                anchor_spl::token::MintTo {
+                    mint: ctx.accounts.deed_mint.to_account_info(),
+                    to: ctx.accounts.testator_deed_account.to_account_info(),
+                    authority: vault.to_account_info(),
+                },
+                &[signer_seeds],
+            ),
+            1,
+        )?;
+
+        anchor_spl::metadata::create_metadata_accounts_v3(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_metadata_program.to_account_info(),
+                anchor_spl::metadata::CreateMetadataAccountsV3 {
+                    metadata: ctx.accounts.metadata.to_account_info(),
+                    mint: ctx.accounts.deed_mint.to_account_info(),
+                    mint_authority: vault.to_account_info(),
+                    payer: ctx.accounts.testator.to_account_info(),
+                    update_authority: vault.to_account_info(),
+                    system_program: ctx.accounts.system_program.to_account_info(),
+                    rent: ctx.accounts.rent.to_account_info(),
+                },
+                &[signer_seeds],
+            ),
+            anchor_spl::metadata::mpl_token_metadata::types::DataV2 {
+                name: format!("Vault Deed #{}", vault.vault_id),
+                symbol: String::from("DEED"),
+                uri,
+                seller_fee_basis_points: 0,
+                creators: None,
+                collection: None,
+                uses: None,
+            },
+            false, // is_mutable
+            true,  // update_authority_is_signer
+            None,
+        )?;
+
+        let deed_mint = ctx.accounts.deed_mint.key();
+        let vault = &mut ctx.accounts.vault;
+        vault.deed_nft_mint = Some(deed_mint);
+
+        emit!(VaultDeedMinted {
+            vault: vault.key(),
+            deed_mint,
+            testator: vault.testator,
+        });
+
+        Ok(())
+    }
+
+    /// Burn the vault's deed NFT. Cancels the deed only; the vault itself is unaffected.
+    /// Callable by whoever currently holds the deed.
+    #[cfg(feature = "deed_nft")]
+    pub fn burn_vault_deed(ctx: Context<BurnVaultDeed>) -> Result<()> {
+        require!(!ctx.accounts.vault.deed_burned, ErrorCode::DeedAlreadyBurned);
+
+        anchor_spl::token::burn(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                anchor_spl::token::Burn {
+                    mint: ctx.accounts.deed_mint.to_account_info(),
+                    from: ctx.accounts.holder_deed_account.to_account_info(),
+                    authority: ctx.accounts.holder.to_account_info(),
+                },
+            ),
+            1,
+        )?;
+
+        let vault = &mut ctx.accounts.vault;
+        vault.deed_burned = true;
+
+        emit!(VaultDeedBurned {
+            vault: vault.key(),
+            deed_mint: ctx.accounts.deed_mint.key(),
+        });
+
+        Ok(())
+    }
+
+    /// Let a task scheduler (Clockwork, Squads, ...) send `update_liveness` on the
+    /// testator's behalf following `cron`. The scheduler signs as both the testator
+    /// and the fee payer. Registering again replaces the previous scheduler.
+    pub fn register_liveness_scheduler(
+        ctx: Context<RegisterLivenessScheduler>,
+        scheduler: Pubkey,
+        cron: String,
+    ) -> Result<()> {
+        authorize(&ctx.accounts.testator, Role::Testator, &ctx.accounts.vault)?;
+        require!(
+            !cron.is_empty()
+                && cron.len() <= SchedulerTask::MAX_CRON_LEN
+                && cron.bytes().all(|b| b.is_ascii_graphic() || b == b' '),
+            ErrorCode::InvalidSchedule
+        );
+        let mut schedule_cron = [0u8; SchedulerTask::MAX_CRON_LEN];
+        schedule_cron[..cron.len()].copy_from_slice(cron.as_bytes());
+
+        let vault = &mut ctx.accounts.vault;
+        vault.assert_not_executed()?;
+        vault.scheduler_pubkey = Some(scheduler);
+        vault.schedule_cron = schedule_cron;
+
+        let task = &mut ctx.accounts.scheduler_task;
+        task.vault = vault.key();
+        task.scheduler = scheduler;
+        task.schedule_cron = schedule_cron;
+        task.registered_at = Clock::get()?.unix_timestamp;
+        task.bump = ctx.bumps.scheduler_task;
+
+        Ok(())
+    }
+
+    /// Revoke the vault's liveness scheduler and close its task account.
+    pub fn unregister_liveness_scheduler(ctx: Context<UnregisterLivenessScheduler>) -> Result<()> {
+        authorize(&ctx.accounts.testator, Role::Testator, &ctx.accounts.vault)?;
+        let vault = &mut ctx.accounts.vault;
+        require!(vault.scheduler_pubkey.is_some(), ErrorCode::SchedulerNotRegistered);
+        vault.scheduler_pubkey = None;
+        vault.schedule_cron = [0u8; SchedulerTask::MAX_CRON_LEN];
+        Ok(())
+    }
+
+    /// Recompute the vault's content hash and compare it with `expected_hash`, e.g. one
+    /// kept in an auditor's archive. Read-only.
+    pub fn verify_content_hash(ctx: Context<VerifyContentHash>, expected_hash: [u8; 32]) -> Result<()> {
+        let vault = &ctx.accounts.vault;
+        let computed_hash = vault.compute_content_hash();
+
+        emit!(ContentHashVerified {
+            vault: vault.key(),
+            matches: constant_time_eq_32(&computed_hash, &expected_hash),
+            stored_hash: vault.content_hash,
+            computed_hash,
+        });
+
+        Ok(())
+    }
+
+    /// Move an NFT (supply 1, 0 decimals) from the testator's associated token account
+    /// into a vault-owned token account and register it for inheritance.
+    pub fn deposit_nft(ctx: Context<DepositNft>, mint: Pubkey) -> Result<()> {
+        authorize(&ctx.accounts.testator, Role::Testator, &ctx.accounts.vault)?;
+        let nft_mint = &ctx.accounts.nft_mint;
+        require!(nft_mint.supply == 1 && nft_mint.decimals == 0, ErrorCode::NotNonFungible);
+
+        let vault = &mut ctx.accounts.vault;
+        vault.assert_not_executed()?;
+        if !vault.registered_nfts.contains(&mint) {
+            require!(
+                vault.registered_nfts.len() < Vault::MAX_REGISTERED_NFTS,
+                ErrorCode::NftRegistrationFull
+            );
+            vault.registered_nfts.push(mint);
+        }
+
+        anchor_spl::token::transfer(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                anchor_spl::token::Transfer {
+                    from: ctx.accounts.testator_token_account.to_account_info(),
+                    to: ctx.accounts.vault_token_account.to_account_info(),
+                    authority: ctx.accounts.testator.to_account_info(),
+                },
+            ),
+            1,
+        )?;
+
+        emit!(NftDeposited {
+            vault: ctx.accounts.vault.key(),
+            mint,
+            depositor: ctx.accounts.testator.key(),
+        });
+
+        Ok(())
+    }
+
+    /// Return a registered NFT to the testator and close the vault's token account.
+    pub fn withdraw_nft(ctx: Context<WithdrawNft>, mint: Pubkey) -> Result<()> {
+        authorize(&ctx.accounts.testator, Role::Testator, &ctx.accounts.vault)?;
+        let vault = &mut ctx.accounts.vault;
+        vault.assert_not_executed()?;
+        let position = vault
+            .registered_nfts
+            .iter()
+            .position(|registered| registered == &mint)
+            .ok_or(ErrorCode::NftNotRegistered)?;
+        vault.registered_nfts.remove(position);
+
+        let signer_seeds: &[&[u8]] = &[
+            b"vault",
+            vault.seed_testator.as_ref(),
+            vault.seed_beneficiary.as_ref(),
+            &[vault.vault_nonce],
+            &[vault.bump],
+        ];
+        anchor_spl::token::transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                anchor_spl::token::Transfer {
+                    from: ctx.accounts.vault_token_account.to_account_info(),
+                    to: ctx.accounts.testator_token_account.to_account_info(),
+                    authority: vault.to_account_info(),
+                },
+                &[signer_seeds],
+            ),
+            1,
+        )?;
+        anchor_spl::token::close_account(CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            anchor_spl::token::CloseAccount {
+                account: ctx.accounts.vault_token_account.to_account_info(),
+                destination: ctx.accounts.testator.to_account_info(),
+                authority: vault.to_account_info(),
+            },
+            &[signer_seeds],
+        ))?;
+
+        emit!(NftWithdrawn {
+            vault: vault.key(),
+            mint,
+            withdrawer: ctx.accounts.testator.key(),
+        });
+
+        Ok(())
+    }
+
+    /// Recompute the testator's activity score (0-100) and store it on the vault.
+    /// Verifiers apply more scrutiny before signing an execution for a low score.
+    /// Callable by anyone.
+    pub fn compute_activity_score(ctx: Context<ComputeActivityScore>) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let vault = &mut ctx.accounts.vault;
+        let components = vault.activity_score_components(now);
+        let score = components.total();
+
+        vault.last_activity_score = score;
+        vault.last_scored_at = now;
+
+        emit!(ActivityScoreComputed {
+            vault: vault.key(),
+            testator: vault.testator,
+            score,
+            components,
+        });
+
+        Ok(())
+    }
+
+    /// Let the vault auto-cancel `expiry_secs` after creation (0 disables expiry).
+    /// Assets go to `refund_destination`, or to the testator if `None`.
+    pub fn set_vault_expiry(
+        ctx: Context<TestatorUpdateVault>,
+        expiry_secs: i64,
+        refund_destination: Option<Pubkey>,
+    ) -> Result<()> {
+        authorize(&ctx.accounts.testator, Role::Testator, &ctx.accounts.vault)?;
+        require!(expiry_secs >= 0, ErrorCode::InvalidTimeoutConfiguration);
+        let vault = &mut ctx.accounts.vault;
+        vault.assert_not_executed()?;
+        vault.vault_expiry_secs = expiry_secs;
+        vault.expiry_refund_destination = refund_destination;
+        Ok(())
+    }
+
+    /// Close a vault whose expiry has passed, returning every asset to the refund
+    /// destination. Callable by anyone. As remaining accounts, pass for each registered
+    /// NFT, in order, the vault token account and the destination's token account; then
+    /// for each token deposit, in order, the mint, the vault's associated token account,
+    /// the destination's token account and the mint's token program; then the transfer
+    /// hook accounts of every hooked mint. pNFTs and cNFTs must be withdrawn by the
+    /// testator beforehand. Fails once the vault is claimable by its beneficiary.
+    pub fn close_expired_vault<'info>(
+        ctx: Context<'_, '_, 'info, 'info, CloseExpiredVault<'info>>,
+    ) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let vault = &ctx.accounts.vault;
+        vault.assert_not_executed()?;
+        require!(
+            vault.vault_expiry_secs > 0
+                && now >= vault.created_at.saturating_add(vault.vault_expiry_secs),
+            ErrorCode::VaultNotExpired
+        );
+        // Once the beneficiary may execute, expiry must not take the estate from them
+        require!(!vault.get_state(now).is_claimable(), ErrorCode::VaultAlreadyClaimable);
+        let nft_accounts = 2 * vault.registered_nfts.len();
+        let token_accounts = 4 * vault.token_deposits.len();
+        require!(
+            ctx.remaining_accounts.len() >= nft_accounts + token_accounts
+                && vault.registered_pnfts.is_empty()
+                && vault.registered_cnfts.is_empty(),
+            ErrorCode::AssetsStillPresentInVault
+        );
+        let (nft_remaining, rest) = ctx.remaining_accounts.split_at(nft_accounts);
+        let (token_remaining, hook_accounts) = rest.split_at(token_accounts);
+
+        let vault_key = vault.key();
+        let refund_destination = ctx.accounts.refund_destination.key();
+        let signer_seeds: &[&[u8]] = &[
+            b"vault",
+            vault.seed_testator.as_ref(),
+            vault.seed_beneficiary.as_ref(),
+            &[vault.vault_nonce],
+            &[vault.bump],
+        ];
+
+        for (mint, accounts) in vault.registered_nfts.iter().zip(nft_remaining.chunks(2)) {
+            let (vault_token_account, destination) = (&accounts[0], &accounts[1]);
+            let (expected, _) = Pubkey::find_program_address(
+                &[b"vault_token", vault_key.as_ref(), mint.as_ref()],
+                &crate::ID,
+            );
+            vault_token_account.assert_key_matches(&expected, ErrorCode::AssetsStillPresentInVault)?;
+            let destination_account = anchor_spl::token::TokenAccount::try_deserialize(
+                &mut &destination.try_borrow_data()?[..],
+            )?;
+            require!(
+                destination_account.mint == *mint && destination_account.owner == refund_destination,
+                ErrorCode::Unauthorized
+            );
+
+            anchor_spl::token::transfer(
+                CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    anchor_spl::token::Transfer {
+                        from: vault_token_account.clone(),
+                        to: destination.clone(),
+                        authority: vault.to_account_info(),
+                    },
+                    &[signer_seeds],
+                ),
+                1,
+            )?;
+            anchor_spl::token::close_account(CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                anchor_spl::token::CloseAccount {
+                    account: vault_token_account.clone(),
+                    destination: ctx.accounts.refund_destination.to_account_info(),
+                    authority: vault.to_account_info(),
+                },
+                &[signer_seeds],
+            ))?;
+        }
+
+        let refund_destination_info = ctx.accounts.refund_destination.to_account_info();
+        for (deposit, accounts) in vault.token_deposits.iter().zip(token_remaining.chunks(4)) {
+            let mint = InterfaceAccount::<anchor_spl::token_interface::Mint>::try_from(&accounts[0])?;
+            let vault_token_account =
+                InterfaceAccount::<anchor_spl::token_interface::TokenAccount>::try_from(&accounts[1])?;
+            let destination =
+                InterfaceAccount::<anchor_spl::token_interface::TokenAccount>::try_from(&accounts[2])?;
+            let token_program = Interface::<anchor_spl::token_interface::TokenInterface>::try_from(&accounts[3])?;
+            require_keys_eq!(mint.key(), deposit.mint, ErrorCode::AssetsStillPresentInVault);
+            accounts[1].assert_key_matches(
+                &anchor_spl::associated_token::get_associated_token_address_with_program_id(
+                    &vault_key,
+                    &deposit.mint,
+                    &token_program.key(),
+                ),
+                ErrorCode::AssetsStillPresentInVault,
+            )?;
+            require!(
+                destination.mint == deposit.mint && destination.owner == refund_destination,
+                ErrorCode::Unauthorized
+            );
+
+            transfer_and_close_vault_tokens(
+                vault,
+                &mint,
+                &vault_token_account,
+                &destination,
+                &refund_destination_info,
+                &token_program,
+                hook_accounts,
+            )?;
+        }
+
+        for sub_account in [
+            &ctx.accounts.time_capsule,
+            &ctx.accounts.state_history,
+            &ctx.accounts.emergency_contacts,
+            &ctx.accounts.used_nonces,
+            &ctx.accounts.tax_escrow,
+        ] {
+            close_if_created(sub_account, &refund_destination_info)?;
+        }
+
+        if let Some(config) = ctx.accounts.config.as_mut() {
+            config.total_vaults = config.total_vaults.saturating_sub(1);
+        }
+        if let Some(registry) = ctx.accounts.vault_registry.as_mut() {
+            registry.count = registry.count.saturating_sub(1);
+        }
+
+        // The vault itself is closed to the refund destination on exit
+        emit!(VaultExpiredAndClosed {
+            vault: vault_key,
+            testator: vault.testator,
+            assets_returned: u8::try_from(vault.registered_nfts.len() + vault.token_deposits.len())
+                .map_err(|_| ErrorCode::ArithmeticOverflow)?,
+            sol_returned: vault.to_account_info().lamports(),
+            closed_at: now,
+        });
+
+        Ok(())
+    }
+
+    /// Create the protocol's nullifier registry. Only callable by the protocol authority.
+    /// It starts with room for `NullifierRegistry::INITIAL_CAPACITY` entries; see
+    /// `grow_nullifier_registry`.
+    pub fn init_nullifier_registry(ctx: Context<InitNullifierRegistry>) -> Result<()> {
+        let registry = &mut ctx.accounts.nullifier_registry;
+        registry.entries = Vec::new();
+        registry.bump = ctx.bumps.nullifier_registry;
+        Ok(())
+    }
+
+    /// Grow the nullifier registry by up to 10 KiB, towards room for
+    /// `NullifierRegistry::MAX_ENTRIES`. Permissionless; the payer funds the rent.
+    pub fn grow_nullifier_registry(ctx: Context<GrowNullifierRegistry>) -> Result<()> {
+        let registry_info = ctx.accounts.nullifier_registry.to_account_info();
+        let new_len = (registry_info.data_len() + anchor_lang::solana_program::entrypoint::MAX_PERMITTED_DATA_INCREASE)
+            .min(8 + NullifierRegistry::space(NullifierRegistry::MAX_ENTRIES));
+        let top_up = minimum_rent_exempt_balance(new_len)?.saturating_sub(registry_info.lamports());
+        if top_up > 0 {
+            anchor_lang::system_program::transfer(
+                CpiContext::new(
+                    ctx.accounts.system_program.to_account_info(),
+                    anchor_lang::system_program::Transfer {
+                        from: ctx.accounts.payer.to_account_info(),
+                        to: registry_info.clone(),
+                    },
+                ),
+                top_up,
+            )?;
+        }
+        registry_info.resize(new_len)?;
+        Ok(())
+    }
+
+    /// Drop nullifiers older than `NullifierRegistry::RETENTION_SECS`. Permissionless.
+    pub fn prune_nullifier_registry(ctx: Context<PruneNullifierRegistry>) -> Result<()> {
+        let cutoff = Clock::get()?.unix_timestamp - NullifierRegistry::RETENTION_SECS;
+        ctx.accounts
+            .nullifier_registry
+            .entries
+            .retain(|entry| entry.used_at >= cutoff);
+        Ok(())
+    }
+
+    /// Turn the extra nullifier check on compressed liveness proofs on or off. Only
+    /// callable by the protocol authority.
+    pub fn set_extra_nullifier_check(ctx: Context<UpdateProtocolConfig>, enabled: bool) -> Result<()> {
+        ctx.accounts.config.extra_nullifier_check = enabled;
+        Ok(())
+    }
+
+    /// Register (or replace) the oracle allowed to value the testator's estate.
+    pub fn register_valuation_oracle(ctx: Context<RegisterValuationOracle>, oracle: Pubkey) -> Result<()> {
+        let valuation = &mut ctx.accounts.valuation;
+        valuation.testator = ctx.accounts.testator.key();
+        valuation.oracle = oracle;
+        valuation.bump = ctx.bumps.valuation;
+        Ok(())
+    }
+
+    /// Record a consolidated valuation of the testator's estate. Only callable by the
+    /// registered oracle. Pass every vault the testator's `VaultRegistry` counts as
+    /// remaining accounts, executed ones included; the deposited SOL of the unexecuted
+    /// ones is summed on-chain, while `valuations` prices the tokens and NFTs (include
+    /// the native mint to count SOL in the USD total).
+    pub fn submit_estate_valuation<'info>(
+        ctx: Context<'_, '_, 'info, 'info, SubmitEstateValuation<'info>>,
+        valuations: Vec<TokenValuation>,
+    ) -> Result<()> {
+        authorize_holder(
+            &ctx.accounts.oracle,
+            Role::ValuationOracle,
+            Some(ctx.accounts.valuation.oracle),
+        )?;
+        require!(
+            valuations.len() <= ValuationOracle::MAX_TOKEN_VALUATIONS,
+            ErrorCode::TooManyTokenValuations
+        );
+
+        let testator = ctx.accounts.valuation.testator;
+        let mut counted: Vec<Pubkey> = Vec::with_capacity(ctx.remaining_accounts.len());
+        let mut valued_vaults: u16 = 0;
+        let mut total_sol_lamports: u64 = 0;
+        for account_info in ctx.remaining_accounts.iter() {
+            require!(!counted.contains(account_info.key), ErrorCode::EstateVaultMismatch);
+            let vault = Account::<Vault>::try_from(account_info)?;
+            require_keys_eq!(vault.testator, testator, ErrorCode::EstateVaultMismatch);
+            counted.push(*account_info.key);
+            if vault.executed {
+                continue;
+            }
+            total_sol_lamports = total_sol_lamports
+                .checked_add(vault.lamports)
+                .ok_or(ErrorCode::ArithmeticOverflow)?;
+            valued_vaults += 1;
+        }
+        // A vault left out would understate the estate
+        require!(
+            counted.len() == usize::from(ctx.accounts.vault_registry.count),
+            ErrorCode::EstateVaultCountMismatch
+        );
+
+        let total_usd_value_e6 = valuations
+            .iter()
+            .try_fold(0u64, |total, v| total.checked_add(v.usd_value_e6))
+            .ok_or(ErrorCode::ArithmeticOverflow)?;
+
+        let now = Clock::get()?.unix_timestamp;
+        let valuation = &mut ctx.accounts.valuation;
+        valuation.total_sol_lamports = total_sol_lamports;
+        valuation.token_valuations = valuations;
+        valuation.total_usd_value_e6 = total_usd_value_e6;
+        valuation.vault_count = valued_vaults;
+        valuation.last_valued_at = now;
+
+        emit!(EstateValuationUpdated {
+            testator,
+            total_usd_value_e6,
+            vault_count: valuation.vault_count,
+            last_valued_at: now,
+        });
+
+        Ok(())
+    }
+
+    /// View: emits the testator's latest estate valuation as `EstateValuationRead`.
+    pub fn read_estate_valuation(ctx: Context<ReadEstateValuation>) -> Result<()> {
+        let valuation = &ctx.accounts.valuation;
+        emit!(EstateValuationRead {
+            testator: valuation.testator,
+            oracle: valuation.oracle,
+            total_sol_lamports: valuation.total_sol_lamports,
+            token_valuations: valuation.token_valuations.clone(),
+            total_usd_value_e6: valuation.total_usd_value_e6,
+            vault_count: valuation.vault_count,
+            last_valued_at: valuation.last_valued_at,
+        });
+        Ok(())
+    }
+
+    /// Set the secret question, an identity check for beneficiaries without biometric
+    /// hardware. Requires both the testator and the verifier. `salt` must be 32 random
+    /// bytes drawn by the client, and `answer_hash` `SHA256(SHA256(answer || salt))`.
+    pub fn set_secret_question(
+        ctx: Context<SetSecretQuestion>,
+        question_hash: [u8; 32],
+        answer_hash: [u8; 32],
+        salt: [u8; 32],
+    ) -> Result<()> {
+        authorize(&ctx.accounts.testator, Role::Testator, &ctx.accounts.vault)?;
+        let vault = &ctx.accounts.vault;
+        vault.assert_not_executed()?;
+        require!(!vault.secret_verified, ErrorCode::SecretAlreadyVerified);
+        require!(salt != [0u8; 32], ErrorCode::SecretSaltInvalid);
+
+        let record = &mut ctx.accounts.secret_question;
+        record.vault = vault.key();
+        record.question_hash = question_hash;
+        record.answer_hash = answer_hash;
+        record.salt = salt;
+        record.bump = ctx.bumps.secret_question;
+        Ok(())
+    }
+
+    /// Answer the vault's secret question. On a match the vault is marked
+    /// `secret_verified`.
+    pub fn verify_secret_answer(ctx: Context<VerifySecretAnswer>, answer: Vec<u8>) -> Result<()> {
+        authorize(&ctx.accounts.beneficiary, Role::Beneficiary, &ctx.accounts.vault)?;
+        let record = ctx.accounts.secret_question.as_ref().ok_or(ErrorCode::SecretNotSet)?;
+        let vault = &mut ctx.accounts.vault;
+        vault.assert_not_executed()?;
+        require!(!vault.secret_verified, ErrorCode::SecretAlreadyVerified);
+        require!(
+            !answer.is_empty() && answer.len() <= SecretQuestionRecord::MAX_ANSWER_LEN,
+            ErrorCode::SecretAnswerIncorrect
+        );
+
+        let inner = real_hash(&[answer.as_slice(), record.salt.as_slice()].concat());
+        require!(
+            constant_time_eq_32(&real_hash(&inner), &record.answer_hash),
+            ErrorCode::SecretAnswerIncorrect
+        );
+
+        let now = Clock::get()?.unix_timestamp;
+        vault.secret_verified = true;
+
+        emit!(SecretAnswerVerified {
+            vault: vault.key(),
+            beneficiary: ctx.accounts.beneficiary.key(),
+            verified_at: now,
+        });
+
+        Ok(())
+    }
+
+    /// Set the fee charged by `init_inheritance` (0 disables it). Only callable by the
+    /// protocol authority.
+    pub fn set_vault_creation_fee(ctx: Context<UpdateProtocolConfig>, fee_lamports: u64) -> Result<()> {
+        ctx.accounts.config.vault_creation_fee_lamports = fee_lamports;
+        Ok(())
+    }
+
+    /// Waive the vault creation fee for `testator`'s next vaults (up to
+    /// `FeeWaiverType::vaults_covered`) for `duration_secs` (0 = no expiry). Granting
+    /// again replaces the previous waiver. Only callable by the protocol authority.
+    pub fn grant_fee_waiver(
+        ctx: Context<GrantFeeWaiver>,
+        testator: Pubkey,
+        waiver_type: FeeWaiverType,
+        duration_secs: i64,
+    ) -> Result<()> {
+        authorize_protocol(&ctx.accounts.authority, Role::ProtocolAuthority, &ctx.accounts.config)?;
+        require!(duration_secs >= 0, ErrorCode::InvalidTimeoutConfiguration);
+        let now = Clock::get()?.unix_timestamp;
+
+        let waiver = &mut ctx.accounts.fee_waiver;
+        waiver.testator = testator;
+        waiver.waiver_type = waiver_type;
+        waiver.granted_by = ctx.accounts.authority.key();
+        waiver.expires_at = (duration_secs > 0).then(|| now.saturating_add(duration_secs));
+        waiver.vaults_covered = waiver_type.vaults_covered();
+        waiver.waivers_used = 0;
+        waiver.bump = ctx.bumps.fee_waiver;
+        Ok(())
+    }
+
+    /// List the right to trigger execution of this vault for `price_lamports`, e.g. for a
+    /// professional executor service. Only while the vault is Active; listing again
+    /// updates the price of an unsold right.
+    pub fn list_execution_right(ctx: Context<ListExecutionRight>, price_lamports: u64) -> Result<()> {
+        authorize(&ctx.accounts.testator, Role::Testator, &ctx.accounts.vault)?;
+        let vault = &ctx.accounts.vault;
+        require!(
+            vault.get_state(Clock::get()?.unix_timestamp) == VaultState::Active,
+            ErrorCode::TransitionNotAllowed
+        );
+
+        let right = &mut ctx.accounts.execution_right;
+        require!(right.sold_to.is_none(), ErrorCode::ExecutionRightAlreadySold);
+        right.vault = vault.key();
+        right.owner = ctx.accounts.testator.key();
+        right.price_lamports = price_lamports;
+        right.listed = true;
+        right.sold_at = None;
+        right.bump = ctx.bumps.execution_right;
+
+        emit!(ExecutionRightListed {
+            vault: right.vault,
+            owner: right.owner,
+            price_lamports,
+        });
+
+        Ok(())
+    }
+
+    /// Buy a listed execution right. The price is added to the vault's funds, and the
+    /// buyer may then execute the vault with `execute_inheritance_as_executor`.
+    pub fn purchase_execution_right(ctx: Context<PurchaseExecutionRight>) -> Result<()> {
+        let right = &mut ctx.accounts.execution_right;
+        require!(right.sold_to.is_none(), ErrorCode::ExecutionRightAlreadySold);
+        require!(right.listed, ErrorCode::ExecutionRightNotListed);
+        ctx.accounts.vault.assert_not_executed()?;
+
+        let price = right.price_lamports;
+        anchor_lang::system_program::transfer(
+            CpiContext::new(
+                ctx.accounts.system_program.to_account_info(),
+                anchor_lang::system_program::Transfer {
+                    from: ctx.accounts.buyer.to_account_info(),
+                    to: ctx.accounts.vault.to_account_info(),
+                },
+            ),
+            price,
+        )?;
+
+        let now = Clock::get()?.unix_timestamp;
+        right.listed = false;
+        right.sold_at = Some(now);
+        right.sold_to = Some(ctx.accounts.buyer.key());
+
+        let vault = &mut ctx.accounts.vault;
+        vault.lamports = vault.lamports.checked_add(price).ok_or(ErrorCode::ArithmeticOverflow)?;
+        vault.refresh_content_hash();
+
+        emit!(ExecutionRightPurchased {
+            vault: vault.key(),
+            buyer: ctx.accounts.buyer.key(),
+            price_lamports: price,
+            sold_at: now,
+        });
+
+        Ok(())
+    }
+
+    /// `execute_inheritance` triggered by the holder of the vault's execution right
+    /// instead of the beneficiary. The verifier still co-signs, and the funds still go
+    /// to the beneficiary.
+    pub fn execute_inheritance_as_executor<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ExecuteInheritanceAsExecutor<'info>>,
+        transfer_funds: bool,
+        execution_nonce_provided: [u8; 8],
+    ) -> Result<()> {
+        authorize_holder(&ctx.accounts.executor, Role::Executor, ctx.accounts.execution_right.sold_to)?;
+        ctx.accounts.vault.assert_consistent()?;
+        let compute_units_start = compute_units_remaining();
+        verify_vault_discriminator(&ctx.accounts.vault.to_account_info(), ctx.program_id)?;
+
+        ctx.accounts.vault.verify_execution_parties(
+            ctx.accounts.beneficiary.key,
+            ctx.accounts.verifier.key,
+            Clock::get()?.unix_timestamp,
+        )?;
+
+        settle_inheritance(
+            SettlementAccounts {
+                vault: &mut ctx.accounts.vault,
+                beneficiary: &ctx.accounts.beneficiary.to_account_info(),
+                config: &ctx.accounts.config,
+                time_capsule: &mut ctx.accounts.time_capsule,
+                tax_escrow: &mut ctx.accounts.tax_escrow,
+                state_history: &mut ctx.accounts.state_history,
+                co_beneficiaries: ctx.remaining_accounts,
+            },
+            Settlement {
+                transfer_funds,
+                execution_nonce: execution_nonce_provided,
+                proof: ExecutionProof::None,
+                compute_units_start,
+            },
+        )
+    }
+
+    /// Anchor the hash of an off-chain legal document (will, power of attorney, ...) to
+    /// the vault. The notary co-signs, proving the document was notarized off-chain.
+    /// Committed documents are published with `InheritanceExecuted`.
+    pub fn commit_legal_document(
+        ctx: Context<CommitLegalDocument>,
+        document_hash: [u8; 32],
+        document_type: LegalDocumentType,
+        notary: Pubkey,
+    ) -> Result<()> {
+        authorize(&ctx.accounts.testator, Role::Testator, &ctx.accounts.vault)?;
+        require_keys_eq!(ctx.accounts.notary.key(), notary, ErrorCode::Unauthorized);
+        let vault = &mut ctx.accounts.vault;
+        vault.assert_not_executed()?;
+        require!(
+            vault.legal_documents.len() < Vault::MAX_LEGAL_DOCUMENTS,
+            ErrorCode::LegalDocumentSlotsFull
+        );
+
+        vault.legal_documents.push(LegalDocumentCommitment {
+            document_type,
+            hash: document_hash,
+            notary,
+            committed_at: Clock::get()?.unix_timestamp,
+        });
+
+        emit!(LegalDocumentCommitted {
+            vault: vault.key(),
+            document_type,
+            hash: document_hash,
+            notary,
+        });
+
+        Ok(())
+    }
+
+    /// Remove the legal document commitment at `index`.
+    pub fn remove_legal_document(ctx: Context<TestatorUpdateVault>, index: u8) -> Result<()> {
+        authorize(&ctx.accounts.testator, Role::Testator, &ctx.accounts.vault)?;
+        let vault = &mut ctx.accounts.vault;
+        vault.assert_not_executed()?;
+        require!(
+            (index as usize) < vault.legal_documents.len(),
+            ErrorCode::LegalDocumentNotFound
+        );
+        vault.legal_documents.remove(index as usize);
+        Ok(())
+    }
+
+    /// Issue a contact verification challenge for the beneficiary. Requires both the
+    /// testator and the verifier; the verifier draws a random 16-byte nonce off-chain,
+    /// delivers it to the beneficiary's email or phone, and commits to it here with
+    /// `challenge_hash = contact_challenge_hash(nonce, vault)`. Only the hash is stored,
+    /// so the nonce cannot be read from chain. The beneficiary proves receipt with
+    /// `verify_contact_challenge` within `ContactVerificationChallenge::VALIDITY_SECS`.
+    /// Issuing again replaces the previous challenge.
+    pub fn issue_contact_challenge(
+        ctx: Context<IssueContactChallenge>,
+        challenge_hash: [u8; 32],
+    ) -> Result<()> {
+        authorize(&ctx.accounts.testator, Role::Testator, &ctx.accounts.vault)?;
+        let vault = &ctx.accounts.vault;
+        vault.assert_not_executed()?;
+
+        let now = Clock::get()?.unix_timestamp;
+        let challenge = &mut ctx.accounts.contact_challenge;
+        challenge.vault = vault.key();
+        challenge.challenge_hash = challenge_hash;
+        challenge.created_at = now;
+        challenge.verified = false;
+        challenge.bump = ctx.bumps.contact_challenge;
+        Ok(())
+    }
+
+    /// Answer the contact verification challenge with the nonce received through the
+    /// beneficiary's contact channel. On a match the vault is marked `contact_verified`
+    /// and the challenge is consumed: answering it again fails until a new one is issued.
+    pub fn verify_contact_challenge(ctx: Context<VerifyContactChallenge>, nonce: [u8; 16]) -> Result<()> {
+        authorize(&ctx.accounts.beneficiary, Role::Beneficiary, &ctx.accounts.vault)?;
+        ctx.accounts.vault.assert_not_executed()?;
+        let now = Clock::get()?.unix_timestamp;
+        let challenge = &mut ctx.accounts.contact_challenge;
+        require!(!challenge.verified, ErrorCode::ContactChallengeAlreadyUsed);
+        require!(
+            now.saturating_sub(challenge.created_at) <= ContactVerificationChallenge::VALIDITY_SECS,
+            ErrorCode::ContactChallengeExpired
+        );
+        require!(
+            constant_time_eq_32(
+                &contact_challenge_hash(&nonce, &ctx.accounts.vault.key()),
+                &challenge.challenge_hash
+            ),
+            ErrorCode::ContactChallengeInvalid
+        );
+        challenge.verified = true;
+
+        let vault = &mut ctx.accounts.vault;
+        vault.contact_verified = true;
+
+        emit!(ContactVerified {
+            vault: vault.key(),
+            beneficiary: ctx.accounts.beneficiary.key(),
+            verified_at: now,
+        });
+
+        Ok(())
+    }
+
+    /// Read up to `MAX_BATCH_LIVENESS_READS` compressed liveness accounts (V1 or V2) in
+    /// one Light Protocol CPI, for monitoring services. Each claim carries the account's
+    /// current `last_ping` (and `ping_count` for V2) as the client read it from the
+    /// indexer, since the vault's own copy can lag behind. The first `claims.len()`
+    /// remaining accounts are the vaults, in the order of `claims`, followed by the Light
+    /// system accounts (address tree first). `proof_data` is a single validity proof
+    /// covering every compressed account in the batch.
+    ///
+    /// A claim whose vault, address or values do not check out emits
+    /// `CompressedLivenessReadFailed` and is skipped. A claim that passes those checks
+    /// but contradicts the proof still reverts the whole batch, as a failed CPI aborts
+    /// the transaction. Debug vaults skip the CPI and report the vault's `last_ping`.
+    pub fn batch_read_compressed_liveness<'info>(
+        ctx: Context<'_, '_, 'info, 'info, BatchReadCompressedLiveness<'info>>,
+        claims: Vec<CompressedLivenessClaim>,
+        proof_data: ValidityProofData,
+    ) -> Result<()> {
+        require!(
+            claims.len() <= MAX_BATCH_LIVENESS_READS,
+            ErrorCode::BatchReadSizeTooLarge
+        );
+        require!(
+            ctx.remaining_accounts.len() >= claims.len(),
+            anchor_lang::error::ErrorCode::AccountNotEnoughKeys
+        );
+        let now = Clock::get()?.unix_timestamp;
+        let (vault_infos, light_accounts) = ctx.remaining_accounts.split_at(claims.len());
+        let address_tree = light_accounts.first().map(|info| info.key());
+
+        let mut reads: Vec<(CompressedLivenessClaim, Account<'info, Vault>)> =
+            Vec::with_capacity(claims.len());
+        for (claim, vault_info) in claims.iter().zip(vault_infos) {
+            match check_compressed_liveness_read(vault_info, claim, address_tree.as_ref(), now) {
+                Ok(vault) => reads.push((claim.clone(), vault)),
+                Err(code) => emit!(CompressedLivenessReadFailed {
+                    address: claim.address,
+                    error_code: u32::from(code),
+                }),
+            }
+        }
+
+        if reads.iter().any(|(_, vault)| !vault.is_debug) {
+            let proof = LightValidityProof::try_from_slice(&proof_data.data)
+                .map_err(|_| ErrorCode::InvalidLightProof)?;
+            let light_cpi_accounts = CpiAccounts::new(
+                ctx.accounts.fee_payer.as_ref(),
+                light_accounts,
+                crate::LIGHT_CPI_SIGNER,
+            );
+
+            let mut cpi = LightSystemProgramCpi::new_cpi(crate::LIGHT_CPI_SIGNER, proof);
+            for (claim, vault) in reads.iter().filter(|(_, vault)| !vault.is_debug) {
+                cpi = match vault.compressed_liveness_version {
+                    CompressedLivenessVersion::V1 => {
+                        let mut liveness_account = LightAccount::<CompressedLiveness>::new_read_only(
+                            &crate::ID,
+                            Some(claim.address),
+                            0,
+                        );
+                        liveness_account.testator = vault.seed_testator;
+                        liveness_account.last_ping = claim.last_ping;
+                        liveness_account.vault_address = vault.key();
+                        cpi.with_light_account(liveness_account)
+                    }
+                    CompressedLivenessVersion::V2 => {
+                        let mut liveness_account = LightAccount::<CompressedLivenessV2>::new_read_only(
+                            &crate::ID,
+                            Some(claim.address),
+                            0,
+                        );
+                        liveness_account.testator = vault.seed_testator;
+                        liveness_account.last_ping = claim.last_ping;
+                        liveness_account.vault_address = vault.key();
+                        liveness_account.ping_count = claim.ping_count;
+                        liveness_account.last_guardian_ping = 0;
+                        liveness_account.compression_created_at = vault.compression_created_at;
+                        cpi.with_light_account(liveness_account)
+                    }
+                }
+                .map_err(|_| ErrorCode::InvalidLightProof)?;
+            }
+            cpi.invoke(light_cpi_accounts)
+                .map_err(|_| ErrorCode::InvalidLightProof)?;
+        }
+
+        let mut all_recent = !reads.is_empty();
+        for (claim, vault) in reads.iter() {
+            let last_ping = if vault.is_debug { vault.last_ping } else { claim.last_ping };
+            all_recent &= now.saturating_sub(last_ping) < vault.warning_timeout_secs;
+            emit!(CompressedLivenessRead {
+                testator: vault.testator,
+                vault: vault.key(),
+                last_ping,
+                address: claim.address,
+            });
+        }
+
+        emit!(BatchCompressedLivenessRead {
+            count: reads.len() as u8,
+            all_recent,
+        });
+
+        Ok(())
+    }
+
+    /// Same as `execute_inheritance`, first writing a legal statement through the SPL
+    /// Memo program: `INHERITANCE_EXEC:<vault>:<beneficiary>:<legal_memo>` (base58 keys).
+    /// The memo lands in the execution transaction itself, a permanent and
+    /// slot-timestamped record. Requires the `memo_program` account.
+    pub fn execute_inheritance_with_memo<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ExecuteInheritance<'info>>,
+        transfer_funds: bool,
+        legal_memo: String,
+        execution_nonce_provided: [u8; 8],
+    ) -> Result<()> {
+        authorize(&ctx.accounts.beneficiary, Role::Heir, &ctx.accounts.vault)?;
+        require!(legal_memo.len() <= MAX_LEGAL_MEMO_LEN, ErrorCode::MemoTooLong);
+        let memo_program = ctx
+            .accounts
+            .memo_program
+            .as_ref()
+            .ok_or(ErrorCode::MemoProgramRequired)?;
+
+        let vault = ctx.accounts.vault.key();
+        let memo = format!(
+            "INHERITANCE_EXEC:{}:{}:{}",
+            vault,
+            ctx.accounts.beneficiary.key(),
+            legal_memo
+        );
+        anchor_lang::solana_program::program::invoke(
+            &anchor_lang::solana_program::instruction::Instruction {
+                program_id: SPL_MEMO_PROGRAM_ID,
+                accounts: vec![],
+                data: memo.as_bytes().to_vec(),
+            },
+            &[memo_program.to_account_info()],
+        )?;
+
+        execute_inheritance(ctx, transfer_funds, execution_nonce_provided)?;
+
+        emit!(InheritanceExecutedWithMemo {
+            vault,
+            memo_hash: real_hash(memo.as_bytes()),
+        });
+
+        Ok(())
+    }
+
+    /// Replace an executed vault with a lightweight, permanent `VaultArchive` and close
+    /// the vault, returning its rent to the beneficiary. An unclaimed lottery prize,
+    /// tokens and NFTs must be claimed first.
+    pub fn archive_vault(ctx: Context<ArchiveVault>) -> Result<()> {
+        authorize(&ctx.accounts.beneficiary, Role::Beneficiary, &ctx.accounts.vault)?;
+        let vault = &ctx.accounts.vault;
+        require!(vault.executed, ErrorCode::VaultNotExecuted);
+        require!(
+            (!vault.lottery_mode || vault.lamports == 0)
+                && vault.token_deposits.is_empty()
+                && vault.registered_nfts.is_empty()
+                && vault.registered_pnfts.is_empty()
+                && vault.registered_cnfts.is_empty(),
+            ErrorCode::AssetsStillPresentInVault
+        );
+
+        let archive = &mut ctx.accounts.archive;
+        archive.testator = vault.testator;
+        archive.beneficiary = vault.beneficiary;
+        archive.executed_at = vault.executed_at;
+        archive.lamports_transferred = vault.lamports_transferred;
+        archive.cid = vault.cid;
+        archive.vault_id = vault.vault_id;
+        archive.content_hash = vault.content_hash;
+
+        if let Some(registry) = ctx.accounts.vault_registry.as_mut() {
+            registry.count = registry.count.saturating_sub(1);
+        }
+
+        // The vault is closed to the beneficiary on exit
+        emit!(VaultArchived {
+            vault: vault.key(),
+            archive_pubkey: archive.key(),
+            rent_returned: vault.to_account_info().lamports(),
+        });
+
+        Ok(())
+    }
+
+    /// Register co-beneficiaries, each receiving `allocation_bps` basis points of the
+    /// lamports paid out at execution (after tax). The primary beneficiary keeps the
+    /// remainder. Replaces any previous splits; an empty list removes them.
+    /// Not available on lottery vaults.
+    pub fn set_beneficiary_splits(
+        ctx: Context<TestatorUpdateVault>,
+        splits: Vec<BeneficiaryEntry>,
+    ) -> Result<()> {
+        authorize(&ctx.accounts.testator, Role::Testator, &ctx.accounts.vault)?;
+        let vault = &mut ctx.accounts.vault;
+        vault.assert_not_executed()?;
+        require!(
+            splits.is_empty() || !vault.lottery_mode,
+            ErrorCode::InvalidBeneficiarySplits
+        );
+        vault.validate_beneficiary_splits(&splits)?;
+        vault.co_beneficiaries = splits;
+        Ok(())
+    }
+
+
+    /// Move `amount` tokens from the testator into the vault's associated token
+    /// account, creating it on the first deposit of the mint. Works with SPL Token and
+    /// Token-2022 mints; for a mint with a transfer hook, pass the hook program, its
+    /// extra account metas PDA and the extra accounts as remaining accounts. The vault
+    /// records what it actually received, net of any transfer fee.
+    pub fn deposit_token<'info>(
+        ctx: Context<'_, '_, 'info, 'info, DepositToken<'info>>,
+        amount: u64,
+    ) -> Result<()> {
+        authorize(&ctx.accounts.testator, Role::Testator, &ctx.accounts.vault)?;
+        require!(amount > 0, ErrorCode::NoAssets);
+        ctx.accounts.vault.assert_not_executed()?;
+        let mint = ctx.accounts.mint.key();
+        require!(
+            ctx.accounts.vault.token_deposits.iter().any(|deposit| deposit.mint == mint)
+                || ctx.accounts.vault.token_deposits.len() < Vault::MAX_TOKEN_DEPOSITS,
+            ErrorCode::TokenDepositsFull
+        );
+
+        let balance_before = ctx.accounts.vault_token_account.amount;
+        anchor_spl::token_2022::spl_token_2022::onchain::invoke_transfer_checked(
+            ctx.accounts.token_program.key,
+            ctx.accounts.testator_token_account.to_account_info(),
+            ctx.accounts.mint.to_account_info(),
+            ctx.accounts.vault_token_account.to_account_info(),
+            ctx.accounts.testator.to_account_info(),
+            ctx.remaining_accounts,
+            amount,
+            ctx.accounts.mint.decimals,
+            &[],
+        )?;
+        ctx.accounts.vault_token_account.reload()?;
+        let received = ctx
+            .accounts
+            .vault_token_account
+            .amount
+            .checked_sub(balance_before)
+            .ok_or(ErrorCode::ArithmeticOverflow)?;
+
+        let vault = &mut ctx.accounts.vault;
+        let total = match vault.token_deposits.iter_mut().find(|deposit| deposit.mint == mint) {
+            Some(deposit) => {
+                deposit.amount = deposit.amount.checked_add(received).ok_or(ErrorCode::ArithmeticOverflow)?;
+                deposit.amount
+            }
+            None => {
+                vault.token_deposits.push(TokenDeposit { mint, amount: received });
+                received
+            }
+        };
+
+        emit!(TokenDeposited {
+            vault: vault.key(),
+            mint,
+            amount: received,
+            transfer_fee: amount.saturating_sub(received),
+            total,
+        });
+
+        Ok(())
+    }
+
+    /// Return all of a mint's tokens to the testator and close the vault's token account.
+    /// Transfer hook accounts are passed as in `deposit_token`.
+    pub fn withdraw_token<'info>(
+        ctx: Context<'_, '_, 'info, 'info, WithdrawToken<'info>>,
+    ) -> Result<()> {
+        authorize(&ctx.accounts.testator, Role::Testator, &ctx.accounts.vault)?;
+        let vault = &mut ctx.accounts.vault;
+        vault.assert_not_executed()?;
+        vault.remove_token_deposit(&ctx.accounts.mint.key())?;
+
+        let amount = ctx.accounts.vault_token_account.amount;
+        let transfer_fee = transfer_and_close_vault_tokens(
+            vault,
+            &ctx.accounts.mint,
+            &ctx.accounts.vault_token_account,
+            &ctx.accounts.testator_token_account,
+            &ctx.accounts.testator.to_account_info(),
+            &ctx.accounts.token_program,
+            ctx.remaining_accounts,
+        )?;
+
+        emit!(TokenWithdrawn {
+            vault: vault.key(),
+            mint: ctx.accounts.mint.key(),
+            amount,
+            transfer_fee,
+        });
+
+        Ok(())
+    }
+
+    /// Token-aware counterpart of `execute_inheritance`: once the vault is executed,
+    /// transfer all of one mint's tokens to the heir (the lottery winner once drawn,
+    /// otherwise the primary beneficiary) and close the vault's token account.
+    /// Call once per deposited mint; transfer hook accounts are passed as in
+    /// `deposit_token`. A Token-2022 transfer fee is withheld from what the heir receives.
+    pub fn execute_token_inheritance<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ExecuteTokenInheritance<'info>>,
+    ) -> Result<()> {
+        authorize(&ctx.accounts.beneficiary, Role::Heir, &ctx.accounts.vault)?;
+        let vault = &mut ctx.accounts.vault;
+        require!(vault.executed, ErrorCode::VaultNotExecuted);
+        vault.remove_token_deposit(&ctx.accounts.mint.key())?;
+
+        let amount = ctx.accounts.vault_token_account.amount;
+        let transfer_fee = transfer_and_close_vault_tokens(
+            vault,
+            &ctx.accounts.mint,
+            &ctx.accounts.vault_token_account,
+            &ctx.accounts.beneficiary_token_account,
+            &ctx.accounts.beneficiary.to_account_info(),
+            &ctx.accounts.token_program,
+            ctx.remaining_accounts,
+        )?;
+
+        emit!(TokenInheritanceExecuted {
+            vault: vault.key(),
+            beneficiary: ctx.accounts.beneficiary.key(),
+            mint: ctx.accounts.mint.key(),
+            amount,
+            transfer_fee,
+        });
+
+        Ok(())
+    }
+
+    /// NFT counterpart of `execute_token_inheritance`: once the vault is executed,
+    /// transfer one registered NFT to the heir and close the vault's token account.
+    /// Call once per registered NFT.
+    pub fn execute_nft_inheritance(ctx: Context<ExecuteNftInheritance>, mint: Pubkey) -> Result<()> {
+        authorize(&ctx.accounts.beneficiary, Role::Heir, &ctx.accounts.vault)?;
+        let vault = &mut ctx.accounts.vault;
+        require!(vault.executed, ErrorCode::VaultNotExecuted);
+        let position = vault
+            .registered_nfts
+            .iter()
+            .position(|registered| registered == &mint)
+            .ok_or(ErrorCode::NftNotRegistered)?;
+        vault.registered_nfts.remove(position);
+
+        let signer_seeds: &[&[u8]] = &[
+            b"vault",
+            vault.seed_testator.as_ref(),
+            vault.seed_beneficiary.as_ref(),
+            &[vault.vault_nonce],
+            &[vault.bump],
+        ];
+        anchor_spl::token::transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                anchor_spl::token::Transfer {
+                    from: ctx.accounts.vault_token_account.to_account_info(),
+                    to: ctx.accounts.beneficiary_token_account.to_account_info(),
+                    authority: vault.to_account_info(),
+                },
+                &[signer_seeds],
+            ),
+            1,
+        )?;
+        anchor_spl::token::close_account(CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            anchor_spl::token::CloseAccount {
+                account: ctx.accounts.vault_token_account.to_account_info(),
+                destination: ctx.accounts.beneficiary.to_account_info(),
+                authority: vault.to_account_info(),
+            },
+            &[signer_seeds],
+        ))?;
+
+        emit!(NftInherited {
+            vault: vault.key(),
+            mint,
+            beneficiary: ctx.accounts.beneficiary.key(),
+        });
+
+        Ok(())
+    }
+
+    /// Escrow a Metaplex programmable NFT in the vault's associated token account.
+    /// pNFTs stay frozen, so they move through Token Metadata's `TransferV1`, which
+    /// also migrates their token records and enforces any rule set.
+    #[cfg(feature = "pnft")]
+    pub fn deposit_pnft(ctx: Context<TransferVaultPnft>) -> Result<()> {
+        authorize(&ctx.accounts.signer, Role::Testator, &ctx.accounts.vault)?;
+        let mint = ctx.accounts.mint.key();
+        let vault = &mut ctx.accounts.vault;
+        vault.assert_not_executed()?;
+        if !vault.registered_pnfts.contains(&mint) {
+            require!(
+                vault.registered_pnfts.len() < Vault::MAX_REGISTERED_PNFTS,
+                ErrorCode::NftRegistrationFull
+            );
+            vault.registered_pnfts.push(mint);
+        }
+
+        transfer_vault_pnft(ctx.accounts, true)?;
+
+        emit!(NftDeposited {
+            vault: ctx.accounts.vault.key(),
+            mint,
+            depositor: ctx.accounts.signer.key(),
+        });
+
+        Ok(())
+    }
+
+    /// Return an escrowed pNFT to the testator.
+    #[cfg(feature = "pnft")]
+    pub fn withdraw_pnft(ctx: Context<TransferVaultPnft>) -> Result<()> {
+        authorize(&ctx.accounts.signer, Role::Testator, &ctx.accounts.vault)?;
+        let mint = ctx.accounts.mint.key();
+        let vault = &mut ctx.accounts.vault;
+        vault.assert_not_executed()?;
+        vault.remove_registered_pnft(&mint)?;
+
+        transfer_vault_pnft(ctx.accounts, false)?;
+
+        emit!(NftWithdrawn {
+            vault: ctx.accounts.vault.key(),
+            mint,
+            withdrawer: ctx.accounts.signer.key(),
+        });
+
+        Ok(())
+    }
+
+    /// Transfer an escrowed pNFT to the heir of an executed vault. Call once per pNFT.
+    #[cfg(feature = "pnft")]
+    pub fn execute_pnft_inheritance(ctx: Context<TransferVaultPnft>) -> Result<()> {
+        authorize(&ctx.accounts.signer, Role::Heir, &ctx.accounts.vault)?;
+        let mint = ctx.accounts.mint.key();
+        let vault = &mut ctx.accounts.vault;
+        require!(vault.executed, ErrorCode::VaultNotExecuted);
+        vault.remove_registered_pnft(&mint)?;
+
+        transfer_vault_pnft(ctx.accounts, false)?;
+
+        emit!(NftInherited {
+            vault: ctx.accounts.vault.key(),
+            mint,
+            beneficiary: ctx.accounts.signer.key(),
+        });
+
+        Ok(())
+    }
+
+    /// Escrow a Bubblegum compressed NFT by transferring the leaf to the vault PDA.
+    /// `leaf` describes the cNFT as indexed by the DAS API; pass the Merkle proof path
+    /// (minus the canopy) as remaining accounts.
+    pub fn deposit_cnft<'info>(
+        ctx: Context<'_, '_, 'info, 'info, TransferVaultCnft<'info>>,
+        leaf: CnftLeaf,
+    ) -> Result<()> {
+        authorize(&ctx.accounts.signer, Role::Testator, &ctx.accounts.vault)?;
+        let entry = RegisteredCnft { merkle_tree: ctx.accounts.merkle_tree.key(), nonce: leaf.nonce };
+        let vault = &mut ctx.accounts.vault;
+        vault.assert_not_executed()?;
+        require!(
+            vault.registered_cnfts.len() < Vault::MAX_REGISTERED_CNFTS,
+            ErrorCode::NftRegistrationFull
+        );
+        vault.registered_cnfts.push(entry);
+
+        transfer_vault_cnft(ctx.accounts, ctx.remaining_accounts, &leaf, true)
+    }
+
+    /// Return an escrowed compressed NFT to the testator.
+    pub fn withdraw_cnft<'info>(
+        ctx: Context<'_, '_, 'info, 'info, TransferVaultCnft<'info>>,
+        leaf: CnftLeaf,
+    ) -> Result<()> {
+        authorize(&ctx.accounts.signer, Role::Testator, &ctx.accounts.vault)?;
+        let merkle_tree = ctx.accounts.merkle_tree.key();
+        let vault = &mut ctx.accounts.vault;
+        vault.assert_not_executed()?;
+        vault.remove_registered_cnft(&merkle_tree, leaf.nonce)?;
+
+        transfer_vault_cnft(ctx.accounts, ctx.remaining_accounts, &leaf, false)
+    }
+
+    /// Transfer an escrowed compressed NFT to the heir of an executed vault. Call once
+    /// per cNFT.
+    pub fn execute_cnft_inheritance<'info>(
+        ctx: Context<'_, '_, 'info, 'info, TransferVaultCnft<'info>>,
+        leaf: CnftLeaf,
+    ) -> Result<()> {
+        authorize(&ctx.accounts.signer, Role::Heir, &ctx.accounts.vault)?;
+        let merkle_tree = ctx.accounts.merkle_tree.key();
+        let vault = &mut ctx.accounts.vault;
+        require!(vault.executed, ErrorCode::VaultNotExecuted);
+        vault.remove_registered_cnft(&merkle_tree, leaf.nonce)?;
+
+        transfer_vault_cnft(ctx.accounts, ctx.remaining_accounts, &leaf, false)
+    }
+
+    /// Change the vault's timeouts; `None` keeps the current value. The new timeouts
+    /// must pass `Vault::validate_timeout_params`, and the vault may be neither executed
+    /// nor claimable, before or after the change. The verifier only changes through the
+    /// `rotate_verifier` timelock.
+    pub fn update_vault_config(
+        ctx: Context<TestatorUpdateVault>,
+        warning_timeout_secs: Option<i64>,
+        timeout_secs: Option<i64>,
+    ) -> Result<()> {
+        authorize(&ctx.accounts.testator, Role::Testator, &ctx.accounts.vault)?;
+        let now = Clock::get()?.unix_timestamp;
+        let vault = &mut ctx.accounts.vault;
+        vault.assert_not_executed()?;
+        require!(
+            !vault.get_state(now).is_claimable(),
+            ErrorCode::TransitionNotAllowed
+        );
+
+        let warning_timeout_secs = warning_timeout_secs.unwrap_or(vault.warning_timeout_secs);
+        let timeout_secs = timeout_secs.unwrap_or(vault.timeout_secs);
+        Vault::validate_timeout_params(
+            warning_timeout_secs,
+            timeout_secs,
+            vault.min_ping_interval_secs,
+            vault.grace_period_secs,
+            vault.execution_delay_secs,
+        )?;
+
+        vault.warning_timeout_secs = warning_timeout_secs;
+        vault.timeout_secs = timeout_secs;
+        // Shortening the timeout must not make the vault claimable on the spot
+        require!(
+            !vault.get_state(now).is_claimable(),
+            ErrorCode::TransitionNotAllowed
+        );
+
+        emit!(VaultConfigUpdated {
+            vault: vault.key(),
+            warning_timeout_secs,
+            timeout_secs,
+        });
+
+        Ok(())
+    }
+
+    /// First phase of a beneficiary change: record the new beneficiary and their
+    /// lookup hashes and encrypted password. Nothing changes on the vault until the
+    /// new beneficiary accepts with `accept_beneficiary_change`; a new proposal
+    /// replaces a pending one.
+    pub fn propose_beneficiary_change(
+        ctx: Context<ProposeBeneficiaryChange>,
+        new_beneficiary: Pubkey,
+        beneficiary_identity_hash: [u8; 32],
+        beneficiary_email_hash: [u8; 32],
+        beneficiary_document_id_hash: [u8; 32],
+        encrypted_password: Vec<u8>,
+    ) -> Result<()> {
+        authorize(&ctx.accounts.testator, Role::Testator, &ctx.accounts.vault)?;
+        require!(!encrypted_password.is_empty(), ErrorCode::EmptyEncryptedPassword);
+        require!(
+            encrypted_password.len() <= Vault::MAX_ENCRYPTED_PASSWORD_SIZE,
+            ErrorCode::EncryptedPasswordTooLarge
+        );
+        let vault = &ctx.accounts.vault;
+        vault.assert_not_executed()?;
+        require!(
+            !vault.get_state(Clock::get()?.unix_timestamp).is_claimable(),
+            ErrorCode::TransitionNotAllowed
+        );
+        require!(
+            new_beneficiary != vault.beneficiary
+                && new_beneficiary != vault.testator
+                && vault.co_beneficiaries.iter().all(|entry| entry.beneficiary != new_beneficiary),
+            ErrorCode::InvalidNewBeneficiary
+        );
+
+        let proposal = &mut ctx.accounts.proposal;
+        proposal.vault = vault.key();
+        proposal.new_beneficiary = new_beneficiary;
+        proposal.beneficiary_identity_hash = beneficiary_identity_hash;
+        proposal.beneficiary_email_hash = beneficiary_email_hash;
+        proposal.beneficiary_document_id_hash = beneficiary_document_id_hash;
+        proposal.encrypted_password = encrypted_password;
+        proposal.proposed_at = Clock::get()?.unix_timestamp;
+        proposal.bump = ctx.bumps.proposal;
+
+        emit!(BeneficiaryChangeProposed {
+            vault: vault.key(),
+            current_beneficiary: vault.beneficiary,
+            proposed_beneficiary: new_beneficiary,
+        });
+
+        Ok(())
+    }
+
+    /// Second phase: the proposed beneficiary signs to take over. The vault's lookup
+    /// data and encrypted password are replaced together, and everything the old
+    /// beneficiary had verified or been given is reset. The proposal is closed to the
+    /// testator.
+    pub fn accept_beneficiary_change(ctx: Context<AcceptBeneficiaryChange>) -> Result<()> {
+        authorize_holder(
+            &ctx.accounts.new_beneficiary,
+            Role::Nominee,
+            Some(ctx.accounts.proposal.new_beneficiary),
+        )?;
+        let proposal = &ctx.accounts.proposal;
+        let vault = &mut ctx.accounts.vault;
+        vault.assert_not_executed()?;
+        require!(
+            !vault.get_state(Clock::get()?.unix_timestamp).is_claimable(),
+            ErrorCode::TransitionNotAllowed
+        );
+
+        let old_beneficiary = vault.beneficiary;
+        vault.beneficiary = proposal.new_beneficiary;
+        vault.beneficiary_identity_hash = proposal.beneficiary_identity_hash;
+        vault.beneficiary_email_hash = proposal.beneficiary_email_hash;
+        vault.beneficiary_document_id_hash = proposal.beneficiary_document_id_hash;
+        vault.encrypted_password = proposal.encrypted_password.clone();
+        vault.beneficiary_instructions = Vec::new();
+        vault.beneficiary_verified_at = None;
+        vault.full_identity_verified = false;
+        vault.last_identity_verified_at = 0;
+        vault.has_been_verified = false;
+        vault.secret_verified = false;
+        vault.contact_verified = false;
+        vault.refresh_content_hash();
+
+        emit!(BeneficiaryChanged {
+            vault: vault.key(),
+            old_beneficiary,
+            new_beneficiary: vault.beneficiary,
+        });
+
+        Ok(())
+    }
+
+    /// Top up an existing vault with `amount` lamports from any payer.
+    pub fn deposit_sol(ctx: Context<DepositSol>, amount: u64) -> Result<()> {
+        require!(amount > 0, ErrorCode::NoAssets);
+        ctx.accounts.vault.assert_not_executed()?;
+
+        anchor_lang::system_program::transfer(
+            CpiContext::new(
+                ctx.accounts.system_program.to_account_info(),
+                anchor_lang::system_program::Transfer {
+                    from: ctx.accounts.payer.to_account_info(),
+                    to: ctx.accounts.vault.to_account_info(),
+                },
+            ),
+            amount,
+        )?;
+
+        let vault = &mut ctx.accounts.vault;
+        vault.lamports = vault
+            .lamports
+            .checked_add(amount)
+            .ok_or(ErrorCode::ArithmeticOverflow)?;
+        vault.refresh_content_hash();
+
+        emit!(VaultFunded {
+            vault: vault.key(),
+            payer: ctx.accounts.payer.key(),
+            amount,
+            new_vault_balance: vault.lamports,
+            content_hash: vault.content_hash,
+        });
+
+        Ok(())
+    }
+
+    /// Withdraw `amount` lamports back to the testator while the vault is Active. The
+    /// destruction bond is not part of `vault.lamports` and stays in the account.
+    pub fn withdraw_sol(ctx: Context<WithdrawSol>, amount: u64) -> Result<()> {
+        authorize(&ctx.accounts.testator, Role::Testator, &ctx.accounts.vault)?;
+        let vault = &mut ctx.accounts.vault;
+        require!(
+            vault.get_state(Clock::get()?.unix_timestamp) == VaultState::Active,
+            ErrorCode::TransitionNotAllowed
+        );
+        require!(amount > 0, ErrorCode::NoAssets);
+        require!(amount <= vault.lamports, ErrorCode::InsufficientVaultBalance);
+
+        vault.lamports -= amount;
+
+        let vault_account_info = vault.to_account_info();
+        **vault_account_info.try_borrow_mut_lamports()? -= amount;
+        **ctx.accounts.testator.to_account_info().try_borrow_mut_lamports()? += amount;
+        check_rent_exempt(&vault_account_info)?;
+        vault.refresh_content_hash();
+
+        emit!(SolWithdrawn {
+            vault: vault.key(),
+            testator: ctx.accounts.testator.key(),
+            amount,
+            new_vault_balance: vault.lamports,
+            content_hash: vault.content_hash,
+        });
+
+        Ok(())
+    }
+
+    /// Name (or clear, with `None`) a fallback beneficiary who may take the primary
+    /// beneficiary's place once the vault has been Claimable for `claim_window_secs`
+    /// without being executed.
+    pub fn set_fallback_beneficiary(
+        ctx: Context<TestatorUpdateVault>,
+        fallback: Option<FallbackBeneficiary>,
+    ) -> Result<()> {
+        authorize(&ctx.accounts.testator, Role::Testator, &ctx.accounts.vault)?;
+        let vault = &mut ctx.accounts.vault;
+        vault.assert_not_executed()?;
+        require!(
+            !vault.get_state(Clock::get()?.unix_timestamp).is_claimable(),
+            ErrorCode::TransitionNotAllowed
+        );
+
+        if let Some(fallback) = &fallback {
+            require!(!fallback.encrypted_password.is_empty(), ErrorCode::EmptyEncryptedPassword);
+            require!(
+                fallback.encrypted_password.len() <= Vault::MAX_ENCRYPTED_PASSWORD_SIZE,
+                ErrorCode::EncryptedPasswordTooLarge
+            );
+            require!(fallback.claim_window_secs > 0, ErrorCode::InvalidTimeoutConfiguration);
+            require!(
+                fallback.beneficiary != vault.beneficiary
+                    && fallback.beneficiary != vault.testator
+                    && vault
+                        .co_beneficiaries
+                        .iter()
+                        .all(|entry| entry.beneficiary != fallback.beneficiary),
+                ErrorCode::InvalidNewBeneficiary
+            );
+        }
+
+        emit!(FallbackBeneficiarySet {
+            vault: vault.key(),
+            fallback_beneficiary: fallback.as_ref().map(|f| f.beneficiary),
+            claim_window_secs: fallback.as_ref().map_or(0, |f| f.claim_window_secs),
+        });
+
+        vault.fallback_beneficiary = fallback;
+
+        Ok(())
+    }
+
+    /// Once the vault is FallbackClaimable, the fallback beneficiary takes the primary
+    /// beneficiary's place and can then run `execute_inheritance` like any beneficiary.
+    pub fn claim_as_fallback_beneficiary(ctx: Context<ClaimAsFallbackBeneficiary>) -> Result<()> {
+        authorize(
+            &ctx.accounts.fallback_beneficiary,
+            Role::FallbackBeneficiary,
+            &ctx.accounts.vault,
+        )?;
+        let vault = &mut ctx.accounts.vault;
+        vault.assert_not_executed()?;
+        require!(
+            vault.get_state(Clock::get()?.unix_timestamp) == VaultState::FallbackClaimable,
+            ErrorCode::TransitionNotAllowed
+        );
+
+        let fallback = vault.fallback_beneficiary.take().ok_or(ErrorCode::Unauthorized)?;
+        let old_beneficiary = vault.beneficiary;
+        vault.beneficiary = fallback.beneficiary;
+        vault.beneficiary_identity_hash = fallback.identity_hash;
+        vault.beneficiary_email_hash = fallback.email_hash;
+        vault.beneficiary_document_id_hash = fallback.document_id_hash;
+        vault.encrypted_password = fallback.encrypted_password;
+        vault.beneficiary_instructions = Vec::new();
+        vault.beneficiary_verified_at = None;
+        vault.full_identity_verified = false;
+        vault.last_identity_verified_at = 0;
+        vault.has_been_verified = false;
+        vault.secret_verified = false;
+        vault.contact_verified = false;
+        vault.refresh_content_hash();
+
+        emit!(FallbackBeneficiaryPromoted {
+            vault: vault.key(),
+            old_beneficiary,
+            new_beneficiary: vault.beneficiary,
+        });
+
+        Ok(())
+    }
+
+    /// Register the M-of-N guardians who can move the vault to a new testator key if
+    /// the testator loses theirs. An approved rotation only applies `rotation_delay_secs`
+    /// later (at least `GuardianSet::MIN_ROTATION_DELAY_SECS`, except on debug vaults),
+    /// which leaves the testator time to `cancel_testator_rotation`.
+    pub fn init_guardian_set(
+        ctx: Context<InitGuardianSet>,
+        guardians: Vec<Pubkey>,
+        threshold: u8,
+        rotation_delay_secs: i64,
+    ) -> Result<()> {
+        authorize(&ctx.accounts.testator, Role::Testator, &ctx.accounts.vault)?;
+        require!(
+            !guardians.is_empty() && guardians.len() <= GuardianSet::MAX_GUARDIANS,
+            ErrorCode::InvalidGuardianConfig
+        );
+        require!(
+            threshold > 0 && threshold as usize <= guardians.len(),
+            ErrorCode::InvalidGuardianConfig
+        );
+        for (i, guardian) in guardians.iter().enumerate() {
+            require!(
+                !guardians[..i].contains(guardian) && *guardian != ctx.accounts.testator.key(),
+                ErrorCode::InvalidGuardianConfig
+            );
+        }
+        require!(
+            rotation_delay_secs <= GuardianSet::MAX_ROTATION_DELAY_SECS
+                && (rotation_delay_secs >= GuardianSet::MIN_ROTATION_DELAY_SECS
+                    || (ctx.accounts.vault.is_debug && rotation_delay_secs >= 0)),
+            ErrorCode::InvalidGuardianConfig
+        );
+        ctx.accounts.vault.assert_not_executed()?;
+
+        let guardian_set = &mut ctx.accounts.guardian_set;
+        guardian_set.vault = ctx.accounts.vault.key();
+        guardian_set.approvals = vec![false; guardians.len()];
+        guardian_set.guardians = guardians;
+        guardian_set.threshold = threshold;
+        guardian_set.pending_testator = None;
+        guardian_set.proposed_at = 0;
+        guardian_set.rotation_delay_secs = rotation_delay_secs;
+        guardian_set.rotation_effective_at = 0;
+        guardian_set.bump = ctx.bumps.guardian_set;
+
+        Ok(())
+    }
+
+    /// Guardian `guardian_index` (the signer) proposes `new_testator` as the vault's
+    /// testator, replacing any pending proposal. The proposal counts as their approval.
+    /// Only before the vault becomes Claimable.
+    pub fn propose_testator_rotation(
+        ctx: Context<GuardianRotation>,
+        guardian_index: u8,
+        new_testator: Pubkey,
+    ) -> Result<()> {
+        let index = guardian_index as usize;
+        authorize_holder(
+            &ctx.accounts.guardian,
+            Role::CoSigner,
+            ctx.accounts.guardian_set.guardians.get(index).copied(),
+        )?;
+        let now = Clock::get()?.unix_timestamp;
+        let vault = &ctx.accounts.vault;
+        vault.assert_not_executed()?;
+        require!(!vault.get_state(now).is_claimable(), ErrorCode::TransitionNotAllowed);
+        require!(
+            new_testator != vault.testator && new_testator != vault.beneficiary,
+            ErrorCode::InvalidGuardianConfig
+        );
+
+        let guardian_set = &mut ctx.accounts.guardian_set;
+
+        guardian_set.pending_testator = Some(new_testator);
+        guardian_set.approvals = vec![false; guardian_set.guardians.len()];
+        guardian_set.approvals[index] = true;
+        guardian_set.proposed_at = now;
+        guardian_set.rotation_effective_at = 0;
+
+        emit!(TestatorRotationProposed {
+            vault: vault.key(),
+            guardian: ctx.accounts.guardian.key(),
+            current_testator: vault.testator,
+            proposed_testator: new_testator,
+        });
+
+        schedule_testator_rotation_if_approved(vault, guardian_set, ctx.accounts.guardian.key(), now);
+        Ok(())
+    }
+
+    /// Guardian `guardian_index` (the signer) approves the pending testator rotation.
+    /// Once `threshold` guardians approved, `apply_testator_rotation` can move the vault
+    /// to the new testator after the guardian set's `rotation_delay_secs`.
+    pub fn approve_testator_rotation(ctx: Context<GuardianRotation>, guardian_index: u8) -> Result<()> {
+        let index = guardian_index as usize;
+        authorize_holder(
+            &ctx.accounts.guardian,
+            Role::CoSigner,
+            ctx.accounts.guardian_set.guardians.get(index).copied(),
+        )?;
+        let now = Clock::get()?.unix_timestamp;
+        let vault = &ctx.accounts.vault;
+        vault.assert_not_executed()?;
+        require!(!vault.get_state(now).is_claimable(), ErrorCode::TransitionNotAllowed);
+
+        let guardian_set = &mut ctx.accounts.guardian_set;
+        require!(
+            guardian_set.pending_testator.is_some(),
+            ErrorCode::NoPendingTestatorRotation
+        );
+        require!(!guardian_set.approvals[index], ErrorCode::AlreadyApproved);
+        guardian_set.approvals[index] = true;
+
+        schedule_testator_rotation_if_approved(vault, guardian_set, ctx.accounts.guardian.key(), now);
+        Ok(())
+    }
+
+    /// Move the vault to the guardian-approved testator once the rotation delay has
+    /// passed. Permissionless. Delegates named by the replaced key (ping delegate,
+    /// deadline delegate, scheduler, custody agent) and its pending verifier rotation
+    /// are dropped.
+    pub fn apply_testator_rotation(ctx: Context<ApplyTestatorRotation>) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let vault = &mut ctx.accounts.vault;
+        vault.assert_not_executed()?;
+        require!(!vault.get_state(now).is_claimable(), ErrorCode::TransitionNotAllowed);
+
+        let guardian_set = &mut ctx.accounts.guardian_set;
+        let new_testator = guardian_set
+            .pending_testator
+            .ok_or(ErrorCode::NoPendingTestatorRotation)?;
+        require!(
+            guardian_set.rotation_effective_at != 0 && now >= guardian_set.rotation_effective_at,
+            ErrorCode::TestatorRotationTimelocked
+        );
+        guardian_set.clear_pending_rotation();
+
+        let old_testator = vault.testator;
+        vault.testator = new_testator;
+        vault.clear_testator_delegates();
+        vault.refresh_content_hash();
+
+        emit!(TestatorRotated {
+            vault: vault.key(),
+            old_testator,
+            new_testator,
+        });
+
+        Ok(())
+    }
+
+    /// Drop the pending testator rotation, approved or not. Only callable by the
+    /// current testator.
+    pub fn cancel_testator_rotation(ctx: Context<CancelTestatorRotation>) -> Result<()> {
+        authorize(&ctx.accounts.testator, Role::Testator, &ctx.accounts.vault)?;
+        let guardian_set = &mut ctx.accounts.guardian_set;
+        let cancelled_testator = guardian_set
+            .pending_testator
+            .ok_or(ErrorCode::NoPendingTestatorRotation)?;
+        guardian_set.clear_pending_rotation();
+
+        emit!(TestatorRotationCancelled {
+            vault: ctx.accounts.vault.key(),
+            cancelled_testator,
+        });
+
+        Ok(())
+    }
+
+    /// Re-wrap a key wrapped before `demo_hash` was replaced by SHA-256: unwrap it with
+    /// the legacy K_light derivation and wrap it again with HKDF-SHA256 under the current
+    /// root of the Light state tree passed as the first remaining account. Fails with
+    /// `KeyUnwrapFailed` for vaults that aren't legacy-wrapped.
+    pub fn migrate_legacy_key_wrap(ctx: Context<TestatorUpdateVault>) -> Result<()> {
+        authorize(&ctx.accounts.testator, Role::Testator, &ctx.accounts.vault)?;
+        let now = Clock::get()?.unix_timestamp;
+        let vault = &mut ctx.accounts.vault;
+        vault.assert_not_executed()?;
+
+        let encrypted_key = vault.encrypted_key_v2.ok_or(ErrorCode::KeyNotYetWrapped)?;
+        let light_root = vault.light_root.ok_or(ErrorCode::InvalidLightRoot)?;
+        let legacy_k_light = derive_key_from_light_legacy(&light_root, &vault.key(), &vault.seed_beneficiary);
+        let k = decrypt_key_v2(&encrypted_key, &legacy_k_light, vault.key().as_ref())?;
+
+        let root = key_wrap_root(ctx.remaining_accounts, vault, now)?;
+        wrap_vault_key(vault, k, root, now)?;
+
+        emit!(LegacyKeyWrapMigrated {
+            vault: vault.key(),
+            migrated_at: now,
+        });
+
+        Ok(())
+    }
+
+    /// Move a vault created by the first deployed program version — the `LegacyVault`
+    /// layout at the nonce-less address `[b"vault", testator, beneficiary]` — into a
+    /// current `Vault` at vault nonce 0. Settings the legacy program didn't have get the
+    /// defaults of `init_inheritance`. The key, plaintext or XOR-wrapped under the old
+    /// K_light, is wrapped again under the current root of the Light state tree passed
+    /// as the first remaining account. The deposit moves to the new vault and the legacy
+    /// account is closed to the testator. A legacy compressed liveness account is not
+    /// carried over; create a new one for the migrated vault.
+    pub fn migrate_plaintext_key(ctx: Context<MigratePlaintextKey>, beneficiary: Pubkey) -> Result<()> {
+        let legacy_info = ctx.accounts.legacy_vault.to_account_info();
+        require_keys_eq!(*legacy_info.owner, *ctx.program_id, ErrorCode::NotALegacyVault);
+        verify_vault_discriminator(&legacy_info, ctx.program_id)?;
+        require!(legacy_info.data_len() == LegacyVault::ACCOUNT_LEN, ErrorCode::NotALegacyVault);
+
+        // The legacy layout doesn't deserialize as `Vault`, so check the testator by hand
+        let legacy = {
+            let data = legacy_info.try_borrow_data()?;
+            LegacyVault::deserialize(&mut &data[8..]).map_err(|_| ErrorCode::NotALegacyVault)?
+        };
+        require_keys_eq!(ctx.accounts.testator.key(), legacy.testator, ErrorCode::Unauthorized);
+        require_keys_eq!(beneficiary, legacy.beneficiary, ErrorCode::NotALegacyVault);
+        require!(!legacy.executed, ErrorCode::AlreadyExecuted);
+
+        let now = Clock::get()?.unix_timestamp;
+        let k = match (legacy.unwrapped_key, legacy.encrypted_key) {
+            (plaintext, Some(encrypted_key)) => {
+                let light_root = legacy.light_root.ok_or(ErrorCode::InvalidLightRoot)?;
+                require!(encrypted_key.len() == 32, ErrorCode::KeyUnwrapFailed);
+                let k_light = derive_key_from_light_legacy(&light_root, &legacy_info.key(), &legacy.beneficiary);
+                let mut k = [0u8; 32];
+                for i in 0..32 {
+                    k[i] = encrypted_key[i] ^ k_light[i];
+                }
+                if plaintext.is_some() {
+                    emit!(UnwrappedKeyErased {
+                        vault: legacy_info.key(),
+                        erased_at: now,
+                    });
+                }
+                k
+            }
+            (Some(k), None) => k,
+            (None, None) => return err!(ErrorCode::NoUnwrappedKey),
+        };
+
+        let vault = &mut ctx.accounts.vault;
+        let vault_key = vault.key();
+        vault.set_inner(Vault {
+            testator: legacy.testator,
+            beneficiary: legacy.beneficiary,
+            verifier: legacy.verifier,
+            beneficiary_identity_hash: legacy.beneficiary_identity_hash,
+            beneficiary_email_hash: legacy.beneficiary_email_hash,
+            beneficiary_document_id_hash: legacy.beneficiary_document_id_hash,
+            cid: legacy.cid,
+            cid_validator: legacy.cid_validator,
+            last_ping: legacy.last_ping,
+            created_at: legacy.created_at,
+            warning_timeout_secs: legacy.warning_timeout_secs,
+            timeout_secs: legacy.timeout_secs,
+            lamports: legacy.lamports,
+            encrypted_password: legacy.encrypted_password,
+            is_debug: legacy.is_debug,
+            execution_nonce: derive_execution_nonce(&ctx.accounts.recent_slothashes, &vault_key, now)?,
+            key_fragment_type: KeyFragmentType::EncryptedSeed,
+            key_fragment_total: 1,
+            seed_beneficiary: legacy.beneficiary,
+            seed_testator: legacy.testator,
+            vault_nonce: 0,
+            verifier_rotation_delay_secs: Vault::DEFAULT_VERIFIER_ROTATION_DELAY_SECS,
+            compressed_liveness_version: CompressedLivenessVersion::V1,
+            bump: ctx.bumps.vault,
+            ..Default::default()
+        });
+        authorize(&ctx.accounts.testator, Role::Testator, vault)?;
+        let root = key_wrap_root(ctx.remaining_accounts, vault, now)?;
+        wrap_vault_key(vault, k, root, now)?;
+        vault.refresh_content_hash();
+
+        // The deposit follows the vault; the legacy account's rent goes back to the testator
+        let deposit = legacy_info
+            .lamports()
+            .saturating_sub(minimum_rent_exempt_balance(LegacyVault::ACCOUNT_LEN)?);
+        **legacy_info.try_borrow_mut_lamports()? -= deposit;
+        **vault.to_account_info().try_borrow_mut_lamports()? += deposit;
+        anchor_lang::common::close(legacy_info.clone(), ctx.accounts.testator.to_account_info())?;
+
+        emit!(LegacyVaultMigrated {
+            legacy_vault: legacy_info.key(),
+            vault: vault_key,
+            migrated_at: now,
+        });
+
+        Ok(())
+    }
+
+    /// Require executions of this vault to go through
+    /// `execute_inheritance_with_liveness_proof`, which checks the timeout against the
+    /// compressed liveness account and not only `Vault::last_ping`.
+    pub fn set_require_liveness_proof(ctx: Context<TestatorUpdateVault>, required: bool) -> Result<()> {
+        authorize(&ctx.accounts.testator, Role::Testator, &ctx.accounts.vault)?;
+        let vault = &mut ctx.accounts.vault;
+        vault.assert_not_executed()?;
+        require!(
+            !required || vault.has_compressed_liveness || vault.is_debug,
+            ErrorCode::CompressedLivenessNotFound
+        );
+        vault.require_liveness_proof = required;
+        Ok(())
+    }
+
+    /// Same as `execute_inheritance`, but the timeout must also have passed since the
+    /// `last_ping` of the vault's compressed liveness account. `compressed_last_ping` is
+    /// that value, proven by `proof_data` through a read-only Light CPI. Remaining
+    /// accounts: the co-beneficiaries, then the Light system accounts (address tree
+    /// first). Debug vaults skip the Light CPI.
+    pub fn execute_inheritance_with_liveness_proof<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ExecuteInheritance<'info>>,
+        transfer_funds: bool,
+        execution_nonce_provided: [u8; 8],
+        compressed_last_ping: i64,
+        proof_data: ValidityProofData,
+    ) -> Result<()> {
+        authorize(&ctx.accounts.beneficiary, Role::Heir, &ctx.accounts.vault)?;
+        ctx.accounts.vault.assert_consistent()?;
+        let compute_units_start = compute_units_remaining();
+        verify_vault_discriminator(&ctx.accounts.vault.to_account_info(), ctx.program_id)?;
+        ctx.accounts.vault.verify_execution_parties(
+            ctx.accounts.beneficiary.key,
+            ctx.accounts.verifier.key,
+            Clock::get()?.unix_timestamp,
+        )?;
+
+        let co_beneficiary_count = ctx
+            .accounts
+            .vault
+            .co_beneficiaries
+            .len()
+            .min(ctx.remaining_accounts.len());
+        let (co_beneficiary_accounts, light_accounts) =
+            ctx.remaining_accounts.split_at(co_beneficiary_count);
+        verify_compressed_last_ping(
+            ctx.accounts.beneficiary.as_ref(),
+            light_accounts,
+            &ctx.accounts.vault,
+            &proof_data,
+            compressed_last_ping,
+            Clock::get()?.unix_timestamp,
+        )?;
+
+        settle_inheritance(
+            SettlementAccounts {
+                vault: &mut ctx.accounts.vault,
+                beneficiary: &ctx.accounts.beneficiary.to_account_info(),
+                config: &ctx.accounts.config,
+                time_capsule: &mut ctx.accounts.time_capsule,
+                tax_escrow: &mut ctx.accounts.tax_escrow,
+                state_history: &mut ctx.accounts.state_history,
+                co_beneficiaries: co_beneficiary_accounts,
+            },
+            Settlement {
+                transfer_funds,
+                execution_nonce: execution_nonce_provided,
+                proof: ExecutionProof::Liveness,
+                compute_units_start,
+            },
+        )
+    }
+
+    /// `cancel_will` for a vault with a compressed liveness account: closes that account
+    /// through Light Protocol first, so it isn't orphaned in the state tree, then cancels
+    /// the will. Light Protocol system accounts are passed via remaining_accounts
+    /// (address tree first). `compressed_last_ping` and `compressed_ping_count` (V2 only,
+    /// ignored for V1) are the account's current values, which the proof checks; they
+    /// can differ from the vault's own, e.g. after an admin ping. Without a compressed
+    /// account this is `cancel_will`.
+    pub fn cancel_will_with_compressed_liveness<'info>(
+        ctx: Context<'_, '_, '_, 'info, CancelWill<'info>>,
+        compressed_last_ping: i64,
+        compressed_ping_count: u64,
+        proof_data: ValidityProofData,
+        output_tree_index: u8,
+    ) -> Result<()> {
+        authorize(&ctx.accounts.testator, Role::Testator, &ctx.accounts.vault)?;
+        let vault = &mut ctx.accounts.vault;
+        vault.assert_not_executed()?;
+
+        if vault.has_compressed_liveness {
+            let address = invoke_close_compressed_liveness(
+                &ctx.accounts.testator,
+                ctx.remaining_accounts,
+                vault,
+                compressed_last_ping,
+                compressed_ping_count,
+                &proof_data,
+                output_tree_index,
+            )?;
+
+            emit!(CompressedLivenessClosed {
+                vault: vault.key(),
+                testator: vault.testator,
+                address,
+            });
+            vault.has_compressed_liveness = false;
+        } else {
+            msg!("ℹ️ No compressed liveness account to close");
+        }
+
+        cancel_will(ctx)
+    }
+
+    /// Set the lamports paid from the vault's balance to whoever cranks `mark_warning`
+    /// or `mark_claimable` (at most `Vault::MAX_CRANK_BOUNTY_LAMPORTS`, 0 for none).
+    pub fn set_crank_bounty(ctx: Context<TestatorUpdateVault>, bounty_lamports: u64) -> Result<()> {
+        authorize(&ctx.accounts.testator, Role::Testator, &ctx.accounts.vault)?;
+        let vault = &mut ctx.accounts.vault;
+        vault.assert_not_executed()?;
+        require!(
+            bounty_lamports <= Vault::MAX_CRANK_BOUNTY_LAMPORTS,
+            ErrorCode::CrankBountyTooLarge
+        );
+        vault.crank_bounty_lamports = bounty_lamports;
+        Ok(())
+    }
+
+    /// Permissionless crank: record on-chain that the vault has entered
+    /// `VaultState::Warning`, emitting `VaultWarning` and paying the crank bounty.
+    /// Once per missed ping.
+    pub fn mark_warning(ctx: Context<CrankVaultState>) -> Result<()> {
+        let vault = &mut ctx.accounts.vault;
+        let last_ping = vault.effective_last_ping();
+        require!(
+            vault.get_state(Clock::get()?.unix_timestamp) == VaultState::Warning,
+            ErrorCode::TransitionNotAllowed
+        );
+        require!(vault.warning_cranked_for_ping != last_ping, ErrorCode::AlreadyCranked);
+        vault.warning_cranked_for_ping = last_ping;
+
+        let bounty_lamports = pay_crank_bounty(vault, &ctx.accounts.cranker)?;
+        emit!(VaultWarning {
+            vault: vault.key(),
+            testator: vault.testator,
+            last_ping,
+            cranker: ctx.accounts.cranker.key(),
+            bounty_lamports,
+        });
+
+        Ok(())
+    }
+
+    /// Permissionless crank: record on-chain that the vault has become claimable,
+    /// emitting `VaultClaimable` and paying the crank bounty. Once per missed ping.
+    pub fn mark_claimable(ctx: Context<CrankVaultState>) -> Result<()> {
+        let vault = &mut ctx.accounts.vault;
+        let last_ping = vault.effective_last_ping();
+        vault.assert_not_executed()?;
+        require!(
+            vault.get_state(Clock::get()?.unix_timestamp).is_claimable(),
+            ErrorCode::TransitionNotAllowed
+        );
+        require!(vault.claimable_cranked_for_ping != last_ping, ErrorCode::AlreadyCranked);
+        vault.claimable_cranked_for_ping = last_ping;
+
+        let bounty_lamports = pay_crank_bounty(vault, &ctx.accounts.cranker)?;
+        emit!(VaultClaimable {
+            vault: vault.key(),
+            testator: vault.testator,
+            beneficiary: vault.beneficiary,
+            last_ping,
+            cranker: ctx.accounts.cranker.key(),
+            bounty_lamports,
+        });
+
+        Ok(())
+    }
+
+    /// Name (or clear) a delegate who may call `extend_deadline` besides the testator, and
+    /// cap the total extension per missed ping (at most
+    /// `Vault::MAX_DEADLINE_EXTENSION_SECS`, 0 disables extensions).
+    pub fn set_deadline_extension_policy(
+        ctx: Context<TestatorUpdateVault>,
+        delegate: Option<Pubkey>,
+        max_extension_secs: i64,
+    ) -> Result<()> {
+        authorize(&ctx.accounts.testator, Role::Testator, &ctx.accounts.vault)?;
+        let vault = &mut ctx.accounts.vault;
+        vault.assert_not_executed()?;
+        require!(
+            (0..=Vault::MAX_DEADLINE_EXTENSION_SECS).contains(&max_extension_secs),
+            ErrorCode::InvalidDeadlineExtension
+        );
+        vault.deadline_delegate = delegate;
+        vault.max_deadline_extension_secs = max_extension_secs;
+        Ok(())
+    }
+
+    /// Push back the point where a vault in `VaultState::Warning` becomes claimable by
+    /// `extra_secs`, without a liveness proof. Callable by the testator or their deadline
+    /// delegate; the extensions since the last ping add up to at most
+    /// `Vault::max_deadline_extension_secs`, and the next ping drops them.
+    pub fn extend_deadline(ctx: Context<ExtendDeadline>, extra_secs: i64) -> Result<()> {
+        authorize(&ctx.accounts.extender, Role::DeadlineExtender, &ctx.accounts.vault)?;
+        let now = Clock::get()?.unix_timestamp;
+        let vault = &mut ctx.accounts.vault;
+        require!(vault.get_state(now) == VaultState::Warning, ErrorCode::TransitionNotAllowed);
+        require!(extra_secs > 0, ErrorCode::InvalidDeadlineExtension);
+
+        let total_extension_secs = vault
+            .active_deadline_extension_secs()
+            .checked_add(extra_secs)
+            .filter(|total| *total <= vault.max_deadline_extension_secs)
+            .ok_or(ErrorCode::DeadlineExtensionLimitReached)?;
+        vault.deadline_extension_secs = total_extension_secs;
+        vault.deadline_extended_for_ping = vault.last_ping;
+
+        emit!(DeadlineExtended {
+            vault: vault.key(),
+            extender: ctx.accounts.extender.key(),
+            extra_secs,
+            total_extension_secs,
+            claimable_after: vault
+                .effective_last_ping()
+                .saturating_add(vault.effective_timeout_secs()),
+        });
+
+        Ok(())
+    }
+
+    /// Let a session key (e.g. a hot key on the testator's phone) call `update_liveness`
+    /// in place of the testator until `expires_at`, without holding the main wallet key.
+    /// Registering again replaces the previous delegate. Only callable by the testator.
+    pub fn register_ping_delegate(
+        ctx: Context<TestatorUpdateVault>,
+        delegate: Pubkey,
+        expires_at: i64,
+    ) -> Result<()> {
+        authorize(&ctx.accounts.testator, Role::Testator, &ctx.accounts.vault)?;
+        let vault = &mut ctx.accounts.vault;
+        vault.assert_not_executed()?;
+        require!(
+            expires_at > Clock::get()?.unix_timestamp && delegate != vault.testator,
+            ErrorCode::InvalidPingDelegate
+        );
+        vault.ping_delegate = Some(delegate);
+        vault.ping_delegate_expires_at = expires_at;
+
+        emit!(PingDelegateRegistered {
+            vault: vault.key(),
+            delegate,
+            expires_at,
+        });
+
+        Ok(())
+    }
+
+    /// Remove the ping delegate before it expires. Only callable by the testator.
+    pub fn revoke_ping_delegate(ctx: Context<TestatorUpdateVault>) -> Result<()> {
+        authorize(&ctx.accounts.testator, Role::Testator, &ctx.accounts.vault)?;
+        let vault = &mut ctx.accounts.vault;
+        let delegate = vault.ping_delegate.take().ok_or(ErrorCode::NoPingDelegate)?;
+        vault.ping_delegate_expires_at = 0;
+
+        emit!(PingDelegateRevoked {
+            vault: vault.key(),
+            delegate,
+        });
+
+        Ok(())
+    }
+
+    /// Gasless ping: any relayer submits a liveness ping the testator signed off-chain, so
+    /// the testator needs no SOL to stay alive. The transaction must carry, right before
+    /// this instruction, an Ed25519 program instruction in which the testator signs
+    /// `RELAYED_PING_DOMAIN || vault || nonce || signed_at || proof_nonce` (u64 / i64 LE).
+    ///
+    /// `nonce` must equal the vault's `relayed_ping_nonce` and `signed_at` be at most
+    /// `MAX_RELAYED_PING_AGE_SECS` old and no older than the vault's `last_ping`, which
+    /// it becomes: the testator was alive when signing, not when the relayer submitted
+    /// the ping. Like every other ping, `proof_nonce` must not have
+    /// been used with the vault before (see `UsedProofNonces`). Vaults with an open
+    /// compressed liveness account or an `expected_ping_note` must keep using
+    /// `update_liveness`.
+    pub fn update_liveness_relayed(
+        ctx: Context<UpdateLivenessRelayed>,
+        nonce: u64,
+        signed_at: i64,
+        proof_nonce: [u8; 8],
+        testator_signature: [u8; 64],
+    ) -> Result<()> {
+        let compute_units_start = compute_units_remaining();
+        let slot = Clock::get()?.slot;
+        check_proof_nonce(&ctx.accounts.used_nonces, &proof_nonce, slot)?;
+        let vault = &mut ctx.accounts.vault;
+        vault.assert_consistent()?;
+        vault.assert_not_executed()?;
+        require!(
+            !vault.has_compressed_liveness || vault.is_debug,
+            ErrorCode::CompressedLivenessStillOpen
+        );
+        require!(vault.expected_ping_note.is_none(), ErrorCode::PingNoteInvalid);
+
+        let message = load_ed25519_message(
+            &ctx.accounts.instructions_sysvar,
+            &vault.testator,
+            &testator_signature,
+            ErrorCode::InvalidRelayedPing,
+        )?;
+        let now = Clock::get()?.unix_timestamp;
+        require!(
+            message == relayed_ping_message(&vault.key(), nonce, signed_at, &proof_nonce)
+                && nonce == vault.relayed_ping_nonce
+                && signed_at <= now
+                && signed_at >= vault.last_ping
+                && now - signed_at <= MAX_RELAYED_PING_AGE_SECS,
+            ErrorCode::InvalidRelayedPing
+        );
+        require!(!vault.execution_pending(now), ErrorCode::ExecutionPending);
+
+        record_proof_nonce(
+            &mut ctx.accounts.used_nonces,
+            vault.key(),
+            ctx.bumps.used_nonces,
+            proof_nonce,
+            slot,
+        );
+
+        let previous_state = vault.get_state(now);
+        vault.relayed_ping_nonce = nonce.saturating_add(1);
+        vault.last_ping = signed_at;
+        vault.ping_count = vault.ping_count.saturating_add(1);
+        vault.admin_pinged = false;
+        vault.refresh_content_hash();
+
+        let relayer = ctx.accounts.relayer.key();
+        if let Some(history) = ctx.accounts.state_history.as_mut() {
+            history.record_transition(previous_state, now, relayer);
+            history.record_transition(vault.get_state(now), now, relayer);
+        }
+
+        emit!(RelayedLivenessPing {
+            vault: vault.key(),
+            relayer,
+            nonce,
+            signed_at,
+        });
+        emit!(PingReceived {
+            vault: vault.key(),
+            testator: vault.testator,
+            timestamp: signed_at,
+            note: None,
+            via_compressed: false,
+            content_hash: vault.content_hash,
+            compute_units_used: compute_units_start.saturating_sub(compute_units_remaining()),
+        });
+
+        Ok(())
+    }
+
+    /// Require executions of this vault to go through
+    /// `execute_inheritance_with_attestation`, so that the verifier's approval is bound to
+    /// this vault, this beneficiary and a single-use nonce instead of a bare co-signature.
+    pub fn set_require_verifier_attestation(
+        ctx: Context<TestatorUpdateVault>,
+        required: bool,
+    ) -> Result<()> {
+        authorize(&ctx.accounts.testator, Role::Testator, &ctx.accounts.vault)?;
+        let vault = &mut ctx.accounts.vault;
+        vault.assert_not_executed()?;
+        vault.require_verifier_attestation = required;
+        Ok(())
+    }
+
+    /// Same as `execute_inheritance`, with a structured attestation from the verifier.
+    /// The transaction must carry, right before this instruction, an Ed25519 program
+    /// instruction in which the verifier signs `VERIFIER_ATTESTATION_DOMAIN || vault ||
+    /// beneficiary || attestation_nonce || expires_at` (u64 / i64 LE).
+    /// `attestation_nonce` must equal the vault's `attestation_nonce`, which is then
+    /// consumed, and `expires_at` must not have passed.
+    pub fn execute_inheritance_with_attestation<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ExecuteInheritance<'info>>,
+        transfer_funds: bool,
+        execution_nonce_provided: [u8; 8],
+        attestation_nonce: u64,
+        expires_at: i64,
+        verifier_signature: [u8; 64],
+    ) -> Result<()> {
+        authorize(&ctx.accounts.beneficiary, Role::Heir, &ctx.accounts.vault)?;
+        ctx.accounts.vault.assert_consistent()?;
+        let compute_units_start = compute_units_remaining();
+        verify_vault_discriminator(&ctx.accounts.vault.to_account_info(), ctx.program_id)?;
+        ctx.accounts.vault.verify_execution_parties(
+            ctx.accounts.beneficiary.key,
+            ctx.accounts.verifier.key,
+            Clock::get()?.unix_timestamp,
+        )?;
+
+        let instructions_sysvar = ctx
+            .accounts
+            .instructions_sysvar
+            .as_ref()
+            .ok_or(ErrorCode::InvalidVerifierAttestation)?;
+        let message = load_ed25519_message(
+            instructions_sysvar,
+            ctx.accounts.verifier.key,
+            &verifier_signature,
+            ErrorCode::InvalidVerifierAttestation,
+        )?;
+        let vault = &mut ctx.accounts.vault;
+        require!(
+            message
+                == verifier_attestation_message(
+                    &vault.key(),
+                    &vault.beneficiary,
+                    attestation_nonce,
+                    expires_at,
+                )
+                && attestation_nonce == vault.attestation_nonce
+                && Clock::get()?.unix_timestamp <= expires_at,
+            ErrorCode::InvalidVerifierAttestation
+        );
+        vault.attestation_nonce = attestation_nonce.saturating_add(1);
+
+        settle_inheritance(
+            SettlementAccounts {
+                vault: &mut ctx.accounts.vault,
+                beneficiary: &ctx.accounts.beneficiary.to_account_info(),
+                config: &ctx.accounts.config,
+                time_capsule: &mut ctx.accounts.time_capsule,
+                tax_escrow: &mut ctx.accounts.tax_escrow,
+                state_history: &mut ctx.accounts.state_history,
+                co_beneficiaries: ctx.remaining_accounts,
+            },
+            Settlement {
+                transfer_funds,
+                execution_nonce: execution_nonce_provided,
+                proof: ExecutionProof::VerifierAttestation,
+                compute_units_start,
+            },
+        )
+    }
+
+    /// Create the protocol's registry of approved verifiers. Only callable by the
+    /// protocol authority.
+    pub fn init_verifier_registry(ctx: Context<InitVerifierRegistry>) -> Result<()> {
+        let registry = &mut ctx.accounts.verifier_registry;
+        registry.verifiers = Vec::new();
+        registry.bump = ctx.bumps.verifier_registry;
+        Ok(())
+    }
+
+    /// Approve `verifier` for new vaults. Only callable by the protocol authority.
+    pub fn add_registered_verifier(ctx: Context<UpdateVerifierRegistry>, verifier: Pubkey) -> Result<()> {
+        let registry = &mut ctx.accounts.verifier_registry;
+        if !registry.contains(&verifier) {
+            require!(
+                registry.verifiers.len() < VerifierRegistry::MAX_VERIFIERS,
+                ErrorCode::VerifierRegistryFull
+            );
+            registry.verifiers.push(verifier);
+        }
+
+        emit!(VerifierRegistryUpdated {
+            verifier,
+            registered: true,
+        });
+
+        Ok(())
+    }
+
+    /// Withdraw the approval of `verifier`. Vaults already using it are unaffected.
+    /// Only callable by the protocol authority.
+    pub fn remove_registered_verifier(ctx: Context<UpdateVerifierRegistry>, verifier: Pubkey) -> Result<()> {
+        let registry = &mut ctx.accounts.verifier_registry;
+        let index = registry
+            .verifiers
+            .iter()
+            .position(|v| *v == verifier)
+            .ok_or(ErrorCode::VerifierNotRegistered)?;
+        registry.verifiers.remove(index);
+
+        emit!(VerifierRegistryUpdated {
+            verifier,
+            registered: false,
+        });
+
+        Ok(())
+    }
+
+    /// Require `init_inheritance` calls that pass the protocol config to use a verifier
+    /// from the `VerifierRegistry`. Only callable by the protocol authority.
+    pub fn set_require_registered_verifier(ctx: Context<UpdateProtocolConfig>, required: bool) -> Result<()> {
+        ctx.accounts.config.require_registered_verifier = required;
+        Ok(())
+    }
+
+    /// Schedule the replacement of the vault's verifier by `new_verifier`. It takes effect
+    /// through `apply_verifier_rotation` once `verifier_rotation_delay_secs` have passed,
+    /// which leaves the testator time to `cancel_verifier_rotation` if their key was used
+    /// by someone else. A new call replaces the pending rotation and restarts the delay.
+    /// Like `init_inheritance`, it only accepts `VerifierRegistry` members while
+    /// `config.require_registered_verifier` is on.
+    pub fn rotate_verifier(ctx: Context<RotateVerifier>, new_verifier: Pubkey) -> Result<()> {
+        authorize(&ctx.accounts.testator, Role::Testator, &ctx.accounts.vault)?;
+        if ctx.accounts.config.require_registered_verifier {
+            require!(
+                ctx.accounts
+                    .verifier_registry
+                    .as_ref()
+                    .is_some_and(|registry| registry.contains(&new_verifier)),
+                ErrorCode::VerifierNotRegistered
+            );
+        }
+        let vault = &mut ctx.accounts.vault;
+        vault.assert_not_executed()?;
+        require!(new_verifier != vault.verifier, ErrorCode::InvalidVerifier);
+
+        let effective_at = Clock::get()?
+            .unix_timestamp
+            .saturating_add(vault.verifier_rotation_delay_secs);
+        vault.pending_verifier = Some(new_verifier);
+        vault.verifier_rotation_effective_at = effective_at;
+
+        emit!(VerifierRotationScheduled {
+            vault: vault.key(),
+            current_verifier: vault.verifier,
+            new_verifier,
+            effective_at,
+        });
+
+        Ok(())
+    }
+
+    /// Drop the pending verifier rotation. Only callable by the testator.
+    pub fn cancel_verifier_rotation(ctx: Context<TestatorUpdateVault>) -> Result<()> {
+        authorize(&ctx.accounts.testator, Role::Testator, &ctx.accounts.vault)?;
+        let vault = &mut ctx.accounts.vault;
+        let new_verifier = vault
+            .pending_verifier
+            .take()
+            .ok_or(ErrorCode::NoPendingVerifierRotation)?;
+        vault.verifier_rotation_effective_at = 0;
+
+        emit!(VerifierRotationSettled {
+            vault: vault.key(),
+            old_verifier: vault.verifier,
+            new_verifier,
+            applied: false,
+        });
+
+        Ok(())
+    }
+
+    /// Make the pending verifier rotation effective once its delay has passed.
+    /// Permissionless.
+    pub fn apply_verifier_rotation(ctx: Context<ApplyVerifierRotation>) -> Result<()> {
+        let vault = &mut ctx.accounts.vault;
+        vault.assert_not_executed()?;
+        let new_verifier = vault
+            .pending_verifier
+            .ok_or(ErrorCode::NoPendingVerifierRotation)?;
+        require!(
+            Clock::get()?.unix_timestamp >= vault.verifier_rotation_effective_at,
+            ErrorCode::VerifierRotationTimelocked
+        );
+
+        let old_verifier = vault.verifier;
+        vault.verifier = new_verifier;
+        vault.pending_verifier = None;
+        vault.verifier_rotation_effective_at = 0;
+
+        emit!(VerifierRotationSettled {
+            vault: vault.key(),
+            old_verifier,
+            new_verifier,
+            applied: true,
+        });
+
+        Ok(())
+    }
+
+    /// Set the delay of future verifier rotations. It can only be lengthened, so that a
+    /// stolen testator key cannot shorten it first (debug vaults may also shorten it).
+    pub fn set_verifier_rotation_delay(ctx: Context<TestatorUpdateVault>, delay_secs: i64) -> Result<()> {
+        authorize(&ctx.accounts.testator, Role::Testator, &ctx.accounts.vault)?;
+        let vault = &mut ctx.accounts.vault;
+        vault.assert_not_executed()?;
+        require!(
+            (0..=Vault::MAX_VERIFIER_ROTATION_DELAY_SECS).contains(&delay_secs)
+                && (delay_secs >= vault.verifier_rotation_delay_secs || vault.is_debug),
+            ErrorCode::InvalidVerifierRotationDelay
+        );
+        vault.verifier_rotation_delay_secs = delay_secs;
+        Ok(())
+    }
+
+    /// Publish the recovery authority's X25519 public key, which testators wrap the
+    /// escrow copy of K to (see `set_recovery_escrow`), together with the matching
+    /// private key encrypted to the recovery authority's members off-chain. Only callable
+    /// by the protocol authority.
+    pub fn set_recovery_encryption_key(
+        ctx: Context<UpdateProtocolConfig>,
+        recovery_encryption_key: [u8; 32],
+        encrypted_recovery_decryption_key: EncryptedKeyV2,
+    ) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        config.recovery_encryption_key = recovery_encryption_key;
+        config.encrypted_recovery_decryption_key = Some(encrypted_recovery_decryption_key);
+        Ok(())
+    }
+
+    /// Opt in to `emergency_key_reveal` by escrowing a copy of K wrapped to
+    /// `ProtocolConfig::recovery_encryption_key` (see `RecoveryEscrow`). Replaces any
+    /// previous escrow. Only callable by the testator.
+    pub fn set_recovery_escrow(ctx: Context<TestatorUpdateVault>, escrow: RecoveryEscrow) -> Result<()> {
+        authorize(&ctx.accounts.testator, Role::Testator, &ctx.accounts.vault)?;
+        let vault = &mut ctx.accounts.vault;
+        vault.assert_not_executed()?;
+        vault.recovery_escrow = Some(escrow);
+        Ok(())
+    }
+
+    /// Drop a verifier committee that has not taken effect yet, so that a committee set
+    /// up with a stolen testator key never gets to finalize an execution. Only callable
+    /// by the testator; an effective committee stays.
+    pub fn cancel_verifier_committee(ctx: Context<TestatorUpdateVault>) -> Result<()> {
+        authorize(&ctx.accounts.testator, Role::Testator, &ctx.accounts.vault)?;
+        let vault = &mut ctx.accounts.vault;
+        vault.assert_not_executed()?;
+        let committee = vault
+            .verifier_committee
+            .ok_or(ErrorCode::NoPendingVerifierCommittee)?;
+        let effective_at = vault.verifier_committee_effective_at;
+        require!(
+            Clock::get()?.unix_timestamp < effective_at,
+            ErrorCode::VerifierCommitteeAlreadyEffective
+        );
+        vault.verifier_committee = None;
+        vault.verifier_committee_effective_at = 0;
+
+        emit!(VerifierCommitteeCancelled {
+            vault: vault.key(),
+            committee,
+            effective_at,
+        });
+
+        Ok(())
+    }
+
+    /// Draw the winner of an executed lottery vault from the hash of the first slot at or
+    /// after `Vault::lottery_draw_slot`, which was still in the future at execution.
+    /// Callable by anyone once that slot has passed. If the sysvar no longer reaches back
+    /// to it (after about 512 slots), a new draw slot is scheduled instead.
+    pub fn draw_lottery_winner(ctx: Context<DrawLotteryWinner>) -> Result<()> {
+        let vault = &mut ctx.accounts.vault;
+        require!(vault.executed && vault.lottery_mode, ErrorCode::TransitionNotAllowed);
+        require!(vault.lottery_winner.is_none(), ErrorCode::LotteryAlreadyDrawn);
+
+        let data = ctx.accounts.recent_slothashes.try_borrow_data()?;
+        let lookup = find_slot_hash(&data, vault.lottery_draw_slot)?;
+        drop(data);
+
+        let (draw_slot, slot_hash) = match lookup {
+            SlotHashLookup::Pending => return err!(ErrorCode::LotteryDrawNotReady),
+            SlotHashLookup::Expired => {
+                schedule_lottery_draw(vault, Clock::get()?.slot);
+                return Ok(());
+            }
+            SlotHashLookup::Found { slot, hash } => (slot, hash),
+        };
+        let participants = vault.lottery_participants.len();
+        let winner = vault.lottery_participants[lottery_draw(&vault.key(), &slot_hash, participants)];
+        vault.lottery_winner = Some(winner);
+
+        emit!(LotteryWinner {
+            vault: vault.key(),
+            winner,
+            participants: u8::try_from(participants).map_err(|_| ErrorCode::ArithmeticOverflow)?,
+            draw_slot,
+            slot_hash,
+        });
+
+        Ok(())
+    }
+}
+
+/// K_light of keys wrapped before HKDF (`Vault::key_wrapped_with_hkdf` unset). XORing
+/// the inputs leaks their structure; new keys are only wrapped with
+/// `derive_key_from_light_hkdf`, this is kept to unwrap the old ones.
+fn derive_key_from_light(
+    light_root: &[u8; 32],
+    vault_pubkey: &Pubkey,
+    beneficiary: &Pubkey,
+) -> [u8; 32] {
+    let mut key = [0u8; 32];
+    for i in 0..32 {
+        key[i] = light_root[i] ^ vault_pubkey.as_ref()[i] ^ beneficiary.as_ref()[i];
+    }
+    real_hash(&key)
+}
+
+/// `derive_key_from_light` as it was before `demo_hash` was replaced by `real_hash`.
+/// Only used by `migrate_legacy_key_wrap` and `migrate_plaintext_key` to unwrap keys
+/// wrapped back then.
+#[allow(deprecated)]
+fn derive_key_from_light_legacy(
+    light_root: &[u8; 32],
+    vault_pubkey: &Pubkey,
+    beneficiary: &Pubkey,
+) -> [u8; 32] {
+    let mut key = [0u8; 32];
+    for i in 0..32 {
+        key[i] = light_root[i] ^ vault_pubkey.as_ref()[i] ^ beneficiary.as_ref()[i];
+    }
+    demo_hash(&key)
+}
+
+/// HKDF-SHA256 (RFC 5869) K_light: the Light root is the input key material, the
+/// vault the salt, and `info` is `K_LIGHT_LABEL || beneficiary`.
+fn derive_key_from_light_hkdf(
+    light_root: &[u8; 32],
+    vault_pubkey: &Pubkey,
+    beneficiary: &Pubkey,
+) -> [u8; 32] {
+    crypto::hkdf_sha256(
+        &vault_pubkey.to_bytes(),
+        light_root,
+        &[crypto::K_LIGHT_LABEL, beneficiary.as_ref()],
+    )
+}
+
+/// K_light for `vault`'s wrapped key, with whichever derivation wrapped it. Bound to
+/// `Vault::seed_beneficiary`, so a beneficiary change doesn't orphan the wrapped key.
+fn vault_k_light(vault: &Account<Vault>, light_root: &[u8; 32]) -> [u8; 32] {
+    if vault.key_wrapped_with_hkdf {
+        derive_key_from_light_hkdf(light_root, &vault.key(), &vault.seed_beneficiary)
+    } else {
+        derive_key_from_light(light_root, &vault.key(), &vault.seed_beneficiary)
+    }
+}
+
+/// Wraps the key `k` under the HKDF K_light of the Light state root `light_root` (see
+/// `key_wrap_root`) and emits `KeyWrapCompleted`. Only key migrations wrap on-chain:
+/// new vaults receive K already wrapped.
+fn wrap_vault_key(vault: &mut Account<Vault>, k: [u8; 32], light_root: [u8; 32], now: i64) -> Result<()> {
+    vault.key_wrapped_with_hkdf = true;
+    let k_light = vault_k_light(vault, &light_root);
+
+    // Encrypt K with K_light (AES-256-GCM, nonce bound to this vault and ping)
+    let nonce = derive_key_nonce(&vault.key(), vault.ping_count);
+    let encrypted_key = encrypt_key_v2(&k, &k_light, nonce, vault.key().as_ref())?;
+
+    vault.encrypted_key_v2 = Some(encrypted_key);
+    vault.light_root = Some(light_root);
+
+    emit!(KeyWrapCompleted {
+        vault: vault.key(),
+        wrapped_at: now,
+    });
+    Ok(())
+}
+
+/// Derives the testator's liveness address and CPIs into the Light System Program to
+/// create the compressed liveness account. Returns the derived address.
+/// Shared by `create_compressed_liveness` and `retry_create_compressed_liveness`.
+fn invoke_create_compressed_liveness<'info>(
+    accounts: &CreateCompressedLiveness<'info>,
+    remaining_accounts: &[AccountInfo<'info>],
+    proof_data: &ValidityProofData,
+    address_tree_info: &AddressTreeInfoData,
+    output_tree_index: u8,
+) -> Result<[u8; 32]> {
+    // Deserialize the validity proof from raw bytes
+    let proof = LightValidityProof::try_from_slice(&proof_data.data)
+        .map_err(|_| ErrorCode::InvalidLightProof)?;
+
+    // Create Light CPI accounts from remaining accounts
+    let light_cpi_accounts = CpiAccounts::new(
+        accounts.fee_payer.as_ref(),
+        remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    // Get the address tree pubkey from remaining accounts
+    let address_tree_pubkey = remaining_accounts
+        .get(address_tree_info.address_merkle_tree_pubkey_index as usize)
+        .ok_or(ErrorCode::InvalidLightRoot)?
+        .key();
+
+    // Derive unique address for this vault's liveness account
+    let (address, address_seed) = CompressedLiveness::derive_address(
+        &accounts.vault.seed_testator,
+        &accounts.vault.key(),
+        &address_tree_pubkey,
+        &crate::ID,
+    );
+
+    // Create packed address params manually
+    let new_address_params = light_sdk::address::PackedNewAddressParams {
+        seed: address_seed.into(),
+        address_merkle_tree_account_index: address_tree_info.address_merkle_tree_pubkey_index,
+        address_queue_account_index: address_tree_info.address_queue_pubkey_index,
+        address_merkle_tree_root_index: 0, // Will be filled by Light Protocol
+    };
+
+    // Create the compressed liveness account
+    let mut liveness_account = LightAccount::<CompressedLiveness>::new_init(
+        &crate::ID,
+        Some(address),
+        output_tree_index,
+    );
+
+    liveness_account.testator = accounts.vault.seed_testator;
+    liveness_account.last_ping = Clock::get()?.unix_timestamp;
+    liveness_account.vault_address = accounts.vault.key();
+
+    // CPI to Light System Program to create the compressed account
+    LightSystemProgramCpi::new_cpi(crate::LIGHT_CPI_SIGNER, proof)
+        .with_light_account(liveness_account)
+        .map_err(|_| ErrorCode::InvalidLightProof)?
+        .with_new_addresses(&[new_address_params])
+        .invoke(light_cpi_accounts)
+        .map_err(|_| ErrorCode::InvalidLightProof)?;
+
+    Ok(address)
+}
+
+/// Input checks shared with the compressed liveness CPI paths, without the CPI:
+/// the proof decodes, the tree accounts are in bounds and the address derives.
+fn validate_compressed_liveness_inputs(
+    testator: &Pubkey,
+    vault: &Pubkey,
+    remaining_accounts: &[AccountInfo],
+    proof_data: &ValidityProofData,
+    address_tree_info: &AddressTreeInfoData,
+    output_tree_index: u8,
+) -> core::result::Result<[u8; 32], ErrorCode> {
+    LightValidityProof::try_from_slice(&proof_data.data).map_err(|_| ErrorCode::InvalidLightProof)?;
+
+    let address_tree_pubkey = remaining_accounts
+        .get(address_tree_info.address_merkle_tree_pubkey_index as usize)
+        .ok_or(ErrorCode::InvalidLightRoot)?
+        .key();
+    let in_bounds = |index: u8| (index as usize) < remaining_accounts.len();
+    if !in_bounds(address_tree_info.address_queue_pubkey_index) || !in_bounds(output_tree_index) {
+        return Err(ErrorCode::InvalidLightRoot);
+    }
+
+    let (address, _) = CompressedLiveness::derive_address(testator, vault, &address_tree_pubkey, &crate::ID);
+    Ok(address)
+}
+
+/// Nullifier of a compressed liveness proof: `SHA256(proof bytes || vault)`.
+fn liveness_nullifier(proof_data: &ValidityProofData, vault: &Pubkey) -> [u8; 32] {
+    real_hash(&[proof_data.data.as_slice(), vault.as_ref()].concat())
+}
+
+/// Fails if `nullifier` is in the registry while the extra nullifier check is on.
+/// Returns whether the nullifier must be recorded once the update succeeds.
+fn check_liveness_nullifier(
+    config: Option<&ProtocolConfig>,
+    registry: Option<&Account<NullifierRegistry>>,
+    nullifier: &[u8; 32],
+) -> Result<bool> {
+    if !config.is_some_and(|c| c.extra_nullifier_check) {
+        return Ok(false);
+    }
+    let registry = registry.ok_or(ErrorCode::NullifierRegistryMissing)?;
+    require!(!registry.contains(nullifier), ErrorCode::NullifierAlreadyUsed);
+    Ok(true)
+}
+
+fn record_liveness_nullifier(
+    registry: Option<&mut Account<NullifierRegistry>>,
+    nullifier: [u8; 32],
+    now: i64,
+) {
+    if let Some(registry) = registry {
+        let data_len = registry.to_account_info().data_len();
+        let capacity = data_len.saturating_sub(8 + NullifierRegistry::space(0)) / NullifierEntry::SIZE;
+        registry.record(nullifier, now, capacity);
+    }
+}
+
+/// Shared body of `create_compressed_liveness` and `retry_create_compressed_liveness`:
+/// the V2 gate, the nullifier and proof nonce checks, then the Light CPI.
+fn process_create_compressed_liveness<'info>(
+    ctx: &mut Context<'_, '_, '_, 'info, CreateCompressedLiveness<'info>>,
+    proof_data: ValidityProofData,
+    address_tree_info: AddressTreeInfoData,
+    output_tree_index: u8,
+    proof_nonce: [u8; 8],
+    compute_units_start: u64,
+) -> Result<()> {
+    require!(
+        !ctx.accounts.vault.has_compressed_liveness,
+        ErrorCode::CompressedLivenessAlreadyExists
+    );
+    if let Some(config) = ctx.accounts.config.as_ref() {
+        require!(!config.v2_enabled, ErrorCode::CompressedLivenessV2Required);
+    }
+    let nullifier = liveness_nullifier(&proof_data, &ctx.accounts.vault.key());
+    let record_nullifier = check_liveness_nullifier(
+        ctx.accounts.config.as_deref(),
+        ctx.accounts.nullifier_registry.as_ref(),
+        &nullifier,
+    )?;
+    let slot = Clock::get()?.slot;
+    check_proof_nonce(&ctx.accounts.used_nonces, &proof_nonce, slot)?;
+
+    let address = invoke_create_compressed_liveness(
+        &ctx.accounts,
+        ctx.remaining_accounts,
+        &proof_data,
+        &address_tree_info,
+        output_tree_index,
+    )?;
+
+    if record_nullifier {
+        let now = Clock::get()?.unix_timestamp;
+        record_liveness_nullifier(ctx.accounts.nullifier_registry.as_mut(), nullifier, now);
+    }
+    record_proof_nonce(
+        &mut ctx.accounts.used_nonces,
+        ctx.accounts.vault.key(),
+        ctx.bumps.used_nonces,
+        proof_nonce,
+        slot,
+    );
+
+    // Mark that the vault now has a compressed liveness account
+    let vault = &mut ctx.accounts.vault;
+    vault.has_compressed_liveness = true;
+
+    emit!(CompressedLivenessCreated {
+        vault: vault.key(),
+        testator: vault.testator,
+        address,
+        compute_units_used: compute_units_start.saturating_sub(compute_units_remaining()),
+    });
+
+    Ok(())
+}
+
+/// Shared body of `update_liveness` and `update_liveness_with_note`, including the
+/// vault consistency check and the single-use `proof_nonce` check (see `UsedProofNonces`).
+fn process_liveness_update<'info>(
+    ctx: &mut Context<'_, '_, '_, 'info, UpdateLiveness<'info>>,
+    proof_data: ValidityProofData,
+    output_tree_index: u8,
+    proof_nonce: [u8; 8],
+    note: Option<[u8; 32]>,
+) -> Result<()> {
+    ctx.accounts.vault.assert_consistent()?;
+    let compute_units_start = compute_units_remaining();
+    let slot = Clock::get()?.slot;
+    check_proof_nonce(&ctx.accounts.used_nonces, &proof_nonce, slot)?;
+    let vault = &mut ctx.accounts.vault;
+    let now = Clock::get()?.unix_timestamp;
+    let signer = ctx.accounts.testator.key();
+    // A registered scheduler signs as both the testator and the fee payer
+    let scheduled = signer != vault.testator
+        && signer == ctx.accounts.fee_payer.key()
+        && vault.scheduler_pubkey == Some(signer);
+    let delegated = !scheduled && vault.testator != signer && vault.ping_delegate == Some(signer);
+    let custodial = !scheduled
+        && !delegated
+        && vault.custody_agent.is_some()
+        && vault.testator != signer;
+    if delegated {
+        require!(now < vault.ping_delegate_expires_at, ErrorCode::PingDelegateExpired);
+    } else if custodial {
+        vault.verify_custody_agent(&signer, now)?;
+    } else if !scheduled {
+        vault.verify_liveness_parties(&signer)?;
+    }
+    vault.assert_not_executed()?;
+    require!(!vault.execution_pending(now), ErrorCode::ExecutionPending);
+    if let Some(expected) = vault.expected_ping_note {
+        require!(
+            note.is_some_and(|note| constant_time_eq_32(&note, &expected)),
+            ErrorCode::PingNoteInvalid
+        );
+    }
+    let previous_state = vault.get_state(now);
+    let via_compressed = vault.has_compressed_liveness && !vault.is_debug;
+    let nullifier = liveness_nullifier(&proof_data, &vault.key());
+    let record_nullifier = check_liveness_nullifier(
+        ctx.accounts.config.as_deref(),
+        ctx.accounts.nullifier_registry.as_ref(),
+        &nullifier,
+    )?;
+
+    // --- Light Protocol CPI Update ---
+    if vault.has_compressed_liveness
+        && !vault.is_debug
+        && vault.compressed_liveness_version == CompressedLivenessVersion::V2
+    {
+        invoke_update_compressed_liveness_v2(
+            &ctx.accounts.fee_payer,
+            ctx.remaining_accounts,
+            vault,
+            &proof_data,
+            output_tree_index,
+            now,
+        )?;
+
+        msg!("✅ Compressed liveness V2 updated via Light Protocol");
+    } else if vault.has_compressed_liveness && !vault.is_debug {
+        // Deserialize the validity proof from raw bytes
+        let proof = LightValidityProof::try_from_slice(&proof_data.data)
+            .map_err(|_| ErrorCode::InvalidLightProof)?;
+        
+        // Create Light CPI accounts from remaining accounts
+        let light_cpi_accounts = CpiAccounts::new(
+            ctx.accounts.fee_payer.as_ref(),
+            ctx.remaining_accounts,
+            crate::LIGHT_CPI_SIGNER,
+        );
+
+        // Derive the address for this vault's liveness account
+        // Must match the address used in create_compressed_liveness
+        let address_tree_pubkey = ctx.remaining_accounts
+            .get(0)
+            .ok_or(ErrorCode::InvalidLightRoot)?
+            .key();
+
+        let (address, _) = CompressedLiveness::derive_address(
+            &vault.seed_testator,
+            &vault.key(),
+            &address_tree_pubkey,
+            &crate::ID,
+        );
+
+        // Update the compressed liveness account with new timestamp
+        let mut liveness_account = LightAccount::<CompressedLiveness>::new_update(
+            &crate::ID,
+            Some(address),
+            output_tree_index,
+        );
+
+        liveness_account.testator = vault.seed_testator;
+        liveness_account.last_ping = now;
+        liveness_account.vault_address = vault.key();
+
+        // CPI to Light System Program to update the compressed account
+        LightSystemProgramCpi::new_cpi(crate::LIGHT_CPI_SIGNER, proof)
+            .with_light_account(liveness_account)
+            .map_err(|_| ErrorCode::InvalidLightProof)?
+            .invoke(light_cpi_accounts)
+            .map_err(|_| ErrorCode::InvalidLightProof)?;
+
+        msg!("✅ Compressed liveness updated via Light Protocol");
+    } else if vault.is_debug {
+        msg!("⚠️ Debug mode: Skipping Light Protocol verification");
+    } else {
+        msg!("ℹ️ No compressed liveness account, using standard update");
+    }
+    // ---------------------------------
+
+    if record_nullifier {
+        record_liveness_nullifier(ctx.accounts.nullifier_registry.as_mut(), nullifier, now);
+    }
+    record_proof_nonce(
+        &mut ctx.accounts.used_nonces,
+        vault.key(),
+        ctx.bumps.used_nonces,
+        proof_nonce,
+        slot,
+    );
+
+    vault.last_ping = now;
+    vault.ping_count = vault.ping_count.saturating_add(1);
+    vault.admin_pinged = false;
+    if let Some(note) = note {
+        vault.last_ping_note = note;
+    }
+    vault.refresh_content_hash();
+
+    if let Some(history) = ctx.accounts.state_history.as_mut() {
+        history.record_transition(previous_state, now, signer);
+        history.record_transition(vault.get_state(now), now, signer);
+    }
+
+    if custodial {
+        emit!(CustodialPingReceived {
+            vault: vault.key(),
+            agent: signer,
+        });
+    }
+
+    if scheduled {
+        vault.scheduler_ping_count = vault.scheduler_ping_count.saturating_add(1);
+        emit!(ScheduledLivenessPing {
+            vault: vault.key(),
+            scheduler: signer,
+            timestamp: now,
+        });
+    }
+
+    emit!(PingReceived {
+        vault: vault.key(),
+        testator: vault.testator,
+        timestamp: now,
+        note,
+        via_compressed,
+        content_hash: vault.content_hash,
+        compute_units_used: compute_units_start.saturating_sub(compute_units_remaining()),
+    });
+
+    Ok(())
+}
+
+/// The accounts `settle_inheritance` works on, borrowed from the execution's context
+struct SettlementAccounts<'a, 'info> {
+    vault: &'a mut Account<'info, Vault>,
+    beneficiary: &'a AccountInfo<'info>,
+    config: &'a ProtocolConfig,
+    time_capsule: &'a mut Option<Account<'info, TimeCapsuleMessage>>,
+    tax_escrow: &'a mut Option<Account<'info, TaxEscrow>>,
+    state_history: &'a mut Option<Account<'info, VaultStateHistory>>,
+    /// Paid in split order
+    co_beneficiaries: &'a [AccountInfo<'info>],
+}
+
+/// Which of the vault's optional execution requirements the caller has already checked
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ExecutionProof {
+    None,
+    /// The compressed liveness account's last ping (`require_liveness_proof`)
+    Liveness,
+    /// A verifier attestation bound to the vault (`require_verifier_attestation`)
+    VerifierAttestation,
+}
+
+/// The execution's arguments, as passed to `settle_inheritance`
+struct Settlement {
+    transfer_funds: bool,
+    execution_nonce: [u8; 8],
+    proof: ExecutionProof,
+    compute_units_start: u64,
+}
+
+/// Steps 2-6 of `execute_inheritance`, shared with `finalize_committee_execution`.
+/// The caller must already have authorized the beneficiary and the verifier.
+fn settle_inheritance<'info>(accounts: SettlementAccounts<'_, 'info>, settlement: Settlement) -> Result<()> {
+    let SettlementAccounts {
+        vault,
+        beneficiary,
+        config,
+        time_capsule,
+        tax_escrow,
+        state_history,
+        co_beneficiaries: co_beneficiary_accounts,
+    } = accounts;
+    let Settlement {
+        transfer_funds,
+        execution_nonce,
+        proof,
+        compute_units_start,
+    } = settlement;
+    let now = Clock::get()?.unix_timestamp;
+    let state = vault.get_state(now);
+
+    // Protocol-wide emergency stop (debug vaults are exempt)
+    require!(
+        !config.executions_paused_at(now) || vault.is_debug,
+        ErrorCode::ProtocolExecutionsPaused
+    );
+
+    // 2. State Machine validation
+    vault.assert_not_executed()?;
+    require!(state.is_claimable(), ErrorCode::TransitionNotAllowed);
+    require!(
+        proof == ExecutionProof::Liveness || !vault.require_liveness_proof,
+        ErrorCode::LivenessProofRequired
+    );
+    require!(
+        proof == ExecutionProof::VerifierAttestation || !vault.require_verifier_attestation,
+        ErrorCode::VerifierAttestationRequired
+    );
+
+    // Anti-replay: a transaction pre-signed with a stale nonce is rejected
+    require!(
+        constant_time_eq(&execution_nonce, &vault.execution_nonce),
+        ErrorCode::ExecutionNonceMismatch
+    );
+
+    // The beneficiary's identity verification must be recent enough, if required
+    vault.check_identity_freshness(now)?;
+
+    // 3. Light Protocol validation (skip in debug mode)
+    // In debug mode, we don't require the Light root to be set.
+    if !vault.is_debug {
+        require!(
+            vault.light_root.is_some(),
+            ErrorCode::InvalidLightRoot
+        );
+    }
+
+    // Lottery vaults draw their heir later, from the hash of a slot still in the future
+    // now, so that whoever executes cannot pick a favourable one (see `draw_lottery_winner`)
+    if vault.lottery_mode {
+        require!(
+            !vault.lottery_participants.is_empty(),
+            ErrorCode::LotteryModeNoParticipants
+        );
+        schedule_lottery_draw(vault, Clock::get()?.slot);
+    }
+
+    // Co-beneficiaries are paid through the remaining accounts, in split order
+    if transfer_funds && !vault.lottery_mode {
+        require!(
+            co_beneficiary_accounts.len() >= vault.co_beneficiaries.len()
+                && vault
+                    .co_beneficiaries
+                    .iter()
+                    .zip(co_beneficiary_accounts)
+                    .all(|(entry, account)| account.key() == entry.beneficiary),
+            ErrorCode::CoBeneficiaryAccountMismatch
+        );
+    }
+
+    // Primary beneficiary first, then each co-beneficiary, with the lamports paid
+    let mut payouts: Vec<(Pubkey, u64)> = core::iter::once(vault.beneficiary)
+        .chain(vault.co_beneficiaries.iter().map(|entry| entry.beneficiary))
+        .map(|key| (key, 0))
+        .collect();
+
+    // 4. Transfer SOL to beneficiary (if enabled)
+    if transfer_funds {
+        let vault_account_info = vault.to_account_info();
+        let vault_lamports = vault.lamports;
+
+        require!(vault_lamports > 0, ErrorCode::NoAssets);
+
+        let transfer_amount = vault_lamports;
+        vault.lamports = 0;
+
+        // Hold back the inheritance tax in escrow for the tax authority oracle
+        let tax_amount = vault.inheritance_tax(transfer_amount);
+        if tax_amount > 0 {
+            let vault_key = vault.key();
+            let tax_authority = vault.tax_authority_oracle.ok_or(ErrorCode::NoTaxAuthority)?;
+            let escrow = tax_escrow.as_mut().ok_or(ErrorCode::TaxEscrowNotSettled)?;
+
+            escrow.vault = vault_key;
+            escrow.tax_authority = tax_authority;
+            escrow.tax_amount = tax_amount;
+            escrow.due_by = now + TaxEscrow::PAYMENT_WINDOW_SECS;
+            escrow.paid = false;
+
+            **vault_account_info.try_borrow_mut_lamports()? -= tax_amount;
+            **escrow.to_account_info().try_borrow_mut_lamports()? += tax_amount;
+
+            emit!(TaxEscrowCreated {
+                vault: vault_key,
+                tax_amount,
+                due_by: escrow.due_by,
+            });
+        }
+
+        let beneficiary_amount = transfer_amount - tax_amount;
+        if vault.lottery_mode {
+            // The winner isn't drawn yet: held in the vault until they call
+            // claim_lottery_prize
+            vault.lamports = beneficiary_amount;
+        } else {
+            let shares = vault.co_beneficiary_shares(beneficiary_amount);
+            let primary_amount = beneficiary_amount - shares.iter().sum::<u64>();
+
+            **vault_account_info.try_borrow_mut_lamports()? -= beneficiary_amount;
+            **beneficiary.try_borrow_mut_lamports()? += primary_amount;
+            payouts[0].1 = primary_amount;
+            for (i, share) in shares.into_iter().enumerate() {
+                **co_beneficiary_accounts[i].try_borrow_mut_lamports()? += share;
+                payouts[i + 1].1 = share;
+            }
+            vault.lamports_transferred = beneficiary_amount;
+        }
+
+        check_rent_exempt(&vault_account_info)?;
+    }
+
+    // 5. Mark as executed and emit the encrypted password as the "reward"
+    vault.executed = true;
+    vault.executed_at = now;
+    vault.execution_nonce = [0u8; 8];
+    vault.refresh_content_hash();
+
+    if let Some(history) = state_history.as_mut() {
+        history.record_transition(state, now, beneficiary.key());
+        history.record_transition(VaultState::Executed, now, beneficiary.key());
+    }
+
+    // Emit an event with the encrypted password so each beneficiary can retrieve it
+    for (beneficiary_key, amount) in payouts {
+        emit!(InheritanceExecuted {
+            vault: vault.key(),
+            beneficiary: beneficiary_key,
+            amount,
+            testator: vault.testator,
+            encrypted_password: vault.encrypted_password.clone(),
+            cid: vault.cid,
+            cid_validator: vault.cid_validator,
+            beneficiary_identity_hash: vault.beneficiary_identity_hash,
+            beneficiary_email_hash: vault.beneficiary_email_hash,
+            beneficiary_document_id_hash: vault.beneficiary_document_id_hash,
+            beneficiary_instructions: vault.beneficiary_instructions.clone(),
+            key_fragment_type: vault.key_fragment_type,
+            key_fragment_index: vault.key_fragment_index,
+            key_fragment_total: vault.key_fragment_total,
+            vault_id: vault.vault_id,
+            content_hash: vault.content_hash,
+            legal_documents: vault.legal_documents.clone(),
+            compute_units_used: compute_units_start.saturating_sub(compute_units_remaining()),
+        });
+    }
+
+    // 6. Reveal the testator's time capsule message, if one was sealed
+    if let Some(capsule) = time_capsule.as_mut() {
+        capsule.revealed = true;
+        emit!(TimeCapsuleRevealed {
+            vault: vault.key(),
+            beneficiary: vault.beneficiary,
+            encrypted_message: capsule.encrypted_message.clone(),
+        });
+    }
+
+    Ok(())
+}
+
+/// Deterministic one-time nonce for wrapping the key: the first 12 bytes of
+/// `hash(vault || ping_count)`. Unique per vault and per ping.
+fn derive_key_nonce(vault_pubkey: &Pubkey, ping_count: u64) -> [u8; 12] {
+    let digest = real_hash(&[vault_pubkey.as_ref(), &ping_count.to_le_bytes()].concat());
+    let mut nonce = [0u8; 12];
+    nonce.copy_from_slice(&digest[..12]);
+    nonce
+}
+
+/// Anti-replay nonce for `execute_inheritance`: the first 8 bytes of
+/// `hash(most recent slot hash || vault || now)`. Not a secret.
+fn derive_execution_nonce(slot_hashes: &AccountInfo, vault_pubkey: &Pubkey, now: i64) -> Result<[u8; 8]> {
+    let data = slot_hashes.try_borrow_data()?;
+    // SlotHashes layout: u64 entry count, then (slot: u64, hash: [u8; 32]), most recent first
+    let recent_hash = data.get(16..48).unwrap_or(&[0u8; 32]);
+    let digest = real_hash(&[recent_hash, vault_pubkey.as_ref(), &now.to_le_bytes()].concat());
+    let mut nonce = [0u8; 8];
+    nonce.copy_from_slice(&digest[..8]);
+    Ok(nonce)
+}
+
+/// Commits a lottery vault to drawing its winner from the hash of a slot
+/// `Vault::LOTTERY_DRAW_DELAY_SLOTS` after `slot`.
+fn schedule_lottery_draw(vault: &mut Account<Vault>, slot: u64) {
+    vault.lottery_draw_slot = slot.saturating_add(Vault::LOTTERY_DRAW_DELAY_SLOTS);
+    emit!(LotteryDrawScheduled {
+        vault: vault.key(),
+        draw_slot: vault.lottery_draw_slot,
+    });
+}
+
+/// Where a slot stands in the SlotHashes sysvar
+#[derive(Debug, PartialEq, Eq)]
+enum SlotHashLookup {
+    /// The slot hasn't passed yet
+    Pending,
+    /// The first slot at or after it, and its hash
+    Found { slot: u64, hash: [u8; 32] },
+    /// The sysvar no longer reaches back to it
+    Expired,
+}
+
+/// Looks `slot` up in SlotHashes sysvar data: u64 entry count, then (slot: u64, hash:
+/// [u8; 32]) entries, most recent first. Skipped slots have no entry, so the first slot
+/// at or after `slot` stands in for it.
+fn find_slot_hash(data: &[u8], slot: u64) -> Result<SlotHashLookup> {
+    let entry = |i: usize| -> Result<(u64, [u8; 32])> {
+        let start = i
+            .checked_mul(40)
+            .and_then(|offset| offset.checked_add(8))
+            .ok_or(ErrorCode::InvalidSlotHashes)?;
+        let bytes = data.get(start..start + 40).ok_or(ErrorCode::InvalidSlotHashes)?;
+        let (entry_slot, hash) = bytes.split_at(8);
+        Ok((
+            u64::from_le_bytes(entry_slot.try_into().map_err(|_| ErrorCode::InvalidSlotHashes)?),
+            hash.try_into().map_err(|_| ErrorCode::InvalidSlotHashes)?,
+        ))
+    };
+    let count = data
+        .get(..8)
+        .and_then(|bytes| bytes.try_into().ok())
+        .map(u64::from_le_bytes)
+        .ok_or(ErrorCode::InvalidSlotHashes)?;
+
+    let mut found = SlotHashLookup::Pending;
+    for i in 0..usize::try_from(count).map_err(|_| ErrorCode::InvalidSlotHashes)? {
+        let (entry_slot, hash) = entry(i)?;
+        if entry_slot < slot {
+            return Ok(found);
+        }
+        found = SlotHashLookup::Found { slot: entry_slot, hash };
+        if entry_slot == slot {
+            return Ok(found);
+        }
+    }
+    // Every entry is after `slot`: an older one, now gone, may have been the first
+    Ok(match found {
+        SlotHashLookup::Pending => SlotHashLookup::Pending,
+        _ => SlotHashLookup::Expired,
+    })
+}
+
+/// Index of the lottery winner among `participants` participants, drawn from
+/// `slot_hash`. Mixed with the vault key so that vaults drawing at the same slot differ.
+fn lottery_draw(vault: &Pubkey, slot_hash: &[u8; 32], participants: usize) -> usize {
+    let digest = real_hash(&[vault.as_ref(), slot_hash.as_slice()].concat());
+    let mut seed = [0u8; 8];
+    seed.copy_from_slice(&digest[..8]);
+    (u64::from_le_bytes(seed) % participants as u64) as usize
+}
+
+/// Encrypts the 32-byte key K under K_light with AES-256-GCM, returning the
+/// ciphertext with its detached authentication tag.
+fn encrypt_key_v2(
+    key: &[u8; 32],
+    k_light: &[u8; 32],
+    nonce: [u8; 12],
+    associated_data: &[u8],
+) -> Result<EncryptedKeyV2> {
+    let cipher = Aes256Gcm::new_from_slice(k_light).map_err(|_| ErrorCode::KeyWrapFailed)?;
+    let mut ciphertext = *key;
+    let tag = cipher
+        .encrypt_in_place_detached(Nonce::from_slice(&nonce), associated_data, &mut ciphertext)
+        .map_err(|_| ErrorCode::KeyWrapFailed)?;
+
+    Ok(EncryptedKeyV2 {
+        nonce,
+        ciphertext,
+        tag: tag.into(),
+    })
+}
+
+/// Inverse of `encrypt_key_v2`: authenticates and decrypts the wrapped key K.
+fn decrypt_key_v2(
+    encrypted_key: &EncryptedKeyV2,
+    k_light: &[u8; 32],
+    associated_data: &[u8],
+) -> Result<[u8; 32]> {
+    let cipher = Aes256Gcm::new_from_slice(k_light).map_err(|_| ErrorCode::KeyUnwrapFailed)?;
+    let mut key = encrypted_key.ciphertext;
+    cipher
+        .decrypt_in_place_detached(
+            Nonce::from_slice(&encrypted_key.nonce),
+            associated_data,
+            &mut key,
+            Tag::from_slice(&encrypted_key.tag),
+        )
+        .map_err(|_| ErrorCode::KeyUnwrapFailed)?;
+    Ok(key)
+}
+
+/// Verifies that an account is a `Vault` owned by this program by comparing the first
+/// 8 bytes of its data against `Vault::DISCRIMINATOR` in constant time.
+/// Anchor already checks this on deserialization; this is a defense-in-depth measure
+/// against cross-program account confusion.
+pub fn verify_vault_discriminator(account_info: &AccountInfo, program_id: &Pubkey) -> Result<()> {
+    account_info.assert_owned_by(program_id)?;
+
+    let data = account_info.try_borrow_data()?;
+    require!(data.len() >= 8, ErrorCode::DiscriminatorMismatch);
+    require!(
+        constant_time_eq(&data[..8], Vault::DISCRIMINATOR),
+        ErrorCode::DiscriminatorMismatch
+    );
+    Ok(())
+}
+
+/// Remaining compute units for the current transaction, logged via `sol_log_compute_units`.
+/// Only measured when built with the `compute_metrics` feature; otherwise returns 0 so
+/// production builds don't pay for the extra syscalls.
+#[inline]
+fn compute_units_remaining() -> u64 {
+    #[cfg(all(feature = "compute_metrics", target_os = "solana"))]
+    unsafe {
+        solana_define_syscall::definitions::sol_log_compute_units_();
+        solana_define_syscall::definitions::sol_remaining_compute_units()
+    }
+    #[cfg(not(all(feature = "compute_metrics", target_os = "solana")))]
+    {
+        0
+    }
+}
+
+/// SHA-256 via the Solana syscall. Replacement for `demo_hash`.
+fn real_hash(data: &[u8]) -> [u8; 32] {
+    solana_sha256_hasher::hashv(&[data]).to_bytes()
+}
+
+/// A simple XOR + bit-shift hash for demonstration purposes. Not a cryptographic
+/// hash: only kept so `migrate_legacy_key_wrap` can unwrap keys wrapped with it.
+#[deprecated(since = "0.1.0", note = "Use real_hash; only for unwrapping legacy keys")]
+pub(crate) fn demo_hash(data: &[u8]) -> [u8; 32] {
+    let mut hash = [0u8; 32];
+    for (i, &byte) in data.iter().enumerate() {
+        hash[i % 32] = hash[i % 32].wrapping_add(byte).rotate_left(3);
+        hash[i % 32] ^= 0x55;
+    }
+    hash
+}
+
+fn validate_capsule_message(encrypted_message: &[u8]) -> Result<()> {
+    require!(!encrypted_message.is_empty(), ErrorCode::EmptyTimeCapsuleMessage);
+    require!(
+        encrypted_message.len() <= TimeCapsuleMessage::MAX_MESSAGE_SIZE,
+        ErrorCode::TimeCapsuleMessageTooLarge
+    );
+    Ok(())
+}
+
+/// Identifies the deployed version of the Light System Program: the first 8 bytes of
+/// the hash of its ProgramData header (deployment slot + upgrade authority), which
+/// changes on every redeploy.
+fn light_program_hash(program_data: &AccountInfo) -> Result<[u8; 8]> {
+    let data = program_data.try_borrow_data()?;
+    require!(
+        data.len() >= LIGHT_PROGRAM_DATA_HEADER_LEN,
+        ErrorCode::LightSdkVersionMismatch
+    );
+
+    let digest = real_hash(&data[..LIGHT_PROGRAM_DATA_HEADER_LEN]);
+    let mut program_hash = [0u8; 8];
+    program_hash.copy_from_slice(&digest[..8]);
+    Ok(program_hash)
+}
+
+/// Minimum balance for an account with `data_len` bytes of data to stay rent-exempt.
+/// Clients can use it to pre-validate deposits and withdrawals.
+pub fn minimum_rent_exempt_balance(data_len: usize) -> Result<u64> {
+    Ok(Rent::get()?.minimum_balance(data_len))
+}
+
+/// Asserts that `account_info` still holds at least its rent-exempt minimum. Call it
+/// after moving lamports out of a vault.
+pub fn check_rent_exempt(account_info: &AccountInfo) -> Result<()> {
+    let minimum = minimum_rent_exempt_balance(account_info.data_len())?;
+    require!(
+        account_info.lamports() >= minimum,
+        ErrorCode::InsufficientFundsForRent
+    );
+    Ok(())
+}
+
+/// Creates a `CompressedLivenessV2` account for the vault, returning its address.
+fn invoke_create_compressed_liveness_v2<'info>(
+    accounts: &CreateCompressedLivenessV2<'info>,
+    remaining_accounts: &[AccountInfo<'info>],
+    proof_data: &ValidityProofData,
+    address_tree_info: &AddressTreeInfoData,
+    output_tree_index: u8,
+    now: i64,
+) -> Result<[u8; 32]> {
+    let proof = LightValidityProof::try_from_slice(&proof_data.data)
+        .map_err(|_| ErrorCode::InvalidLightProof)?;
+
+    let light_cpi_accounts = CpiAccounts::new(
+        accounts.fee_payer.as_ref(),
+        remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let address_tree_pubkey = remaining_accounts
+        .get(address_tree_info.address_merkle_tree_pubkey_index as usize)
+        .ok_or(ErrorCode::InvalidLightRoot)?
+        .key();
+
+    let (address, address_seed) = CompressedLivenessV2::derive_address(
+        &accounts.vault.seed_testator,
+        &accounts.vault.key(),
+        &address_tree_pubkey,
+        &crate::ID,
+    );
+
+    let new_address_params = light_sdk::address::PackedNewAddressParams {
+        seed: address_seed.into(),
+        address_merkle_tree_account_index: address_tree_info.address_merkle_tree_pubkey_index,
+        address_queue_account_index: address_tree_info.address_queue_pubkey_index,
+        address_merkle_tree_root_index: 0, // Will be filled by Light Protocol
+    };
+
+    let mut liveness_account = LightAccount::<CompressedLivenessV2>::new_init(
+        &crate::ID,
+        Some(address),
+        output_tree_index,
+    );
+
+    liveness_account.testator = accounts.vault.seed_testator;
+    liveness_account.last_ping = now;
+    liveness_account.vault_address = accounts.vault.key();
+    liveness_account.ping_count = accounts.vault.ping_count;
+    liveness_account.last_guardian_ping = 0;
+    liveness_account.compression_created_at = now;
+
+    LightSystemProgramCpi::new_cpi(crate::LIGHT_CPI_SIGNER, proof)
+        .with_light_account(liveness_account)
+        .map_err(|_| ErrorCode::InvalidLightProof)?
+        .with_new_addresses(&[new_address_params])
+        .invoke(light_cpi_accounts)
+        .map_err(|_| ErrorCode::InvalidLightProof)?;
+
+    Ok(address)
+}
+
+/// Updates the vault's `CompressedLivenessV2` account with a new ping.
+fn invoke_update_compressed_liveness_v2<'info>(
+    fee_payer: &Signer<'info>,
+    remaining_accounts: &[AccountInfo<'info>],
+    vault: &Account<'info, Vault>,
+    proof_data: &ValidityProofData,
+    output_tree_index: u8,
+    now: i64,
+) -> Result<()> {
+    let proof = LightValidityProof::try_from_slice(&proof_data.data)
+        .map_err(|_| ErrorCode::InvalidLightProof)?;
+
+    let light_cpi_accounts = CpiAccounts::new(
+        fee_payer.as_ref(),
+        remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    // Must match the address used in create_compressed_liveness_v2
+    let address_tree_pubkey = remaining_accounts
+        .get(0)
+        .ok_or(ErrorCode::InvalidLightRoot)?
+        .key();
+
+    let (address, _) = CompressedLivenessV2::derive_address(
+        &vault.seed_testator,
+        &vault.key(),
+        &address_tree_pubkey,
+        &crate::ID,
+    );
+
+    let mut liveness_account = LightAccount::<CompressedLivenessV2>::new_update(
+        &crate::ID,
+        Some(address),
+        output_tree_index,
+    );
+
+    liveness_account.testator = vault.seed_testator;
+    liveness_account.last_ping = now;
+    liveness_account.vault_address = vault.key();
+    liveness_account.ping_count = vault.ping_count.saturating_add(1);
+    liveness_account.last_guardian_ping = 0;
+    liveness_account.compression_created_at = vault.compression_created_at;
+
+    LightSystemProgramCpi::new_cpi(crate::LIGHT_CPI_SIGNER, proof)
+        .with_light_account(liveness_account)
+        .map_err(|_| ErrorCode::InvalidLightProof)?
+        .invoke(light_cpi_accounts)
+        .map_err(|_| ErrorCode::InvalidLightProof)?;
+
+    Ok(())
+}
+
+/// Closes the vault's V1 compressed liveness account and creates its V2 replacement
+/// in one CPI. Returns `(v1_address, v2_address)`.
+fn invoke_migrate_compressed_liveness<'info>(
+    accounts: &CreateCompressedLivenessV2<'info>,
+    remaining_accounts: &[AccountInfo<'info>],
+    proof_data: &ValidityProofData,
+    address_tree_info: &AddressTreeInfoData,
+    output_tree_index: u8,
+    now: i64,
+) -> Result<([u8; 32], [u8; 32])> {
+    let proof = LightValidityProof::try_from_slice(&proof_data.data)
+        .map_err(|_| ErrorCode::InvalidLightProof)?;
+
+    let light_cpi_accounts = CpiAccounts::new(
+        accounts.fee_payer.as_ref(),
+        remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let address_tree_pubkey = remaining_accounts
+        .get(address_tree_info.address_merkle_tree_pubkey_index as usize)
+        .ok_or(ErrorCode::InvalidLightRoot)?
+        .key();
+
+    let (testator, vault) = (accounts.vault.seed_testator, accounts.vault.key());
+    let (v1_address, _) =
+        CompressedLiveness::derive_address(&testator, &vault, &address_tree_pubkey, &crate::ID);
+    let (v2_address, v2_address_seed) =
+        CompressedLivenessV2::derive_address(&testator, &vault, &address_tree_pubkey, &crate::ID);
+
+    let v1_account = LightAccount::<CompressedLiveness>::new_close(
+        &crate::ID,
+        Some(v1_address),
+        output_tree_index,
+    );
+
+    let new_address_params = light_sdk::address::PackedNewAddressParams {
+        seed: v2_address_seed.into(),
+        address_merkle_tree_account_index: address_tree_info.address_merkle_tree_pubkey_index,
+        address_queue_account_index: address_tree_info.address_queue_pubkey_index,
+        address_merkle_tree_root_index: 0, // Will be filled by Light Protocol
+    };
+
+    let mut v2_account = LightAccount::<CompressedLivenessV2>::new_init(
+        &crate::ID,
+        Some(v2_address),
+        output_tree_index,
+    );
+
+    v2_account.testator = testator;
+    v2_account.last_ping = accounts.vault.last_ping;
+    v2_account.vault_address = accounts.vault.key();
+    v2_account.ping_count = accounts.vault.ping_count;
+    v2_account.last_guardian_ping = 0;
+    v2_account.compression_created_at = now;
+
+    LightSystemProgramCpi::new_cpi(crate::LIGHT_CPI_SIGNER, proof)
+        .with_light_account(v1_account)
+        .map_err(|_| ErrorCode::InvalidLightProof)?
+        .with_light_account(v2_account)
+        .map_err(|_| ErrorCode::InvalidLightProof)?
+        .with_new_addresses(&[new_address_params])
+        .invoke(light_cpi_accounts)
+        .map_err(|_| ErrorCode::InvalidLightProof)?;
+
+    Ok((v1_address, v2_address))
+}
+
+/// Checks that the instruction right before the current one is an Ed25519 program
+/// instruction in which `authority` signed `evidence_cid || slot` with `signature`,
+/// and that `slot` is recent.
+fn verify_admin_ping_signature(
+    instructions_sysvar: &AccountInfo,
+    authority: &Pubkey,
+    evidence_cid: &[u8; 32],
+    signature: &[u8; 64],
+    current_slot: u64,
+) -> Result<()> {
+    let message = load_ed25519_message(
+        instructions_sysvar,
+        authority,
+        signature,
+        ErrorCode::InvalidAdminSignature,
+    )?;
+    require!(
+        message.len() == 40 && message[..32] == evidence_cid[..],
+        ErrorCode::InvalidAdminSignature
+    );
+
+    let mut slot_bytes = [0u8; 8];
+    slot_bytes.copy_from_slice(&message[32..]);
+    let signed_slot = u64::from_le_bytes(slot_bytes);
+    require!(
+        signed_slot <= current_slot
+            && current_slot - signed_slot <= MAX_ADMIN_SIGNATURE_AGE_SLOTS,
+        ErrorCode::InvalidAdminSignature
+    );
+
+    Ok(())
+}
+
+/// Returns the message of the Ed25519 program instruction right before the current one,
+/// after checking that it carries a single signature by `signer` equal to `signature`.
+/// All offsets must point into that instruction itself. Fails with `error` otherwise.
+fn load_ed25519_message(
+    instructions_sysvar: &AccountInfo,
+    signer: &Pubkey,
+    signature: &[u8; 64],
+    error: ErrorCode,
+) -> Result<Vec<u8>> {
+    use anchor_lang::solana_program::sysvar::instructions::{
+        load_current_index_checked, load_instruction_at_checked,
+    };
+
+    let current_index = load_current_index_checked(instructions_sysvar)?;
+    require!(current_index > 0, error);
+    let ed25519_ix = load_instruction_at_checked(current_index as usize - 1, instructions_sysvar)?;
+    require!(
+        ed25519_ix.program_id == anchor_lang::solana_program::ed25519_program::ID,
+        error
+    );
+
+    // Layout: u8 signature count, u8 padding, then u16 LE offsets: signature, signature
+    // instruction index, public key, public key instruction index, message, message
+    // size, message instruction index
+    let data = &ed25519_ix.data;
+    require!(data.len() >= 16 && data[0] == 1, error);
+    let read_u16 = |at: usize| u16::from_le_bytes([data[at], data[at + 1]]) as usize;
+    let this_instruction = u16::MAX as usize;
+    require!(
+        read_u16(4) == this_instruction
+            && read_u16(8) == this_instruction
+            && read_u16(14) == this_instruction,
+        error
+    );
+    let field = |offset: usize, len: usize| {
+        data.get(offset..offset + len).ok_or_else(|| error!(error))
+    };
+
+    let signed_signature = field(read_u16(2), 64)?;
+    let signed_by = field(read_u16(6), 32)?;
+    let message = field(read_u16(10), read_u16(12))?;
+    require!(
+        signed_by == signer.as_ref() && signed_signature == signature.as_slice(),
+        error
+    );
+
+    Ok(message.to_vec())
+}
+
+/// First 8 bytes of `hash("inheritance_demo::Vault/<Vault::SIZE>")`: identifies the
+/// Borsh layout of a vault export and changes whenever the account layout does.
+fn vault_export_schema_hash() -> [u8; 8] {
+    let schema = format!("inheritance_demo::Vault/{}", Vault::SIZE);
+    let mut schema_hash = [0u8; 8];
+    schema_hash.copy_from_slice(&real_hash(schema.as_bytes())[..8]);
+    schema_hash
+}
+
+/// Fails with `ProofNonceAlreadyUsed` if `proof_nonce` was already accepted for the
+/// vault and has not aged out of `used_nonces` yet.
+fn check_proof_nonce(used_nonces: &UsedProofNonces, proof_nonce: &[u8; 8], slot: u64) -> Result<()> {
+    require!(
+        !used_nonces.contains(proof_nonce, slot),
+        ErrorCode::ProofNonceAlreadyUsed
+    );
+    Ok(())
+}
+
+/// Records an accepted `proof_nonce`, initializing `used_nonces` on first use.
+fn record_proof_nonce(
+    used_nonces: &mut UsedProofNonces,
+    vault: Pubkey,
+    bump: u8,
+    proof_nonce: [u8; 8],
+    slot: u64,
+) {
+    used_nonces.vault = vault;
+    used_nonces.bump = bump;
+    used_nonces.insert(proof_nonce, slot);
+}
+
+/// Commitment to a contact verification challenge nonce: `sha256(nonce || vault)`.
+fn contact_challenge_hash(nonce: &[u8; 16], vault_pubkey: &Pubkey) -> [u8; 32] {
+    real_hash(&[nonce.as_slice(), vault_pubkey.as_ref()].concat())
+}
+
+/// Checks one `batch_read_compressed_liveness` claim: `vault_info` must be a vault
+/// whose compressed liveness account lives at `claim.address`, and the claimed values
+/// must be possible for it: `last_ping` between the vault's creation and `now`, and a
+/// V2 `ping_count` no higher than the vault's. Debug vaults have no compressed account
+/// and are read from the vault alone.
+fn check_compressed_liveness_read<'info>(
+    vault_info: &'info AccountInfo<'info>,
+    claim: &CompressedLivenessClaim,
+    address_tree: Option<&Pubkey>,
+    now: i64,
+) -> core::result::Result<Account<'info, Vault>, ErrorCode> {
+    let vault = Account::<Vault>::try_from(vault_info).map_err(|_| ErrorCode::DiscriminatorMismatch)?;
+    if vault.is_debug {
+        return Ok(vault);
+    }
+    if !vault.has_compressed_liveness {
+        return Err(ErrorCode::CompressedLivenessNotFound);
+    }
+
+    let address_tree = address_tree.ok_or(ErrorCode::InvalidLightRoot)?;
+    let (expected, _) = match vault.compressed_liveness_version {
+        CompressedLivenessVersion::V1 => {
+            CompressedLiveness::derive_address(&vault.seed_testator, &vault.key(), address_tree, &crate::ID)
+        }
+        CompressedLivenessVersion::V2 => {
+            CompressedLivenessV2::derive_address(&vault.seed_testator, &vault.key(), address_tree, &crate::ID)
+        }
+    };
+    if expected != claim.address {
+        return Err(ErrorCode::InvalidLightProof);
+    }
+    let ping_count_possible = vault.compressed_liveness_version == CompressedLivenessVersion::V1
+        || claim.ping_count <= vault.ping_count;
+    if !(vault.created_at..=now).contains(&claim.last_ping) || !ping_count_possible {
+        return Err(ErrorCode::InvalidCompressedLivenessClaim);
+    }
+    Ok(vault)
+}
+
+/// Vault PDA of `testator` and `beneficiary` with `vault_nonce`:
+/// `[b"vault", testator, beneficiary, [vault_nonce]]`. `testator` and `beneficiary` are
+/// the ones the vault was created for (`Vault::seed_testator`, `Vault::seed_beneficiary`),
+/// even after a testator rotation or beneficiary change.
+pub fn compute_vault_pda(testator: &Pubkey, beneficiary: &Pubkey, vault_nonce: u8) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[b"vault", testator.as_ref(), beneficiary.as_ref(), &[vault_nonce]],
+        &crate::ID,
+    )
+}
+
+/// The Token-2022 transfer fee on `amount` in the current epoch, or `None` if the
+/// mint has no transfer fee extension (always the case for SPL Token mints)
+fn token_transfer_fee(
+    mint: &InterfaceAccount<'_, anchor_spl::token_interface::Mint>,
+    amount: u64,
+) -> Result<Option<u64>> {
+    use anchor_spl::token_2022::spl_token_2022::{
+        extension::{transfer_fee::TransferFeeConfig, BaseStateWithExtensions, StateWithExtensions},
+        state::Mint,
+    };
+
+    let mint_info = mint.to_account_info();
+    if *mint_info.owner != anchor_spl::token_2022::ID {
+        return Ok(None);
+    }
+    let data = mint_info.try_borrow_data()?;
+    let state = StateWithExtensions::<Mint>::unpack(&data)?;
+    match state.get_extension::<TransferFeeConfig>() {
+        Ok(config) => Ok(Some(
+            config
+                .calculate_epoch_fee(Clock::get()?.epoch, amount)
+                .ok_or(ErrorCode::ArithmeticOverflow)?,
+        )),
+        Err(_) => Ok(None),
+    }
+}
+
+/// Transfer a vault token account's whole balance to `destination`, then close it
+/// and return its rent to `rent_destination`. Signed by the vault PDA. Transfer hook
+/// extra accounts are resolved from `hook_accounts`, and fees withheld on the vault's
+/// account are harvested to the mint first so it can be closed. Returns the transfer
+/// fee withheld from `destination` (0 without the extension).
+fn transfer_and_close_vault_tokens<'info>(
+    vault: &Account<'info, Vault>,
+    mint: &InterfaceAccount<'info, anchor_spl::token_interface::Mint>,
+    vault_token_account: &InterfaceAccount<'info, anchor_spl::token_interface::TokenAccount>,
+    destination: &InterfaceAccount<'info, anchor_spl::token_interface::TokenAccount>,
+    rent_destination: &AccountInfo<'info>,
+    token_program: &Interface<'info, anchor_spl::token_interface::TokenInterface>,
+    hook_accounts: &[AccountInfo<'info>],
+) -> Result<u64> {
+    let signer_seeds: &[&[u8]] = &[
+        b"vault",
+        vault.seed_testator.as_ref(),
+        vault.seed_beneficiary.as_ref(),
+        &[vault.vault_nonce],
+        &[vault.bump],
+    ];
+    let amount = vault_token_account.amount;
+    let transfer_fee = token_transfer_fee(mint, amount)?;
+
+    anchor_spl::token_2022::spl_token_2022::onchain::invoke_transfer_checked(
+        token_program.key,
+        vault_token_account.to_account_info(),
+        mint.to_account_info(),
+        destination.to_account_info(),
+        vault.to_account_info(),
+        hook_accounts,
+        amount,
+        mint.decimals,
+        &[signer_seeds],
+    )?;
+    if transfer_fee.is_some() {
+        anchor_spl::token_2022_extensions::transfer_fee::harvest_withheld_tokens_to_mint(
+            CpiContext::new(
+                token_program.to_account_info(),
+                anchor_spl::token_2022_extensions::transfer_fee::HarvestWithheldTokensToMint {
+                    token_program_id: token_program.to_account_info(),
+                    mint: mint.to_account_info(),
+                },
+            ),
+            vec![vault_token_account.to_account_info()],
+        )?;
+    }
+    anchor_spl::token_interface::close_account(CpiContext::new_with_signer(
+        token_program.to_account_info(),
+        anchor_spl::token_interface::CloseAccount {
+            account: vault_token_account.to_account_info(),
+            destination: rent_destination.clone(),
+            authority: vault.to_account_info(),
+        },
+        &[signer_seeds],
+    ))?;
+
+    Ok(transfer_fee.unwrap_or(0))
+}
+
+/// Move a pNFT between `accounts.signer` and the vault through Token Metadata's
+/// `TransferV1`: into the vault when `to_vault`, otherwise out of it, signed by the
+/// vault PDA. Token Metadata creates the destination token account and token record.
+/// The vault's emptied token account stays open.
+#[cfg(feature = "pnft")]
+fn transfer_vault_pnft(accounts: &TransferVaultPnft, to_vault: bool) -> Result<()> {
+    use anchor_spl::associated_token::get_associated_token_address;
+    use anchor_spl::metadata::mpl_token_metadata::instructions::TransferV1CpiBuilder;
+
+    let vault = &accounts.vault;
+    let mint = accounts.mint.key();
+    let vault_info = vault.to_account_info();
+    let signer_info = accounts.signer.to_account_info();
+    let (source_owner, destination_owner) = if to_vault {
+        (&signer_info, &vault_info)
+    } else {
+        (&vault_info, &signer_info)
+    };
+    accounts
+        .source_token
+        .assert_key_matches(&get_associated_token_address(source_owner.key, &mint), ErrorCode::Unauthorized)?;
+    accounts.destination_token.assert_key_matches(
+        &get_associated_token_address(destination_owner.key, &mint),
+        ErrorCode::Unauthorized,
+    )?;
+
+    let token_metadata_program = accounts.token_metadata_program.to_account_info();
+    let source_token = accounts.source_token.to_account_info();
+    let destination_token = accounts.destination_token.to_account_info();
+    let mint_info = accounts.mint.to_account_info();
+    let metadata = accounts.metadata.to_account_info();
+    let edition = accounts.edition.to_account_info();
+    let source_token_record = accounts.source_token_record.to_account_info();
+    let destination_token_record = accounts.destination_token_record.to_account_info();
+    let system_program = accounts.system_program.to_account_info();
+    let sysvar_instructions = accounts.sysvar_instructions.to_account_info();
+    let token_program = accounts.token_program.to_account_info();
+    let associated_token_program = accounts.associated_token_program.to_account_info();
+    let authorization_rules_program = accounts
+        .authorization_rules_program
+        .as_ref()
+        .map(|program| program.to_account_info());
+    let authorization_rules = accounts.authorization_rules.as_ref().map(|rules| rules.to_account_info());
+
+    let mut transfer = TransferV1CpiBuilder::new(&token_metadata_program);
+    transfer
+        .token(&source_token)
+        .token_owner(source_owner)
+        .destination_token(&destination_token)
+        .destination_owner(destination_owner)
+        .mint(&mint_info)
+        .metadata(&metadata)
+        .edition(Some(&edition))
+        .token_record(Some(&source_token_record))
+        .destination_token_record(Some(&destination_token_record))
+        .authority(source_owner)
+        .payer(&signer_info)
+        .system_program(&system_program)
+        .sysvar_instructions(&sysvar_instructions)
+        .spl_token_program(&token_program)
+        .spl_ata_program(&associated_token_program)
+        .authorization_rules_program(authorization_rules_program.as_ref())
+        .authorization_rules(authorization_rules.as_ref())
+        .amount(1);
+
+    if to_vault {
+        transfer.invoke()?;
+    } else {
+        let signer_seeds: &[&[u8]] = &[
+            b"vault",
+            vault.seed_testator.as_ref(),
+            vault.seed_beneficiary.as_ref(),
+            &[vault.vault_nonce],
+            &[vault.bump],
+        ];
+        transfer.invoke_signed(&[signer_seeds])?;
+    }
+    Ok(())
+}
+
+/// Move a compressed NFT between `accounts.signer` and the vault with Bubblegum's
+/// `transfer`: into the vault when `to_vault`, otherwise out of it, signed by the
+/// vault PDA as leaf owner. `proof` is the Merkle proof path.
+fn transfer_vault_cnft<'info>(
+    accounts: &TransferVaultCnft<'info>,
+    proof: &[AccountInfo<'info>],
+    leaf: &CnftLeaf,
+    to_vault: bool,
+) -> Result<()> {
+    use anchor_lang::solana_program::instruction::{AccountMeta, Instruction};
+
+    let vault = &accounts.vault;
+    let vault_info = vault.to_account_info();
+    let signer_info = accounts.signer.to_account_info();
+    let (leaf_owner, new_leaf_owner) = if to_vault {
+        (&signer_info, &vault_info)
+    } else {
+        (&vault_info, &signer_info)
+    };
+
+    let mut data = BUBBLEGUM_TRANSFER_DISCRIMINATOR.to_vec();
+    data.extend_from_slice(&leaf.root);
+    data.extend_from_slice(&leaf.data_hash);
+    data.extend_from_slice(&leaf.creator_hash);
+    data.extend_from_slice(&leaf.nonce.to_le_bytes());
+    data.extend_from_slice(&leaf.index.to_le_bytes());
+
+    let mut metas = vec![
+        AccountMeta::new_readonly(accounts.tree_config.key(), false),
+        AccountMeta::new_readonly(leaf_owner.key(), true),
+        AccountMeta::new_readonly(accounts.leaf_delegate.key(), false),
+        AccountMeta::new_readonly(new_leaf_owner.key(), false),
+        AccountMeta::new(accounts.merkle_tree.key(), false),
+        AccountMeta::new_readonly(accounts.log_wrapper.key(), false),
+        AccountMeta::new_readonly(accounts.compression_program.key(), false),
+        AccountMeta::new_readonly(accounts.system_program.key(), false),
+    ];
+    metas.extend(proof.iter().map(|node| AccountMeta::new_readonly(node.key(), false)));
+
+    let mut infos = vec![
+        accounts.tree_config.to_account_info(),
+        leaf_owner.clone(),
+        accounts.leaf_delegate.to_account_info(),
+        new_leaf_owner.clone(),
+        accounts.merkle_tree.to_account_info(),
+        accounts.log_wrapper.to_account_info(),
+        accounts.compression_program.to_account_info(),
+        accounts.system_program.to_account_info(),
+        accounts.bubblegum_program.to_account_info(),
+    ];
+    infos.extend_from_slice(proof);
+
+    let instruction = Instruction {
+        program_id: BUBBLEGUM_PROGRAM_ID,
+        accounts: metas,
+        data,
+    };
+    if to_vault {
+        anchor_lang::solana_program::program::invoke(&instruction, &infos)?;
+    } else {
+        let signer_seeds: &[&[u8]] = &[
+            b"vault",
+            vault.seed_testator.as_ref(),
+            vault.seed_beneficiary.as_ref(),
+            &[vault.vault_nonce],
+            &[vault.bump],
+        ];
+        anchor_lang::solana_program::program::invoke_signed(&instruction, &infos, &[signer_seeds])?;
+    }
+
+    let (asset_id, _) = Pubkey::find_program_address(
+        &[b"asset", accounts.merkle_tree.key().as_ref(), &leaf.nonce.to_le_bytes()],
+        &BUBBLEGUM_PROGRAM_ID,
+    );
+    emit!(CnftTransferred {
+        vault: vault.key(),
+        asset_id,
+        from: leaf_owner.key(),
+        to: new_leaf_owner.key(),
+    });
+
+    Ok(())
+}
+
+/// Records `guardian`'s approval and, once `guardian_set.threshold` is met, hands the
+/// vault to the pending testator and clears the proposal.
+fn schedule_testator_rotation_if_approved(
+    vault: &Account<Vault>,
+    guardian_set: &mut Account<GuardianSet>,
+    guardian: Pubkey,
+    now: i64,
+) {
+    let approvals = guardian_set.approval_count();
+    if approvals >= guardian_set.threshold && guardian_set.rotation_effective_at == 0 {
+        guardian_set.rotation_effective_at = now.saturating_add(guardian_set.rotation_delay_secs);
+    }
+    emit!(TestatorRotationApproved {
+        vault: vault.key(),
+        guardian,
+        approvals,
+        threshold: guardian_set.threshold,
+        effective_at: guardian_set.rotation_effective_at,
+    });
+}
+
+/// Proves through a read-only Light CPI that the vault's compressed liveness account
+/// holds `last_ping`, then checks that the vault's timeout has passed since it.
+/// `light_accounts` are the Light system accounts, address tree first. Debug vaults
+/// have no compressed account and only get the timeout check.
+fn verify_compressed_last_ping<'info>(
+    fee_payer: &AccountInfo<'info>,
+    light_accounts: &[AccountInfo<'info>],
+    vault: &Account<'info, Vault>,
+    proof_data: &ValidityProofData,
+    last_ping: i64,
+    now: i64,
+) -> Result<()> {
+    if !vault.is_debug {
+        require!(vault.has_compressed_liveness, ErrorCode::CompressedLivenessNotFound);
+        let proof = LightValidityProof::try_from_slice(&proof_data.data)
+            .map_err(|_| ErrorCode::InvalidLightProof)?;
+        let address_tree_pubkey = light_accounts
+            .first()
+            .ok_or(ErrorCode::InvalidLightRoot)?
+            .key();
+        let light_cpi_accounts = CpiAccounts::new(fee_payer, light_accounts, crate::LIGHT_CPI_SIGNER);
+
+        // The proof only verifies if the account holds exactly these fields
+        let cpi = LightSystemProgramCpi::new_cpi(crate::LIGHT_CPI_SIGNER, proof);
+        let cpi = match vault.compressed_liveness_version {
+            CompressedLivenessVersion::V1 => {
+                let (address, _) = CompressedLiveness::derive_address(
+                    &vault.seed_testator,
+                    &vault.key(),
+                    &address_tree_pubkey,
+                    &crate::ID,
+                );
+                let mut liveness_account =
+                    LightAccount::<CompressedLiveness>::new_read_only(&crate::ID, Some(address), 0);
+                liveness_account.testator = vault.seed_testator;
+                liveness_account.last_ping = last_ping;
+                liveness_account.vault_address = vault.key();
+                cpi.with_light_account(liveness_account)
+            }
+            CompressedLivenessVersion::V2 => {
+                let (address, _) = CompressedLivenessV2::derive_address(
+                    &vault.seed_testator,
+                    &vault.key(),
+                    &address_tree_pubkey,
+                    &crate::ID,
+                );
+                let mut liveness_account =
+                    LightAccount::<CompressedLivenessV2>::new_read_only(&crate::ID, Some(address), 0);
+                liveness_account.testator = vault.seed_testator;
+                liveness_account.last_ping = last_ping;
+                liveness_account.vault_address = vault.key();
+                liveness_account.ping_count = vault.ping_count;
+                liveness_account.last_guardian_ping = 0;
+                liveness_account.compression_created_at = vault.compression_created_at;
+                cpi.with_light_account(liveness_account)
+            }
+        }
+        .map_err(|_| ErrorCode::InvalidLightProof)?;
+        cpi.invoke(light_cpi_accounts)
+            .map_err(|_| ErrorCode::InvalidLightProof)?;
+    }
+
+    require!(
+        now.saturating_sub(last_ping) > vault.effective_timeout_secs(),
+        ErrorCode::CompressedLivenessTimeoutNotReached
+    );
+    Ok(())
+}
+
+/// Closes the vault's compressed liveness account (V1 or V2, per
+/// `Vault::compressed_liveness_version`), whose current `last_ping` and, for V2,
+/// `ping_count` are claimed by the caller and checked by the proof. Returns its address.
+fn invoke_close_compressed_liveness<'info>(
+    fee_payer: &Signer<'info>,
+    remaining_accounts: &[AccountInfo<'info>],
+    vault: &Account<'info, Vault>,
+    last_ping: i64,
+    ping_count: u64,
+    proof_data: &ValidityProofData,
+    output_tree_index: u8,
+) -> Result<[u8; 32]> {
+    let proof = LightValidityProof::try_from_slice(&proof_data.data)
+        .map_err(|_| ErrorCode::InvalidLightProof)?;
+
+    let light_cpi_accounts = CpiAccounts::new(
+        fee_payer.as_ref(),
+        remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let address_tree_pubkey = remaining_accounts
+        .get(0)
+        .ok_or(ErrorCode::InvalidLightRoot)?
+        .key();
+
+    // The closed account's current state, which the proof is checked against
+    let cpi = LightSystemProgramCpi::new_cpi(crate::LIGHT_CPI_SIGNER, proof);
+    let (address, cpi) = match vault.compressed_liveness_version {
+        CompressedLivenessVersion::V1 => {
+            let (address, _) = CompressedLiveness::derive_address(
+                &vault.seed_testator,
+                &vault.key(),
+                &address_tree_pubkey,
+                &crate::ID,
+            );
+            let mut liveness_account =
+                LightAccount::<CompressedLiveness>::new_close(&crate::ID, Some(address), output_tree_index);
+            liveness_account.testator = vault.seed_testator;
+            liveness_account.last_ping = last_ping;
+            liveness_account.vault_address = vault.key();
+            (address, cpi.with_light_account(liveness_account))
+        }
+        CompressedLivenessVersion::V2 => {
+            let (address, _) = CompressedLivenessV2::derive_address(
+                &vault.seed_testator,
+                &vault.key(),
+                &address_tree_pubkey,
+                &crate::ID,
+            );
+            let mut liveness_account =
+                LightAccount::<CompressedLivenessV2>::new_close(&crate::ID, Some(address), output_tree_index);
+            liveness_account.testator = vault.seed_testator;
+            liveness_account.last_ping = last_ping;
+            liveness_account.vault_address = vault.key();
+            liveness_account.ping_count = ping_count;
+            liveness_account.last_guardian_ping = 0;
+            liveness_account.compression_created_at = vault.compression_created_at;
+            (address, cpi.with_light_account(liveness_account))
+        }
+    };
+
+    cpi.map_err(|_| ErrorCode::InvalidLightProof)?
+        .invoke(light_cpi_accounts)
+        .map_err(|_| ErrorCode::InvalidLightProof)?;
+
+    Ok(address)
+}
+
+/// Length of the account compression program's header on a V1 state Merkle tree
+/// account: the 8-byte discriminator and the 216-byte `MerkleTreeMetadata`.
+const LIGHT_STATE_TREE_HEADER_LEN: usize = 8 + 216;
+
+/// The root history of a Light V1 state Merkle tree account's data: the stored roots,
+/// 32 bytes each, and the index of the current one. The concurrent Merkle tree after
+/// the header starts with `height`, `canopy_depth`, `next_index`, `sequence_number`
+/// (u64 each) and the rightmost leaf, then the metadata of its vectors: filled subtrees
+/// and canopy (capacity, length), changelog and roots (capacity, length, first index,
+/// last index), in the order filled subtrees, changelog, roots, canopy. The vectors'
+/// contents follow in the same order. `None` if the data doesn't fit that layout.
+fn light_state_tree_roots(data: &[u8]) -> Option<(&[u8], usize)> {
+    let word = |offset: usize| -> Option<usize> {
+        let bytes = data.get(offset..offset.checked_add(8)?)?;
+        usize::try_from(u64::from_le_bytes(bytes.try_into().ok()?)).ok()
+    };
+    let height = word(LIGHT_STATE_TREE_HEADER_LEN)?;
+    let vectors = LIGHT_STATE_TREE_HEADER_LEN + 4 * 8 + 32;
+    let filled_subtrees_capacity = word(vectors)?;
+    let changelog_capacity = word(vectors + 16)?;
+    let roots_capacity = word(vectors + 48)?;
+    let roots_length = word(vectors + 56)?;
+    let current = word(vectors + 72)?;
+    if roots_length == 0 || roots_length > roots_capacity || current >= roots_length {
+        return None;
+    }
+
+    // A changelog entry is the path (`height` nodes) and the leaf index
+    let changelog_entry_len = height.checked_mul(32)?.checked_add(8)?;
+    let roots_start = (vectors + 96)
+        .checked_add(filled_subtrees_capacity.checked_mul(32)?)?
+        .checked_add(changelog_capacity.checked_mul(changelog_entry_len)?)?;
+    let roots = data.get(roots_start..roots_start.checked_add(roots_length.checked_mul(32)?)?)?;
+    Some((roots, current))
+}
+
+/// Current root of the Light state Merkle tree `state_tree`
+pub fn light_state_root(state_tree: &AccountInfo) -> Result<[u8; 32]> {
+    require_keys_eq!(
+        *state_tree.owner,
+        LIGHT_ACCOUNT_COMPRESSION_PROGRAM_ID,
+        ErrorCode::InvalidLightRoot
+    );
+    let data = state_tree.try_borrow_data()?;
+    let (roots, current) = light_state_tree_roots(&data).ok_or(ErrorCode::InvalidLightRoot)?;
+    let mut root = [0u8; 32];
+    root.copy_from_slice(&roots[32 * current..32 * (current + 1)]);
+    Ok(root)
+}
+
+/// Whether `root` is still in the root history of the Light state Merkle tree
+/// `state_tree`
+pub fn is_light_state_root(state_tree: &AccountInfo, root: &[u8; 32]) -> Result<bool> {
+    require_keys_eq!(
+        *state_tree.owner,
+        LIGHT_ACCOUNT_COMPRESSION_PROGRAM_ID,
+        ErrorCode::InvalidLightRoot
+    );
+    let data = state_tree.try_borrow_data()?;
+    let (roots, _) = light_state_tree_roots(&data).ok_or(ErrorCode::InvalidLightRoot)?;
+    Ok(roots.chunks_exact(32).any(|stored| constant_time_eq(stored, root)))
+}
+
+/// Light state root that K_light is derived from when the program wraps a key: the
+/// current root of the state Merkle tree passed as the first remaining account.
+#[cfg(not(feature = "mock-light"))]
+fn key_wrap_root(remaining_accounts: &[AccountInfo], _vault: &Vault, _now: i64) -> Result<[u8; 32]> {
+    light_state_root(remaining_accounts.first().ok_or(ErrorCode::InvalidLightRoot)?)
+}
+
+/// `mock-light` builds (local tests without Light Protocol) derive the root from the
+/// testator and the time instead.
+#[cfg(feature = "mock-light")]
+fn key_wrap_root(_remaining_accounts: &[AccountInfo], vault: &Vault, now: i64) -> Result<[u8; 32]> {
+    Ok(real_hash(&[vault.testator.as_ref(), &now.to_le_bytes()].concat()))
+}
+
+/// Checks that a client-supplied `root` is in the root history of the Light state Merkle
+/// tree passed as the first remaining account.
+#[cfg(not(feature = "mock-light"))]
+fn check_light_state_root(remaining_accounts: &[AccountInfo], root: &[u8; 32]) -> Result<()> {
+    let state_tree = remaining_accounts.first().ok_or(ErrorCode::InvalidLightRoot)?;
+    require!(is_light_state_root(state_tree, root)?, ErrorCode::InvalidLightRoot);
+    Ok(())
+}
+
+/// `mock-light` builds accept any root.
+#[cfg(feature = "mock-light")]
+fn check_light_state_root(_remaining_accounts: &[AccountInfo], _root: &[u8; 32]) -> Result<()> {
+    Ok(())
+}
+
+/// Closes a vault sub-account PDA to `destination` if it was ever created. Sub-accounts
+/// only exist once an instruction needed them, so an uncreated one is skipped.
+fn close_if_created<'info>(account: &AccountInfo<'info>, destination: &AccountInfo<'info>) -> Result<()> {
+    if account.owner == &crate::ID {
+        anchor_lang::common::close(account.clone(), destination.clone())?;
+    }
+    Ok(())
+}
+
+/// Pays the vault's crank bounty, capped by its SOL balance (`Vault::lamports`), to
+/// `cranker`. Returns the lamports paid.
+fn pay_crank_bounty(vault: &mut Account<Vault>, cranker: &Signer) -> Result<u64> {
+    let bounty = vault.crank_bounty_lamports.min(vault.lamports);
+    if bounty == 0 {
+        return Ok(0);
+    }
+
+    vault.lamports -= bounty;
+    let vault_account_info = vault.to_account_info();
+    **vault_account_info.try_borrow_mut_lamports()? -= bounty;
+    **cranker.to_account_info().try_borrow_mut_lamports()? += bounty;
+    check_rent_exempt(&vault_account_info)?;
+    vault.refresh_content_hash();
+
+    Ok(bounty)
+}
+
+/// The message a testator signs off-chain for `update_liveness_relayed`
+fn relayed_ping_message(
+    vault: &Pubkey,
+    nonce: u64,
+    signed_at: i64,
+    proof_nonce: &[u8; 8],
+) -> Vec<u8> {
+    let mut message = Vec::with_capacity(RELAYED_PING_DOMAIN.len() + 32 + 8 + 8 + 8);
+    message.extend_from_slice(RELAYED_PING_DOMAIN);
+    message.extend_from_slice(vault.as_ref());
+    message.extend_from_slice(&nonce.to_le_bytes());
+    message.extend_from_slice(&signed_at.to_le_bytes());
+    message.extend_from_slice(proof_nonce);
+    message
+}
+
+/// The message a verifier signs off-chain for `execute_inheritance_with_attestation`
+fn verifier_attestation_message(
+    vault: &Pubkey,
+    beneficiary: &Pubkey,
+    nonce: u64,
+    expires_at: i64,
+) -> Vec<u8> {
+    let mut message = Vec::with_capacity(VERIFIER_ATTESTATION_DOMAIN.len() + 32 + 32 + 8 + 8);
+    message.extend_from_slice(VERIFIER_ATTESTATION_DOMAIN);
+    message.extend_from_slice(vault.as_ref());
+    message.extend_from_slice(beneficiary.as_ref());
+    message.extend_from_slice(&nonce.to_le_bytes());
+    message.extend_from_slice(&expires_at.to_le_bytes());
+    message
+}
+
+#[derive(Accounts)]
+#[instruction(beneficiary: Pubkey, vault_nonce: u8)]
+pub struct InitInheritance<'info> {
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + Vault::SIZE,
+        seeds = [b"vault", testator.key().as_ref(), beneficiary.as_ref(), &[vault_nonce]],
+        bump
+    )]
+    pub vault: Account<'info, Vault>,
+
+    /// The testator who owns this will (must sign to prove ownership)
+    pub testator: Signer<'info>,
+
+    /// The payer who funds the vault creation and initial deposit
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    /// Optional protocol config, enforcing the protocol-wide vault cap
+    #[account(
+        mut,
+        seeds = [b"protocol_config"],
+        bump = config.bump
+    )]
+    pub config: Option<Account<'info, ProtocolConfig>>,
+
+    /// Optional index from the new vault's id to its PDA, for `lookup_vault_by_id`
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + VaultIdIndex::SIZE,
+        seeds = [b"vid_index", &config.as_ref().map_or(0, |c| c.next_vault_id).to_le_bytes()],
+        bump
+    )]
+    pub vault_id_index: Option<Account<'info, VaultIdIndex>>,
+
+    /// Optional per-testator registry, enforcing `max_vaults_per_testator`
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = 8 + VaultRegistry::SIZE,
+        seeds = [b"registry", testator.key().as_ref()],
+        bump
+    )]
+    pub vault_registry: Option<Account<'info, VaultRegistry>>,
+
+    /// Optional fee waiver, skipping the vault creation fee while valid
+    #[account(
+        mut,
+        seeds = [b"fee_waiver", testator.key().as_ref()],
+        bump = fee_waiver.bump
+    )]
+    pub fee_waiver: Option<Account<'info, FeeWaiver>>,
+
+    /// Optional verifier registry, required when `config.require_registered_verifier` is on
+    #[account(
+        seeds = [b"verifier_registry"],
+        bump = verifier_registry.bump
+    )]
+    pub verifier_registry: Option<Account<'info, VerifierRegistry>>,
+
+    /// CHECK: Must be `config.treasury`; receives the vault creation fee
+    #[account(mut)]
+    pub treasury: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: SlotHashes sysvar, seeds the vault's execution nonce
+    #[account(address = anchor_lang::solana_program::sysvar::slot_hashes::ID)]
+    pub recent_slothashes: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Accounts for creating a compressed liveness account in Light Protocol
+#[derive(Accounts)]
+pub struct CreateCompressedLiveness<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault", vault.seed_testator.as_ref(), vault.seed_beneficiary.as_ref(), &[vault.vault_nonce]],
+        bump = vault.bump,
+        has_one = testator @ ErrorCode::Unauthorized
+    )]
+    pub vault: Account<'info, Vault>,
+    
+    #[account(mut)]
+    pub testator: Signer<'info>,
+    
+    #[account(mut)]
+    pub fee_payer: Signer<'info>,
+
+    /// Optional protocol config; when V2 is enabled, V1 creation is rejected
+    #[account(
+        seeds = [b"protocol_config"],
+        bump = config.bump
+    )]
+    pub config: Option<Account<'info, ProtocolConfig>>,
+
+    /// Optional nullifier registry, required when `config.extra_nullifier_check` is on
+    #[account(
+        mut,
+        seeds = [b"nullifier_registry"],
+        bump = nullifier_registry.bump
+    )]
+    pub nullifier_registry: Option<Account<'info, NullifierRegistry>>,
+
+    /// Proof nonces already accepted for this vault
+    #[account(
+        init_if_needed,
+        payer = fee_payer,
+        space = 8 + UsedProofNonces::SIZE,
+        seeds = [b"used_nonces", vault.key().as_ref()],
+        bump
+    )]
+    pub used_nonces: Account<'info, UsedProofNonces>,
+
+    pub system_program: Program<'info, System>,
+
+    // Light Protocol system accounts are passed via remaining_accounts
+}
+
+/// Accounts for updating liveness via Light Protocol
+#[derive(Accounts)]
+pub struct UpdateLiveness<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault", vault.seed_testator.as_ref(), vault.seed_beneficiary.as_ref(), &[vault.vault_nonce]],
+        bump = vault.bump
+    )]
+    pub vault: Account<'info, Vault>,
+    
+    /// The testator, the custody agent during an active custody window, or an unexpired
+    /// ping delegate. Checked by `process_liveness_update`
+    #[account(mut)]
+    pub testator: Signer<'info>,
+    
+    #[account(mut)]
+    pub fee_payer: Signer<'info>,
+
+    /// Optional state history, updated when the ping changes the vault state
+    #[account(
+        mut,
+        seeds = [b"state_history", vault.key().as_ref()],
+        bump = state_history.bump
+    )]
+    pub state_history: Option<Account<'info, VaultStateHistory>>,
+
+    pub system_program: Program<'info, System>,
+
+    /// Optional protocol config, for the extra nullifier check
+    #[account(
+        seeds = [b"protocol_config"],
+        bump = config.bump
+    )]
+    pub config: Option<Account<'info, ProtocolConfig>>,
+
+    /// Optional nullifier registry, required when `config.extra_nullifier_check` is on
+    #[account(
+        mut,
+        seeds = [b"nullifier_registry"],
+        bump = nullifier_registry.bump
+    )]
+    pub nullifier_registry: Option<Account<'info, NullifierRegistry>>,
+
+    /// Proof nonces already accepted for this vault
+    #[account(
+        init_if_needed,
+        payer = fee_payer,
+        space = 8 + UsedProofNonces::SIZE,
+        seeds = [b"used_nonces", vault.key().as_ref()],
+        bump
+    )]
+    pub used_nonces: Account<'info, UsedProofNonces>,
+
+    // Light Protocol system accounts are passed via remaining_accounts:
+    // - Address Merkle Tree
+    // - State Tree
+    // - Light System Program
+    // These are dynamically provided by the Light SDK client
+}
+
+// Removed InitLightRegistry - in production, Light Protocol manages its own state trees
+// For testing, we use a mock LightProtocolState account
+
+#[account]
+pub struct LightProtocolState {
+    pub current_root: [u8; 32],
+}
+
+#[derive(Accounts)]
+pub struct InitLightRegistry<'info> {
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + 32
+    )]
+    pub light_state: Account<'info, LightProtocolState>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteInheritance<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault", vault.seed_testator.as_ref(), vault.seed_beneficiary.as_ref(), &[vault.vault_nonce]],
+        bump = vault.bump
+    )]
+    pub vault: Account<'info, Vault>,
+
+    /// CHECK: Must be the vault's testator
+    #[account(address = vault.testator @ ErrorCode::Unauthorized)]
+    pub testator: AccountInfo<'info>,
+
+    /// Checked against `vault.beneficiary` by `verify_execution_parties`
+    #[account(mut)]
+    pub beneficiary: Signer<'info>,
+
+    /// The Oracle/Verifier that confirms the biometric face match
+    pub verifier: Signer<'info>,
+
+    /// Checked for an emergency execution pause
+    #[account(
+        seeds = [b"protocol_config"],
+        bump = config.bump
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+
+    /// Optional time capsule message, revealed atomically with the inheritance
+    #[account(
+        mut,
+        seeds = [b"capsule", vault.key().as_ref()],
+        bump = time_capsule.bump
+    )]
+    pub time_capsule: Option<Account<'info, TimeCapsuleMessage>>,
+
+    /// Escrow for the inheritance tax, required when the vault has tax configured
+    #[account(
+        init_if_needed,
+        payer = beneficiary,
+        space = 8 + TaxEscrow::SIZE,
+        seeds = [b"tax_escrow", vault.key().as_ref()],
+        bump
+    )]
+    pub tax_escrow: Option<Account<'info, TaxEscrow>>,
+
+    /// Optional state history, records the Claimable -> Executed transition
+    #[account(
+        mut,
+        seeds = [b"state_history", vault.key().as_ref()],
+        bump = state_history.bump
+    )]
+    pub state_history: Option<Account<'info, VaultStateHistory>>,
+
+    pub system_program: Program<'info, System>,
+
+    /// CHECK: SPL Memo program, only needed by `execute_inheritance_with_memo`
+    #[account(address = SPL_MEMO_PROGRAM_ID)]
+    pub memo_program: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: Instructions sysvar, only needed by `execute_inheritance_with_attestation`
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    pub instructions_sysvar: Option<UncheckedAccount<'info>>,
+}
+
+#[derive(Accounts)]
+#[instruction(identity_hash: [u8; 32])]
+pub struct VerifyBeneficiaryIdentity<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault", vault.seed_testator.as_ref(), vault.seed_beneficiary.as_ref(), &[vault.vault_nonce]],
+        bump = vault.bump,
+    )]
+    pub vault: Account<'info, Vault>,
+
+    #[account(address = vault.beneficiary @ ErrorCode::Unauthorized)]
+    pub beneficiary: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CancelWill<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault", vault.seed_testator.as_ref(), vault.seed_beneficiary.as_ref(), &[vault.vault_nonce]],
+        bump = vault.bump,
+        has_one = testator @ ErrorCode::Unauthorized,
+        close = testator
+    )]
+    pub vault: Account<'info, Vault>,
+
+    #[account(mut)]
+    pub testator: Signer<'info>,
+
+    /// Optional protocol config, released from the protocol-wide vault count
+    #[account(
+        mut,
+        seeds = [b"protocol_config"],
+        bump = config.bump
+    )]
+    pub config: Option<Account<'info, ProtocolConfig>>,
+
+    /// Optional per-testator registry, released from the testator's vault count
+    #[account(
+        mut,
+        seeds = [b"registry", vault.seed_testator.as_ref()],
+        bump
+    )]
+    pub vault_registry: Option<Account<'info, VaultRegistry>>,
+
+    /// CHECK: Receives half of a forfeited destruction bond
+    #[account(mut, address = vault.beneficiary @ ErrorCode::Unauthorized)]
+    pub beneficiary: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: Must be `config.treasury`; receives the other half of a forfeited bond
+    #[account(mut)]
+    pub treasury: Option<UncheckedAccount<'info>>,
+}
+
+#[derive(Accounts)]
+pub struct InitVaultFundingAddress<'info> {
+    #[account(
+        seeds = [b"vault", vault.seed_testator.as_ref(), vault.seed_beneficiary.as_ref(), &[vault.vault_nonce]],
+        bump = vault.bump,
+    )]
+    pub vault: Account<'info, Vault>,
+
+    /// Pass-through system account that anyone can send SOL to
+    #[account(
+        mut,
+        seeds = [b"funding", vault.key().as_ref()],
+        bump
+    )]
+    pub funding_address: SystemAccount<'info>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CollectVaultDonations<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault", vault.seed_testator.as_ref(), vault.seed_beneficiary.as_ref(), &[vault.vault_nonce]],
+        bump = vault.bump,
+    )]
+    pub vault: Account<'info, Vault>,
+
+    #[account(
+        mut,
+        seeds = [b"funding", vault.key().as_ref()],
+        bump
+    )]
+    pub funding_address: SystemAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct AddEmergencyContact<'info> {
+    #[account(
+        seeds = [b"vault", vault.seed_testator.as_ref(), vault.seed_beneficiary.as_ref(), &[vault.vault_nonce]],
+        bump = vault.bump,
+        has_one = testator @ ErrorCode::Unauthorized,
+        has_one = verifier @ ErrorCode::InvalidVerifier
+    )]
+    pub vault: Account<'info, Vault>,
+
+    #[account(
+        init_if_needed,
+        payer = testator,
+        space = 8 + EmergencyContactRegistry::SIZE,
+        seeds = [b"contacts", vault.key().as_ref()],
+        bump
+    )]
+    pub contact_registry: Account<'info, EmergencyContactRegistry>,
+
+    #[account(mut)]
+    pub testator: Signer<'info>,
+
+    pub verifier: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ReadEmergencyContacts<'info> {
+    #[account(
+        seeds = [b"vault", vault.seed_testator.as_ref(), vault.seed_beneficiary.as_ref(), &[vault.vault_nonce]],
+        bump = vault.bump,
+        has_one = verifier @ ErrorCode::InvalidVerifier
+    )]
+    pub vault: Account<'info, Vault>,
+
+    #[account(
+        seeds = [b"contacts", vault.key().as_ref()],
+        bump = contact_registry.bump
+    )]
+    pub contact_registry: Account<'info, EmergencyContactRegistry>,
+
+    pub verifier: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct RemoveEmergencyContact<'info> {
+    #[account(
+        seeds = [b"vault", vault.seed_testator.as_ref(), vault.seed_beneficiary.as_ref(), &[vault.vault_nonce]],
+        bump = vault.bump,
+        has_one = testator @ ErrorCode::Unauthorized
+    )]
+    pub vault: Account<'info, Vault>,
+
+    #[account(
+        mut,
+        seeds = [b"contacts", vault.key().as_ref()],
+        bump = contact_registry.bump
+    )]
+    pub contact_registry: Account<'info, EmergencyContactRegistry>,
+
+    pub testator: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SealTimeCapsule<'info> {
+    #[account(
+        seeds = [b"vault", vault.seed_testator.as_ref(), vault.seed_beneficiary.as_ref(), &[vault.vault_nonce]],
+        bump = vault.bump,
+        has_one = testator @ ErrorCode::Unauthorized
+    )]
+    pub vault: Account<'info, Vault>,
+
+    #[account(
+        init_if_needed,
+        payer = testator,
+        space = 8 + TimeCapsuleMessage::SIZE,
+        seeds = [b"capsule", vault.key().as_ref()],
+        bump
+    )]
+    pub time_capsule: Account<'info, TimeCapsuleMessage>,
+
+    #[account(mut)]
+    pub testator: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateCapsule<'info> {
+    #[account(
+        seeds = [b"vault", vault.seed_testator.as_ref(), vault.seed_beneficiary.as_ref(), &[vault.vault_nonce]],
+        bump = vault.bump,
+        has_one = testator @ ErrorCode::Unauthorized
+    )]
+    pub vault: Account<'info, Vault>,
+
+    #[account(
+        mut,
+        seeds = [b"capsule", vault.key().as_ref()],
+        bump = time_capsule.bump
+    )]
+    pub time_capsule: Account<'info, TimeCapsuleMessage>,
+
+    pub testator: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CloseCapsule<'info> {
+    #[account(
+        seeds = [b"vault", vault.seed_testator.as_ref(), vault.seed_beneficiary.as_ref(), &[vault.vault_nonce]],
+        bump = vault.bump,
+        has_one = testator @ ErrorCode::Unauthorized
+    )]
+    pub vault: Account<'info, Vault>,
+
+    #[account(
+        mut,
+        seeds = [b"capsule", vault.key().as_ref()],
+        bump = time_capsule.bump,
+        close = testator
+    )]
+    pub time_capsule: Account<'info, TimeCapsuleMessage>,
+
+    #[account(mut)]
+    pub testator: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct InitProtocolConfig<'info> {
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + ProtocolConfig::SIZE,
+        seeds = [b"protocol_config"],
+        bump
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Accounts for protocol-authority-only configuration changes
+#[derive(Accounts)]
+pub struct UpdateProtocolConfig<'info> {
+    #[account(
+        mut,
+        seeds = [b"protocol_config"],
+        bump = config.bump,
+        has_one = authority @ ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct VerifyLightSdkCompatibility<'info> {
+    #[account(
+        seeds = [b"protocol_config"],
+        bump = config.bump
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+
+    /// CHECK: ProgramData account of the Light System Program, validated via seeds
+    #[account(
+        seeds = [LIGHT_SYSTEM_PROGRAM_ID.as_ref()],
+        bump,
+        seeds::program = anchor_lang::solana_program::bpf_loader_upgradeable::ID
+    )]
+    pub light_program_data: UncheckedAccount<'info>,
+}
+
+/// Accounts for testator-only updates to their vault
+#[derive(Accounts)]
+pub struct TestatorUpdateVault<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault", vault.seed_testator.as_ref(), vault.seed_beneficiary.as_ref(), &[vault.vault_nonce]],
+        bump = vault.bump,
+        has_one = testator @ ErrorCode::Unauthorized
+    )]
+    pub vault: Account<'info, Vault>,
+
+    pub testator: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct MarkTaxPaid<'info> {
+    #[account(
+        mut,
+        seeds = [b"tax_escrow", tax_escrow.vault.as_ref()],
+        bump,
+        has_one = tax_authority @ ErrorCode::Unauthorized
+    )]
+    pub tax_escrow: Account<'info, TaxEscrow>,
+
+    #[account(mut)]
+    pub tax_authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct InitStateHistory<'info> {
+    #[account(
+        seeds = [b"vault", vault.seed_testator.as_ref(), vault.seed_beneficiary.as_ref(), &[vault.vault_nonce]],
+        bump = vault.bump
+    )]
+    pub vault: Account<'info, Vault>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + VaultStateHistory::SIZE,
+        seeds = [b"state_history", vault.key().as_ref()],
+        bump
+    )]
+    pub state_history: Account<'info, VaultStateHistory>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ReadStateHistory<'info> {
+    #[account(
+        seeds = [b"state_history", state_history.vault.as_ref()],
+        bump = state_history.bump
+    )]
+    pub state_history: Account<'info, VaultStateHistory>,
+}
+
+#[derive(Accounts)]
+pub struct PruneStateHistory<'info> {
+    #[account(has_one = testator @ ErrorCode::Unauthorized)]
+    pub vault: Account<'info, Vault>,
+
+    #[account(
+        mut,
+        seeds = [b"state_history", vault.key().as_ref()],
+        bump = state_history.bump
+    )]
+    pub state_history: Account<'info, VaultStateHistory>,
+
+    pub testator: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CreateGovernanceProposal<'info> {
+    #[account(
+        mut,
+        seeds = [b"protocol_config"],
+        bump = config.bump
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+
+    #[account(
+        init,
+        payer = testator,
+        space = 8 + GovernanceProposal::SIZE,
+        seeds = [b"proposal", config.proposal_count.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub proposal: Account<'info, GovernanceProposal>,
+
+    /// The proposer's vault, whose ping count gates the proposal
+    #[account(has_one = testator @ ErrorCode::Unauthorized)]
+    pub vault: Account<'info, Vault>,
+
+    #[account(mut)]
+    pub testator: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(proposal_id: u64)]
+pub struct VoteOnProposal<'info> {
+    #[account(
+        mut,
+        seeds = [b"proposal", proposal_id.to_le_bytes().as_ref()],
+        bump = proposal.bump
+    )]
+    pub proposal: Account<'info, GovernanceProposal>,
+
+    #[account(
+        seeds = [b"vault", vault.seed_testator.as_ref(), vault.seed_beneficiary.as_ref(), &[vault.vault_nonce]],
+        bump = vault.bump,
+        has_one = testator @ ErrorCode::Unauthorized
+    )]
+    pub vault: Account<'info, Vault>,
+
+    /// One vote per vault: initializing the record fails if the vault already voted
+    #[account(
+        init,
+        payer = testator,
+        space = 8 + ProposalVote::SIZE,
+        seeds = [b"vote", proposal.key().as_ref(), vault.key().as_ref()],
+        bump
+    )]
+    pub vote_record: Account<'info, ProposalVote>,
+
+    #[account(mut)]
+    pub testator: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteProposal<'info> {
+    #[account(
+        mut,
+        seeds = [b"protocol_config"],
+        bump = config.bump
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"proposal", proposal.proposal_id.to_le_bytes().as_ref()],
+        bump = proposal.bump
+    )]
+    pub proposal: Account<'info, GovernanceProposal>,
+}
+
+#[derive(Accounts)]
+#[instruction(caller_program_id: Pubkey)]
+pub struct GrantCpiAccess<'info> {
+    #[account(
+        seeds = [b"protocol_config"],
+        bump = config.bump
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+
+    #[account(
+        init,
+        payer = upgrade_proxy_authority,
+        space = 8 + CpiAccessToken::SIZE,
+        seeds = [b"cpi_access", caller_program_id.as_ref()],
+        bump
+    )]
+    pub cpi_access: Account<'info, CpiAccessToken>,
+
+    #[account(mut)]
+    pub upgrade_proxy_authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(caller_program_id: Pubkey)]
+pub struct MigrateVault<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault", vault.seed_testator.as_ref(), vault.seed_beneficiary.as_ref(), &[vault.vault_nonce]],
+        bump = vault.bump
+    )]
+    pub vault: Account<'info, Vault>,
+
+    #[account(
+        seeds = [b"protocol_config"],
+        bump = config.bump
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+
+    /// The calling program's `[b"upgrade_proxy"]` PDA, present for CPI calls
+    pub upgrade_proxy: Option<Signer<'info>>,
+
+    /// Access token of the calling program, required for CPI calls
+    #[account(
+        seeds = [b"cpi_access", caller_program_id.as_ref()],
+        bump = cpi_access.bump
+    )]
+    pub cpi_access: Option<Account<'info, CpiAccessToken>>,
+
+    /// The testator, required for direct (non-CPI) calls
+    pub testator: Option<Signer<'info>>,
+}
+
+/// Accounts for creating (or migrating to) a V2 compressed liveness account
+#[derive(Accounts)]
+pub struct CreateCompressedLivenessV2<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault", vault.seed_testator.as_ref(), vault.seed_beneficiary.as_ref(), &[vault.vault_nonce]],
+        bump = vault.bump,
+        has_one = testator @ ErrorCode::Unauthorized
+    )]
+    pub vault: Account<'info, Vault>,
+
+    #[account(
+        seeds = [b"protocol_config"],
+        bump = config.bump
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+
+    #[account(mut)]
+    pub testator: Signer<'info>,
+
+    #[account(mut)]
+    pub fee_payer: Signer<'info>,
+
+    /// Optional nullifier registry, required when `config.extra_nullifier_check` is on
+    #[account(
+        mut,
+        seeds = [b"nullifier_registry"],
+        bump = nullifier_registry.bump
+    )]
+    pub nullifier_registry: Option<Account<'info, NullifierRegistry>>,
+
+    /// Proof nonces already accepted for this vault
+    #[account(
+        init_if_needed,
+        payer = fee_payer,
+        space = 8 + UsedProofNonces::SIZE,
+        seeds = [b"used_nonces", vault.key().as_ref()],
+        bump
+    )]
+    pub used_nonces: Account<'info, UsedProofNonces>,
+
+    pub system_program: Program<'info, System>,
+
+    // Light Protocol system accounts are passed via remaining_accounts
+}
+
+#[derive(Accounts)]
+pub struct ProposeVerifierMigration<'info> {
+    #[account(
+        init,
+        payer = old_verifier,
+        space = 8 + MassVerifierMigration::SIZE,
+        seeds = [b"migration", old_verifier.key().as_ref()],
+        bump
+    )]
+    pub migration: Account<'info, MassVerifierMigration>,
+
+    #[account(mut)]
+    pub old_verifier: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ApproveVerifierMigration<'info> {
+    #[account(
+        seeds = [b"protocol_config"],
+        bump = config.bump,
+        has_one = authority @ ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"migration", migration.old_verifier.as_ref()],
+        bump = migration.bump
+    )]
+    pub migration: Account<'info, MassVerifierMigration>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ApplyVerifierMigration<'info> {
+    #[account(
+        seeds = [b"migration", migration.old_verifier.as_ref()],
+        bump = migration.bump
+    )]
+    pub migration: Account<'info, MassVerifierMigration>,
+
+    #[account(
+        mut,
+        seeds = [b"vault", vault.seed_testator.as_ref(), vault.seed_beneficiary.as_ref(), &[vault.vault_nonce]],
+        bump = vault.bump,
+        constraint = vault.verifier == migration.old_verifier @ ErrorCode::InvalidVerifier
+    )]
+    pub vault: Account<'info, Vault>,
+}
+
+#[derive(Accounts)]
+pub struct VerifyLightCpiSigner {}
+
+#[derive(Accounts)]
+pub struct RunBenchmark<'info> {
+    #[account(
+        seeds = [b"protocol_config"],
+        bump = config.bump
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+
+    /// Any vault; it is only read
+    pub vault: Account<'info, Vault>,
+}
+
+/// What kind of secret (or secret fragment) `encrypted_password` holds
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum KeyFragmentType {
+    Mnemonic24Words,
+    Xprv,
+    RawPrivateKey,
+    #[default]
+    EncryptedSeed,
+}
+
+#[derive(Accounts)]
+pub struct AtomicBiometricUpdate<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault", vault.seed_testator.as_ref(), vault.seed_beneficiary.as_ref(), &[vault.vault_nonce]],
+        bump = vault.bump,
+        has_one = testator @ ErrorCode::Unauthorized,
+        has_one = verifier @ ErrorCode::Unauthorized
+    )]
+    pub vault: Account<'info, Vault>,
+
+    pub testator: Signer<'info>,
+
+    /// The vault's verifier co-signs to attest the new ZelfProof
+    pub verifier: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(testator: Pubkey, beneficiary: Pubkey, vault_nonce: u8)]
+pub struct LookupVaultAddress<'info> {
+    /// CHECK: Only checked for existence; the seeds pin it to the derived vault PDA
+    #[account(seeds = [b"vault", testator.as_ref(), beneficiary.as_ref(), &[vault_nonce]], bump)]
+    pub vault: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct InitVerifierCommittee<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault", vault.seed_testator.as_ref(), vault.seed_beneficiary.as_ref(), &[vault.vault_nonce]],
+        bump = vault.bump,
+        has_one = testator @ ErrorCode::Unauthorized
+    )]
+    pub vault: Account<'info, Vault>,
+
+    /// Re-initialised after `cancel_verifier_committee`
+    #[account(
+        init_if_needed,
+        payer = testator,
+        space = 8 + VerifierCommittee::SIZE,
+        seeds = [b"committee", vault.key().as_ref()],
+        bump
+    )]
+    pub committee: Account<'info, VerifierCommittee>,
+
+    #[account(mut)]
+    pub testator: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SubmitCommitteeApproval<'info> {
+    #[account(
+        seeds = [b"vault", vault.seed_testator.as_ref(), vault.seed_beneficiary.as_ref(), &[vault.vault_nonce]],
+        bump = vault.bump
+    )]
+    pub vault: Account<'info, Vault>,
+
+    #[account(
+        mut,
+        seeds = [b"committee", vault.key().as_ref()],
+        bump = committee.bump,
+        has_one = vault @ ErrorCode::Unauthorized
+    )]
+    pub committee: Account<'info, VerifierCommittee>,
+
+    /// Checked against `committee.verifiers[verifier_index]`
+    pub verifier: Signer<'info>,
+}
+
+/// Same accounts as `ExecuteInheritance`, with the committee in place of the verifier
+#[derive(Accounts)]
+pub struct FinalizeCommitteeExecution<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault", vault.seed_testator.as_ref(), vault.seed_beneficiary.as_ref(), &[vault.vault_nonce]],
+        bump = vault.bump
+    )]
+    pub vault: Account<'info, Vault>,
+
+    /// CHECK: Must be the vault's testator
+    #[account(address = vault.testator @ ErrorCode::Unauthorized)]
+    pub testator: AccountInfo<'info>,
+
+    /// Checked against `vault.beneficiary` by `verify_execution_parties`
+    #[account(mut)]
+    pub beneficiary: Signer<'info>,
+
+    /// Checked against `vault.verifier_committee` by `verify_execution_parties`
+    #[account(
+        seeds = [b"committee", vault.key().as_ref()],
+        bump = committee.bump,
+        has_one = vault @ ErrorCode::Unauthorized
+    )]
+    pub committee: Account<'info, VerifierCommittee>,
+
+    /// Checked for an emergency execution pause
+    #[account(
+        seeds = [b"protocol_config"],
+        bump = config.bump
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"capsule", vault.key().as_ref()],
+        bump = time_capsule.bump
+    )]
+    pub time_capsule: Option<Account<'info, TimeCapsuleMessage>>,
+
+    #[account(
+        init_if_needed,
+        payer = beneficiary,
+        space = 8 + TaxEscrow::SIZE,
+        seeds = [b"tax_escrow", vault.key().as_ref()],
+        bump
+    )]
+    pub tax_escrow: Option<Account<'info, TaxEscrow>>,
+
+    #[account(
+        mut,
+        seeds = [b"state_history", vault.key().as_ref()],
+        bump = state_history.bump
+    )]
+    pub state_history: Option<Account<'info, VaultStateHistory>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct AdminPingLiveness<'info> {
+    #[account(
+        seeds = [b"protocol_config"],
+        bump = config.bump,
+        has_one = authority @ ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+
+    pub authority: Signer<'info>,
+
+    /// CHECK: Instructions sysvar, used to inspect the Ed25519 signature instruction
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    pub instructions_sysvar: AccountInfo<'info>,
+    // The vaults to ping are passed as writable remaining accounts
+}
+
+#[derive(Accounts)]
+pub struct ExportVaultState<'info> {
+    #[account(
+        seeds = [b"vault", vault.seed_testator.as_ref(), vault.seed_beneficiary.as_ref(), &[vault.vault_nonce]],
+        bump = vault.bump
+    )]
+    pub vault: Account<'info, Vault>,
+
+    /// CHECK: SlotHashes sysvar, source of the slot commitment
+    #[account(address = anchor_lang::solana_program::sysvar::slot_hashes::ID)]
+    pub recent_slothashes: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct InitCompressedLivenessIndex<'info> {
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + CompressedLivenessIndexRoot::SIZE,
+        seeds = [b"idx_root"],
+        bump
+    )]
+    pub root: Account<'info, CompressedLivenessIndexRoot>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + CompressedLivenessIndexPage::SIZE,
+        seeds = [b"idx_page", 0u8.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub page: Box<Account<'info, CompressedLivenessIndexPage>>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct InsertCompressedLivenessIndex<'info> {
+    #[account(
+        seeds = [b"vault", vault.seed_testator.as_ref(), vault.seed_beneficiary.as_ref(), &[vault.vault_nonce]],
+        bump = vault.bump,
+        has_one = testator @ ErrorCode::Unauthorized
+    )]
+    pub vault: Account<'info, Vault>,
+
+    #[account(mut, seeds = [b"idx_root"], bump = root.bump)]
+    pub root: Account<'info, CompressedLivenessIndexRoot>,
+
+    #[account(mut, seeds = [b"idx_page", page.page_id.to_le_bytes().as_ref()], bump = page.bump)]
+    pub page: Box<Account<'info, CompressedLivenessIndexPage>>,
+
+    /// Only needed when `page` is full
+    #[account(
+        init,
+        payer = testator,
+        space = 8 + CompressedLivenessIndexPage::SIZE,
+        seeds = [b"idx_page", (root.pages.len() as u8).to_le_bytes().as_ref()],
+        bump
+    )]
+    pub new_page: Option<Box<Account<'info, CompressedLivenessIndexPage>>>,
+
+    #[account(mut)]
+    pub testator: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct LookupCompressedLiveness<'info> {
+    #[account(seeds = [b"idx_root"], bump = root.bump)]
+    pub root: Account<'info, CompressedLivenessIndexRoot>,
+
+    #[account(seeds = [b"idx_page", page.page_id.to_le_bytes().as_ref()], bump = page.bump)]
+    pub page: Box<Account<'info, CompressedLivenessIndexPage>>,
+}
+
+#[derive(Accounts)]
+pub struct SetHealthOracle<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault", vault.seed_testator.as_ref(), vault.seed_beneficiary.as_ref(), &[vault.vault_nonce]],
+        bump = vault.bump,
+        has_one = testator @ ErrorCode::Unauthorized,
+        has_one = verifier @ ErrorCode::Unauthorized
+    )]
+    pub vault: Account<'info, Vault>,
+
+    pub testator: Signer<'info>,
+
+    pub verifier: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SubmitHealthSignal<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault", vault.seed_testator.as_ref(), vault.seed_beneficiary.as_ref(), &[vault.vault_nonce]],
+        bump = vault.bump
+    )]
+    pub vault: Account<'info, Vault>,
+
+    /// Checked against `vault.health_oracle`
+    pub oracle: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimLotteryPrize<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault", vault.seed_testator.as_ref(), vault.seed_beneficiary.as_ref(), &[vault.vault_nonce]],
+        bump = vault.bump
+    )]
+    pub vault: Account<'info, Vault>,
+
+    /// Checked against `vault.lottery_winner`
+    #[account(mut)]
+    pub winner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct DrawLotteryWinner<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault", vault.seed_testator.as_ref(), vault.seed_beneficiary.as_ref(), &[vault.vault_nonce]],
+        bump = vault.bump
+    )]
+    pub vault: Account<'info, Vault>,
+
+    /// CHECK: SlotHashes sysvar, seeds the lottery draw
+    #[account(address = anchor_lang::solana_program::sysvar::slot_hashes::ID)]
+    pub recent_slothashes: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct EmergencyKeyReveal<'info> {
+    #[account(
+        seeds = [b"protocol_config"],
+        bump = config.bump,
+        constraint = config.recovery_authority == Some(recovery_authority.key()) @ ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+
+    pub recovery_authority: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"vault", vault.seed_testator.as_ref(), vault.seed_beneficiary.as_ref(), &[vault.vault_nonce]],
+        bump = vault.bump
+    )]
+    pub vault: Account<'info, Vault>,
+}
+
+#[derive(Accounts)]
+#[instruction(vault_id: u64)]
+pub struct LookupVaultById<'info> {
+    /// CHECK: Only read if it exists; the seeds pin it to the id's index PDA
+    #[account(seeds = [b"vid_index", &vault_id.to_le_bytes()], bump)]
+    pub vault_id_index: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct DryRunCompressedLivenessUpdate<'info> {
+    #[account(
+        seeds = [b"protocol_config"],
+        bump = config.bump
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+
+    /// The vault the update would be sent for; it is only read
+    #[account(
+        seeds = [b"vault", vault.seed_testator.as_ref(), vault.seed_beneficiary.as_ref(), &[vault.vault_nonce]],
+        bump = vault.bump
+    )]
+    pub vault: Account<'info, Vault>,
+}
+
+#[cfg(feature = "deed_nft")]
+#[derive(Accounts)]
+pub struct MintVaultDeed<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault", vault.seed_testator.as_ref(), vault.seed_beneficiary.as_ref(), &[vault.vault_nonce]],
+        bump = vault.bump,
+        has_one = testator @ ErrorCode::Unauthorized
+    )]
+    pub vault: Account<'info, Vault>,
+
+    #[account(mut)]
+    pub testator: Signer<'info>,
+
+    #[account(
+        init,
+        payer = testator,
+        seeds = [b"deed_mint", vault.key().as_ref()],
+        bump,
+        mint::decimals = 0,
+        mint::authority = vault,
+        mint::freeze_authority = vault
+    )]
+    pub deed_mint: Account<'info, anchor_spl::token::Mint>,
+
+    #[account(
+        init_if_needed,
+        payer = testator,
+        associated_token::mint = deed_mint,
+        associated_token::authority = testator
+    )]
+    pub testator_deed_account: Account<'info, anchor_spl::token::TokenAccount>,
+
+    /// CHECK: Created by the Token Metadata program; the seeds pin it to the deed mint
+    #[account(
+        mut,
+        seeds = [b"metadata", token_metadata_program.key().as_ref(), deed_mint.key().as_ref()],
+        seeds::program = token_metadata_program.key(),
+        bump
+    )]
+    pub metadata: UncheckedAccount<'info>,
+
+    pub token_metadata_program: Program<'info, anchor_spl::metadata::Metadata>,
+    pub token_program: Program<'info, anchor_spl::token::Token>,
+    pub associated_token_program: Program<'info, anchor_spl::associated_token::AssociatedToken>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[cfg(feature = "deed_nft")]
+#[derive(Accounts)]
+pub struct BurnVaultDeed<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault", vault.seed_testator.as_ref(), vault.seed_beneficiary.as_ref(), &[vault.vault_nonce]],
+        bump = vault.bump,
+        constraint = vault.deed_nft_mint == Some(deed_mint.key()) @ ErrorCode::Unauthorized
+    )]
+    pub vault: Account<'info, Vault>,
+
+    #[account(mut)]
+    pub deed_mint: Account<'info, anchor_spl::token::Mint>,
+
+    #[account(
+        mut,
+        token::mint = deed_mint,
+        token::authority = holder
+    )]
+    pub holder_deed_account: Account<'info, anchor_spl::token::TokenAccount>,
+
+    pub holder: Signer<'info>,
+
+    pub token_program: Program<'info, anchor_spl::token::Token>,
+}
+
+#[derive(Accounts)]
+pub struct RegisterLivenessScheduler<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault", vault.seed_testator.as_ref(), vault.seed_beneficiary.as_ref(), &[vault.vault_nonce]],
+        bump = vault.bump,
+        has_one = testator @ ErrorCode::Unauthorized
+    )]
+    pub vault: Account<'info, Vault>,
+
+    #[account(
+        init_if_needed,
+        payer = testator,
+        space = 8 + SchedulerTask::SIZE,
+        seeds = [b"scheduler_task", vault.key().as_ref()],
+        bump
+    )]
+    pub scheduler_task: Account<'info, SchedulerTask>,
+
+    #[account(mut)]
+    pub testator: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UnregisterLivenessScheduler<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault", vault.seed_testator.as_ref(), vault.seed_beneficiary.as_ref(), &[vault.vault_nonce]],
+        bump = vault.bump,
+        has_one = testator @ ErrorCode::Unauthorized
+    )]
+    pub vault: Account<'info, Vault>,
+
+    #[account(
+        mut,
+        close = testator,
+        seeds = [b"scheduler_task", vault.key().as_ref()],
+        bump = scheduler_task.bump
+    )]
+    pub scheduler_task: Account<'info, SchedulerTask>,
+
+    #[account(mut)]
+    pub testator: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct VerifyContentHash<'info> {
+    #[account(
+        seeds = [b"vault", vault.seed_testator.as_ref(), vault.seed_beneficiary.as_ref(), &[vault.vault_nonce]],
+        bump = vault.bump
+    )]
+    pub vault: Account<'info, Vault>,
+}
+
+#[derive(Accounts)]
+#[instruction(mint: Pubkey)]
+pub struct DepositNft<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault", vault.seed_testator.as_ref(), vault.seed_beneficiary.as_ref(), &[vault.vault_nonce]],
+        bump = vault.bump,
+        has_one = testator @ ErrorCode::Unauthorized
+    )]
+    pub vault: Account<'info, Vault>,
+
+    #[account(mut)]
+    pub testator: Signer<'info>,
+
+    #[account(address = mint)]
+    pub nft_mint: Account<'info, anchor_spl::token::Mint>,
+
+    #[account(
+        mut,
+        associated_token::mint = nft_mint,
+        associated_token::authority = testator
+    )]
+    pub testator_token_account: Account<'info, anchor_spl::token::TokenAccount>,
+
+    #[account(
+        init_if_needed,
+        payer = testator,
+        seeds = [b"vault_token", vault.key().as_ref(), mint.as_ref()],
+        bump,
+        token::mint = nft_mint,
+        token::authority = vault
+    )]
+    pub vault_token_account: Account<'info, anchor_spl::token::TokenAccount>,
+
+    pub token_program: Program<'info, anchor_spl::token::Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(mint: Pubkey)]
+pub struct WithdrawNft<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault", vault.seed_testator.as_ref(), vault.seed_beneficiary.as_ref(), &[vault.vault_nonce]],
+        bump = vault.bump,
+        has_one = testator @ ErrorCode::Unauthorized
+    )]
+    pub vault: Account<'info, Vault>,
+
+    #[account(mut)]
+    pub testator: Signer<'info>,
+
+    #[account(address = mint)]
+    pub nft_mint: Account<'info, anchor_spl::token::Mint>,
+
+    #[account(
+        init_if_needed,
+        payer = testator,
+        associated_token::mint = nft_mint,
+        associated_token::authority = testator
+    )]
+    pub testator_token_account: Account<'info, anchor_spl::token::TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"vault_token", vault.key().as_ref(), mint.as_ref()],
+        bump
+    )]
+    pub vault_token_account: Account<'info, anchor_spl::token::TokenAccount>,
+
+    pub token_program: Program<'info, anchor_spl::token::Token>,
+    pub associated_token_program: Program<'info, anchor_spl::associated_token::AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ComputeActivityScore<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault", vault.seed_testator.as_ref(), vault.seed_beneficiary.as_ref(), &[vault.vault_nonce]],
+        bump = vault.bump
+    )]
+    pub vault: Account<'info, Vault>,
+}
+
+#[derive(Accounts)]
+pub struct CloseExpiredVault<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault", vault.seed_testator.as_ref(), vault.seed_beneficiary.as_ref(), &[vault.vault_nonce]],
+        bump = vault.bump,
+        close = refund_destination
+    )]
+    pub vault: Account<'info, Vault>,
+
+    /// CHECK: The vault's expiry refund destination, or its testator
+    #[account(
+        mut,
+        address = vault.expiry_refund_destination.unwrap_or(vault.testator) @ ErrorCode::Unauthorized
+    )]
+    pub refund_destination: UncheckedAccount<'info>,
+
+    /// CHECK: The vault's `TimeCapsuleMessage` PDA, closed if it was created
+    #[account(mut, seeds = [b"capsule", vault.key().as_ref()], bump)]
+    pub time_capsule: UncheckedAccount<'info>,
+
+    /// CHECK: The vault's `VaultStateHistory` PDA, closed if it was created
+    #[account(mut, seeds = [b"state_history", vault.key().as_ref()], bump)]
+    pub state_history: UncheckedAccount<'info>,
+
+    /// CHECK: The vault's `EmergencyContactRegistry` PDA, closed if it was created
+    #[account(mut, seeds = [b"contacts", vault.key().as_ref()], bump)]
+    pub emergency_contacts: UncheckedAccount<'info>,
+
+    /// CHECK: The vault's `UsedProofNonces` PDA, closed if it was created
+    #[account(mut, seeds = [b"used_nonces", vault.key().as_ref()], bump)]
+    pub used_nonces: UncheckedAccount<'info>,
+
+    /// CHECK: The vault's `TaxEscrow` PDA, closed if it was created
+    #[account(mut, seeds = [b"tax_escrow", vault.key().as_ref()], bump)]
+    pub tax_escrow: UncheckedAccount<'info>,
+
+    /// Optional protocol config, released from the protocol-wide vault count
+    #[account(
+        mut,
+        seeds = [b"protocol_config"],
+        bump = config.bump
+    )]
+    pub config: Option<Account<'info, ProtocolConfig>>,
+
+    /// Optional per-testator registry, released from the testator's vault count
+    #[account(
+        mut,
+        seeds = [b"registry", vault.seed_testator.as_ref()],
+        bump
+    )]
+    pub vault_registry: Option<Account<'info, VaultRegistry>>,
+
+    pub token_program: Program<'info, anchor_spl::token::Token>,
+}
+
+#[derive(Accounts)]
+pub struct InitNullifierRegistry<'info> {
+    #[account(
+        seeds = [b"protocol_config"],
+        bump = config.bump,
+        has_one = authority @ ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + NullifierRegistry::space(NullifierRegistry::INITIAL_CAPACITY),
+        seeds = [b"nullifier_registry"],
+        bump
+    )]
+    pub nullifier_registry: Account<'info, NullifierRegistry>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct GrowNullifierRegistry<'info> {
+    #[account(
+        mut,
+        seeds = [b"nullifier_registry"],
+        bump = nullifier_registry.bump
+    )]
+    pub nullifier_registry: Account<'info, NullifierRegistry>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct PruneNullifierRegistry<'info> {
+    #[account(
+        mut,
+        seeds = [b"nullifier_registry"],
+        bump = nullifier_registry.bump
+    )]
+    pub nullifier_registry: Account<'info, NullifierRegistry>,
+}
+
+#[derive(Accounts)]
+pub struct RegisterValuationOracle<'info> {
+    #[account(
+        init_if_needed,
+        payer = testator,
+        space = 8 + ValuationOracle::SIZE,
+        seeds = [b"valuation", testator.key().as_ref()],
+        bump
+    )]
+    pub valuation: Account<'info, ValuationOracle>,
+
+    #[account(mut)]
+    pub testator: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SubmitEstateValuation<'info> {
+    #[account(
+        mut,
+        seeds = [b"valuation", valuation.testator.as_ref()],
+        bump = valuation.bump,
+        has_one = oracle @ ErrorCode::Unauthorized
+    )]
+    pub valuation: Account<'info, ValuationOracle>,
+
+    pub oracle: Signer<'info>,
+
+    /// The testator's registry: the vaults it counts must all be passed
+    #[account(seeds = [b"registry", valuation.testator.as_ref()], bump)]
+    pub vault_registry: Account<'info, VaultRegistry>,
+    // The testator's vaults are passed via remaining_accounts
+}
+
+#[derive(Accounts)]
+pub struct ReadEstateValuation<'info> {
+    #[account(
+        seeds = [b"valuation", valuation.testator.as_ref()],
+        bump = valuation.bump
+    )]
+    pub valuation: Account<'info, ValuationOracle>,
+}
+
+#[derive(Accounts)]
+pub struct SetSecretQuestion<'info> {
+    #[account(
+        seeds = [b"vault", vault.seed_testator.as_ref(), vault.seed_beneficiary.as_ref(), &[vault.vault_nonce]],
+        bump = vault.bump,
+        has_one = testator @ ErrorCode::Unauthorized,
+        has_one = verifier @ ErrorCode::Unauthorized
+    )]
+    pub vault: Account<'info, Vault>,
+
+    #[account(
+        init_if_needed,
+        payer = testator,
+        space = 8 + SecretQuestionRecord::SIZE,
+        seeds = [b"secret_question", vault.key().as_ref()],
+        bump
+    )]
+    pub secret_question: Account<'info, SecretQuestionRecord>,
+
+    #[account(mut)]
+    pub testator: Signer<'info>,
+
+    pub verifier: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct VerifySecretAnswer<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault", vault.seed_testator.as_ref(), vault.seed_beneficiary.as_ref(), &[vault.vault_nonce]],
+        bump = vault.bump
+    )]
+    pub vault: Account<'info, Vault>,
+
+    /// Omitted when the testator never set a secret question
+    #[account(
+        seeds = [b"secret_question", vault.key().as_ref()],
+        bump = secret_question.bump
+    )]
+    pub secret_question: Option<Account<'info, SecretQuestionRecord>>,
+
+    pub beneficiary: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(testator: Pubkey)]
+pub struct GrantFeeWaiver<'info> {
+    #[account(
+        seeds = [b"protocol_config"],
+        bump = config.bump,
+        has_one = authority @ ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = 8 + FeeWaiver::SIZE,
+        seeds = [b"fee_waiver", testator.as_ref()],
+        bump
+    )]
+    pub fee_waiver: Account<'info, FeeWaiver>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ListExecutionRight<'info> {
+    #[account(
+        seeds = [b"vault", vault.seed_testator.as_ref(), vault.seed_beneficiary.as_ref(), &[vault.vault_nonce]],
+        bump = vault.bump,
+        has_one = testator @ ErrorCode::Unauthorized
+    )]
+    pub vault: Account<'info, Vault>,
+
+    #[account(
+        init_if_needed,
+        payer = testator,
+        space = 8 + ExecutionRight::SIZE,
+        seeds = [b"exec_right", vault.key().as_ref()],
+        bump
+    )]
+    pub execution_right: Account<'info, ExecutionRight>,
+
+    #[account(mut)]
+    pub testator: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct PurchaseExecutionRight<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault", vault.seed_testator.as_ref(), vault.seed_beneficiary.as_ref(), &[vault.vault_nonce]],
+        bump = vault.bump
+    )]
+    pub vault: Account<'info, Vault>,
+
+    #[account(
+        mut,
+        seeds = [b"exec_right", vault.key().as_ref()],
+        bump = execution_right.bump
+    )]
+    pub execution_right: Account<'info, ExecutionRight>,
+
+    #[account(mut)]
+    pub buyer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteInheritanceAsExecutor<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault", vault.seed_testator.as_ref(), vault.seed_beneficiary.as_ref(), &[vault.vault_nonce]],
+        bump = vault.bump
+    )]
+    pub vault: Account<'info, Vault>,
+
+    /// Checked against `sold_to` by the handler
+    #[account(
+        seeds = [b"exec_right", vault.key().as_ref()],
+        bump = execution_right.bump
+    )]
+    pub execution_right: Account<'info, ExecutionRight>,
+
+    /// The buyer of the execution right
+    #[account(mut)]
+    pub executor: Signer<'info>,
+
+    /// CHECK: The vault's beneficiary, who receives the funds
+    #[account(mut, address = vault.beneficiary @ ErrorCode::Unauthorized)]
+    pub beneficiary: UncheckedAccount<'info>,
+
+    /// The Oracle/Verifier that confirms the biometric face match
+    pub verifier: Signer<'info>,
+
+    /// Checked for an emergency execution pause
+    #[account(
+        seeds = [b"protocol_config"],
+        bump = config.bump
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+
+    /// Optional time capsule message, revealed atomically with the inheritance
+    #[account(
+        mut,
+        seeds = [b"capsule", vault.key().as_ref()],
+        bump = time_capsule.bump
+    )]
+    pub time_capsule: Option<Account<'info, TimeCapsuleMessage>>,
+
+    /// Escrow for the inheritance tax, required when the vault has tax configured
+    #[account(
+        init_if_needed,
+        payer = executor,
+        space = 8 + TaxEscrow::SIZE,
+        seeds = [b"tax_escrow", vault.key().as_ref()],
+        bump
+    )]
+    pub tax_escrow: Option<Account<'info, TaxEscrow>>,
+
+    /// Optional state history, records the Claimable -> Executed transition
+    #[account(
+        mut,
+        seeds = [b"state_history", vault.key().as_ref()],
+        bump = state_history.bump
+    )]
+    pub state_history: Option<Account<'info, VaultStateHistory>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CommitLegalDocument<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault", vault.seed_testator.as_ref(), vault.seed_beneficiary.as_ref(), &[vault.vault_nonce]],
+        bump = vault.bump,
+        has_one = testator @ ErrorCode::Unauthorized
+    )]
+    pub vault: Account<'info, Vault>,
+
+    pub testator: Signer<'info>,
+
+    /// The notary who certified the document off-chain
+    pub notary: Signer<'info>,
+}
+
+/// Kind of legal document anchored with `commit_legal_document`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LegalDocumentType {
+    LastWillAndTestament,
+    PowerOfAttorney,
+    TrustDeed,
+    CourtOrder,
+}
+
+/// Hash of a notarized off-chain legal document, anchored to the vault
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct LegalDocumentCommitment {
+    pub document_type: LegalDocumentType,
+    pub hash: [u8; 32],
+    pub notary: Pubkey,
+    pub committed_at: i64,
+}
+
+impl LegalDocumentCommitment {
+    pub const SIZE: usize = 1 + 32 + 32 + 8;
+}
+
+#[derive(Accounts)]
+pub struct IssueContactChallenge<'info> {
+    #[account(
+        seeds = [b"vault", vault.seed_testator.as_ref(), vault.seed_beneficiary.as_ref(), &[vault.vault_nonce]],
+        bump = vault.bump,
+        has_one = testator @ ErrorCode::Unauthorized,
+        has_one = verifier @ ErrorCode::Unauthorized
+    )]
+    pub vault: Account<'info, Vault>,
+
+    #[account(
+        init_if_needed,
+        payer = testator,
+        space = 8 + ContactVerificationChallenge::SIZE,
+        seeds = [b"contact_challenge", vault.key().as_ref()],
+        bump
+    )]
+    pub contact_challenge: Account<'info, ContactVerificationChallenge>,
+
+    #[account(mut)]
+    pub testator: Signer<'info>,
+
+    pub verifier: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct VerifyContactChallenge<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault", vault.seed_testator.as_ref(), vault.seed_beneficiary.as_ref(), &[vault.vault_nonce]],
+        bump = vault.bump
+    )]
+    pub vault: Account<'info, Vault>,
+
+    #[account(
+        mut,
+        seeds = [b"contact_challenge", vault.key().as_ref()],
+        bump = contact_challenge.bump
+    )]
+    pub contact_challenge: Account<'info, ContactVerificationChallenge>,
+
+    pub beneficiary: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct BatchReadCompressedLiveness<'info> {
+    #[account(mut)]
+    pub fee_payer: Signer<'info>,
+
+    // Vaults, then Light Protocol system accounts, are passed via remaining_accounts
+}
+
+#[derive(Accounts)]
+pub struct ArchiveVault<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault", vault.seed_testator.as_ref(), vault.seed_beneficiary.as_ref(), &[vault.vault_nonce]],
+        bump = vault.bump,
+        close = beneficiary
+    )]
+    pub vault: Account<'info, Vault>,
+
+    #[account(
+        init,
+        payer = beneficiary,
+        space = 8 + VaultArchive::SIZE,
+        seeds = [b"archive", vault.key().as_ref()],
+        bump
+    )]
+    pub archive: Account<'info, VaultArchive>,
+
+    #[account(mut, address = vault.beneficiary @ ErrorCode::Unauthorized)]
+    pub beneficiary: Signer<'info>,
+
+    /// Optional per-testator registry, released from the testator's vault count
+    #[account(
+        mut,
+        seeds = [b"registry", vault.seed_testator.as_ref()],
+        bump
+    )]
+    pub vault_registry: Option<Account<'info, VaultRegistry>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// A co-beneficiary and its share of the payout, in basis points
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct BeneficiaryEntry {
+    pub beneficiary: Pubkey,
+    pub allocation_bps: u16,
+}
+
+impl BeneficiaryEntry {
+    pub const SIZE: usize = 32 + 2;
+}
+
+/// Stands in for the beneficiary if the vault stays unexecuted for `claim_window_secs`
+/// after turning Claimable. Carries its own identity hashes and encrypted password.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq, Debug)]
+pub struct FallbackBeneficiary {
+    pub beneficiary: Pubkey,
+    pub identity_hash: [u8; 32],
+    pub email_hash: [u8; 32],
+    pub document_id_hash: [u8; 32],
+    pub encrypted_password: Vec<u8>,
+    pub claim_window_secs: i64,
+}
+
+impl FallbackBeneficiary {
+    pub const SIZE: usize = 32 + 32 + 32 + 32 + 4 + Vault::MAX_ENCRYPTED_PASSWORD_SIZE + 8;
+}
+
+/// SPL Token or Token-2022 tokens of one mint held in the vault's associated token account
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct TokenDeposit {
+    pub mint: Pubkey,
+    pub amount: u64,
+}
+
+impl TokenDeposit {
+    pub const SIZE: usize = 32 + 8;
+}
+
+#[derive(Accounts)]
+pub struct DepositToken<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault", vault.seed_testator.as_ref(), vault.seed_beneficiary.as_ref(), &[vault.vault_nonce]],
+        bump = vault.bump,
+        has_one = testator @ ErrorCode::Unauthorized
+    )]
+    pub vault: Account<'info, Vault>,
+
+    #[account(mut)]
+    pub testator: Signer<'info>,
+
+    #[account(mint::token_program = token_program)]
+    pub mint: InterfaceAccount<'info, anchor_spl::token_interface::Mint>,
+
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = testator,
+        associated_token::token_program = token_program
+    )]
+    pub testator_token_account: InterfaceAccount<'info, anchor_spl::token_interface::TokenAccount>,
+
+    #[account(
+        init_if_needed,
+        payer = testator,
+        associated_token::mint = mint,
+        associated_token::authority = vault,
+        associated_token::token_program = token_program
+    )]
+    pub vault_token_account: InterfaceAccount<'info, anchor_spl::token_interface::TokenAccount>,
+
+    /// SPL Token or Token-2022
+    pub token_program: Interface<'info, anchor_spl::token_interface::TokenInterface>,
+    pub associated_token_program: Program<'info, anchor_spl::associated_token::AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawToken<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault", vault.seed_testator.as_ref(), vault.seed_beneficiary.as_ref(), &[vault.vault_nonce]],
+        bump = vault.bump,
+        has_one = testator @ ErrorCode::Unauthorized
+    )]
+    pub vault: Account<'info, Vault>,
+
+    #[account(mut)]
+    pub testator: Signer<'info>,
+
+    #[account(mut, mint::token_program = token_program)]
+    pub mint: InterfaceAccount<'info, anchor_spl::token_interface::Mint>,
+
+    #[account(
+        init_if_needed,
+        payer = testator,
+        associated_token::mint = mint,
+        associated_token::authority = testator,
+        associated_token::token_program = token_program
+    )]
+    pub testator_token_account: InterfaceAccount<'info, anchor_spl::token_interface::TokenAccount>,
+
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = vault,
+        associated_token::token_program = token_program
+    )]
+    pub vault_token_account: InterfaceAccount<'info, anchor_spl::token_interface::TokenAccount>,
+
+    /// SPL Token or Token-2022
+    pub token_program: Interface<'info, anchor_spl::token_interface::TokenInterface>,
+    pub associated_token_program: Program<'info, anchor_spl::associated_token::AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteTokenInheritance<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault", vault.seed_testator.as_ref(), vault.seed_beneficiary.as_ref(), &[vault.vault_nonce]],
+        bump = vault.bump
+    )]
+    pub vault: Account<'info, Vault>,
+
+    /// The heir: checked against `vault.lottery_winner`, or `vault.beneficiary`
+    #[account(mut)]
+    pub beneficiary: Signer<'info>,
+
+    /// Writable so withheld transfer fees can be harvested to it
+    #[account(mut, mint::token_program = token_program)]
+    pub mint: InterfaceAccount<'info, anchor_spl::token_interface::Mint>,
+
+    #[account(
+        init_if_needed,
+        payer = beneficiary,
+        associated_token::mint = mint,
+        associated_token::authority = beneficiary,
+        associated_token::token_program = token_program
+    )]
+    pub beneficiary_token_account: InterfaceAccount<'info, anchor_spl::token_interface::TokenAccount>,
+
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = vault,
+        associated_token::token_program = token_program
+    )]
+    pub vault_token_account: InterfaceAccount<'info, anchor_spl::token_interface::TokenAccount>,
+
+    /// SPL Token or Token-2022
+    pub token_program: Interface<'info, anchor_spl::token_interface::TokenInterface>,
+    pub associated_token_program: Program<'info, anchor_spl::associated_token::AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(mint: Pubkey)]
+pub struct ExecuteNftInheritance<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault", vault.seed_testator.as_ref(), vault.seed_beneficiary.as_ref(), &[vault.vault_nonce]],
+        bump = vault.bump
+    )]
+    pub vault: Account<'info, Vault>,
+
+    /// The heir: checked against `vault.lottery_winner`, or `vault.beneficiary`
+    #[account(mut)]
+    pub beneficiary: Signer<'info>,
+
+    #[account(address = mint)]
+    pub nft_mint: Account<'info, anchor_spl::token::Mint>,
+
+    #[account(
+        init_if_needed,
+        payer = beneficiary,
+        associated_token::mint = nft_mint,
+        associated_token::authority = beneficiary
+    )]
+    pub beneficiary_token_account: Account<'info, anchor_spl::token::TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"vault_token", vault.key().as_ref(), mint.as_ref()],
+        bump
+    )]
+    pub vault_token_account: Account<'info, anchor_spl::token::TokenAccount>,
+
+    pub token_program: Program<'info, anchor_spl::token::Token>,
+    pub associated_token_program: Program<'info, anchor_spl::associated_token::AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+/// Shared by `deposit_pnft`, `withdraw_pnft` and `execute_pnft_inheritance`
+#[cfg(feature = "pnft")]
+#[derive(Accounts)]
+pub struct TransferVaultPnft<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault", vault.seed_testator.as_ref(), vault.seed_beneficiary.as_ref(), &[vault.vault_nonce]],
+        bump = vault.bump
+    )]
+    pub vault: Account<'info, Vault>,
+
+    /// The testator (deposit, withdraw) or the heir (execute); checked by the handler
+    #[account(mut)]
+    pub signer: Signer<'info>,
+
+    pub mint: Account<'info, anchor_spl::token::Mint>,
+
+    /// CHECK: The source owner's associated token account, checked by `transfer_vault_pnft`
+    #[account(mut)]
+    pub source_token: UncheckedAccount<'info>,
+
+    /// CHECK: The destination owner's associated token account, checked by
+    /// `transfer_vault_pnft` and created by Token Metadata if missing
+    #[account(mut)]
+    pub destination_token: UncheckedAccount<'info>,
+
+    /// CHECK: The seeds pin it to the mint
+    #[account(
+        mut,
+        seeds = [b"metadata", token_metadata_program.key().as_ref(), mint.key().as_ref()],
+        seeds::program = token_metadata_program.key(),
+        bump
+    )]
+    pub metadata: UncheckedAccount<'info>,
+
+    /// CHECK: The seeds pin it to the mint
+    #[account(
+        seeds = [b"metadata", token_metadata_program.key().as_ref(), mint.key().as_ref(), b"edition"],
+        seeds::program = token_metadata_program.key(),
+        bump
+    )]
+    pub edition: UncheckedAccount<'info>,
+
+    /// CHECK: The seeds pin it to the source token account
+    #[account(
+        mut,
+        seeds = [b"metadata", token_metadata_program.key().as_ref(), mint.key().as_ref(), b"token_record", source_token.key().as_ref()],
+        seeds::program = token_metadata_program.key(),
+        bump
+    )]
+    pub source_token_record: UncheckedAccount<'info>,
+
+    /// CHECK: The seeds pin it to the destination token account
+    #[account(
+        mut,
+        seeds = [b"metadata", token_metadata_program.key().as_ref(), mint.key().as_ref(), b"token_record", destination_token.key().as_ref()],
+        seeds::program = token_metadata_program.key(),
+        bump
+    )]
+    pub destination_token_record: UncheckedAccount<'info>,
+
+    /// CHECK: The pNFT's rule set program, if it has one
+    pub authorization_rules_program: Option<UncheckedAccount<'info>>,
+    /// CHECK: The pNFT's rule set, validated by Token Metadata
+    pub authorization_rules: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: Instructions sysvar, read by Token Metadata
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    pub sysvar_instructions: UncheckedAccount<'info>,
+
+    pub token_metadata_program: Program<'info, anchor_spl::metadata::Metadata>,
+    pub token_program: Program<'info, anchor_spl::token::Token>,
+    pub associated_token_program: Program<'info, anchor_spl::associated_token::AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+/// A compressed NFT escrowed in the vault, identified by its tree and leaf nonce
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct RegisteredCnft {
+    pub merkle_tree: Pubkey,
+    pub nonce: u64,
+}
+
+impl RegisteredCnft {
+    pub const SIZE: usize = 32 + 8;
+}
+
+/// Shared by `deposit_cnft`, `withdraw_cnft` and `execute_cnft_inheritance`
+#[derive(Accounts)]
+pub struct TransferVaultCnft<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault", vault.seed_testator.as_ref(), vault.seed_beneficiary.as_ref(), &[vault.vault_nonce]],
+        bump = vault.bump
+    )]
+    pub vault: Account<'info, Vault>,
+
+    /// The testator (deposit, withdraw) or the heir (execute); checked by the handler
+    #[account(mut)]
+    pub signer: Signer<'info>,
+
+    /// CHECK: Bubblegum tree config; the seeds pin it to the tree
+    #[account(
+        seeds = [merkle_tree.key().as_ref()],
+        seeds::program = bubblegum_program.key(),
+        bump
+    )]
+    pub tree_config: UncheckedAccount<'info>,
+
+    /// CHECK: The leaf's current delegate (the owner itself if none), verified by the proof
+    pub leaf_delegate: UncheckedAccount<'info>,
+
+    /// CHECK: Concurrent Merkle tree, verified by the account compression program
+    #[account(mut)]
+    pub merkle_tree: UncheckedAccount<'info>,
+
+    /// CHECK: SPL Noop program
+    #[account(address = SPL_NOOP_PROGRAM_ID)]
+    pub log_wrapper: UncheckedAccount<'info>,
+
+    /// CHECK: SPL Account Compression program
+    #[account(address = SPL_ACCOUNT_COMPRESSION_PROGRAM_ID)]
+    pub compression_program: UncheckedAccount<'info>,
+
+    /// CHECK: Bubblegum program
+    #[account(address = BUBBLEGUM_PROGRAM_ID)]
+    pub bubblegum_program: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Pending `propose_beneficiary_change`, PDA `[b"beneficiary_change", vault]`
+#[account]
+pub struct BeneficiaryChangeProposal {
+    pub vault: Pubkey,
+    pub new_beneficiary: Pubkey,
+    pub beneficiary_identity_hash: [u8; 32],
+    pub beneficiary_email_hash: [u8; 32],
+    pub beneficiary_document_id_hash: [u8; 32],
+    pub encrypted_password: Vec<u8>,
+    pub proposed_at: i64,
+    pub bump: u8,
+}
+
+impl BeneficiaryChangeProposal {
+    pub const SIZE: usize =
+        32 +  // vault
+        32 +  // new_beneficiary
+        32 +  // beneficiary_identity_hash
+        32 +  // beneficiary_email_hash
+        32 +  // beneficiary_document_id_hash
+        4  + Vault::MAX_ENCRYPTED_PASSWORD_SIZE +  // Vec<u8> encrypted_password
+        8  +  // proposed_at
+        1;    // bump
+}
+
+#[derive(Accounts)]
+pub struct ProposeBeneficiaryChange<'info> {
+    #[account(
+        seeds = [b"vault", vault.seed_testator.as_ref(), vault.seed_beneficiary.as_ref(), &[vault.vault_nonce]],
+        bump = vault.bump,
+        has_one = testator @ ErrorCode::Unauthorized
+    )]
+    pub vault: Account<'info, Vault>,
+
+    #[account(mut)]
+    pub testator: Signer<'info>,
+
+    #[account(
+        init_if_needed,
+        payer = testator,
+        space = 8 + BeneficiaryChangeProposal::SIZE,
+        seeds = [b"beneficiary_change", vault.key().as_ref()],
+        bump
+    )]
+    pub proposal: Account<'info, BeneficiaryChangeProposal>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct AcceptBeneficiaryChange<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault", vault.seed_testator.as_ref(), vault.seed_beneficiary.as_ref(), &[vault.vault_nonce]],
+        bump = vault.bump
+    )]
+    pub vault: Account<'info, Vault>,
+
+    #[account(address = proposal.new_beneficiary @ ErrorCode::Unauthorized)]
+    pub new_beneficiary: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"beneficiary_change", vault.key().as_ref()],
+        bump = proposal.bump,
+        close = testator
+    )]
+    pub proposal: Account<'info, BeneficiaryChangeProposal>,
+
+    /// CHECK: Receives the proposal's rent
+    #[account(mut, address = vault.testator @ ErrorCode::Unauthorized)]
+    pub testator: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct DepositSol<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault", vault.seed_testator.as_ref(), vault.seed_beneficiary.as_ref(), &[vault.vault_nonce]],
+        bump = vault.bump
+    )]
+    pub vault: Account<'info, Vault>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawSol<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault", vault.seed_testator.as_ref(), vault.seed_beneficiary.as_ref(), &[vault.vault_nonce]],
+        bump = vault.bump,
+        has_one = testator @ ErrorCode::Unauthorized
+    )]
+    pub vault: Account<'info, Vault>,
+
+    #[account(mut)]
+    pub testator: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimAsFallbackBeneficiary<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault", vault.seed_testator.as_ref(), vault.seed_beneficiary.as_ref(), &[vault.vault_nonce]],
+        bump = vault.bump
+    )]
+    pub vault: Account<'info, Vault>,
+
+    pub fallback_beneficiary: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct InitGuardianSet<'info> {
+    #[account(
+        seeds = [b"vault", vault.seed_testator.as_ref(), vault.seed_beneficiary.as_ref(), &[vault.vault_nonce]],
+        bump = vault.bump,
+        has_one = testator @ ErrorCode::Unauthorized
+    )]
+    pub vault: Account<'info, Vault>,
+
+    #[account(
+        init,
+        payer = testator,
+        space = 8 + GuardianSet::SIZE,
+        seeds = [b"guardians", vault.key().as_ref()],
+        bump
+    )]
+    pub guardian_set: Account<'info, GuardianSet>,
+
+    #[account(mut)]
+    pub testator: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ApplyTestatorRotation<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault", vault.seed_testator.as_ref(), vault.seed_beneficiary.as_ref(), &[vault.vault_nonce]],
+        bump = vault.bump
+    )]
+    pub vault: Account<'info, Vault>,
+
+    #[account(
+        mut,
+        seeds = [b"guardians", vault.key().as_ref()],
+        bump = guardian_set.bump,
+        has_one = vault @ ErrorCode::Unauthorized
+    )]
+    pub guardian_set: Account<'info, GuardianSet>,
+}
+
+#[derive(Accounts)]
+pub struct CancelTestatorRotation<'info> {
+    #[account(
+        seeds = [b"vault", vault.seed_testator.as_ref(), vault.seed_beneficiary.as_ref(), &[vault.vault_nonce]],
+        bump = vault.bump,
+        has_one = testator @ ErrorCode::Unauthorized
+    )]
+    pub vault: Account<'info, Vault>,
+
+    #[account(
+        mut,
+        seeds = [b"guardians", vault.key().as_ref()],
+        bump = guardian_set.bump,
+        has_one = vault @ ErrorCode::Unauthorized
+    )]
+    pub guardian_set: Account<'info, GuardianSet>,
+
+    pub testator: Signer<'info>,
+}
+
+/// Shared by `propose_testator_rotation` and `approve_testator_rotation`
+#[derive(Accounts)]
+pub struct GuardianRotation<'info> {
+    #[account(
+        seeds = [b"vault", vault.seed_testator.as_ref(), vault.seed_beneficiary.as_ref(), &[vault.vault_nonce]],
+        bump = vault.bump
+    )]
+    pub vault: Account<'info, Vault>,
+
+    #[account(
+        mut,
+        seeds = [b"guardians", vault.key().as_ref()],
+        bump = guardian_set.bump,
+        has_one = vault @ ErrorCode::Unauthorized
+    )]
+    pub guardian_set: Account<'info, GuardianSet>,
+
+    /// Checked against `guardian_set.guardians[guardian_index]`
+    pub guardian: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(beneficiary: Pubkey)]
+pub struct MigratePlaintextKey<'info> {
+    /// CHECK: A vault in the `LegacyVault` layout, which doesn't deserialize as `Vault`;
+    /// owner, discriminator, length and testator are checked by `migrate_plaintext_key`
+    #[account(
+        mut,
+        seeds = [b"vault", testator.key().as_ref(), beneficiary.as_ref()],
+        bump
+    )]
+    pub legacy_vault: UncheckedAccount<'info>,
+
+    #[account(
+        init,
+        payer = testator,
+        space = 8 + Vault::SIZE,
+        seeds = [b"vault", testator.key().as_ref(), beneficiary.as_ref(), &[0]],
+        bump
+    )]
+    pub vault: Account<'info, Vault>,
+
+    #[account(mut)]
+    pub testator: Signer<'info>,
+
+    /// CHECK: SlotHashes sysvar, seeds the vault's execution nonce
+    #[account(address = anchor_lang::solana_program::sysvar::slot_hashes::ID)]
+    pub recent_slothashes: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CrankVaultState<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault", vault.seed_testator.as_ref(), vault.seed_beneficiary.as_ref(), &[vault.vault_nonce]],
+        bump = vault.bump
+    )]
+    pub vault: Account<'info, Vault>,
+
+    /// Anyone; receives the crank bounty
+    #[account(mut)]
+    pub cranker: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ExtendDeadline<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault", vault.seed_testator.as_ref(), vault.seed_beneficiary.as_ref(), &[vault.vault_nonce]],
+        bump = vault.bump
+    )]
+    pub vault: Account<'info, Vault>,
+
+    /// The testator or the vault's deadline delegate, checked by `authorize`
+    pub extender: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateLivenessRelayed<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault", vault.seed_testator.as_ref(), vault.seed_beneficiary.as_ref(), &[vault.vault_nonce]],
+        bump = vault.bump
+    )]
+    pub vault: Account<'info, Vault>,
+
+    /// Anyone can relay; the testator's Ed25519 signature is what authorizes the ping
+    #[account(mut)]
+    pub relayer: Signer<'info>,
+
+    /// Optional state history, updated when the ping changes the vault state
+    #[account(
+        mut,
+        seeds = [b"state_history", vault.key().as_ref()],
+        bump = state_history.bump
+    )]
+    pub state_history: Option<Account<'info, VaultStateHistory>>,
+
+    /// CHECK: Instructions sysvar, used to inspect the Ed25519 signature instruction
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    pub instructions_sysvar: AccountInfo<'info>,
+
+    /// Proof nonces already accepted for this vault
+    #[account(
+        init_if_needed,
+        payer = relayer,
+        space = 8 + UsedProofNonces::SIZE,
+        seeds = [b"used_nonces", vault.key().as_ref()],
+        bump
+    )]
+    pub used_nonces: Account<'info, UsedProofNonces>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct InitVerifierRegistry<'info> {
+    #[account(
+        seeds = [b"protocol_config"],
+        bump = config.bump,
+        has_one = authority @ ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + VerifierRegistry::SIZE,
+        seeds = [b"verifier_registry"],
+        bump
+    )]
+    pub verifier_registry: Account<'info, VerifierRegistry>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateVerifierRegistry<'info> {
+    #[account(
+        seeds = [b"protocol_config"],
+        bump = config.bump,
+        has_one = authority @ ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"verifier_registry"],
+        bump = verifier_registry.bump
+    )]
+    pub verifier_registry: Account<'info, VerifierRegistry>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ApplyVerifierRotation<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault", vault.seed_testator.as_ref(), vault.seed_beneficiary.as_ref(), &[vault.vault_nonce]],
+        bump = vault.bump
+    )]
+    pub vault: Account<'info, Vault>,
+}
+
+#[derive(Accounts)]
+pub struct RotateVerifier<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault", vault.seed_testator.as_ref(), vault.seed_beneficiary.as_ref(), &[vault.vault_nonce]],
+        bump = vault.bump,
+        has_one = testator @ ErrorCode::Unauthorized
+    )]
+    pub vault: Account<'info, Vault>,
+
+    pub testator: Signer<'info>,
+
+    /// Checked for `require_registered_verifier`
+    #[account(
+        seeds = [b"protocol_config"],
+        bump = config.bump
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+
+    /// Optional verifier registry, required when `config.require_registered_verifier` is on
+    #[account(
+        seeds = [b"verifier_registry"],
+        bump = verifier_registry.bump
+    )]
+    pub verifier_registry: Option<Account<'info, VerifierRegistry>>,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum VaultState {
+    /// No longer entered now that keys arrive pre-wrapped: vaults of older program
+    /// versions sat here until their first ping wrapped the plaintext key. Kept so
+    /// recorded `VaultStateHistory` entries decode unchanged.
+    #[default]
+    PendingKeyWrap,
+    Active,
+    Warning,
+    Claimable,
+    /// Claimable for longer than the fallback beneficiary's claim window
+    FallbackClaimable,
+    Executed,
+}
+
+impl VaultState {
+    /// Claimable by the beneficiary, whether or not the fallback window has opened
+    pub fn is_claimable(self) -> bool {
+        matches!(self, VaultState::Claimable | VaultState::FallbackClaimable)
+    }
+}
+
+/// Whether the vault holds its key, derived from `encrypted_key_v2` (see
+/// `Vault::key_state`). The plaintext key is never stored.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum KeyState {
+    /// Key wrapped under K_light
+    Wrapped,
+    /// No key stored (only left behind by older program versions)
+    Erased,
+}
+
+/// Violations reported by `Vault::verify_internal_consistency`. Mirrors
+/// `ErrorCode::VaultInvariantViolated` with the detail, for off-chain tooling.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum VaultConsistencyError {
+    EncryptedPasswordTooLarge,
+    WarningNotBeforeTimeout,
+    LastPingBeforeCreation,
+    LamportsOutOfRange,
+    DebugVaultWithCompressedLiveness,
+    LotteryWinnerBeforeExecution,
+}
+
+#[account]
+#[derive(Default)]
+pub struct Vault {
+    pub testator: Pubkey,
+    pub beneficiary: Pubkey,
+    pub verifier: Pubkey,                // Authorized Verifier (Oracle)
+    pub beneficiary_identity_hash: [u8; 32], // ZelfProof Identity Anchor
+    pub beneficiary_email_hash: [u8; 32],    // SHA-256 hash of beneficiary email
+    pub beneficiary_document_id_hash: [u8; 32], // SHA-256 hash of document ID
+    pub cid: [u8; 32],                    // IPFS Content ID for artifact
+    pub cid_validator: [u8; 32],          // IPFS Content ID for validator data
+    pub last_ping: i64,
+    pub created_at: i64,
+    pub warning_timeout_secs: i64,
+    pub timeout_secs: i64,
+    pub min_ping_interval_secs: i64,
+    pub grace_period_secs: i64,
+    pub execution_delay_secs: i64,
+    pub executed: bool,
+    pub lamports: u64,
+
+    pub encrypted_password: Vec<u8>,
+    pub encrypted_key_v2: Option<EncryptedKeyV2>,
+    pub light_root: Option<[u8; 32]>,
+    pub is_debug: bool,
+    pub has_compressed_liveness: bool,    // NEW: Whether a compressed liveness account exists
+    pub ping_count: u64,                  // Number of successful liveness updates
+    pub compression_retry_count: u8,      // Failed compression attempts recorded so far
+    pub last_compression_failure_slot: u64, // Slot of the last recorded failed attempt
+    pub tax_authority_oracle: Option<Pubkey>, // Receives the inheritance tax escrow
+    pub tax_rate_bps: u16,                // Inheritance tax rate in basis points
+    pub migrated_to: Option<Pubkey>,     // Program version the vault was handed over to
+    pub execution_nonce: [u8; 8],        // Anti-replay nonce execute_inheritance must echo
+    pub compressed_liveness_version: CompressedLivenessVersion,
+    pub compression_created_at: i64,     // When the V2 compressed liveness account was created
+    pub custody_agent: Option<Pubkey>,   // May ping on the testator's behalf until custody_expires_at
+    pub custody_expires_at: i64,
+    pub beneficiary_instructions: Vec<u8>, // Guidance encrypted to the beneficiary's key
+    pub key_fragment_type: KeyFragmentType, // How to interpret encrypted_password
+    pub key_fragment_index: u8,           // Which fragment this vault holds (0-based)
+    pub key_fragment_total: u8,           // Number of fragments the secret is split into
+    pub destruction_bond_lamports: u64,  // Forfeited if cancelled after beneficiary verification
+    pub beneficiary_verified_at: Option<i64>, // First successful verify_beneficiary_identity
+    pub update_sequence: u64,            // Number of atomic_biometric_update calls
+    pub full_identity_verified: bool,    // Beneficiary identity hash was matched on-chain
+    pub execution_revocable: bool,       // Testator may abort a pending execution during execution_delay_secs
+    pub execution_revocation_count: u8,  // Pending executions aborted so far
+    pub max_verification_age_secs: i64,  // Identity verification must be this fresh to execute (0 = no expiry)
+    pub last_identity_verified_at: i64,  // Latest successful identity verification
+    pub has_been_verified: bool,         // last_identity_verified_at is set
+    pub last_ping_note: [u8; 32],        // Note attached to the latest update_liveness_with_note
+    pub expected_ping_note: Option<[u8; 32]>, // Liveness updates must carry this note when set
+    pub admin_pinged: bool,              // Liveness was last attested by the protocol authority
+    pub admin_ping_count: u8,            // admin_ping_liveness calls covering this vault
+    pub health_oracle: Option<Pubkey>,   // Wearable health data oracle contributing to liveness
+    pub last_health_signal: i64,         // Timestamp of the oracle's latest signal
+    pub health_signal_weight_bps: u16,   // Share of the health signal in the effective last ping
+    pub lottery_mode: bool,                  // Execution draws the heir among lottery_participants
+    pub lottery_participants: Vec<Pubkey>,   // Max 32
+    pub lottery_winner: Option<Pubkey>,      // Drawn by draw_lottery_winner, claims via claim_lottery_prize
+    pub emergency_revealed: bool,        // Key K was published by emergency_key_reveal
+    pub vault_id: u64,                   // Sequential id from ProtocolConfig (0 = none assigned)
+    pub deed_nft_mint: Option<Pubkey>,  // Metaplex NFT deed minted by mint_vault_deed
+    pub deed_burned: bool,               // The deed was burned; the vault stays live
+    pub scheduler_pubkey: Option<Pubkey>, // Task scheduler allowed to ping on the testator's behalf
+    pub schedule_cron: [u8; 32],         // ASCII cron expression, zero-padded
+    pub scheduler_ping_count: u64,       // Pings sent by the scheduler
+    pub content_hash: [u8; 32],          // Integrity hash of the time-varying fields, see compute_content_hash
+    pub registered_nfts: Vec<Pubkey>,    // NFT mints held in vault token accounts, max 8
+    pub last_activity_score: u8,         // Latest compute_activity_score result (0-100)
+    pub last_scored_at: i64,
+    pub vault_expiry_secs: i64,          // Auto-cancel this long after created_at (0 = never)
+    pub expiry_refund_destination: Option<Pubkey>, // Receives the assets of an expired vault (testator if None)
+    pub key_wrapped_with_hkdf: bool,     // K_light for encrypted_key_v2 comes from derive_key_from_light_hkdf
+    pub secret_verified: bool,           // Beneficiary answered the secret question
+    pub legal_documents: Vec<LegalDocumentCommitment>, // Notarized documents published at execution, max 4
+    pub contact_verified: bool,          // Beneficiary answered a contact challenge
+    pub vault_nonce: u8,                 // Vault PDA seed: sequential vaults of one testator-beneficiary pair
+    pub executed_at: i64,                // 0 until executed
+    pub lamports_transferred: u64,       // Paid to the beneficiaries (or lottery winner) at execution
+    pub co_beneficiaries: Vec<BeneficiaryEntry>, // Paid allocation_bps each at execution, max 4
+    pub token_deposits: Vec<TokenDeposit>, // SPL tokens held in vault associated token accounts, max 8
+    pub registered_pnfts: Vec<Pubkey>,  // Programmable NFT mints escrowed via Token Metadata, max 4
+    pub registered_cnfts: Vec<RegisteredCnft>, // Bubblegum compressed NFTs owned by the vault PDA, max 8
+    pub seed_beneficiary: Pubkey,        // Vault PDA seed: the beneficiary at creation, kept across beneficiary changes
+    pub fallback_beneficiary: Option<FallbackBeneficiary>, // Takes over if the beneficiary doesn't execute in time
+    pub seed_testator: Pubkey,           // Vault PDA seed: the testator at creation, kept across testator rotations
+    pub require_liveness_proof: bool,    // Executions must prove the compressed liveness account's last ping
+    pub crank_bounty_lamports: u64,      // Paid to whoever cranks mark_warning / mark_claimable
+    pub warning_cranked_for_ping: i64,   // effective_last_ping for which mark_warning last ran
+    pub claimable_cranked_for_ping: i64, // effective_last_ping for which mark_claimable last ran
+    pub deadline_delegate: Option<Pubkey>, // May call extend_deadline besides the testator
+    pub max_deadline_extension_secs: i64, // Cap on the extensions per missed ping
+    pub deadline_extension_secs: i64,    // Extensions granted since deadline_extended_for_ping
+    pub deadline_extended_for_ping: i64, // last_ping the extensions belong to; a ping drops them
+    pub ping_delegate: Option<Pubkey>,   // Session key allowed to ping until ping_delegate_expires_at
+    pub ping_delegate_expires_at: i64,
+    pub relayed_ping_nonce: u64,         // Next nonce accepted by update_liveness_relayed
+    pub require_verifier_attestation: bool, // Executions must carry a signed verifier attestation
+    pub attestation_nonce: u64,          // Next nonce accepted in a verifier attestation
+    pub pending_verifier: Option<Pubkey>, // Scheduled by rotate_verifier
+    pub verifier_rotation_effective_at: i64, // When pending_verifier may be applied
+    pub verifier_rotation_delay_secs: i64, // Timelock of rotate_verifier
+    pub recovery_escrow: Option<RecoveryEscrow>, // Copy of K wrapped to the recovery authority
+    pub verifier_committee: Option<Pubkey>, // Signs executions in place of the verifier once effective
+    pub verifier_committee_effective_at: i64, // When verifier_committee may finalize executions
+    pub lottery_draw_slot: u64,          // Committed at execution: its slot hash draws lottery_winner
+    pub bump: u8,
+}
+
+impl Vault {
+    pub const MAX_ENCRYPTED_PASSWORD_SIZE: usize = 64;
+    pub const MAX_BENEFICIARY_INSTRUCTIONS_SIZE: usize = 512;
+    /// Maximum number of fragments a secret can be split into
+    pub const MAX_KEY_FRAGMENTS: u8 = 7;
+    /// Failures that may be recorded, each allowing one retry (5 attempts in total)
+    pub const MAX_COMPRESSION_RETRIES: u8 = 4;
+    /// How recent (in slots) a recorded failed compression attempt must be
+    pub const MAX_RETRY_SLOT_AGE: u64 = 150;
+    /// `BiometricDataUpdated::updated_fields_mask` bits
+    pub const BIOMETRIC_CID: u8 = 1 << 0;
+    pub const BIOMETRIC_CID_VALIDATOR: u8 = 1 << 1;
+    pub const BIOMETRIC_ENCRYPTED_PASSWORD: u8 = 1 << 2;
+    pub const BIOMETRIC_IDENTITY_HASH: u8 = 1 << 3;
+    /// After this many revocations `execution_revocable` is switched off
+    pub const MAX_EXECUTION_REVOCATIONS: u8 = 3;
+    /// Oldest health signal accepted by submit_health_signal
+    pub const MAX_HEALTH_SIGNAL_AGE_SECS: i64 = 60 * 60;
+    /// Maximum number of participants in a lottery vault
+    pub const MAX_LOTTERY_PARTICIPANTS: usize = 32;
+    /// Slots between an execution and the slot whose hash draws the lottery winner
+    pub const LOTTERY_DRAW_DELAY_SLOTS: u64 = 10;
+    /// Maximum number of NFTs a vault can hold
+    pub const MAX_REGISTERED_NFTS: usize = 8;
+    /// Window over which pings count towards the activity score
+    pub const ACTIVITY_WINDOW_SECS: i64 = 30 * 24 * 60 * 60;
+    /// Identity verifications older than this no longer count towards the activity score
+    pub const ACTIVITY_IDENTITY_MAX_AGE_SECS: i64 = 365 * 24 * 60 * 60;
+    pub const MAX_LEGAL_DOCUMENTS: usize = 4;
+    pub const MAX_CO_BENEFICIARIES: usize = 4;
+    pub const MAX_TOKEN_DEPOSITS: usize = 8;
+    pub const MAX_REGISTERED_PNFTS: usize = 4;
+    pub const MAX_REGISTERED_CNFTS: usize = 8;
+    /// Largest bounty `set_crank_bounty` accepts (0.001 SOL)
+    pub const MAX_CRANK_BOUNTY_LAMPORTS: u64 = 1_000_000;
+    /// Largest total deadline extension a vault may allow per missed ping (30 days)
+    pub const MAX_DEADLINE_EXTENSION_SECS: i64 = 30 * 24 * 60 * 60;
+    /// Longest identity verification age `set_max_verification_age` accepts (1 year)
+    pub const MAX_VERIFICATION_AGE_SECS: i64 = 365 * 24 * 60 * 60;
+    /// Timelock of `rotate_verifier` on new vaults (72 hours)
+    pub const DEFAULT_VERIFIER_ROTATION_DELAY_SECS: i64 = 72 * 60 * 60;
+    /// Longest timelock `set_verifier_rotation_delay` accepts (30 days)
+    pub const MAX_VERIFIER_ROTATION_DELAY_SECS: i64 = 30 * 24 * 60 * 60;
+
+    /// Drop every party the testator named to act for them, and their pending verifier
+    /// rotation, when the vault moves to a new testator key
+    pub fn clear_testator_delegates(&mut self) {
+        self.ping_delegate = None;
+        self.ping_delegate_expires_at = 0;
+        self.deadline_delegate = None;
+        self.scheduler_pubkey = None;
+        self.schedule_cron = [0u8; SchedulerTask::MAX_CRON_LEN];
+        self.custody_agent = None;
+        self.custody_expires_at = 0;
+        self.pending_verifier = None;
+        self.verifier_rotation_effective_at = 0;
+    }
+
+    /// Drop the cNFT at `nonce` in `merkle_tree` once it leaves the vault
+    pub fn remove_registered_cnft(&mut self, merkle_tree: &Pubkey, nonce: u64) -> Result<()> {
+        let position = self
+            .registered_cnfts
+            .iter()
+            .position(|cnft| cnft.merkle_tree == *merkle_tree && cnft.nonce == nonce)
+            .ok_or(ErrorCode::NftNotRegistered)?;
+        self.registered_cnfts.remove(position);
+        Ok(())
+    }
+
+    /// Drop `mint` from the escrowed pNFTs once it leaves the vault
+    pub fn remove_registered_pnft(&mut self, mint: &Pubkey) -> Result<()> {
+        let position = self
+            .registered_pnfts
+            .iter()
+            .position(|registered| registered == mint)
+            .ok_or(ErrorCode::NftNotRegistered)?;
+        self.registered_pnfts.remove(position);
+        Ok(())
+    }
+
+    /// Drop the bookkeeping entry for `mint` once its tokens leave the vault
+    pub fn remove_token_deposit(&mut self, mint: &Pubkey) -> Result<()> {
+        let position = self
+            .token_deposits
+            .iter()
+            .position(|deposit| deposit.mint == *mint)
+            .ok_or(ErrorCode::TokenNotDeposited)?;
+        self.token_deposits.remove(position);
+        Ok(())
+    }
+
+    /// Co-beneficiary splits must name distinct accounts other than the primary
+    /// beneficiary, each with a non-zero allocation, together at most 10_000 bps.
+    pub fn validate_beneficiary_splits(&self, splits: &[BeneficiaryEntry]) -> Result<()> {
+        require!(
+            splits.len() <= Self::MAX_CO_BENEFICIARIES,
+            ErrorCode::InvalidBeneficiarySplits
+        );
+        let mut total_bps: u32 = 0;
+        for (i, entry) in splits.iter().enumerate() {
+            require!(entry.allocation_bps > 0, ErrorCode::InvalidBeneficiarySplits);
+            require!(
+                entry.beneficiary != self.beneficiary
+                    && splits[..i].iter().all(|other| other.beneficiary != entry.beneficiary),
+                ErrorCode::InvalidBeneficiarySplits
+            );
+            total_bps += entry.allocation_bps as u32;
+        }
+        require!(total_bps <= 10_000, ErrorCode::InvalidBeneficiarySplits);
+        Ok(())
+    }
+
+    /// Lamports owed to each co-beneficiary out of `amount`, in split order. Rounding
+    /// dust goes to the primary beneficiary with the remainder.
+    pub fn co_beneficiary_shares(&self, amount: u64) -> Vec<u64> {
+        self.co_beneficiaries
+            .iter()
+            .map(|entry| (amount as u128 * entry.allocation_bps as u128 / 10_000) as u64)
+            .collect()
+    }
+
+    /// Checks all timeout invariants in one place and fails on the first violation:
+    /// `min_ping > 0`, `warning > min_ping`, `timeout > warning`, `grace >= 0`,
+    /// `grace < timeout - warning` and `execution_delay >= 0`.
+    pub fn validate_timeout_params(
+        warning: i64,
+        timeout: i64,
+        min_ping: i64,
+        grace: i64,
+        execution_delay: i64,
+    ) -> Result<()> {
+        let violation = if min_ping <= 0 {
+            Some("min_ping_interval_secs must be positive")
+        } else if warning <= min_ping {
+            Some("warning_timeout_secs must exceed min_ping_interval_secs")
+        } else if timeout <= warning {
+            return err!(ErrorCode::InvalidWarningTimeout);
+        } else if grace < 0 {
+            Some("grace_period_secs must not be negative")
+        } else if grace >= timeout - warning {
+            Some("grace_period_secs must be shorter than timeout_secs - warning_timeout_secs")
+        } else if execution_delay < 0 {
+            Some("execution_delay_secs must not be negative")
+        } else {
+            None
+        };
+
+        if let Some(reason) = violation {
+            msg!("Invalid timeout configuration: {}", reason);
+            return err!(ErrorCode::InvalidTimeoutConfiguration);
+        }
+        Ok(())
+    }
+
+    /// Portion of `amount` held back for the tax authority (0 when no tax is configured).
+    pub fn inheritance_tax(&self, amount: u64) -> u64 {
+        if self.tax_authority_oracle.is_none() {
+            return 0;
+        }
+        (amount as u128 * self.tax_rate_bps as u128 / 10_000) as u64
+    }
+
+    /// Who must sign off an execution at `now`: the verifier committee once it is
+    /// effective, otherwise the verifier.
+    pub fn execution_verifier(&self, now: i64) -> Pubkey {
+        match self.verifier_committee {
+            Some(committee) if now >= self.verifier_committee_effective_at => committee,
+            _ => self.verifier,
+        }
+    }
+
+    /// Checks the beneficiary and the `execution_verifier` of an execution against the
+    /// vault in constant time. Any mismatch yields the same `ErrorCode::Unauthorized`,
+    /// so a caller cannot learn which party was wrong.
+    pub fn verify_execution_parties(&self, beneficiary: &Pubkey, verifier: &Pubkey, now: i64) -> Result<()> {
+        let beneficiary_ok = constant_time_eq_32(&self.beneficiary.to_bytes(), &beneficiary.to_bytes());
+        let verifier_ok = constant_time_eq_32(&self.execution_verifier(now).to_bytes(), &verifier.to_bytes());
+        require!(beneficiary_ok & verifier_ok, ErrorCode::Unauthorized);
+        Ok(())
+    }
+
+    pub fn key_state(&self) -> KeyState {
+        if self.encrypted_key_v2.is_some() {
+            KeyState::Wrapped
+        } else {
+            KeyState::Erased
+        }
+    }
+
+    /// Checks invariants every reachable vault satisfies. Pure, so it can also run
+    /// off-chain against fetched accounts.
+    ///
+    /// `bump` isn't checked: every instruction already re-derives the vault address from
+    /// it through its `seeds` constraint. Nor is `executed => lamports == 0`: executions
+    /// without `transfer_funds`, and lottery prizes awaiting `claim_lottery_prize`, keep
+    /// their lamports in the executed vault.
+    pub fn verify_internal_consistency(&self) -> core::result::Result<(), VaultConsistencyError> {
+        if self.encrypted_password.len() > Self::MAX_ENCRYPTED_PASSWORD_SIZE {
+            return Err(VaultConsistencyError::EncryptedPasswordTooLarge);
+        }
+        if self.warning_timeout_secs >= self.timeout_secs {
+            return Err(VaultConsistencyError::WarningNotBeforeTimeout);
+        }
+        if self.last_ping < self.created_at {
+            return Err(VaultConsistencyError::LastPingBeforeCreation);
+        }
+        if self.lamports > u64::MAX / 2 {
+            return Err(VaultConsistencyError::LamportsOutOfRange);
+        }
+        if self.has_compressed_liveness && self.is_debug {
+            return Err(VaultConsistencyError::DebugVaultWithCompressedLiveness);
+        }
+        // `draw_lottery_winner` only runs on executed lottery vaults
+        if self.lottery_winner.is_some() && !(self.executed && self.lottery_mode) {
+            return Err(VaultConsistencyError::LotteryWinnerBeforeExecution);
+        }
+        Ok(())
+    }
+
+    /// On-chain wrapper of `verify_internal_consistency`.
+    pub fn assert_consistent(&self) -> Result<()> {
+        if let Err(violation) = self.verify_internal_consistency() {
+            msg!("Vault invariant violated: {:?}", violation);
+            return err!(ErrorCode::VaultInvariantViolated);
+        }
+        Ok(())
+    }
+
+    /// Checks that `agent` holds an unexpired ping custody for the vault.
+    pub fn verify_custody_agent(&self, agent: &Pubkey, now: i64) -> Result<()> {
+        require!(self.custody_agent == Some(*agent), ErrorCode::CustodyAgentMismatch);
+        require!(now < self.custody_expires_at, ErrorCode::CustodyExpired);
+        Ok(())
+    }
+
+    /// Marks the beneficiary's identity as verified at `now`. The first verification
+    /// also starts the destruction bond forfeiture (`beneficiary_verified_at`).
+    pub fn record_identity_verification(&mut self, now: i64) {
+        if self.beneficiary_verified_at.is_none() {
+            self.beneficiary_verified_at = Some(now);
+        }
+        self.full_identity_verified = true;
+        self.last_identity_verified_at = now;
+        self.has_been_verified = true;
+    }
+
+    /// Fails with `VerificationRecordExpired` when a maximum verification age is set
+    /// and the latest identity verification is missing or older than that.
+    pub fn check_identity_freshness(&self, now: i64) -> Result<()> {
+        if self.max_verification_age_secs > 0 {
+            require!(
+                self.has_been_verified
+                    && now.saturating_sub(self.last_identity_verified_at) <= self.max_verification_age_secs,
+                ErrorCode::VerificationRecordExpired
+            );
+        }
+        Ok(())
+    }
+
+    /// Checks the signer of a liveness update against the testator in constant time.
+    pub fn verify_liveness_parties(&self, testator: &Pubkey) -> Result<()> {
+        require!(
+            constant_time_eq_32(&self.testator.to_bytes(), &testator.to_bytes()),
+            ErrorCode::Unauthorized
+        );
+        Ok(())
+    }
+
+    /// `last_ping`, moved forward by a newer health oracle signal: the blend
+    /// `(last_ping * (10000 - weight) + last_health_signal * weight) / 10000`.
+    /// A stale health signal never makes the vault look less alive.
+    pub fn effective_last_ping(&self) -> i64 {
+        if self.health_oracle.is_none() || self.last_health_signal <= self.last_ping {
+            return self.last_ping;
+        }
+        let weight = self.health_signal_weight_bps as i128;
+        let blended = (self.last_ping as i128 * (10_000 - weight)
+            + self.last_health_signal as i128 * weight)
+            / 10_000;
+        blended as i64
+    }
+
+    /// `SHA256(testator || beneficiary || last_ping || lamports || executed || cid ||
+    /// cid_validator || encrypted_password_len)`, integers little-endian.
+    pub fn compute_content_hash(&self) -> [u8; 32] {
+        solana_sha256_hasher::hashv(&[
+            self.testator.as_ref(),
+            self.beneficiary.as_ref(),
+            &self.last_ping.to_le_bytes(),
+            &self.lamports.to_le_bytes(),
+            &[self.executed as u8],
+            &self.cid,
+            &self.cid_validator,
+            &(self.encrypted_password.len() as u32).to_le_bytes(),
+        ])
+        .to_bytes()
+    }
+
+    /// Stores the current `compute_content_hash`. Called by every instruction that
+    /// changes one of the hashed fields.
+    pub fn refresh_content_hash(&mut self) {
+        self.content_hash = self.compute_content_hash();
+    }
+
+    /// Activity score at `now`: `40 * min(pings / expected_pings, 1)` over the last 30
+    /// days, plus 30 with a compressed liveness account, plus 30 for an identity
+    /// verification less than a year old. Only the ping count is stored, so pings in
+    /// the window are estimated by spreading `ping_count` evenly over the vault's age.
+    /// A testator must ping once per `warning_timeout_secs` to stay Active, which gives
+    /// `expected_pings`.
+    pub fn activity_score_components(&self, now: i64) -> ActivityScoreComponents {
+        let age = now.saturating_sub(self.created_at).max(1);
+        let window = age.min(Self::ACTIVITY_WINDOW_SECS);
+        let liveness_component = if now.saturating_sub(self.last_ping) > Self::ACTIVITY_WINDOW_SECS {
+            0
+        } else {
+            let pings = self.ping_count as i128 * window as i128 / age as i128;
+            let expected = (window / self.warning_timeout_secs.max(1)).max(1) as i128;
+            (40 * pings.min(expected) / expected) as u8
+        };
+
+        let identity_fresh = self.has_been_verified
+            && now.saturating_sub(self.last_identity_verified_at) < Self::ACTIVITY_IDENTITY_MAX_AGE_SECS;
+
+        ActivityScoreComponents {
+            liveness_component,
+            compression_component: if self.has_compressed_liveness { 30 } else { 0 },
+            identity_component: if identity_fresh { 30 } else { 0 },
+        }
+    }
+
+    /// Extension granted by `extend_deadline` since the last ping
+    pub fn active_deadline_extension_secs(&self) -> i64 {
+        if self.deadline_extended_for_ping == self.last_ping {
+            self.deadline_extension_secs
+        } else {
+            0
+        }
+    }
+
+    /// Who inherits the assets: the lottery winner once drawn, otherwise the beneficiary.
+    /// `None` while an executed lottery vault waits for `draw_lottery_winner`.
+    pub fn heir(&self) -> Option<Pubkey> {
+        match self.lottery_winner {
+            Some(winner) => Some(winner),
+            None if self.lottery_mode && self.executed => None,
+            None => Some(self.beneficiary),
+        }
+    }
+
+    /// Whether an execution is pending at `now`: the vault is claimable but still within
+    /// `execution_delay_secs`. Only `revoke_pending_execution` returns it to Active then.
+    pub fn execution_pending(&self, now: i64) -> bool {
+        self.get_state(now).is_claimable()
+            && now
+                .saturating_sub(self.effective_last_ping())
+                .saturating_sub(self.effective_timeout_secs())
+                <= self.execution_delay_secs
+    }
+
+    /// `timeout_secs` plus the active `extend_deadline` extension
+    pub fn effective_timeout_secs(&self) -> i64 {
+        self.timeout_secs.saturating_add(self.active_deadline_extension_secs())
+    }
+
+    pub fn get_state(&self, now: i64) -> VaultState {
+        if self.executed {
+            return VaultState::Executed;
+        }
+        let time_since_ping = now.saturating_sub(self.effective_last_ping());
+        let timeout_secs = self.effective_timeout_secs();
+        if time_since_ping > timeout_secs {
+            match &self.fallback_beneficiary {
+                Some(fallback)
+                    if time_since_ping - timeout_secs > fallback.claim_window_secs =>
+                {
+                    VaultState::FallbackClaimable
+                }
+                _ => VaultState::Claimable,
+            }
+        } else if time_since_ping > self.warning_timeout_secs {
+            VaultState::Warning
+        } else {
+            VaultState::Active
+        }
+    }
+
+    pub const SIZE: usize =
+        32 +  // testator
+        32 +  // beneficiary
+        32 +  // verifier
+        32 +  // beneficiary_identity_hash
+        32 +  // beneficiary_email_hash
+        32 +  // beneficiary_document_id_hash
+        32 +  // cid
+        32 +  // cid_validator
+        8  +  // last_ping
+        8  +  // created_at
+        8  +  // warning_timeout_secs
+        8  +  // timeout_secs
+        8  +  // min_ping_interval_secs
+        8  +  // grace_period_secs
+        8  +  // execution_delay_secs
+        1  +  // executed
+        8  +  // lamports
+        4  + Self::MAX_ENCRYPTED_PASSWORD_SIZE +  // Vec<u8> encrypted_password
+        1  + EncryptedKeyV2::SIZE +                // Option<EncryptedKeyV2> encrypted_key_v2
+        1  + 32 +                                  // Option<[u8; 32]> light_root
+        1  +                                       // is_debug
+        1  +                                       // has_compressed_liveness
+        8  +                                       // ping_count
+        1  +                                       // compression_retry_count
+        8  +                                       // last_compression_failure_slot
+        1  + 32 +                                  // Option<Pubkey> tax_authority_oracle
+        2  +                                       // tax_rate_bps
+        1  + 32 +                                  // Option<Pubkey> migrated_to
+        8  +                                       // execution_nonce
+        1  +                                       // compressed_liveness_version
+        8  +                                       // compression_created_at
+        1  + 32 +                                  // Option<Pubkey> custody_agent
+        8  +                                       // custody_expires_at
+        4  + Self::MAX_BENEFICIARY_INSTRUCTIONS_SIZE + // Vec<u8> beneficiary_instructions
+        1  +                                       // key_fragment_type
+        1  +                                       // key_fragment_index
+        1  +                                       // key_fragment_total
+        8  +                                       // destruction_bond_lamports
+        1  + 8 +                                   // Option<i64> beneficiary_verified_at
+        8  +  // update_sequence
+        1  +  // full_identity_verified
+        1  +  // execution_revocable
+        1  +  // execution_revocation_count
+        8  +  // max_verification_age_secs
+        8  +  // last_identity_verified_at
+        1  +  // has_been_verified
+        32 +  // last_ping_note
+        1  + 32 +  // Option<[u8; 32]> expected_ping_note
+        1  +  // admin_pinged
+        1  +  // admin_ping_count
+        1  + 32 +  // Option<Pubkey> health_oracle
+        8  +  // last_health_signal
+        2  +  // health_signal_weight_bps
+        1  +  // lottery_mode
+        4  + 32 * Self::MAX_LOTTERY_PARTICIPANTS +  // Vec<Pubkey> lottery_participants
+        1  + 32 +  // Option<Pubkey> lottery_winner
+        1  +  // emergency_revealed
+        8  +  // vault_id
+        1  + 32 +  // Option<Pubkey> deed_nft_mint
+        1  +  // deed_burned
+        1  + 32 +  // Option<Pubkey> scheduler_pubkey
+        32 +  // schedule_cron
+        8  +  // scheduler_ping_count
+        32 +  // content_hash
+        4  + 32 * Self::MAX_REGISTERED_NFTS +  // Vec<Pubkey> registered_nfts
+        1  +  // last_activity_score
+        8  +  // last_scored_at
+        8  +  // vault_expiry_secs
+        1  + 32 +  // Option<Pubkey> expiry_refund_destination
+        1  +  // key_wrapped_with_hkdf
+        1  +  // secret_verified
+        4  + LegalDocumentCommitment::SIZE * Self::MAX_LEGAL_DOCUMENTS +  // Vec<LegalDocumentCommitment> legal_documents
+        1  +  // contact_verified
+        1  +  // vault_nonce
+        8  +  // executed_at
+        8  +  // lamports_transferred
+        4  + BeneficiaryEntry::SIZE * Self::MAX_CO_BENEFICIARIES +  // Vec<BeneficiaryEntry> co_beneficiaries
+        4  + TokenDeposit::SIZE * Self::MAX_TOKEN_DEPOSITS +  // Vec<TokenDeposit> token_deposits
+        4  + 32 * Self::MAX_REGISTERED_PNFTS +  // Vec<Pubkey> registered_pnfts
+        4  + RegisteredCnft::SIZE * Self::MAX_REGISTERED_CNFTS +  // Vec<RegisteredCnft> registered_cnfts
+        32 +  // seed_beneficiary
+        1  + FallbackBeneficiary::SIZE +  // Option<FallbackBeneficiary> fallback_beneficiary
+        32 +  // seed_testator
+        1  +  // require_liveness_proof
+        8  +  // crank_bounty_lamports
+        8  +  // warning_cranked_for_ping
+        8  +  // claimable_cranked_for_ping
+        1  + 32 +  // Option<Pubkey> deadline_delegate
+        8  +  // max_deadline_extension_secs
+        8  +  // deadline_extension_secs
+        8  +  // deadline_extended_for_ping
+        1  + 32 +  // Option<Pubkey> ping_delegate
+        8  +  // ping_delegate_expires_at
+        8  +  // relayed_ping_nonce
+        1  +  // require_verifier_attestation
+        8  +  // attestation_nonce
+        1  + 32 +  // Option<Pubkey> pending_verifier
+        8  +  // verifier_rotation_effective_at
+        8  +  // verifier_rotation_delay_secs
+        1  + RecoveryEscrow::SIZE +  // Option<RecoveryEscrow> recovery_escrow
+        1  + 32 +  // Option<Pubkey> verifier_committee
+        8  +  // verifier_committee_effective_at
+        8  +  // lottery_draw_slot
+        1;    // bump
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ContactType {
+    Family,
+    Attorney,
+    MedicalProxy,
+    Executor,
+}
+
+/// An emergency contact, encrypted to the verifier's key.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct EncryptedContact {
+    pub encrypted_data: [u8; 128],
+    pub contact_type: ContactType,
+}
+
+impl EncryptedContact {
+    pub const SIZE: usize = 128 + 1;
+}
+
+/// Emergency contacts to notify when a vault enters the Warning state.
+/// PDA: `[b"contacts", vault]`. Only the verifier can read them.
+#[account]
+pub struct EmergencyContactRegistry {
+    pub vault: Pubkey,
+    pub contacts: Vec<EncryptedContact>,
+    pub bump: u8,
+}
+
+impl EmergencyContactRegistry {
+    pub const MAX_CONTACTS: usize = 5;
+
+    pub const SIZE: usize =
+        32 +  // vault
+        4  + Self::MAX_CONTACTS * EncryptedContact::SIZE +  // Vec<EncryptedContact> contacts
+        1;    // bump
+}
+
+/// A personal message from the testator, encrypted to the beneficiary's key and
+/// revealed atomically with the inheritance. PDA: `[b"capsule", vault]`.
+#[account]
+pub struct TimeCapsuleMessage {
+    pub vault: Pubkey,
+    pub encrypted_message: Vec<u8>,
+    pub created_at: i64,
+    pub revealed: bool,
+    pub bump: u8,
+}
+
+impl TimeCapsuleMessage {
+    pub const MAX_MESSAGE_SIZE: usize = 1024;
+
+    pub const SIZE: usize =
+        32 +  // vault
+        4  + Self::MAX_MESSAGE_SIZE +  // Vec<u8> encrypted_message
+        8  +  // created_at
+        1  +  // revealed
+        1;    // bump
+}
+
+/// Length of the upgradeable loader's ProgramData header: 4-byte enum tag,
+/// 8-byte deployment slot, 1 + 32-byte optional upgrade authority.
+const LIGHT_PROGRAM_DATA_HEADER_LEN: usize = 4 + 8 + 1 + 32;
+
+/// Program-wide configuration, managed by the protocol authority.
+/// PDA: `[b"protocol_config"]`.
+#[account]
+#[derive(Default)]
+pub struct ProtocolConfig {
+    pub authority: Pubkey,
+    pub light_program_hash: [u8; 8],     // Expected hash of the deployed Light System Program
+    pub max_vaults_per_testator: u8,     // Per-testator cap, enforced via VaultRegistry
+    pub max_total_protocol_vaults: u64,  // Protocol-wide cap on live vaults
+    pub total_vaults: u64,               // Live vaults created with the config provided
+    pub proposal_count: u64,             // Id of the next governance proposal
+    pub upgrade_proxy_authority: Option<Pubkey>, // Grants CPI access to new program versions
+    pub v2_enabled: bool,                // New compressed liveness accounts use CompressedLivenessV2
+    pub benchmarks_enabled: bool,        // run_benchmark is available (devnet/testnet only)
+    pub treasury: Pubkey,                // Receives protocol fees and forfeited bonds
+    pub executions_paused: bool,         // Emergency stop for execute_inheritance
+    pub pause_expires_at: i64,           // The pause lapses on its own after this time
+    pub recovery_authority: Option<Pubkey>, // Multisig allowed to call emergency_key_reveal
+    pub next_vault_id: u64,              // Id assigned to the next vault created with the config
+    pub dry_runs_enabled: bool,          // dry_run_compressed_liveness_update is available
+    pub extra_nullifier_check: bool,     // Liveness proofs are checked against the NullifierRegistry
+    pub vault_creation_fee_lamports: u64, // Charged by init_inheritance unless a FeeWaiver applies
+    pub require_registered_verifier: bool, // init_inheritance only accepts VerifierRegistry members
+    pub recovery_encryption_key: [u8; 32], // X25519 key recovery escrows are wrapped to
+    pub encrypted_recovery_decryption_key: Option<EncryptedKeyV2>, // Its private half, for the recovery authority
+    pub bump: u8,
+}
+
+impl ProtocolConfig {
+    pub const DEFAULT_MAX_VAULTS_PER_TESTATOR: u8 = 16;
+    pub const DEFAULT_MAX_TOTAL_PROTOCOL_VAULTS: u64 = 1_000_000;
+    /// An execution pause lapses after 24 hours unless renewed
+    pub const EXECUTION_PAUSE_SECS: i64 = 24 * 60 * 60;
+
+    pub fn executions_paused_at(&self, now: i64) -> bool {
+        self.executions_paused && now < self.pause_expires_at
+    }
+
+    pub const SIZE: usize =
+        32 +  // authority
+        8  +  // light_program_hash
+        1  +  // max_vaults_per_testator
+        8  +  // max_total_protocol_vaults
+        8  +  // total_vaults
+        8  +  // proposal_count
+        1  + 32 +  // Option<Pubkey> upgrade_proxy_authority
+        1  +  // v2_enabled
+        1  +  // benchmarks_enabled
+        32 +  // treasury
+        1  +  // executions_paused
+        8  +  // pause_expires_at
+        1  + 32 +  // Option<Pubkey> recovery_authority
+        8  +  // next_vault_id
+        1  +  // dry_runs_enabled
+        1  +  // extra_nullifier_check
+        8  +  // vault_creation_fee_lamports
+        1  +  // require_registered_verifier
+        32 +  // recovery_encryption_key
+        1  + EncryptedKeyV2::SIZE +  // Option<EncryptedKeyV2> encrypted_recovery_decryption_key
+        1;    // bump
+}
+
+/// Per-testator count of live vaults, and the vault nonces used for each
+/// beneficiary. PDA: `[b"registry", testator]`.
+#[account]
+pub struct VaultRegistry {
+    pub testator: Pubkey,
+    pub count: u8,
+    pub vault_nonces: Vec<BeneficiaryVaultNonces>,
+}
+
+impl VaultRegistry {
+    pub const MAX_BENEFICIARIES: usize = 16;
+
+    pub const SIZE: usize =
+        32 +  // testator
+        1  +  // count
+        4  + Self::MAX_BENEFICIARIES * BeneficiaryVaultNonces::SIZE;  // Vec<BeneficiaryVaultNonces> vault_nonces
+
+    /// Marks `vault_nonce` as used for `beneficiary`. Nonces are never released, so
+    /// a vault address is only ever used once per pair, even after the vault closes.
+    pub fn claim_vault_nonce(&mut self, beneficiary: Pubkey, vault_nonce: u8) -> Result<()> {
+        let index = match self.vault_nonces.iter().position(|entry| entry.beneficiary == beneficiary) {
+            Some(index) => index,
+            None => {
+                require!(
+                    self.vault_nonces.len() < Self::MAX_BENEFICIARIES,
+                    ErrorCode::TestatorVaultLimitExceeded
+                );
+                self.vault_nonces.push(BeneficiaryVaultNonces {
+                    beneficiary,
+                    used: [0; 32],
+                });
+                self.vault_nonces.len() - 1
+            }
+        };
+
+        let entry = &mut self.vault_nonces[index];
+        require!(!entry.is_exhausted(), ErrorCode::VaultNonceExhausted);
+        require!(!entry.is_used(vault_nonce), ErrorCode::VaultNonceInUse);
+        entry.used[vault_nonce as usize / 8] |= 1 << (vault_nonce % 8);
+        Ok(())
+    }
+}
+
+/// Inheritance tax held back at execution until the tax authority oracle confirms
+/// payment. PDA: `[b"tax_escrow", vault]`.
+#[account]
+pub struct TaxEscrow {
+    pub vault: Pubkey,
+    pub tax_authority: Pubkey,
+    pub tax_amount: u64,
+    pub due_by: i64,
+    pub paid: bool,
+    pub evidence_cid: [u8; 32],          // IPFS CID of the payment evidence
+}
+
+impl TaxEscrow {
+    /// Time the tax authority has to settle the tax after execution (180 days)
+    pub const PAYMENT_WINDOW_SECS: i64 = 180 * 24 * 60 * 60;
+
+    pub const SIZE: usize =
+        32 +  // vault
+        32 +  // tax_authority
+        8  +  // tax_amount
+        8  +  // due_by
+        1  +  // paid
+        32;   // evidence_cid
+}
+
+/// A single state transition recorded in a vault's state history.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct StateTransitionEntry {
+    pub state: VaultState,
+    pub entered_at: i64,               // 0 marks an empty (or pruned) slot
+    pub triggered_by: Pubkey,
+}
+
+impl StateTransitionEntry {
+    pub const SIZE: usize =
+        1  +  // state
+        8  +  // entered_at
+        32;   // triggered_by
+}
+
+/// Ring buffer of the last 16 state transitions of a vault.
+/// PDA: `[b"state_history", vault]`.
+#[account]
+#[derive(Default)]
+pub struct VaultStateHistory {
+    pub vault: Pubkey,
+    pub entries: [StateTransitionEntry; 16],
+    pub head: u8,                      // slot the next transition is written to
+    pub total_transitions: u64,
+    pub bump: u8,
+}
+
+impl VaultStateHistory {
+    pub const CAPACITY: usize = 16;
+
+    pub const SIZE: usize =
+        32 +  // vault
+        Self::CAPACITY * StateTransitionEntry::SIZE +  // entries
+        1  +  // head
+        8  +  // total_transitions
+        1;    // bump
+
+    fn latest(&self) -> &StateTransitionEntry {
+        &self.entries[(self.head as usize + Self::CAPACITY - 1) % Self::CAPACITY]
+    }
+
+    /// Records `state` unless it is the state the vault was last seen in.
+    pub fn record_transition(&mut self, state: VaultState, entered_at: i64, triggered_by: Pubkey) {
+        let latest = self.latest();
+        if latest.entered_at != 0 && latest.state == state {
+            return;
+        }
+
+        self.entries[self.head as usize] = StateTransitionEntry {
+            state,
+            entered_at,
+            triggered_by,
+        };
+        self.head = ((self.head as usize + 1) % Self::CAPACITY) as u8;
+        self.total_transitions = self.total_transitions.saturating_add(1);
+    }
+
+    /// Recorded entries, oldest first, skipping empty slots.
+    pub fn chronological_entries(&self) -> Vec<StateTransitionEntry> {
+        (0..Self::CAPACITY)
+            .map(|i| self.entries[(self.head as usize + i) % Self::CAPACITY])
+            .filter(|entry| entry.entered_at != 0)
+            .collect()
+    }
+
+    /// Clears every entry but the latest that was recorded before `cutoff`.
+    pub fn prune(&mut self, cutoff: i64) {
+        let latest = (self.head as usize + Self::CAPACITY - 1) % Self::CAPACITY;
+        for (i, entry) in self.entries.iter_mut().enumerate() {
+            if i != latest && entry.entered_at != 0 && entry.entered_at < cutoff {
+                *entry = StateTransitionEntry::default();
+            }
+        }
+    }
+}
+
+/// `ProtocolConfig` fields that testators can change through governance.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum GovernanceParameter {
+    MaxVaultsPerTestator,
+    MaxTotalProtocolVaults,
+}
+
+impl GovernanceParameter {
+    /// Checks that `value` is in range for the parameter.
+    pub fn validate(&self, value: i64) -> Result<()> {
+        let valid = match self {
+            GovernanceParameter::MaxVaultsPerTestator => (1..=u8::MAX as i64).contains(&value),
+            GovernanceParameter::MaxTotalProtocolVaults => value > 0,
+        };
+        require!(valid, ErrorCode::InvalidProposalValue);
+        Ok(())
+    }
+
+    /// Writes `value` into the corresponding `ProtocolConfig` field.
+    pub fn apply(&self, config: &mut ProtocolConfig, value: i64) -> Result<()> {
+        self.validate(value)?;
+        match self {
+            GovernanceParameter::MaxVaultsPerTestator => config.max_vaults_per_testator = value as u8,
+            GovernanceParameter::MaxTotalProtocolVaults => config.max_total_protocol_vaults = value as u64,
         }
-        // ---------------------------------
+        Ok(())
+    }
+}
 
-        // First liveness update: wrap the key
-        if vault.encrypted_key.is_none() {
-            require!(
-                vault.unwrapped_key.is_some(),
-                ErrorCode::NoUnwrappedKey
-            );
+/// A testator-driven proposal to change a protocol parameter.
+/// PDA: `[b"proposal", proposal_id.to_le_bytes()]`.
+#[account]
+pub struct GovernanceProposal {
+    pub proposal_id: u64,
+    pub proposer: Pubkey,
+    pub parameter: GovernanceParameter,
+    pub proposed_value: i64,
+    pub votes_for: u64,                // Sum of voters' ping_count
+    pub votes_against: u64,
+    pub voting_ends_at: i64,
+    pub executed: bool,
+    pub bump: u8,
+}
 
-            // Derive K_light from a deterministic source
-            // In production with real Light Protocol, this would use the actual state root
-            let mock_root = demo_hash(&[vault.testator.as_ref(), &now.to_le_bytes()].concat());
-            let k_light = derive_key_from_light(
-                &mock_root,
-                &vault.key(),
-                &vault.beneficiary,
-            );
+impl GovernanceProposal {
+    /// Pings a vault needs before its testator may open a proposal
+    pub const MIN_PROPOSER_PING_COUNT: u64 = 100;
+    /// How long a proposal stays open for voting (7 days)
+    pub const VOTING_PERIOD_SECS: i64 = 7 * 24 * 60 * 60;
 
-            // Encrypt K with K_light (simple XOR for demo)
-            let k = vault.unwrapped_key.unwrap();
-            let mut encrypted_key = Vec::with_capacity(32);
-            for i in 0..32 {
-                encrypted_key.push(k[i] ^ k_light[i]);
-            }
+    pub const SIZE: usize =
+        8  +  // proposal_id
+        32 +  // proposer
+        1  +  // parameter
+        8  +  // proposed_value
+        8  +  // votes_for
+        8  +  // votes_against
+        8  +  // voting_ends_at
+        1  +  // executed
+        1;    // bump
+}
 
-            vault.encrypted_key = Some(encrypted_key);
-            vault.unwrapped_key = None; // Clear plaintext
-            vault.light_root = Some(mock_root);
-        }
+/// Record of a vault's vote on a proposal. PDA: `[b"vote", proposal, vault]`.
+#[account]
+pub struct ProposalVote {
+    pub proposal_id: u64,
+    pub vault: Pubkey,
+    pub vote: bool,
+    pub weight: u64,
+}
 
-        vault.last_ping = now;
+impl ProposalVote {
+    pub const SIZE: usize =
+        8  +  // proposal_id
+        32 +  // vault
+        1  +  // vote
+        8;    // weight
+}
 
-        Ok(())
+/// Seed of the PDA a calling program signs with when invoking `migrate_vault` via CPI
+pub const UPGRADE_PROXY_SEED: &[u8] = b"upgrade_proxy";
+
+/// Grants an external program (typically a newer version of this one) access to
+/// vaults via CPI. PDA: `[b"cpi_access", caller_program_id]`.
+#[account]
+pub struct CpiAccessToken {
+    pub caller_program_id: Pubkey,
+    pub granted_by: Pubkey,
+    pub granted_at: i64,
+    pub bump: u8,
+}
+
+impl CpiAccessToken {
+    pub const SIZE: usize =
+        32 +  // caller_program_id
+        32 +  // granted_by
+        8  +  // granted_at
+        1;    // bump
+}
+
+/// A verifier key rotation approved by the protocol authority and applied vault by
+/// vault. PDA: `[b"migration", old_verifier]`.
+#[account]
+pub struct MassVerifierMigration {
+    pub old_verifier: Pubkey,
+    pub new_verifier: Pubkey,
+    pub signed_at: i64,
+    pub approved_by_authority: bool,
+    pub bump: u8,
+}
+
+impl MassVerifierMigration {
+    pub const SIZE: usize =
+        32 +  // old_verifier
+        32 +  // new_verifier
+        8  +  // signed_at
+        1  +  // approved_by_authority
+        1;    // bump
+}
+
+/// Upper bound on `run_benchmark` iterations
+pub const MAX_BENCHMARK_ITERATIONS: u8 = 100;
+
+/// Vault operations measurable with `run_benchmark`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BenchmarkOperation {
+    GetState,
+    ValidatePubkeys,
+    ComputeRentExempt,
+    SerializeVault,
+}
+
+/// Benchmarked cost of `update_liveness` by path, reported by dry runs
+pub const DRY_RUN_DEBUG_UPDATE_CU: u64 = 25_000;
+pub const DRY_RUN_COMPRESSED_V1_UPDATE_CU: u64 = 190_000;
+pub const DRY_RUN_COMPRESSED_V2_UPDATE_CU: u64 = 215_000;
+
+/// Maximum compressed liveness accounts read by one `batch_read_compressed_liveness`
+pub const MAX_BATCH_LIVENESS_READS: usize = 8;
+
+/// One entry of `batch_read_compressed_liveness`: a compressed liveness account and the
+/// values the caller claims it holds, which the batch's validity proof checks
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct CompressedLivenessClaim {
+    pub address: [u8; 32],
+    pub last_ping: i64,
+    /// Only checked for V2 accounts
+    pub ping_count: u64,
+}
+
+/// M-of-N verifier committee replacing a vault's single verifier.
+/// PDA: `[b"committee", vault]`.
+#[account]
+pub struct VerifierCommittee {
+    pub vault: Pubkey,
+    pub verifiers: Vec<Pubkey>,
+    pub threshold: u8,
+    pub approvals: Vec<bool>,            // Parallel to `verifiers`
+    pub approval_timestamps: Vec<i64>,   // When each approval was submitted
+    pub approval_expires_in_secs: i64,
+    pub bump: u8,
+}
+
+impl VerifierCommittee {
+    pub const MAX_VERIFIERS: usize = 7;
+
+    pub const SIZE: usize =
+        32 +  // vault
+        4  + 32 * Self::MAX_VERIFIERS +  // Vec<Pubkey> verifiers
+        1  +  // threshold
+        4  + Self::MAX_VERIFIERS +  // Vec<bool> approvals
+        4  + 8 * Self::MAX_VERIFIERS +  // Vec<i64> approval_timestamps
+        8  +  // approval_expires_in_secs
+        1;    // bump
+
+    /// Whether member `index` has an approval that hasn't expired yet
+    pub fn is_live_approval(&self, index: usize, now: i64) -> bool {
+        self.approvals[index]
+            && now.saturating_sub(self.approval_timestamps[index]) < self.approval_expires_in_secs
     }
 
-    /// Execute inheritance - transfers assets and reveals the encrypted password to the beneficiary.
-    /// 
-    /// # Arguments
-    /// * `transfer_funds` - If true, transfer SOL to beneficiary. If false, only mark as executed and emit password.
-    pub fn execute_inheritance(ctx: Context<ExecuteInheritance>, transfer_funds: bool) -> Result<()> {
-        let now = Clock::get()?.unix_timestamp;
-        let state = ctx.accounts.vault.get_state(now);
+    pub fn live_approvals(&self, now: i64) -> u8 {
+        (0..self.verifiers.len())
+            .filter(|&i| self.is_live_approval(i, now))
+            .count() as u8
+    }
+}
 
-        // 1. State Machine validation
-        require!(state != VaultState::Executed, ErrorCode::AlreadyExecuted);
-        require!(state == VaultState::Claimable, ErrorCode::TransitionNotAllowed);
+/// Upper bound on vaults pinged by one admin_ping_liveness call
+pub const MAX_ADMIN_PING_VAULTS: usize = 16;
+/// How old (in slots) the slot signed for admin_ping_liveness may be
+pub const MAX_ADMIN_SIGNATURE_AGE_SLOTS: u64 = 150;
+/// Domain separator of the message signed for update_liveness_relayed
+pub const RELAYED_PING_DOMAIN: &[u8] = b"zelf-relayed-ping-v2";
+/// Domain separator of the message signed for execute_inheritance_with_attestation
+pub const VERIFIER_ATTESTATION_DOMAIN: &[u8] = b"zelf-verifier-attestation-v1";
+/// How old (in seconds) the timestamp signed for update_liveness_relayed may be
+pub const MAX_RELAYED_PING_AGE_SECS: i64 = 300;
 
-        // 2. Identity Verification (Verifier must sign)
-        // This confirms the "Face Scan + ID Match" from your diagram happened off-chain.
-        require!(
-            ctx.accounts.verifier.key() == ctx.accounts.vault.verifier,
-            ErrorCode::InvalidVerifier
-        );
+/// Upper bound on the `data` of a `VaultStateExported` event
+pub const MAX_VAULT_EXPORT_SIZE: usize = 2048;
 
-        // 3. Light Protocol validation (skip in debug mode)
-        // In debug mode, we don't require the Light root to be set.
-        if !ctx.accounts.vault.is_debug {
-            require!(
-                ctx.accounts.vault.light_root.is_some(),
-                ErrorCode::InvalidLightRoot
-            );
-        }
+/// A testator's compressed liveness account, as recorded in the liveness index
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct CompressedLivenessEntry {
+    pub testator: Pubkey,
+    pub vault: Pubkey,
+    pub address: [u8; 32],               // Compressed account address
+}
 
-        // 4. Transfer SOL to beneficiary (if enabled)
-        if transfer_funds {
-            let vault_account_info = ctx.accounts.vault.to_account_info();
-            let vault_lamports = ctx.accounts.vault.lamports;
+impl CompressedLivenessEntry {
+    pub const SIZE: usize =
+        32 +  // testator
+        32 +  // vault
+        32;   // address
+}
 
-            require!(vault_lamports > 0, ErrorCode::NoAssets);
+/// Range of testators stored in one index page
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy)]
+pub struct IndexPageRoot {
+    pub page_id: u8,
+    pub min_testator: Pubkey,            // Smallest testator routed to this page
+}
 
-            let transfer_amount = vault_lamports;
-            let rent = Rent::get()?;
-            let min_rent = rent.minimum_balance(vault_account_info.data_len());
-            let current_balance = vault_account_info.lamports();
-            
-            require!(
-                current_balance - transfer_amount >= min_rent,
-                ErrorCode::InsufficientFundsForRent
-            );
+/// Root of the compressed liveness index: page ranges sorted by `min_testator`.
+/// PDA: `[b"idx_root"]`.
+#[account]
+pub struct CompressedLivenessIndexRoot {
+    pub pages: Vec<IndexPageRoot>,
+    pub bump: u8,
+}
 
-            ctx.accounts.vault.lamports = 0;
+impl CompressedLivenessIndexRoot {
+    pub const MAX_PAGES: usize = 64;
 
-            **vault_account_info.try_borrow_mut_lamports()? -= transfer_amount;
-            **ctx.accounts.beneficiary.to_account_info().try_borrow_mut_lamports()? += transfer_amount;
-        }
+    pub const SIZE: usize =
+        4  + Self::MAX_PAGES * (1 + 32) +  // Vec<IndexPageRoot> pages
+        1;    // bump
 
-        // 5. Mark as executed and emit the encrypted password as the "reward"
-        let vault = &mut ctx.accounts.vault;
-        vault.executed = true;
+    /// Position in `pages` of the page holding `testator` (binary search)
+    pub fn route(&self, testator: &Pubkey) -> usize {
+        self.pages
+            .partition_point(|page| page.min_testator <= *testator)
+            .saturating_sub(1)
+    }
+}
 
-        // Emit an event with the encrypted password so the beneficiary can retrieve it
-        emit!(InheritanceExecuted {
-            vault: vault.key(),
-            beneficiary: vault.beneficiary,
-            testator: vault.testator,
-            encrypted_password: vault.encrypted_password.clone(),
-            cid: vault.cid,
-            cid_validator: vault.cid_validator,
-            beneficiary_identity_hash: vault.beneficiary_identity_hash,
-            beneficiary_email_hash: vault.beneficiary_email_hash,
-            beneficiary_document_id_hash: vault.beneficiary_document_id_hash,
-        });
+/// One fixed-size page of the compressed liveness index; the first `count` entries
+/// are sorted by testator. PDA: `[b"idx_page", &[page_id]]`.
+#[account]
+pub struct CompressedLivenessIndexPage {
+    pub page_id: u8,
+    pub count: u8,
+    pub entries: [CompressedLivenessEntry; 32],
+    pub bump: u8,
+}
 
-        Ok(())
+impl CompressedLivenessIndexPage {
+    pub const CAPACITY: usize = 32;
+
+    pub const SIZE: usize =
+        1  +  // page_id
+        1  +  // count
+        Self::CAPACITY * CompressedLivenessEntry::SIZE +  // entries
+        1;    // bump
+
+    /// Binary search over the used entries
+    pub fn find(&self, testator: &Pubkey) -> std::result::Result<usize, usize> {
+        self.entries[..self.count as usize].binary_search_by(|entry| entry.testator.cmp(testator))
     }
 
-    pub fn init_light_registry(
-        ctx: Context<InitLightRegistry>,
-        initial_root: [u8; 32],
-    ) -> Result<()> {
-        let state = &mut ctx.accounts.light_state;
-        state.current_root = initial_root;
+    /// Inserts `entry` in order, or replaces the testator's existing entry.
+    pub fn upsert(&mut self, entry: CompressedLivenessEntry) -> Result<()> {
+        match self.find(&entry.testator) {
+            Ok(i) => self.entries[i] = entry,
+            Err(i) => {
+                let count = self.count as usize;
+                require!(count < Self::CAPACITY, ErrorCode::IndexPageFull);
+                self.entries.copy_within(i..count, i + 1);
+                self.entries[i] = entry;
+                self.count += 1;
+            }
+        }
         Ok(())
     }
 
-    /// Verify if a given identity hash matches a vault's beneficiary_identity_hash.
-    /// This allows a user to prove they are the intended beneficiary.
-    /// 
-    /// Returns an event with vault details if the identity matches.
-    /// This is useful for beneficiaries to discover their inheritance claims.
-    pub fn verify_beneficiary_identity(
-        ctx: Context<VerifyBeneficiaryIdentity>,
-        identity_hash: [u8; 32],
-    ) -> Result<()> {
-        let vault = &ctx.accounts.vault;
-        
-        // Check if the provided identity hash matches
-        require!(
-            vault.beneficiary_identity_hash == identity_hash,
-            ErrorCode::IdentityHashMismatch
-        );
-        
-        // Emit an event with vault info for the beneficiary
-        emit!(BeneficiaryVerified {
-            vault: vault.key(),
-            beneficiary: vault.beneficiary,
-            testator: vault.testator,
-            cid: vault.cid,
-            cid_validator: vault.cid_validator,
-            is_claimable: vault.get_state(Clock::get()?.unix_timestamp) == VaultState::Claimable,
-            executed: vault.executed,
-        });
-        
-        Ok(())
+    /// Moves the upper half of this (full) page into the empty page `other`.
+    pub fn split_into(&mut self, other: &mut CompressedLivenessIndexPage) {
+        let half = self.count as usize / 2;
+        let moved = self.count as usize - half;
+        other.entries[..moved].copy_from_slice(&self.entries[half..self.count as usize]);
+        other.count = moved as u8;
+        for entry in &mut self.entries[half..] {
+            *entry = CompressedLivenessEntry::default();
+        }
+        self.count = half as u8;
     }
+}
 
-    /// Cancel a will/inheritance - closes the vault account and returns SOL to the testator.
-    /// This can only be called by the testator.
-    pub fn cancel_will(ctx: Context<CancelWill>) -> Result<()> {
-        let vault = &ctx.accounts.vault;
-        
-        // Safety check: Don't allow cancellation if already executed?
-        // Actually, Anchor's 'close' will handle the transfer.
-        // We just need to make sure the testator is the one signing (handled by accounts).
-        require!(!vault.executed, ErrorCode::AlreadyExecuted);
-        
-        Ok(())
+/// Maps a sequential vault id to its vault PDA. PDA: `[b"vid_index", vault_id (LE)]`.
+#[account]
+pub struct VaultIdIndex {
+    pub vault_id: u64,
+    pub vault: Pubkey,
+    pub bump: u8,
+}
+
+impl VaultIdIndex {
+    pub const SIZE: usize =
+        8  +  // vault_id
+        32 +  // vault
+        1;    // bump
+}
+
+/// A task scheduler's registration for a vault's automatic liveness pings.
+/// PDA: `[b"scheduler_task", vault]`.
+#[account]
+pub struct SchedulerTask {
+    pub vault: Pubkey,
+    pub scheduler: Pubkey,
+    pub schedule_cron: [u8; 32],  // ASCII cron expression, zero-padded
+    pub registered_at: i64,
+    pub bump: u8,
+}
+
+impl SchedulerTask {
+    pub const MAX_CRON_LEN: usize = 32;
+
+    pub const SIZE: usize =
+        32 +  // vault
+        32 +  // scheduler
+        32 +  // schedule_cron
+        8  +  // registered_at
+        1;    // bump
+}
+
+/// Breakdown of a testator's activity score, see `Vault::activity_score_components`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ActivityScoreComponents {
+    pub liveness_component: u8,     // 0-40
+    pub compression_component: u8,  // 0 or 30
+    pub identity_component: u8,     // 0 or 30
+}
+
+impl ActivityScoreComponents {
+    pub fn total(&self) -> u8 {
+        (self.liveness_component + self.compression_component + self.identity_component).min(100)
     }
 }
 
-fn derive_key_from_light(
-    light_root: &[u8; 32],
-    vault_pubkey: &Pubkey,
-    beneficiary: &Pubkey,
-) -> [u8; 32] {
-    // Light Protocol v3: Keys are derived from the state tree index or root.
-    // We use a deterministic XOR-based derivation for this demo.
-    let mut key = [0u8; 32];
-    for i in 0..32 {
-        key[i] = light_root[i] ^ vault_pubkey.as_ref()[i] ^ beneficiary.as_ref()[i];
+/// A compressed liveness proof already used, see `NullifierRegistry`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NullifierEntry {
+    pub nullifier: [u8; 32],
+    pub used_at: i64,
+}
+
+impl NullifierEntry {
+    pub const SIZE: usize = 32 + 8;
+}
+
+/// Nullifiers of recently used compressed liveness proofs, oldest first. A defense in
+/// depth on top of Light Protocol's own nullifiers, enabled by
+/// `ProtocolConfig::extra_nullifier_check`. PDA: `[b"nullifier_registry"]`.
+#[account]
+pub struct NullifierRegistry {
+    pub entries: Vec<NullifierEntry>,
+    pub bump: u8,
+}
+
+impl NullifierRegistry {
+    pub const MAX_ENTRIES: usize = 1024;
+    /// What fits in the 10 KiB an account can be created with from a program
+    pub const INITIAL_CAPACITY: usize = 255;
+    /// Nullifiers older than this can be pruned
+    pub const RETENTION_SECS: i64 = 7 * 24 * 60 * 60;
+
+    pub const fn space(entries: usize) -> usize {
+        4 + entries * NullifierEntry::SIZE +  // Vec<NullifierEntry> entries
+        1                                     // bump
+    }
+
+    pub fn contains(&self, nullifier: &[u8; 32]) -> bool {
+        self.entries.iter().any(|entry| constant_time_eq_32(&entry.nullifier, nullifier))
+    }
+
+    /// Appends `nullifier`, evicting the oldest entry once `capacity` is reached.
+    pub fn record(&mut self, nullifier: [u8; 32], used_at: i64, capacity: usize) {
+        if self.entries.len() >= capacity.min(Self::MAX_ENTRIES) {
+            self.entries.remove(0);
+        }
+        self.entries.push(NullifierEntry { nullifier, used_at });
     }
-    demo_hash(&key)
 }
 
-/// A simple XOR + bit-shift hash for demonstration purposes.
-/// Replaces Keccak256 to avoid Edition 2024 build conflicts.
-fn demo_hash(data: &[u8]) -> [u8; 32] {
-    let mut hash = [0u8; 32];
-    for (i, &byte) in data.iter().enumerate() {
-        hash[i % 32] = hash[i % 32].wrapping_add(byte).rotate_left(3);
-        hash[i % 32] ^= 0x55;
+/// Verifiers approved by the protocol authority. When
+/// `ProtocolConfig::require_registered_verifier` is on, `init_inheritance` only accepts
+/// these. PDA: `[b"verifier_registry"]`.
+#[account]
+pub struct VerifierRegistry {
+    pub verifiers: Vec<Pubkey>,
+    pub bump: u8,
+}
+
+impl VerifierRegistry {
+    pub const MAX_VERIFIERS: usize = 64;
+
+    pub const SIZE: usize =
+        4 + Self::MAX_VERIFIERS * 32 +  // Vec<Pubkey> verifiers
+        1;                              // bump
+
+    pub fn contains(&self, verifier: &Pubkey) -> bool {
+        self.verifiers.contains(verifier)
     }
-    hash
 }
 
-#[derive(Accounts)]
-#[instruction(beneficiary: Pubkey)]
-pub struct InitInheritance<'info> {
-    #[account(
-        init,
-        payer = payer,
-        space = 8 + Vault::SIZE,
-        seeds = [b"vault", testator.key().as_ref(), beneficiary.as_ref()],
-        bump
-    )]
-    pub vault: Account<'info, Vault>,
+/// Oracle-supplied value of one token or NFT holding
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TokenValuation {
+    pub mint: Pubkey,
+    pub amount: u64,
+    pub usd_value_e6: u64,
+}
+
+impl TokenValuation {
+    pub const SIZE: usize = 32 + 8 + 8;
+}
+
+/// Consolidated value of a testator's estate across all their vaults, for tax and
+/// reporting. PDA: `[b"valuation", testator]`.
+#[account]
+pub struct ValuationOracle {
+    pub testator: Pubkey,
+    pub oracle: Pubkey,                  // Only signer allowed to submit valuations
+    pub total_sol_lamports: u64,         // Deposited SOL across the testator's vaults
+    pub token_valuations: Vec<TokenValuation>, // Max 16
+    pub total_usd_value_e6: u64,         // Sum of token_valuations, in micro-USD
+    pub vault_count: u16,                // Unexecuted vaults valued by the last valuation
+    pub last_valued_at: i64,
+    pub bump: u8,
+}
+
+impl ValuationOracle {
+    pub const MAX_TOKEN_VALUATIONS: usize = 16;
+
+    pub const SIZE: usize =
+        32 +  // testator
+        32 +  // oracle
+        8  +  // total_sol_lamports
+        4  + Self::MAX_TOKEN_VALUATIONS * TokenValuation::SIZE +  // Vec<TokenValuation> token_valuations
+        8  +  // total_usd_value_e6
+        2  +  // vault_count
+        8  +  // last_valued_at
+        1;    // bump
+}
+
+/// Secret question letting a beneficiary without biometrics prove their identity.
+/// PDA: `[b"secret_question", vault]`.
+#[account]
+pub struct SecretQuestionRecord {
+    pub vault: Pubkey,
+    pub question_hash: [u8; 32],         // Hash of the question text
+    pub answer_hash: [u8; 32],           // SHA256(SHA256(answer || salt))
+    pub salt: [u8; 32],                  // Random, chosen by the client in set_secret_question
+    pub bump: u8,
+}
+
+impl SecretQuestionRecord {
+    pub const MAX_ANSWER_LEN: usize = 256;
+
+    pub const SIZE: usize =
+        32 +  // vault
+        32 +  // question_hash
+        32 +  // answer_hash
+        32 +  // salt
+        1;    // bump
+}
+
+/// Why a testator's vault creation fees are waived
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FeeWaiverType {
+    Veteran,
+    Charity,
+    EarlyAdopter,
+    ProtocolPartner,
+}
+
+impl FeeWaiverType {
+    /// Number of vaults a waiver of this type covers
+    pub const fn vaults_covered(self) -> u8 {
+        match self {
+            FeeWaiverType::Veteran => 3,
+            FeeWaiverType::Charity => 10,
+            FeeWaiverType::EarlyAdopter => 1,
+            FeeWaiverType::ProtocolPartner => 25,
+        }
+    }
+}
 
-    /// The testator who owns this will (must sign to prove ownership)
-    pub testator: Signer<'info>,
+/// Vault creation fee waiver granted by the protocol authority.
+/// PDA: `[b"fee_waiver", testator]`.
+#[account]
+pub struct FeeWaiver {
+    pub testator: Pubkey,
+    pub waiver_type: FeeWaiverType,
+    pub granted_by: Pubkey,
+    pub expires_at: Option<i64>,         // None = never expires
+    pub vaults_covered: u8,              // Max vaults the waiver applies to
+    pub waivers_used: u8,
+    pub bump: u8,
+}
 
-    /// The payer who funds the vault creation and initial deposit
-    #[account(mut)]
-    pub payer: Signer<'info>,
+impl FeeWaiver {
+    pub const SIZE: usize =
+        32 +  // testator
+        1  +  // waiver_type
+        32 +  // granted_by
+        1  + 8 +  // Option<i64> expires_at
+        1  +  // vaults_covered
+        1  +  // waivers_used
+        1;    // bump
 
-    pub system_program: Program<'info, System>,
+    pub fn is_expired(&self, now: i64) -> bool {
+        self.expires_at.is_some_and(|expires_at| now >= expires_at)
+    }
 }
 
-/// Accounts for creating a compressed liveness account in Light Protocol
-#[derive(Accounts)]
-pub struct CreateCompressedLiveness<'info> {
-    #[account(
-        mut,
-        seeds = [b"vault", testator.key().as_ref(), vault.beneficiary.as_ref()],
-        bump = vault.bump,
-        has_one = testator @ ErrorCode::Unauthorized
-    )]
-    pub vault: Account<'info, Vault>,
-    
-    #[account(mut)]
-    pub testator: Signer<'info>,
-    
-    #[account(mut)]
-    pub fee_payer: Signer<'info>,
-    
-    // Light Protocol system accounts are passed via remaining_accounts
+/// Right to trigger a vault's execution, sold by the testator to a professional
+/// executor. PDA: `[b"exec_right", vault]`.
+#[account]
+pub struct ExecutionRight {
+    pub vault: Pubkey,
+    pub owner: Pubkey,                   // Testator who listed the right
+    pub price_lamports: u64,
+    pub listed: bool,
+    pub sold_at: Option<i64>,
+    pub sold_to: Option<Pubkey>,         // Executor allowed to call execute_inheritance_as_executor
+    pub bump: u8,
 }
 
-/// Accounts for updating liveness via Light Protocol
-#[derive(Accounts)]
-pub struct UpdateLiveness<'info> {
-    #[account(
-        mut,
-        seeds = [b"vault", testator.key().as_ref(), vault.beneficiary.as_ref()],
-        bump = vault.bump,
-        has_one = testator @ ErrorCode::Unauthorized
-    )]
-    pub vault: Account<'info, Vault>,
-    
-    #[account(mut)]
-    pub testator: Signer<'info>,
-    
-    #[account(mut)]
-    pub fee_payer: Signer<'info>,
-    
-    // Light Protocol system accounts are passed via remaining_accounts:
-    // - Address Merkle Tree
-    // - State Tree
-    // - Light System Program
-    // These are dynamically provided by the Light SDK client
+impl ExecutionRight {
+    pub const SIZE: usize =
+        32 +  // vault
+        32 +  // owner
+        8  +  // price_lamports
+        1  +  // listed
+        1  + 8 +   // Option<i64> sold_at
+        1  + 32 +  // Option<Pubkey> sold_to
+        1;    // bump
 }
 
-// Removed InitLightRegistry - in production, Light Protocol manages its own state trees
-// For testing, we use a mock LightProtocolState account
+/// A proof nonce and the slot it was accepted in.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct UsedProofNonce {
+    pub nonce: [u8; 8],
+    pub slot: u64, // 0 for an empty entry
+}
 
-#[account]
-pub struct LightProtocolState {
-    pub current_root: [u8; 32],
+impl UsedProofNonce {
+    pub const SIZE: usize = 8 + 8;
 }
 
-#[derive(Accounts)]
-pub struct InitLightRegistry<'info> {
-    #[account(
-        init,
-        payer = payer,
-        space = 8 + 32
-    )]
-    pub light_state: Account<'info, LightProtocolState>,
-    #[account(mut)]
-    pub payer: Signer<'info>,
-    pub system_program: Program<'info, System>,
+/// Ring buffer of the last 32 proof nonces accepted by `create_compressed_liveness`
+/// and `update_liveness`, so one validity proof cannot serve both operations.
+/// PDA: `[b"used_nonces", vault]`.
+#[account]
+pub struct UsedProofNonces {
+    pub vault: Pubkey,
+    pub entries: [UsedProofNonce; 32],
+    pub head: u8,                      // slot the next nonce is written to
+    pub last_nonce_slot: u64,
+    pub bump: u8,
 }
 
-#[derive(Accounts)]
-pub struct ExecuteInheritance<'info> {
-    #[account(
-        mut,
-        seeds = [b"vault", testator.key().as_ref(), beneficiary.key().as_ref()],
-        bump = vault.bump,
-        has_one = beneficiary @ ErrorCode::Unauthorized
-    )]
-    pub vault: Account<'info, Vault>,
+impl UsedProofNonces {
+    pub const CAPACITY: usize = 32;
+    /// A nonce older than this many slots no longer blocks reuse and may be evicted
+    pub const EVICTION_SLOTS: u64 = 5_000;
 
-    /// CHECK: Validated via seeds on vault
-    pub testator: AccountInfo<'info>,
+    pub const SIZE: usize =
+        32 +  // vault
+        Self::CAPACITY * UsedProofNonce::SIZE +  // entries
+        1  +  // head
+        8  +  // last_nonce_slot
+        1;    // bump
 
-    #[account(mut)]
-    pub beneficiary: Signer<'info>,
+    /// Whether `nonce` was accepted and is still within its eviction window at `slot`.
+    pub fn contains(&self, nonce: &[u8; 8], slot: u64) -> bool {
+        self.entries.iter().any(|entry| {
+            entry.slot != 0
+                && entry.nonce == *nonce
+                && slot <= entry.slot.saturating_add(Self::EVICTION_SLOTS)
+        })
+    }
 
-    /// The Oracle/Verifier that confirms the biometric face match
-    pub verifier: Signer<'info>,
+    /// Records `nonce`, overwriting an evictable entry if there is one and the
+    /// oldest entry otherwise.
+    pub fn insert(&mut self, nonce: [u8; 8], slot: u64) {
+        let index = self
+            .entries
+            .iter()
+            .position(|entry| entry.slot == 0 || slot > entry.slot.saturating_add(Self::EVICTION_SLOTS))
+            .unwrap_or(self.head as usize);
+        self.entries[index] = UsedProofNonce { nonce, slot };
+        if index == self.head as usize {
+            self.head = ((self.head as usize + 1) % Self::CAPACITY) as u8;
+        }
+        self.last_nonce_slot = slot;
+    }
 }
 
-#[derive(Accounts)]
-#[instruction(identity_hash: [u8; 32])]
-pub struct VerifyBeneficiaryIdentity<'info> {
-    #[account(
-        seeds = [b"vault", vault.testator.as_ref(), vault.beneficiary.as_ref()],
-        bump = vault.bump,
-    )]
-    pub vault: Account<'info, Vault>,
+/// Challenge proving the beneficiary can be reached on their contact channel.
+/// PDA: `[b"contact_challenge", vault]`.
+#[account]
+pub struct ContactVerificationChallenge {
+    pub vault: Pubkey,
+    pub challenge_hash: [u8; 32],        // sha256(nonce || vault); the nonce goes off-chain
+    pub created_at: i64,
+    pub verified: bool,
+    pub bump: u8,
 }
 
-#[derive(Accounts)]
-pub struct CancelWill<'info> {
-    #[account(
-        mut,
-        seeds = [b"vault", testator.key().as_ref(), vault.beneficiary.as_ref()],
-        bump = vault.bump,
-        has_one = testator @ ErrorCode::Unauthorized,
-        close = testator
-    )]
-    pub vault: Account<'info, Vault>,
+impl ContactVerificationChallenge {
+    pub const VALIDITY_SECS: i64 = 48 * 60 * 60;
 
-    #[account(mut)]
-    pub testator: Signer<'info>,
+    pub const SIZE: usize =
+        32 +  // vault
+        32 +  // challenge_hash
+        8  +  // created_at
+        1  +  // verified
+        1;    // bump
 }
 
-#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
-pub enum VaultState {
-    Active,
-    Warning,
-    Claimable,
-    Executed,
+/// Vault nonces a testator has used for one beneficiary, tracked in `VaultRegistry`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy)]
+pub struct BeneficiaryVaultNonces {
+    pub beneficiary: Pubkey,
+    pub used: [u8; 32],                  // Bit n is set once nonce n was used
+}
+
+impl BeneficiaryVaultNonces {
+    pub const SIZE: usize = 32 + 32;
+
+    pub fn is_used(&self, vault_nonce: u8) -> bool {
+        self.used[vault_nonce as usize / 8] & (1 << (vault_nonce % 8)) != 0
+    }
+
+    pub fn is_exhausted(&self) -> bool {
+        self.used.iter().all(|byte| *byte == u8::MAX)
+    }
 }
 
+/// Permanent record of an executed vault, kept after `archive_vault` closes it.
+/// PDA: `[b"archive", vault]`. There is no instruction to close it.
 #[account]
-pub struct Vault {
+pub struct VaultArchive {
     pub testator: Pubkey,
     pub beneficiary: Pubkey,
-    pub verifier: Pubkey,                // Authorized Verifier (Oracle)
-    pub beneficiary_identity_hash: [u8; 32], // ZelfProof Identity Anchor
-    pub beneficiary_email_hash: [u8; 32],    // SHA-256 hash of beneficiary email
-    pub beneficiary_document_id_hash: [u8; 32], // SHA-256 hash of document ID
-    pub cid: [u8; 32],                    // IPFS Content ID for artifact
-    pub cid_validator: [u8; 32],          // IPFS Content ID for validator data
-    pub last_ping: i64,
-    pub created_at: i64,
-    pub warning_timeout_secs: i64,
-    pub timeout_secs: i64,
-    pub executed: bool,
-    pub lamports: u64,
-
-    pub encrypted_password: Vec<u8>,
-    pub encrypted_key: Option<Vec<u8>>,
-    pub unwrapped_key: Option<[u8; 32]>,
-    pub light_root: Option<[u8; 32]>,
-    pub is_debug: bool,
-    pub has_compressed_liveness: bool,    // NEW: Whether a compressed liveness account exists
-    pub bump: u8,
+    pub executed_at: i64,
+    pub lamports_transferred: u64,
+    pub cid: [u8; 32],                   // IPFS Content ID for artifact
+    pub vault_id: u64,
+    pub content_hash: [u8; 32],          // Vault content hash at archive time
 }
 
-impl Vault {
-    pub const MAX_ENCRYPTED_PASSWORD_SIZE: usize = 64;
-    pub const MAX_ENCRYPTED_KEY_SIZE: usize = 64;
-
-    pub fn get_state(&self, now: i64) -> VaultState {
-        if self.executed {
-            return VaultState::Executed;
-        }
-        let time_since_ping = now.saturating_sub(self.last_ping);
-        if time_since_ping > self.timeout_secs {
-            VaultState::Claimable
-        } else if time_since_ping > self.warning_timeout_secs {
-            VaultState::Warning
-        } else {
-            VaultState::Active
-        }
-    }
-
+impl VaultArchive {
     pub const SIZE: usize =
         32 +  // testator
         32 +  // beneficiary
-        32 +  // verifier
-        32 +  // beneficiary_identity_hash
-        32 +  // beneficiary_email_hash
-        32 +  // beneficiary_document_id_hash
+        8  +  // executed_at
+        8  +  // lamports_transferred
         32 +  // cid
-        32 +  // cid_validator
-        8  +  // last_ping
-        8  +  // created_at
-        8  +  // warning_timeout_secs
-        8  +  // timeout_secs
-        1  +  // executed
-        8  +  // lamports
-        4  + Self::MAX_ENCRYPTED_PASSWORD_SIZE +  // Vec<u8> encrypted_password
-        1  + 4 + Self::MAX_ENCRYPTED_KEY_SIZE +   // Option<Vec<u8>> encrypted_key
-        1  + 32 +                                  // Option<[u8; 32]> unwrapped_key
-        1  + 32 +                                  // Option<[u8; 32]> light_root
-        1  +                                       // is_debug
-        1  +                                       // has_compressed_liveness
+        8  +  // vault_id
+        32;   // content_hash
+}
+
+/// Leaf of a Bubblegum compressed NFT, in the order of the arguments of its
+/// `transfer` instruction
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug)]
+pub struct CnftLeaf {
+    /// Current root of the Merkle tree
+    pub root: [u8; 32],
+    pub data_hash: [u8; 32],
+    pub creator_hash: [u8; 32],
+    pub nonce: u64,
+    pub index: u32,
+}
+
+/// M-of-N guardians who can rotate a vault's testator key (social recovery).
+/// PDA: `[b"guardians", vault]`.
+#[account]
+pub struct GuardianSet {
+    pub vault: Pubkey,
+    pub guardians: Vec<Pubkey>,
+    pub threshold: u8,
+    pub pending_testator: Option<Pubkey>, // Proposed by propose_testator_rotation
+    pub approvals: Vec<bool>,             // Parallel to `guardians`, for `pending_testator`
+    pub proposed_at: i64,
+    pub rotation_delay_secs: i64,         // Timelock between approval and apply_testator_rotation
+    pub rotation_effective_at: i64,       // When the approved rotation may apply, 0 until approved
+    pub bump: u8,
+}
+
+impl GuardianSet {
+    pub const MAX_GUARDIANS: usize = 7;
+    /// Shortest rotation timelock `init_guardian_set` accepts on non-debug vaults (24 hours)
+    pub const MIN_ROTATION_DELAY_SECS: i64 = 24 * 60 * 60;
+    /// Longest rotation timelock `init_guardian_set` accepts (30 days)
+    pub const MAX_ROTATION_DELAY_SECS: i64 = 30 * 24 * 60 * 60;
+
+    pub const SIZE: usize =
+        32 +  // vault
+        4  + 32 * Self::MAX_GUARDIANS +  // Vec<Pubkey> guardians
+        1  +  // threshold
+        1  + 32 +  // Option<Pubkey> pending_testator
+        4  + Self::MAX_GUARDIANS +  // Vec<bool> approvals
+        8  +  // proposed_at
+        8  +  // rotation_delay_secs
+        8  +  // rotation_effective_at
         1;    // bump
+
+    pub fn approval_count(&self) -> u8 {
+        self.approvals.iter().filter(|&&approved| approved).count() as u8
+    }
+
+    /// Forget the pending rotation and its approvals
+    pub fn clear_pending_rotation(&mut self) {
+        self.pending_testator = None;
+        self.approvals = vec![false; self.guardians.len()];
+        self.rotation_effective_at = 0;
+    }
+}
+
+/// `Vault` as laid out by the first deployed program version, before vault nonces,
+/// pre-wrapped keys and every later setting. Only read by `migrate_plaintext_key`.
+#[derive(AnchorDeserialize)]
+struct LegacyVault {
+    testator: Pubkey,
+    beneficiary: Pubkey,
+    verifier: Pubkey,
+    beneficiary_identity_hash: [u8; 32],
+    beneficiary_email_hash: [u8; 32],
+    beneficiary_document_id_hash: [u8; 32],
+    cid: [u8; 32],
+    cid_validator: [u8; 32],
+    last_ping: i64,
+    created_at: i64,
+    warning_timeout_secs: i64,
+    timeout_secs: i64,
+    executed: bool,
+    lamports: u64,
+    encrypted_password: Vec<u8>,
+    encrypted_key: Option<Vec<u8>>,     // K XOR K_light, see derive_key_from_light_legacy
+    unwrapped_key: Option<[u8; 32]>,
+    light_root: Option<[u8; 32]>,
+    is_debug: bool,
+    #[allow(dead_code)]
+    has_compressed_liveness: bool,
+    #[allow(dead_code)]
+    bump: u8,
 }
 
+impl LegacyVault {
+    /// Account size of every legacy vault, fixed by the deployed program
+    pub const ACCOUNT_LEN: usize = 511;
+}
+
+const _: () = assert!(
+    LegacyVault::ACCOUNT_LEN
+        == 8 +  // discriminator
+        32 * 8 +  // testator .. cid_validator
+        8 * 4 +  // last_ping, created_at, warning_timeout_secs, timeout_secs
+        1 +  // executed
+        8 +  // lamports
+        4 + 64 +  // Vec<u8> encrypted_password
+        1 + 4 + 64 +  // Option<Vec<u8>> encrypted_key
+        1 + 32 +  // Option<[u8; 32]> unwrapped_key
+        1 + 32 +  // Option<[u8; 32]> light_root
+        1 +  // is_debug
+        1 +  // has_compressed_liveness
+        1 // bump
+);
+
 #[error_code]
 pub enum ErrorCode {
     #[msg("Testator still alive")]
@@ -700,5 +12154,549 @@ pub enum ErrorCode {
     InvalidVerifier,
     #[msg("Identity hash mismatch: The provided identity does not match the beneficiary.")]
     IdentityHashMismatch,
+    #[msg("Account discriminator does not match the expected Vault discriminator")]
+    DiscriminatorMismatch,
+    #[msg("Account is not owned by this program")]
+    WrongAccountOwner,
+    #[msg("Key not yet wrapped: call update_liveness first")]
+    KeyNotYetWrapped,
+    #[msg("Failed to wrap the key with K_light")]
+    KeyWrapFailed,
+    #[msg("Compressed liveness account already exists for this vault")]
+    CompressedLivenessAlreadyExists,
+    #[msg("Maximum compressed liveness retries exceeded: manual intervention required")]
+    MaxCompressionRetriesExceeded,
+    #[msg("Previous attempt slot is not a recent recorded failure")]
+    InvalidPreviousAttemptSlot,
+    #[msg("No donations to collect at the funding address")]
+    NoDonationsToCollect,
+    #[msg("Arithmetic overflow")]
+    ArithmeticOverflow,
+    #[msg("Emergency contact registry is full (max 5)")]
+    ContactRegistryFull,
+    #[msg("Emergency contact not found")]
+    ContactNotFound,
+    #[msg("Time capsule already sealed: use update_capsule instead")]
+    TimeCapsuleAlreadySealed,
+    #[msg("Time capsule message cannot be empty")]
+    EmptyTimeCapsuleMessage,
+    #[msg("Time capsule message exceeds maximum size")]
+    TimeCapsuleMessageTooLarge,
+    #[msg("Deployed Light System Program does not match the expected version")]
+    LightSdkVersionMismatch,
+    #[msg("Invalid timeout configuration (see program logs for the violated invariant)")]
+    InvalidTimeoutConfiguration,
+    #[msg("Testator has reached the maximum number of vaults")]
+    TestatorVaultLimitExceeded,
+    #[msg("Protocol has reached its maximum number of vaults")]
+    ProtocolCapacityReached,
+    #[msg("Tax escrow not settled: the tax escrow account must be provided when tax is configured")]
+    TaxEscrowNotSettled,
+    #[msg("Inheritance tax already paid")]
+    TaxAlreadyPaid,
+    #[msg("Invalid tax rate (must be at most 10000 basis points)")]
+    InvalidTaxRate,
+    #[msg("Vault ping count is too low for this governance action")]
+    InsufficientVotingPower,
+    #[msg("Proposed value is out of range for the parameter")]
+    InvalidProposalValue,
+    #[msg("Voting on this proposal has ended")]
+    VotingPeriodEnded,
+    #[msg("Voting on this proposal is still open")]
+    VotingPeriodActive,
+    #[msg("Proposal did not pass")]
+    ProposalRejected,
+    #[msg("Proposal already executed")]
+    ProposalAlreadyExecuted,
+    #[msg("Calling program has no CPI access to vaults")]
+    CpiAccessDenied,
+    #[msg("Execution nonce does not match the vault")]
+    ExecutionNonceMismatch,
+    #[msg("Compressed liveness V2 is not enabled in the protocol config")]
+    CompressedLivenessV2Disabled,
+    #[msg("V2 compressed liveness is enabled: use create_compressed_liveness_v2")]
+    CompressedLivenessV2Required,
+    #[msg("Vault's compressed liveness account has a different version")]
+    CompressedLivenessVersionMismatch,
+    #[msg("Vault has no compressed liveness account")]
+    CompressedLivenessNotFound,
+    #[msg("Custody window has expired")]
+    CustodyExpired,
+    #[msg("Signer is not the vault's custody agent")]
+    CustodyAgentMismatch,
+    #[msg("Custody duration must be positive")]
+    InvalidCustodyDuration,
+    #[msg("Beneficiary instructions exceed maximum size")]
+    InstructionsTooLarge,
+    #[msg("Verifier migration has not been approved by the protocol authority")]
+    VerifierMigrationNotApproved,
+    #[msg("LIGHT_CPI_SIGNER does not match the program ID")]
+    LightCpiSignerMismatch,
+    #[msg("Benchmarks are disabled in the protocol config")]
+    BenchmarksDisabled,
+    #[msg("Benchmark iterations must be between 1 and 100")]
+    InvalidBenchmarkIterations,
+    #[msg("Key fragment index must be below the total, and the total at most 7")]
+    InvalidKeyFragmentConfig,
+    #[msg("Vault balance does not cover the destruction bond")]
+    DestructionBondInsufficient,
+    #[msg("Beneficiary, treasury and protocol config accounts are required to forfeit the bond")]
+    BondRecipientMissing,
+    #[msg("Atomic biometric update rejected: invalid field")]
+    AtomicUpdateFailed,
+    #[msg("System program account required for a deposit")]
+    SystemProgramRequired,
+    #[msg("Identity hash mismatch in verify_and_execute")]
+    IdentityVerificationFailedInComposite,
+    #[msg("Inheritance executions are paused by the protocol authority")]
+    ProtocolExecutionsPaused,
+    #[msg("Not enough committee approvals to execute")]
+    CommitteeThresholdNotMet,
+    #[msg("Committee approvals have expired")]
+    CommitteeApprovalExpired,
+    #[msg("Committee member has already approved")]
+    AlreadyApproved,
+    #[msg("Invalid verifier committee configuration")]
+    InvalidCommitteeConfig,
+    #[msg("Vault executions are not revocable")]
+    ExecutionNotRevocable,
+    #[msg("Maximum number of execution revocations reached")]
+    RevocationLimitReached,
+    #[msg("Execution grace period has expired")]
+    GracePeriodExpired,
+    #[msg("Beneficiary identity verification is too old, revalidate first")]
+    VerificationRecordExpired,
+    #[msg("Ping note is missing or does not match the expected note")]
+    PingNoteInvalid,
+    #[msg("Too many vaults for a single admin liveness ping")]
+    AdminPingLimitExceeded,
+    #[msg("Missing or invalid Ed25519 signature from the protocol authority")]
+    InvalidAdminSignature,
+    #[msg("Serialized vault export exceeds the maximum size")]
+    VaultExportTooLarge,
+    #[msg("Index page is full, pass a new page to split it")]
+    IndexPageFull,
+    #[msg("Index page does not cover this testator")]
+    IndexPageMismatch,
+    #[msg("Health signal is too old or not newer than the previous one")]
+    HealthSignalTooOld,
+    #[msg("Health oracle weight must be at most 10000 bps")]
+    InvalidHealthOracleWeight,
+    #[msg("Lottery mode is enabled but no participants are registered")]
+    LotteryModeNoParticipants,
+    #[msg("The lottery already has the maximum number of participants")]
+    LotteryFull,
+    #[msg("The vault's key has already been revealed in an emergency")]
+    EmergencyAlreadyRevealed,
+    #[msg("Failed to unwrap the key with K_light")]
+    KeyUnwrapFailed,
+    #[msg("No vault is indexed under this vault id")]
+    VaultIdNotFound,
+    #[msg("Dry runs are not enabled in the protocol config")]
+    DryRunNotEnabled,
+    #[msg("A deed NFT has already been minted for this vault")]
+    DeedAlreadyMinted,
+    #[msg("The vault's deed NFT has already been burned")]
+    DeedAlreadyBurned,
+    #[msg("No liveness scheduler is registered for this vault")]
+    SchedulerNotRegistered,
+    #[msg("Cron expression must be 1-32 printable ASCII characters")]
+    InvalidSchedule,
+    #[msg("The vault already holds the maximum number of NFTs")]
+    NftRegistrationFull,
+    #[msg("Mint is not a non-fungible token (supply 1, 0 decimals)")]
+    NotNonFungible,
+    #[msg("NFT is not registered in this vault")]
+    NftNotRegistered,
+    #[msg("The vault has not reached its expiry")]
+    VaultNotExpired,
+    #[msg("Every registered NFT and deposited token must leave the vault before it can close")]
+    AssetsStillPresentInVault,
+    #[msg("This compressed liveness proof has already been used")]
+    NullifierAlreadyUsed,
+    #[msg("The nullifier registry is required while the extra nullifier check is enabled")]
+    NullifierRegistryMissing,
+    #[msg("The vault's key has already been wrapped")]
+    KeyAlreadyWrapped,
+    #[msg("The signer does not hold the role this instruction requires")]
+    RoleNotAuthorized,
+    #[msg("Too many token valuations (max 16)")]
+    TooManyTokenValuations,
+    #[msg("Every vault in an estate valuation must belong to the testator, once")]
+    EstateVaultMismatch,
+    #[msg("The answer to the secret question is incorrect")]
+    SecretAnswerIncorrect,
+    #[msg("No secret question was set for this vault")]
+    SecretNotSet,
+    #[msg("The secret question was already answered")]
+    SecretAlreadyVerified,
+    #[msg("The protocol treasury account is required to pay the vault creation fee")]
+    TreasuryAccountRequired,
+    #[msg("The execution right is not listed for sale")]
+    ExecutionRightNotListed,
+    #[msg("The execution right has already been sold")]
+    ExecutionRightAlreadySold,
+    #[msg("All legal document slots are used (max 4)")]
+    LegalDocumentSlotsFull,
+    #[msg("No legal document at this index")]
+    LegalDocumentNotFound,
+    #[msg("Vault failed its internal consistency check")]
+    VaultInvariantViolated,
+    #[msg("Proof nonce was already used for this vault")]
+    ProofNonceAlreadyUsed,
+    #[msg("The contact verification challenge has expired")]
+    ContactChallengeExpired,
+    #[msg("The contact verification nonce is incorrect")]
+    ContactChallengeInvalid,
+    #[msg("Too many compressed liveness accounts in one batch read (max 8)")]
+    BatchReadSizeTooLarge,
+    #[msg("All 256 vault nonces of this testator-beneficiary pair are used")]
+    VaultNonceExhausted,
+    #[msg("This vault nonce was already used for this beneficiary")]
+    VaultNonceInUse,
+    #[msg("Legal memo is too long (max 128 bytes)")]
+    MemoTooLong,
+    #[msg("The SPL Memo program account is required")]
+    MemoProgramRequired,
+    #[msg("The vault has not been executed")]
+    VaultNotExecuted,
+    #[msg("Invalid beneficiary splits")]
+    InvalidBeneficiarySplits,
+    #[msg("Co-beneficiary accounts must be passed in split order")]
+    CoBeneficiaryAccountMismatch,
+    #[msg("The vault already holds the maximum number of token mints")]
+    TokenDepositsFull,
+    #[msg("No tokens of this mint are deposited in the vault")]
+    TokenNotDeposited,
+    #[msg("The proposed beneficiary must differ from the testator and the current beneficiaries")]
+    InvalidNewBeneficiary,
+    #[msg("Withdrawal exceeds the vault balance")]
+    InsufficientVaultBalance,
+    #[msg("Guardian set or proposed testator is invalid")]
+    InvalidGuardianConfig,
+    #[msg("No testator rotation is pending")]
+    NoPendingTestatorRotation,
+    #[msg("Vault account is not in the legacy plaintext key layout")]
+    NotALegacyVault,
+    #[msg("This vault only executes with a compressed liveness proof")]
+    LivenessProofRequired,
+    #[msg("The timeout has not passed since the compressed liveness account's last ping")]
+    CompressedLivenessTimeoutNotReached,
+    #[msg("Close the compressed liveness account with cancel_will_with_compressed_liveness")]
+    CompressedLivenessStillOpen,
+    #[msg("The crank bounty exceeds Vault::MAX_CRANK_BOUNTY_LAMPORTS")]
+    CrankBountyTooLarge,
+    #[msg("This state was already marked for the vault's last ping")]
+    AlreadyCranked,
+    #[msg("Deadline extensions must be positive and within Vault::MAX_DEADLINE_EXTENSION_SECS")]
+    InvalidDeadlineExtension,
+    #[msg("The extension would exceed the vault's deadline extension cap")]
+    DeadlineExtensionLimitReached,
+    #[msg("A ping delegate must differ from the testator and expire in the future")]
+    InvalidPingDelegate,
+    #[msg("The ping delegate has expired")]
+    PingDelegateExpired,
+    #[msg("Missing or invalid Ed25519 signature from the testator, or stale relayed ping")]
+    InvalidRelayedPing,
+    #[msg("This vault requires a verifier attestation (execute_inheritance_with_attestation)")]
+    VerifierAttestationRequired,
+    #[msg("Missing, expired or replayed verifier attestation")]
+    InvalidVerifierAttestation,
+    #[msg("The verifier registry is full")]
+    VerifierRegistryFull,
+    #[msg("The verifier is not in the verifier registry, or the registry was not provided")]
+    VerifierNotRegistered,
+    #[msg("No verifier rotation is pending")]
+    NoPendingVerifierRotation,
+    #[msg("The verifier rotation delay has not passed yet")]
+    VerifierRotationTimelocked,
+    #[msg("The verifier rotation delay can only be lengthened, up to 30 days")]
+    InvalidVerifierRotationDelay,
+    #[msg("The vault has no recovery escrow to reveal")]
+    RecoveryEscrowMissing,
+    #[msg("A verifier rotation is already pending on this vault")]
+    VerifierRotationPending,
+    #[msg("Secret question salt must be random, not zero")]
+    SecretSaltInvalid,
+    #[msg("Maximum verification age must be within 0..=Vault::MAX_VERIFICATION_AGE_SECS")]
+    InvalidVerificationAge,
+    #[msg("No ping delegate is registered")]
+    NoPingDelegate,
+    #[msg("The testator rotation is not approved yet or its delay has not passed")]
+    TestatorRotationTimelocked,
+    #[msg("The claimed compressed liveness values are impossible for this vault")]
+    InvalidCompressedLivenessClaim,
+    #[msg("The vault has no verifier committee to cancel")]
+    NoPendingVerifierCommittee,
+    #[msg("The verifier committee has already taken effect")]
+    VerifierCommitteeAlreadyEffective,
+    #[msg("The vault is claimable by its beneficiary and can no longer expire")]
+    VaultAlreadyClaimable,
+    #[msg("An execution is pending; only revoke_pending_execution returns the vault to Active")]
+    ExecutionPending,
+    #[msg("The contact verification challenge was already answered; issue a new one")]
+    ContactChallengeAlreadyUsed,
+    #[msg("The lottery draw slot has not passed yet")]
+    LotteryDrawNotReady,
+    #[msg("The lottery winner has already been drawn")]
+    LotteryAlreadyDrawn,
+    #[msg("The SlotHashes sysvar data is malformed")]
+    InvalidSlotHashes,
+    #[msg("The vault has no tax authority oracle")]
+    NoTaxAuthority,
+    #[msg("The vaults passed don't match the testator's vault registry count")]
+    EstateVaultCountMismatch,
+    #[msg("admin_ping_liveness needs exactly one remaining account per listed vault")]
+    AdminPingAccountsMismatch,
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn light_cpi_signer_constant_test() {
+        assert!(is_light_cpi_signer_of(&LIGHT_CPI_SIGNER, &ID));
+        assert_eq!(LIGHT_CPI_SIGNER.cpi_signer[..8], EXPECTED_CPI_SIGNER_PREFIX);
+
+        // The constant doesn't pass for any other program ID, nor does another program's signer
+        let other_program = Pubkey::new_unique();
+        assert!(!is_light_cpi_signer_of(&LIGHT_CPI_SIGNER, &other_program));
+        let (cpi_signer, bump) = Pubkey::find_program_address(&[LIGHT_CPI_AUTHORITY_SEED], &other_program);
+        let other_signer = CpiSigner {
+            program_id: other_program.to_bytes(),
+            cpi_signer: cpi_signer.to_bytes(),
+            bump,
+        };
+        assert!(is_light_cpi_signer_of(&other_signer, &other_program));
+        assert!(!is_light_cpi_signer_of(&other_signer, &ID));
+    }
+
+    #[test]
+    fn k_light_known_vector() {
+        // HMAC-SHA256 computed off-chain: HKDF(salt = vault, ikm = root, info = label || beneficiary)
+        let root = [1u8; 32];
+        let vault = Pubkey::new_from_array([2; 32]);
+        let beneficiary = Pubkey::new_from_array([3; 32]);
+        let expected = [
+            0x84, 0x24, 0x12, 0x84, 0x90, 0x8f, 0x4d, 0x52, 0x2b, 0x36, 0x95, 0x44, 0x6a, 0x44, 0xa9, 0xd9,
+            0x4f, 0xdb, 0xaa, 0xbb, 0x40, 0x35, 0xcf, 0xd9, 0x0a, 0xe3, 0x99, 0x12, 0x06, 0xaf, 0x8c, 0xe5,
+        ];
+        assert_eq!(derive_key_from_light_hkdf(&root, &vault, &beneficiary), expected);
+    }
+
+    #[test]
+    fn compressed_liveness_address_known_vectors() {
+        // Computed off-chain with Light's v1 derivation: seed = keccak256(program_id ||
+        // seeds) and address = keccak256(tree || seed || 0xff), each with the top byte zeroed
+        let testator = Pubkey::new_from_array([1; 32]);
+        let vault = Pubkey::new_from_array([2; 32]);
+        let tree = Pubkey::new_from_array([3; 32]);
+
+        let v1_seed = [
+            0x00, 0x3b, 0xc4, 0xca, 0x0f, 0x20, 0xe7, 0x01, 0x6c, 0xbc, 0x88, 0x8f, 0x0b, 0xcb, 0x95, 0xf0,
+            0xc6, 0x5d, 0xd0, 0xa8, 0x1d, 0x28, 0xfc, 0xda, 0x1c, 0xe5, 0xc9, 0xaa, 0x4e, 0x6f, 0xf6, 0xd2,
+        ];
+        let v1_address = [
+            0x00, 0xd3, 0xf9, 0x2a, 0xe5, 0x90, 0xb2, 0x63, 0x82, 0x2a, 0xa0, 0xe5, 0xee, 0x1f, 0x99, 0x23,
+            0xbb, 0xd0, 0xfb, 0x46, 0xb4, 0x7e, 0xcd, 0x7e, 0xe1, 0x1c, 0xc9, 0xdd, 0x2f, 0xd5, 0x6b, 0xe7,
+        ];
+        assert_eq!(
+            CompressedLiveness::derive_address(&testator, &vault, &tree, &ID),
+            (v1_address, v1_seed)
+        );
+
+        let v2_seed = [
+            0x00, 0x71, 0x06, 0xa5, 0x28, 0x8e, 0xe1, 0x41, 0x85, 0xe0, 0xf9, 0x7b, 0xcb, 0xd9, 0x6e, 0xdb,
+            0x03, 0x0e, 0x9e, 0x90, 0x49, 0x5e, 0x9e, 0x3b, 0xec, 0xe1, 0x65, 0xba, 0xb0, 0xd6, 0x5b, 0xd5,
+        ];
+        let v2_address = [
+            0x00, 0x3d, 0x76, 0x8a, 0x67, 0xdc, 0xe5, 0xd7, 0xe2, 0xa9, 0x89, 0x54, 0x4c, 0xbf, 0x63, 0x28,
+            0x42, 0x57, 0x29, 0x6f, 0x01, 0xc0, 0x3f, 0x5f, 0xc7, 0x8f, 0x6a, 0xc2, 0x74, 0xf3, 0x4d, 0x76,
+        ];
+        assert_eq!(
+            CompressedLivenessV2::derive_address(&testator, &vault, &tree, &ID),
+            (v2_address, v2_seed)
+        );
+    }
+
+    #[test]
+    fn encrypted_key_v2_layout() {
+        assert_eq!(core::mem::size_of::<EncryptedKeyV2>(), 60);
+        assert_eq!(EncryptedKeyV2::SIZE, 60);
+        assert_eq!(WRAPPED_KEY.try_to_vec().unwrap().len(), EncryptedKeyV2::SIZE);
+        assert_eq!(Some(WRAPPED_KEY).try_to_vec().unwrap().len(), 1 + EncryptedKeyV2::SIZE);
+    }
+
+    #[test]
+    fn compute_units_are_only_measured_on_chain() {
+        // Events report 0 outside the SBF runtime; the on-chain ranges are checked in
+        // tests/inheritance_demo.ts against builds with `compute_metrics`
+        assert_eq!(compute_units_remaining(), 0);
+    }
+
+    /// xorshift64, so the vault property tests are reproducible without extra dependencies.
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn below(&mut self, n: u64) -> u64 {
+            self.next() % n
+        }
+
+        fn flag(&mut self) -> bool {
+            self.next() & 1 == 1
+        }
+
+        /// Small values around `base`, so the generated vaults straddle every boundary.
+        fn around(&mut self, base: i64) -> i64 {
+            base + self.below(5) as i64 - 2
+        }
+    }
+
+    const WRAPPED_KEY: EncryptedKeyV2 = EncryptedKeyV2 {
+        nonce: [0; 12],
+        ciphertext: [0; 32],
+        tag: [0; 16],
+    };
+
+    fn random_vault(rng: &mut Rng) -> Vault {
+        let password_len = Vault::MAX_ENCRYPTED_PASSWORD_SIZE + 2 - rng.below(4) as usize;
+        let created_at = rng.around(1_700_000_000);
+        let timeout_secs = rng.around(100);
+        Vault {
+            encrypted_password: vec![0; password_len],
+            warning_timeout_secs: rng.around(timeout_secs),
+            timeout_secs,
+            created_at,
+            last_ping: rng.around(created_at),
+            lamports: u64::MAX / 2 + 2 - rng.below(4),
+            has_compressed_liveness: rng.flag(),
+            is_debug: rng.flag(),
+            encrypted_key_v2: rng.flag().then_some(WRAPPED_KEY),
+            executed: rng.flag(),
+            lottery_mode: rng.flag(),
+            lottery_winner: rng.flag().then(Pubkey::new_unique),
+            ..Default::default()
+        }
+    }
+
+    /// Every invariant the vault breaks, in the order `verify_internal_consistency` checks them.
+    fn expected_violations(vault: &Vault) -> Vec<VaultConsistencyError> {
+        let mut violations = Vec::new();
+        if vault.encrypted_password.len() > Vault::MAX_ENCRYPTED_PASSWORD_SIZE {
+            violations.push(VaultConsistencyError::EncryptedPasswordTooLarge);
+        }
+        if vault.warning_timeout_secs >= vault.timeout_secs {
+            violations.push(VaultConsistencyError::WarningNotBeforeTimeout);
+        }
+        if vault.last_ping < vault.created_at {
+            violations.push(VaultConsistencyError::LastPingBeforeCreation);
+        }
+        if vault.lamports > u64::MAX / 2 {
+            violations.push(VaultConsistencyError::LamportsOutOfRange);
+        }
+        if vault.has_compressed_liveness && vault.is_debug {
+            violations.push(VaultConsistencyError::DebugVaultWithCompressedLiveness);
+        }
+        if vault.lottery_winner.is_some() && (!vault.executed || !vault.lottery_mode) {
+            violations.push(VaultConsistencyError::LotteryWinnerBeforeExecution);
+        }
+        violations
+    }
+
+    #[test]
+    fn consistency_classifies_random_vaults() {
+        let mut rng = Rng(0x9e37_79b9_7f4a_7c15);
+        let mut seen_consistent = false;
+        let mut seen = Vec::new();
+        for _ in 0..10_000 {
+            let vault = random_vault(&mut rng);
+            let expected = expected_violations(&vault);
+            match vault.verify_internal_consistency() {
+                Ok(()) => {
+                    assert!(expected.is_empty(), "missed {:?}", expected);
+                    assert!(vault.assert_consistent().is_ok());
+                    seen_consistent = true;
+                }
+                Err(violation) => {
+                    assert_eq!(Some(&violation), expected.first());
+                    assert_eq!(
+                        vault.assert_consistent().unwrap_err(),
+                        anchor_lang::error::Error::from(ErrorCode::VaultInvariantViolated)
+                    );
+                    if !seen.contains(&violation) {
+                        seen.push(violation);
+                    }
+                }
+            }
+        }
+        assert!(seen_consistent);
+        assert_eq!(seen.len(), 6, "only saw {:?}", seen);
+    }
+
+    #[test]
+    fn consistency_ignores_key_state() {
+        let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+        for _ in 0..1_000 {
+            let mut vault = random_vault(&mut rng);
+            let before = vault.verify_internal_consistency();
+            vault.encrypted_key_v2 = if vault.key_state() == KeyState::Wrapped {
+                None
+            } else {
+                Some(WRAPPED_KEY)
+            };
+            assert_eq!(vault.verify_internal_consistency(), before);
+        }
+    }
+
+    #[test]
+    fn lottery_winner_is_always_a_participant() {
+        let mut rng = Rng(0x6a09_e667_f3bc_c908);
+        let mut wins = [0u32; 3];
+        for _ in 0..10_000 {
+            let participants = 1 + rng.below(Vault::MAX_LOTTERY_PARTICIPANTS as u64) as usize;
+            let vault = Pubkey::new_from_array(core::array::from_fn(|_| rng.next() as u8));
+            let slot_hash = core::array::from_fn(|_| rng.next() as u8);
+            assert!(lottery_draw(&vault, &slot_hash, participants) < participants);
+            wins[lottery_draw(&vault, &slot_hash, wins.len())] += 1;
+        }
+        // Every participant gets drawn
+        assert!(wins.iter().all(|count| *count > 3_000), "{wins:?}");
+    }
+
+    fn slot_hashes(slots: &[u64]) -> Vec<u8> {
+        let mut data = (slots.len() as u64).to_le_bytes().to_vec();
+        for slot in slots {
+            data.extend_from_slice(&slot.to_le_bytes());
+            data.extend_from_slice(&[*slot as u8; 32]);
+        }
+        data
+    }
+
+    #[test]
+    fn lottery_draw_slot_lookup() {
+        // Most recent first, slot 103 skipped
+        let data = slot_hashes(&[105, 104, 102, 101]);
+        let found = |slot: u64| SlotHashLookup::Found { slot, hash: [slot as u8; 32] };
+        assert_eq!(find_slot_hash(&data, 106).unwrap(), SlotHashLookup::Pending);
+        assert_eq!(find_slot_hash(&data, 105).unwrap(), found(105));
+        assert_eq!(find_slot_hash(&data, 103).unwrap(), found(104));
+        assert_eq!(find_slot_hash(&data, 101).unwrap(), found(101));
+        assert_eq!(find_slot_hash(&data, 100).unwrap(), SlotHashLookup::Expired);
+        assert_eq!(find_slot_hash(&slot_hashes(&[]), 100).unwrap(), SlotHashLookup::Pending);
+
+        let truncated = &data[..data.len() - 1];
+        assert_eq!(
+            find_slot_hash(truncated, 100).unwrap_err(),
+            anchor_lang::error::Error::from(ErrorCode::InvalidSlotHashes)
+        );
+        assert!(find_slot_hash(&data[..4], 100).is_err());
+    }
+}
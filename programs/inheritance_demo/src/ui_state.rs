@@ -0,0 +1,222 @@
+//! Off-chain view of a vault for front-ends. Not compiled into the on-chain program.
+
+use crate::{ProtocolConfig, Vault, VaultState, VaultStateHistory};
+
+/// Why a vault that would otherwise progress is on hold
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PauseReason {
+    /// The protocol authority paused all executions (`pause_all_executions`)
+    ProtocolExecutionsPaused,
+}
+
+/// Richer counterpart of `VaultState` for mobile and web clients
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UIVaultState {
+    Active { health_score: u8 },
+    WarningZone { hours_until_claimable: u64, guardian_extended: bool },
+    PendingKeyWrap,
+    /// Claimable, but still inside `execution_delay_secs`
+    GracePeriod { execution_blocked_for_secs: i64 },
+    Claimable { since_hours: u64 },
+    /// `at` comes from the state history; 0 if no history was provided
+    Executed { at: i64 },
+    Paused { reason: PauseReason },
+    /// Handed over to another program version (`migrate_vault`); `at` is the last
+    /// recorded transition, or the last ping without history
+    Expired { at: i64 },
+}
+
+const SECS_PER_HOUR: i64 = 60 * 60;
+
+impl Vault {
+    /// Derives the front-end state at `now`. Pass the vault's `VaultStateHistory` and
+    /// the `ProtocolConfig` when available for the execution time and pause status.
+    pub fn effective_state_for_ui(
+        &self,
+        now: i64,
+        history: Option<&VaultStateHistory>,
+        config: Option<&ProtocolConfig>,
+    ) -> UIVaultState {
+        let latest_transition = history
+            .and_then(|h| h.chronological_entries().last().copied())
+            .map(|entry| entry.entered_at);
+
+        if self.migrated_to.is_some() && !self.executed {
+            return UIVaultState::Expired {
+                at: latest_transition.unwrap_or(self.last_ping),
+            };
+        }
+
+        let since_ping = now.saturating_sub(self.effective_last_ping());
+        match self.get_state(now) {
+            VaultState::PendingKeyWrap => UIVaultState::PendingKeyWrap,
+            VaultState::Executed => UIVaultState::Executed {
+                at: history
+                    .and_then(|h| {
+                        h.chronological_entries()
+                            .into_iter()
+                            .rev()
+                            .find(|entry| entry.state == VaultState::Executed)
+                    })
+                    .map_or(0, |entry| entry.entered_at),
+            },
+            VaultState::Active => {
+                let remaining = self.warning_timeout_secs.saturating_sub(since_ping).max(0);
+                let health_score = (remaining * 100 / self.warning_timeout_secs.max(1)) as u8;
+                UIVaultState::Active { health_score }
+            }
+            VaultState::Warning => {
+                let secs_left = self.effective_timeout_secs().saturating_sub(since_ping).max(0);
+                UIVaultState::WarningZone {
+                    hours_until_claimable: (secs_left as u64).div_ceil(SECS_PER_HOUR as u64),
+                    guardian_extended: self.active_deadline_extension_secs() > 0,
+                }
+            }
+            VaultState::Claimable | VaultState::FallbackClaimable => {
+                if !self.is_debug && config.is_some_and(|c| c.executions_paused_at(now)) {
+                    return UIVaultState::Paused {
+                        reason: PauseReason::ProtocolExecutionsPaused,
+                    };
+                }
+                let claimable_for = since_ping.saturating_sub(self.effective_timeout_secs());
+                if claimable_for < self.execution_delay_secs {
+                    return UIVaultState::GracePeriod {
+                        execution_blocked_for_secs: self.execution_delay_secs - claimable_for,
+                    };
+                }
+                UIVaultState::Claimable {
+                    since_hours: (claimable_for / SECS_PER_HOUR) as u64,
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_lang::prelude::Pubkey;
+
+    const SECS_PER_DAY: i64 = 24 * SECS_PER_HOUR;
+    const CREATED_AT: i64 = 1_700_000_000;
+    const WARNING_SECS: i64 = 10 * SECS_PER_DAY;
+    const TIMEOUT_SECS: i64 = 20 * SECS_PER_DAY;
+
+    fn vault() -> Vault {
+        Vault {
+            created_at: CREATED_AT,
+            last_ping: CREATED_AT,
+            warning_timeout_secs: WARNING_SECS,
+            timeout_secs: TIMEOUT_SECS,
+            ..Default::default()
+        }
+    }
+
+    fn ui_state(vault: &Vault, since_ping: i64) -> UIVaultState {
+        vault.effective_state_for_ui(vault.last_ping + since_ping, None, None)
+    }
+
+    #[test]
+    fn active() {
+        assert_eq!(
+            ui_state(&vault(), WARNING_SECS / 2),
+            UIVaultState::Active { health_score: 50 }
+        );
+        assert_eq!(
+            ui_state(&vault(), SECS_PER_HOUR),
+            UIVaultState::Active { health_score: 99 }
+        );
+    }
+
+    #[test]
+    fn warning_zone() {
+        assert_eq!(
+            ui_state(&vault(), TIMEOUT_SECS - 90 * 60),
+            UIVaultState::WarningZone { hours_until_claimable: 2, guardian_extended: false }
+        );
+    }
+
+    #[test]
+    fn warning_zone_with_deadline_extension() {
+        let mut vault = vault();
+        vault.deadline_extension_secs = SECS_PER_DAY;
+        vault.deadline_extended_for_ping = vault.last_ping;
+        assert_eq!(
+            ui_state(&vault, TIMEOUT_SECS + SECS_PER_HOUR),
+            UIVaultState::WarningZone { hours_until_claimable: 23, guardian_extended: true }
+        );
+
+        // A ping after the extension ends it
+        vault.last_ping += 1;
+        assert_eq!(
+            ui_state(&vault, TIMEOUT_SECS - SECS_PER_HOUR),
+            UIVaultState::WarningZone { hours_until_claimable: 1, guardian_extended: false }
+        );
+    }
+
+    #[test]
+    fn grace_period() {
+        let mut vault = vault();
+        vault.execution_delay_secs = SECS_PER_DAY;
+        assert_eq!(
+            ui_state(&vault, TIMEOUT_SECS + SECS_PER_HOUR),
+            UIVaultState::GracePeriod { execution_blocked_for_secs: SECS_PER_DAY - SECS_PER_HOUR }
+        );
+    }
+
+    #[test]
+    fn claimable() {
+        assert_eq!(
+            ui_state(&vault(), TIMEOUT_SECS + 3 * SECS_PER_HOUR),
+            UIVaultState::Claimable { since_hours: 3 }
+        );
+    }
+
+    #[test]
+    fn executed() {
+        let mut vault = vault();
+        vault.executed = true;
+        assert_eq!(ui_state(&vault, 0), UIVaultState::Executed { at: 0 });
+
+        let mut history = VaultStateHistory::default();
+        history.record_transition(VaultState::Active, CREATED_AT, vault.testator);
+        history.record_transition(VaultState::Executed, CREATED_AT + 5, vault.testator);
+        assert_eq!(
+            vault.effective_state_for_ui(CREATED_AT + 10, Some(&history), None),
+            UIVaultState::Executed { at: CREATED_AT + 5 }
+        );
+    }
+
+    #[test]
+    fn paused() {
+        let now = CREATED_AT + TIMEOUT_SECS + SECS_PER_HOUR;
+        let config = ProtocolConfig {
+            executions_paused: true,
+            pause_expires_at: now + 1,
+            ..Default::default()
+        };
+        assert_eq!(
+            vault().effective_state_for_ui(now, None, Some(&config)),
+            UIVaultState::Paused { reason: PauseReason::ProtocolExecutionsPaused }
+        );
+
+        // Debug vaults and lapsed pauses aren't held
+        let mut debug_vault = vault();
+        debug_vault.is_debug = true;
+        assert!(matches!(
+            debug_vault.effective_state_for_ui(now, None, Some(&config)),
+            UIVaultState::Claimable { .. }
+        ));
+        assert!(matches!(
+            vault().effective_state_for_ui(now + 1, None, Some(&config)),
+            UIVaultState::Claimable { .. }
+        ));
+    }
+
+    #[test]
+    fn expired() {
+        let mut vault = vault();
+        vault.migrated_to = Some(Pubkey::new_unique());
+        assert_eq!(ui_state(&vault, 0), UIVaultState::Expired { at: CREATED_AT });
+    }
+}
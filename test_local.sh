@@ -11,7 +11,7 @@ sed -i '' 's/cluster = "devnet"/cluster = "localnet"/g' Anchor.toml
 echo "🚀 Running Anchor tests..."
 
 # Run anchor test
-anchor test
+anchor test -- --features mock-light
 
 # Check exit status
 TEST_RESULT=$?